@@ -0,0 +1,184 @@
+/// Shapes how the accent amount maps onto the voice
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccentCurve {
+    /// Original behaviour: peak scales with amount, fixed sweep
+    Classic,
+    /// Peak, resonance and level all scale proportionally with amount
+    Linear,
+    /// Gentle at low amounts, aggressive near the top of the range
+    Exponential,
+    /// Closer to the TB-303: mostly a level jump and the sweep
+    Hardware,
+}
+
+/// What an accented note does to the voice
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AccentResponse {
+    /// Multiplier for the envelope peak
+    pub env_peak: f32,
+    /// How strongly the filter envelope charges the accent sweep
+    pub sweep: f32,
+    /// Multiplier for the VCA level
+    pub vca_gain: f32,
+}
+
+impl AccentResponse {
+    /// Response for an unaccented note
+    pub const NONE: AccentResponse = AccentResponse {
+        env_peak: 1.0,
+        sweep: 0.0,
+        vca_gain: 1.0,
+    };
+}
+
+impl AccentCurve {
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(AccentCurve::Classic),
+            1 => Some(AccentCurve::Linear),
+            2 => Some(AccentCurve::Exponential),
+            3 => Some(AccentCurve::Hardware),
+            _ => None,
+        }
+    }
+
+    /// Response for an accented note at `amount` (0.0 - 1.0)
+    pub fn response(self, amount: f32) -> AccentResponse {
+        let a = amount.clamp(0.0, 1.0);
+        match self {
+            AccentCurve::Classic => AccentResponse {
+                env_peak: 1.0 + a,
+                sweep: 1.0,
+                vca_gain: 1.0,
+            },
+            AccentCurve::Linear => AccentResponse {
+                env_peak: 1.0 + a,
+                sweep: a,
+                vca_gain: 1.0 + 0.5 * a,
+            },
+            AccentCurve::Exponential => {
+                let shaped = a * a;
+                AccentResponse {
+                    env_peak: 1.0 + shaped,
+                    sweep: shaped,
+                    vca_gain: 1.0 + 0.5 * shaped,
+                }
+            }
+            AccentCurve::Hardware => AccentResponse {
+                env_peak: 1.0 + 0.5 * a,
+                sweep: a,
+                vca_gain: 1.0 + a,
+            },
+        }
+    }
+}
+
+/// Time for the sweep capacitor to follow a rising envelope
+const SWEEP_CHARGE_MS: f32 = 30.0;
+
+/// The 303's accent sweep circuit. Accented notes charge a capacitor from
+/// the filter envelope and its voltage opens the cutoff; it charges faster
+/// than it drains, so a run of accents stacks into the rising "wow".
+pub struct AccentSweep {
+    sample_rate: f32,
+    charge: f32,
+    charge_coeff: f32,
+    decay_ms: f32,
+    decay_rate: f32,
+}
+
+impl AccentSweep {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut sweep = Self {
+            sample_rate,
+            charge: 0.0,
+            charge_coeff: 1.0 - (-1.0 / (SWEEP_CHARGE_MS / 1000.0 * sample_rate)).exp(),
+            decay_ms: 0.0,
+            decay_rate: 0.0,
+        };
+        sweep.set_decay(300.0);
+        sweep
+    }
+
+    /// How long the capacitor takes to drain to ~1%, in milliseconds
+    /// (10 - 5000)
+    pub fn set_decay(&mut self, ms: f32) {
+        let ms = ms.clamp(10.0, 5000.0);
+        self.decay_ms = ms;
+        let samples = (ms / 1000.0) * self.sample_rate;
+        self.decay_rate = 0.01_f32.powf(1.0 / samples);
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.decay_ms
+    }
+
+    /// Charge towards `input` (the accented envelope, 0 when unaccented)
+    /// or drain, and return the charge
+    pub fn process(&mut self, input: f32) -> f32 {
+        if input > self.charge {
+            self.charge += (input - self.charge) * self.charge_coeff;
+        } else {
+            self.charge *= self.decay_rate;
+        }
+        self.charge
+    }
+
+    pub fn reset(&mut self) {
+        self.charge = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic_matches_original() {
+        let r = AccentCurve::Classic.response(0.7);
+        assert!((r.env_peak - 1.7).abs() < 1e-6);
+        assert_eq!(r.sweep, 1.0);
+        assert_eq!(r.vca_gain, 1.0);
+    }
+
+    #[test]
+    fn test_exponential_softer_than_linear() {
+        let lin = AccentCurve::Linear.response(0.5);
+        let exp = AccentCurve::Exponential.response(0.5);
+        assert!(exp.env_peak < lin.env_peak);
+
+        // Both reach the same maximum
+        let lin = AccentCurve::Linear.response(1.0);
+        let exp = AccentCurve::Exponential.response(1.0);
+        assert_eq!(lin, exp);
+    }
+
+    #[test]
+    fn test_zero_amount_is_neutral() {
+        for curve in [AccentCurve::Linear, AccentCurve::Exponential, AccentCurve::Hardware] {
+            assert_eq!(curve.response(0.0), AccentResponse::NONE);
+        }
+    }
+
+    #[test]
+    fn test_consecutive_accents_stack() {
+        // Accented notes every 100 samples, each with a short envelope
+        let mut sweep = AccentSweep::new(1000.0);
+        let mut peaks = Vec::new();
+        for _ in 0..4 {
+            let mut peak = 0.0f32;
+            for i in 0..100 {
+                let env = if i < 20 { 1.0 } else { 0.0 };
+                peak = peak.max(sweep.process(env));
+            }
+            peaks.push(peak);
+        }
+        assert!(peaks.windows(2).all(|w| w[1] > w[0]), "{:?}", peaks);
+
+        // Left alone it drains away
+        for _ in 0..2000 {
+            sweep.process(0.0);
+        }
+        assert!(sweep.process(0.0) < 0.01);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::sequencer::MAX_STEPS;
+
+/// Parameters that can be recorded into automation lanes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AutomationParam {
+    Cutoff,
+    Resonance,
+    EnvMod,
+    Decay,
+    Accent,
+    Distortion,
+}
+
+pub const PARAM_COUNT: usize = 6;
+
+/// Automation points per sequencer step
+pub const POINTS_PER_STEP: usize = 8;
+
+/// Points in one lane, covering the longest pattern
+pub const LANE_POINTS: usize = MAX_STEPS * POINTS_PER_STEP;
+
+impl AutomationParam {
+    pub const ALL: [AutomationParam; PARAM_COUNT] = [
+        AutomationParam::Cutoff,
+        AutomationParam::Resonance,
+        AutomationParam::EnvMod,
+        AutomationParam::Decay,
+        AutomationParam::Accent,
+        AutomationParam::Distortion,
+    ];
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Map a normalized control position (0.0 - 1.0) onto the parameter's
+    /// range, exponentially for the frequency and time parameters
+    pub fn scale_unit(self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            AutomationParam::Cutoff => 20.0 * 1000.0_f32.powf(x),
+            AutomationParam::Decay => 10.0 * 500.0_f32.powf(x),
+            _ => x,
+        }
+    }
+}
+
+/// Knob movements recorded against the time into the pattern, one lane per
+/// parameter, replayed on every loop. A lane covers as many steps as the
+/// pattern it was recorded over.
+pub struct Automation {
+    lanes: [[Option<f32>; LANE_POINTS]; PARAM_COUNT],
+    recording: bool,
+    playback: bool,
+}
+
+impl Automation {
+    pub fn new() -> Self {
+        Self {
+            lanes: [[None; LANE_POINTS]; PARAM_COUNT],
+            recording: false,
+            playback: true,
+        }
+    }
+
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn set_playback(&mut self, playback: bool) {
+        self.playback = playback;
+    }
+
+    pub fn is_playback(&self) -> bool {
+        self.playback
+    }
+
+    /// Store `value` at the point containing `position` (in steps) of a
+    /// `steps`-long pattern
+    pub fn record(&mut self, param: AutomationParam, position: f32, steps: usize, value: f32) {
+        let point = point_at(position, steps);
+        self.lanes[param.index()][point] = Some(value);
+    }
+
+    /// Recorded value for `param` at `point`, if any
+    pub fn value_at(&self, param: AutomationParam, point: usize) -> Option<f32> {
+        self.lanes[param.index()].get(point).copied().flatten()
+    }
+
+    pub fn has_data(&self, param: AutomationParam) -> bool {
+        self.lanes[param.index()].iter().any(|v| v.is_some())
+    }
+
+    pub fn clear_lane(&mut self, param: AutomationParam) {
+        self.lanes[param.index()] = [None; LANE_POINTS];
+    }
+
+    pub fn clear(&mut self) {
+        self.lanes = [[None; LANE_POINTS]; PARAM_COUNT];
+    }
+}
+
+impl Default for Automation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One-shot exponential ramp between two positive values, for sweeps
+/// that should sound even across the frequency range
+pub struct Sweep {
+    from: f32,
+    ratio: f32,
+    length: u32,
+    elapsed: u32,
+}
+
+impl Sweep {
+    pub fn new(from: f32, to: f32, length: u32) -> Self {
+        let from = from.max(1e-3);
+        Self {
+            from,
+            ratio: to.max(1e-3) / from,
+            length: length.max(1),
+            elapsed: 0,
+        }
+    }
+
+    /// Value for the next sample, or None once the ramp has finished
+    pub fn next_value(&mut self) -> Option<f32> {
+        if self.elapsed >= self.length {
+            return None;
+        }
+        let t = self.elapsed as f32 / self.length as f32;
+        self.elapsed += 1;
+        Some(self.from * self.ratio.powf(t))
+    }
+}
+
+/// Lane point for a position in steps, wrapping at the end of a
+/// `steps`-long pattern
+pub fn point_at(position: f32, steps: usize) -> usize {
+    let points = steps.clamp(1, MAX_STEPS) * POINTS_PER_STEP;
+    ((position.max(0.0) * POINTS_PER_STEP as f32) as usize) % points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_read() {
+        let mut auto = Automation::new();
+        auto.record(AutomationParam::Cutoff, 2.5, 16, 800.0);
+        assert_eq!(auto.value_at(AutomationParam::Cutoff, point_at(2.5, 16)), Some(800.0));
+        assert_eq!(auto.value_at(AutomationParam::Resonance, point_at(2.5, 16)), None);
+        assert!(auto.has_data(AutomationParam::Cutoff));
+    }
+
+    #[test]
+    fn test_unit_ranges() {
+        assert!((AutomationParam::Cutoff.scale_unit(0.0) - 20.0).abs() < 1e-3);
+        assert!((AutomationParam::Cutoff.scale_unit(1.0) - 20000.0).abs() < 1.0);
+        assert!((AutomationParam::Decay.scale_unit(1.0) - 5000.0).abs() < 1.0);
+        assert_eq!(AutomationParam::Resonance.scale_unit(0.5), 0.5);
+    }
+
+    #[test]
+    fn test_sweep_ramps_then_ends() {
+        let mut sweep = Sweep::new(100.0, 1600.0, 4);
+        let values: Vec<f32> = std::iter::from_fn(|| sweep.next_value()).collect();
+        assert_eq!(values.len(), 4);
+        assert!((values[0] - 100.0).abs() < 1e-3);
+        assert!((values[2] - 400.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_position_wraps() {
+        assert_eq!(point_at(16.0, 16), 0);
+        assert_eq!(point_at(15.99, 16), 16 * POINTS_PER_STEP - 1);
+        assert_eq!(point_at(16.0, 32), 16 * POINTS_PER_STEP);
+        assert_eq!(point_at(31.99, 32), LANE_POINTS - 1);
+    }
+
+    #[test]
+    fn test_clear_lane() {
+        let mut auto = Automation::new();
+        auto.record(AutomationParam::Cutoff, 1.0, 16, 500.0);
+        auto.record(AutomationParam::Decay, 1.0, 16, 300.0);
+        auto.clear_lane(AutomationParam::Cutoff);
+        assert!(!auto.has_data(AutomationParam::Cutoff));
+        assert!(auto.has_data(AutomationParam::Decay));
+        auto.clear();
+        assert!(!auto.has_data(AutomationParam::Decay));
+    }
+}
@@ -0,0 +1,275 @@
+//! Master step clock
+//!
+//! Counts samples against the exact, fractional step length, so each step
+//! lands on the nearest sample and the average tempo never drifts. Studio
+//! runs one clock and both sequencers follow it, keeping them phase-locked.
+
+/// How late the off-beat 16ths land at full swing, as a fraction of a step
+pub const MAX_SWING: f32 = 0.75;
+
+/// Furthest a step can be nudged off the grid, in percent of a step
+pub const MAX_NUDGE: i8 = 50;
+
+pub struct Clock {
+    sample_rate: f32,
+    tempo: f32,
+    swing: f32,
+    samples_per_step: f64,
+
+    // Samples since the last step, and the length of the step in progress
+    phase: f64,
+    interval: f64,
+
+    // Index of the next step (for swing), and whether none has played yet
+    next: usize,
+    first: bool,
+
+    running: bool,
+    started: bool,
+}
+
+impl Clock {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut clock = Self {
+            sample_rate,
+            tempo: 120.0,
+            swing: 0.0,
+            samples_per_step: 0.0,
+            phase: 0.0,
+            interval: 0.0,
+            next: 0,
+            first: true,
+            running: false,
+            started: false,
+        };
+        clock.set_tempo(120.0);
+        clock
+    }
+
+    /// Tempo in BPM of 16th-note steps (60 - 300)
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm.clamp(60.0, 300.0);
+        let sixteenths_per_second = self.tempo as f64 / 60.0 * 4.0;
+        self.samples_per_step = self.sample_rate as f64 / sixteenths_per_second;
+        self.interval = self.step_interval();
+    }
+
+    pub fn tempo(&self) -> f32 {
+        self.tempo
+    }
+
+    /// Rate `tick` is called at, which the step length is counted in
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_tempo(self.tempo);
+    }
+
+    /// Delay every other 16th by up to MAX_SWING of a step (0.0 = straight)
+    pub fn set_swing(&mut self, amount: f32) {
+        self.swing = amount.clamp(0.0, 1.0);
+        self.interval = self.step_interval();
+    }
+
+    pub fn swing(&self) -> f32 {
+        self.swing
+    }
+
+    /// Exact step length in samples, before swing
+    pub fn samples_per_step(&self) -> f64 {
+        self.samples_per_step
+    }
+
+    /// Start counting towards `step`, which starts one step length from now
+    pub fn start_at(&mut self, step: usize) {
+        self.running = true;
+        self.started = false;
+        self.phase = 0.0;
+        self.next = step;
+        self.first = true;
+        self.interval = self.step_interval();
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+        self.started = false;
+    }
+
+    /// Advance one sample. Returns true if a step starts on it.
+    pub fn tick(&mut self) -> bool {
+        self.started = false;
+        if !self.running {
+            return false;
+        }
+        self.phase += 1.0;
+        if self.phase >= self.interval {
+            // Keep the remainder so rounding never accumulates
+            self.phase -= self.interval;
+            self.next += 1;
+            self.first = false;
+            self.interval = self.step_interval();
+            self.started = true;
+        }
+        self.started
+    }
+
+    /// Whether the last tick started a step
+    pub fn step_started(&self) -> bool {
+        self.started
+    }
+
+    /// Progress towards the next step (0.0 - 1.0)
+    pub fn progress(&self) -> f32 {
+        if self.interval > 0.0 {
+            (self.phase / self.interval) as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Length of the step leading up to the next one. Off-beat steps are
+    /// pushed late and the following on-beat steps pulled back by the same
+    /// amount; the first step after a start is never pulled back.
+    fn step_interval(&self) -> f64 {
+        let delay = self.samples_per_step * (self.swing * MAX_SWING) as f64;
+        if !self.next.is_multiple_of(2) {
+            self.samples_per_step + delay
+        } else if self.first {
+            self.samples_per_step
+        } else {
+            self.samples_per_step - delay
+        }
+    }
+}
+
+/// Moves each step's trigger off its grid line by the step's nudge. The
+/// grid still comes from the clock; a step nudged early plays towards the
+/// end of the grid step before its own.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nudger {
+    // Clock progress the last step played at, counted from the start of
+    // the current grid step
+    fired_at: f32,
+    // Progress into the current grid step at which its own step plays, if
+    // it hasn't yet
+    due: Option<f32>,
+    // Whether the upcoming step played early, before its grid step began
+    early: bool,
+}
+
+impl Nudger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget any step in flight, for a restart
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Whether the upcoming step, nudged by `nudge` percent of a step,
+    /// plays on this tick. `step_started` and `progress` come from the
+    /// clock after its tick.
+    pub fn due(&mut self, step_started: bool, progress: f32, nudge: i8) -> bool {
+        let nudge = nudge.clamp(-MAX_NUDGE, MAX_NUDGE) as f32 / 100.0;
+        if step_started {
+            self.fired_at -= 1.0;
+            self.due = if self.early { None } else { Some(nudge.max(0.0)) };
+            self.early = false;
+        }
+        match self.due {
+            Some(at) => progress >= at,
+            None => progress >= 1.0 + nudge.min(0.0),
+        }
+    }
+
+    /// Record that the upcoming step played at `progress`
+    pub fn fire(&mut self, progress: f32) {
+        self.early = self.due.is_none();
+        self.due = None;
+        self.fired_at = progress;
+    }
+
+    /// Steps since the last step played, for gates and ratchets
+    pub fn elapsed(&self, progress: f32) -> f32 {
+        progress - self.fired_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples between each of the first `count` steps
+    fn step_lengths(clock: &mut Clock, count: usize) -> Vec<usize> {
+        let mut lengths = Vec::new();
+        let mut since = 0;
+        while lengths.len() < count {
+            since += 1;
+            if clock.tick() {
+                lengths.push(since);
+                since = 0;
+            }
+        }
+        lengths
+    }
+
+    #[test]
+    fn test_fractional_steps_average_out() {
+        // 5512.5 samples per step at 120 BPM
+        let mut clock = Clock::new(44100.0);
+        clock.start_at(0);
+        let lengths = step_lengths(&mut clock, 4);
+        assert_eq!(lengths, vec![5513, 5512, 5513, 5512]);
+
+        // An awkward tempo stays within a sample of exact over many bars
+        clock.set_tempo(123.0);
+        clock.start_at(0);
+        let total: usize = step_lengths(&mut clock, 1600).iter().sum();
+        let exact = clock.samples_per_step() * 1600.0;
+        assert!((total as f64 - exact).abs() <= 1.0, "{} vs {}", total, exact);
+    }
+
+    #[test]
+    fn test_swing_keeps_pairs_straight() {
+        let mut clock = Clock::new(48000.0);
+        clock.set_tempo(120.0);
+        clock.set_swing(1.0);
+        clock.start_at(0);
+        let lengths = step_lengths(&mut clock, 4);
+        // 6000 samples per step, off-beats 4500 late
+        assert_eq!(lengths, vec![6000, 10500, 1500, 10500]);
+    }
+
+    #[test]
+    fn test_nudges_move_steps_off_the_grid() {
+        let mut clock = Clock::new(48000.0);
+        let mut nudger = Nudger::new();
+        // Step 0 on the grid, step 1 a quarter late, step 2 a quarter early
+        let nudges = [0, 25, -25, 0];
+        let mut fired = Vec::new();
+        clock.start_at(0);
+        for i in 1..=6000 * 4 {
+            let started = clock.tick();
+            if nudger.due(started, clock.progress(), nudges[fired.len()]) {
+                nudger.fire(clock.progress());
+                fired.push(i);
+            }
+        }
+        assert_eq!(fired, vec![6000, 13500, 16500, 24000]);
+        assert!((nudger.elapsed(clock.progress()) - 0.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_progress_and_stop() {
+        let mut clock = Clock::new(48000.0);
+        clock.start_at(0);
+        for _ in 0..3000 {
+            clock.tick();
+        }
+        assert!((clock.progress() - 0.5).abs() < 0.001);
+        assert!(!clock.step_started());
+
+        clock.stop();
+        assert!((0..10000).all(|_| !clock.tick()));
+    }
+}
@@ -1,15 +1,22 @@
+use wasm_bindgen::prelude::*;
+
 /// Soft clipping distortion/overdrive
 /// Adds warmth and grit to the 303 sound
+#[wasm_bindgen]
 pub struct Distortion {
     drive: f32,
     mix: f32,
+    drive_mod: f32,
 }
 
+#[wasm_bindgen]
 impl Distortion {
+    #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
         Self {
             drive: 0.3,
             mix: 1.0,
+            drive_mod: 0.0,
         }
     }
 
@@ -18,19 +25,29 @@ impl Distortion {
         self.drive = drive.clamp(0.0, 1.0);
     }
 
+    pub fn drive(&self) -> f32 {
+        self.drive
+    }
+
     /// Set wet/dry mix (0.0 = dry, 1.0 = wet)
     pub fn set_mix(&mut self, mix: f32) {
         self.mix = mix.clamp(0.0, 1.0);
     }
 
+    /// Offset added to the drive by a modulation source (-1.0 to 1.0)
+    pub fn set_drive_mod(&mut self, amount: f32) {
+        self.drive_mod = amount.clamp(-1.0, 1.0);
+    }
+
     pub fn process(&mut self, input: f32) -> f32 {
-        if self.drive < 0.01 {
+        let drive = (self.drive + self.drive_mod).clamp(0.0, 1.0);
+        if drive < 0.01 {
             return input;
         }
 
         // Scale input by drive amount
         // Higher drive = more gain before clipping
-        let gain = 1.0 + self.drive * 10.0;
+        let gain = 1.0 + drive * 10.0;
         let driven = input * gain;
 
         // Soft clipping using tanh
@@ -38,11 +55,18 @@ impl Distortion {
         let clipped = driven.tanh();
 
         // Compensate for volume increase
-        let compensated = clipped / (1.0 + self.drive * 0.5);
+        let compensated = clipped / (1.0 + drive * 0.5);
 
         // Mix dry and wet
         input * (1.0 - self.mix) + compensated * self.mix
     }
+
+    /// Process `buffer` in place
+    pub fn process_block(&mut self, buffer: &mut [f32]) {
+        for sample in buffer.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
 }
 
 impl Default for Distortion {
@@ -97,6 +121,18 @@ mod tests {
         assert!((pos + neg).abs() < 0.01);
     }
 
+    #[test]
+    fn test_drive_mod() {
+        let mut dist = Distortion::new();
+        dist.set_drive(0.0);
+        dist.set_drive_mod(1.0);
+        assert!(dist.process(0.5) > 0.5);
+
+        dist.set_drive(1.0);
+        dist.set_drive_mod(-1.0);
+        assert_eq!(dist.process(0.5), 0.5);
+    }
+
     #[test]
     fn test_drive_range() {
         let mut dist = Distortion::new();
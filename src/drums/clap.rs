@@ -0,0 +1,228 @@
+use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+
+use super::kit::KitModel;
+
+/// 808/909-style hand clap synthesizer
+/// A few quick noise bursts, like several hands not quite together, then a
+/// longer diffuse tail, all through a bandpass around 1kHz
+#[wasm_bindgen]
+pub struct Clap {
+    sample_rate: f32,
+
+    // Noise generator
+    noise_state: u32,
+
+    // Bandpass (state variable filter)
+    bp_low: f32,
+    bp_band: f32,
+    bp_f: f32,
+    bp_damping: f32,
+    bp_hz: f32,
+
+    // Bursts: how many are left to fire, samples until the next one, and
+    // the envelope of the one sounding
+    bursts_left: u32,
+    burst_timer: u32,
+    burst_spacing: u32,
+    burst_env: f32,
+    burst_decay: f32,
+
+    // Reverb-like tail after the last burst
+    tail_env: f32,
+    tail_decay: f32,
+    tail_level: f32,
+    decay: f32,
+
+    active: bool,
+}
+
+/// Noise bursts in one clap
+const BURSTS: u32 = 4;
+
+/// Time between bursts
+const BURST_SPACING_MS: f32 = 10.0;
+
+/// Decay of each burst to -60dB
+const BURST_MS: f32 = 8.0;
+
+/// Damping of the bandpass
+const BANDPASS_DAMPING: f32 = 0.6;
+
+#[wasm_bindgen]
+impl Clap {
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_rate: f32) -> Self {
+        let mut clap = Self {
+            sample_rate,
+            noise_state: 0xB5C3,
+            bp_low: 0.0,
+            bp_band: 0.0,
+            bp_f: 0.0,
+            bp_damping: BANDPASS_DAMPING,
+            bp_hz: 1100.0,
+            bursts_left: 0,
+            burst_timer: 0,
+            burst_spacing: 0,
+            burst_env: 0.0,
+            burst_decay: 0.0,
+            tail_env: 0.0,
+            tail_decay: 0.0,
+            tail_level: 0.6,
+            decay: 0.4,
+            active: false,
+        };
+        clap.set_sample_rate(sample_rate);
+        clap
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.bp_f = 2.0 * (PI * self.bp_hz / sample_rate).sin();
+        self.burst_spacing = (BURST_SPACING_MS / 1000.0 * sample_rate) as u32;
+        self.burst_decay = 0.001_f32.powf(1.0 / (BURST_MS / 1000.0 * sample_rate));
+        self.set_decay(self.decay);
+    }
+
+    pub fn trigger(&mut self) {
+        self.bursts_left = BURSTS;
+        self.burst_timer = 0;
+        self.tail_env = 0.0;
+        self.active = true;
+    }
+
+    /// Silence the voice immediately
+    pub fn reset(&mut self) {
+        self.bursts_left = 0;
+        self.burst_env = 0.0;
+        self.tail_env = 0.0;
+        self.bp_low = 0.0;
+        self.bp_band = 0.0;
+        self.active = false;
+    }
+
+    pub fn process(&mut self) -> f32 {
+        if !self.active {
+            return 0.0;
+        }
+
+        // Fire the next burst; the last one hands over to the tail
+        if self.bursts_left > 0 {
+            if self.burst_timer == 0 {
+                self.burst_env = 1.0;
+                self.bursts_left -= 1;
+                self.burst_timer = self.burst_spacing;
+                if self.bursts_left == 0 {
+                    self.tail_env = 1.0;
+                }
+            }
+            self.burst_timer -= 1;
+        }
+
+        let noise = self.generate_noise();
+        let high = noise - self.bp_low - self.bp_damping * self.bp_band;
+        self.bp_band += self.bp_f * high;
+        self.bp_low += self.bp_f * self.bp_band;
+
+        let output = self.bp_band * (self.burst_env + self.tail_env * self.tail_level);
+
+        self.burst_env *= self.burst_decay;
+        self.tail_env *= self.tail_decay;
+        if self.bursts_left == 0 && self.burst_env < 0.001 && self.tail_env < 0.001 {
+            self.active = false;
+        }
+
+        (output * 1.5).tanh()
+    }
+
+    /// Fill `output` with consecutive samples
+    pub fn process_block(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
+    /// Generate white noise using LFSR
+    fn generate_noise(&mut self) -> f32 {
+        let bit = (self.noise_state ^ (self.noise_state >> 2)
+                 ^ (self.noise_state >> 3) ^ (self.noise_state >> 5)) & 1;
+        self.noise_state = (self.noise_state >> 1) | (bit << 15);
+        (self.noise_state as f32 / 32768.0) - 1.0
+    }
+
+    /// Set the tail length (0.0 = dry, 1.0 = roomy)
+    pub fn set_decay(&mut self, decay: f32) {
+        let decay = decay.clamp(0.0, 1.0);
+        self.decay = decay;
+        let tail_ms = 60.0 + decay * 440.0;
+        self.tail_decay = 0.001_f32.powf(1.0 / (tail_ms / 1000.0 * self.sample_rate));
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+impl Clap {
+    /// 808: darker, with more of the room. 909: brighter and tighter.
+    pub fn set_model(&mut self, model: KitModel) {
+        (self.bp_hz, self.tail_level) = match model {
+            KitModel::Tr808 => (900.0, 0.9),
+            KitModel::Tr909 => (1100.0, 0.6),
+        };
+        self.set_sample_rate(self.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(clap: &mut Clap, len: usize) -> Vec<f32> {
+        (0..len).map(|_| clap.process()).collect()
+    }
+
+    #[test]
+    fn test_clap_bursts_then_tail() {
+        let mut clap = Clap::new(44100.0);
+        clap.trigger();
+        let out = render(&mut clap, 2205);
+        // Each burst restarts the envelope: the start of every 10ms
+        // window is louder than the end of the one before it
+        let spacing = 441;
+        let level = |from: usize| out[from..from + 40].iter().map(|s| s.abs()).sum::<f32>();
+        for burst in 1..BURSTS as usize {
+            assert!(level(burst * spacing) > level(burst * spacing - 40) * 2.0, "burst {}", burst);
+        }
+        assert!(clap.is_active());
+    }
+
+    #[test]
+    fn test_clap_decays() {
+        for decay in [0.0, 1.0] {
+            let mut clap = Clap::new(44100.0);
+            clap.set_decay(decay);
+            clap.trigger();
+            render(&mut clap, 44100);
+            assert!(!clap.is_active());
+        }
+    }
+
+    #[test]
+    fn test_decay_lengthens_tail() {
+        let tail = |decay: f32| {
+            let mut clap = Clap::new(44100.0);
+            clap.set_decay(decay);
+            clap.trigger();
+            render(&mut clap, 8820)[4410..].iter().map(|s| s * s).sum::<f32>()
+        };
+        assert!(tail(1.0) > tail(0.0) * 10.0);
+    }
+
+    #[test]
+    fn test_clap_output_range() {
+        let mut clap = Clap::new(44100.0);
+        clap.trigger();
+        assert!(render(&mut clap, 4410).iter().all(|s| (-1.0..=1.0).contains(s)));
+    }
+}
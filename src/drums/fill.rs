@@ -0,0 +1,89 @@
+//! Fills derived from the current drum pattern
+//! Unlike the fixed FILL_* presets these keep the groove of whatever is
+//! playing and only rework the end of the bar
+
+use super::sequencer::DrumStep;
+use crate::sequencer::{MAX_STEPS, STEPS};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillKind {
+    /// Snares getting denser through the second half of the bar
+    SnareBuild,
+    /// Snare roll on the last beat
+    Roll,
+    /// Kick drops out of the second half
+    DroppedKick,
+}
+
+impl FillKind {
+    /// 0 = snare build, 1 = last beat roll, 2 = dropped kick
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(FillKind::SnareBuild),
+            1 => Some(FillKind::Roll),
+            2 => Some(FillKind::DroppedKick),
+            _ => None,
+        }
+    }
+}
+
+/// A pass of `pattern` with a fill over its last bar. Only the first
+/// `pattern.len()` steps of the result are used.
+pub fn derive_fill(pattern: &[DrumStep], kind: FillKind) -> [DrumStep; MAX_STEPS] {
+    let mut fill = [DrumStep::default(); MAX_STEPS];
+    let length = pattern.len().min(MAX_STEPS);
+    fill[..length].copy_from_slice(&pattern[..length]);
+    // Position of each step in the last bar, lined up so the pattern ends
+    // where the bar does
+    let bar = fill[..length]
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(i, step)| (STEPS + i).checked_sub(length).map(|pos| (pos, step)));
+    for (pos, step) in bar {
+        match kind {
+            // Eighths, then sixteenths over the last beat
+            FillKind::SnareBuild if pos >= 8 => step.snare = pos % 2 == 0 || pos >= 12,
+            FillKind::Roll if pos >= 12 => {
+                step.kick = false;
+                step.snare = true;
+                step.open_hh = false;
+            }
+            FillKind::DroppedKick if pos >= 8 => {
+                step.kick = false;
+                // Open hat into the downbeat
+                step.open_hh |= pos == STEPS - 2;
+            }
+            _ => {}
+        }
+    }
+    fill
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drums::BASIC_BEAT;
+
+    #[test]
+    fn test_first_half_keeps_the_groove() {
+        for kind in [FillKind::SnareBuild, FillKind::Roll, FillKind::DroppedKick] {
+            let fill = derive_fill(&BASIC_BEAT, kind);
+            for i in 0..8 {
+                assert_eq!(fill[i].bits(), BASIC_BEAT[i].bits());
+            }
+        }
+    }
+
+    #[test]
+    fn test_roll_fills_last_beat() {
+        let fill = derive_fill(&BASIC_BEAT, FillKind::Roll);
+        assert!(fill[12..16].iter().all(|s| s.snare && !s.kick));
+    }
+
+    #[test]
+    fn test_dropped_kick() {
+        let fill = derive_fill(&BASIC_BEAT, FillKind::DroppedKick);
+        assert!(fill[8..].iter().all(|s| !s.kick));
+        assert!(fill[..8].iter().any(|s| s.kick));
+    }
+}
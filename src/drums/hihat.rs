@@ -1,7 +1,34 @@
-/// Hihat synthesizer using metallic noise
-/// Based on 808/909 approach: multiple square waves + noise through bandpass
+//! Hihat synthesizer using metallic noise
+//! Based on 808/909 approach: multiple square waves + noise through bandpass
+
+use wasm_bindgen::prelude::*;
+
+use super::kit::KitModel;
+
+/// Rate the per-sample envelope coefficients below were tuned at
+const REFERENCE_RATE: f32 = 44100.0;
+
+const CLOSED_DECAY: f32 = 0.9985;
+const OPEN_DECAY: f32 = 0.9998; // Longer decay than closed
+const CHOKE_RATE: f32 = 0.99;
+
+/// Bandpass opening and noise level of the metallic mix per kit model: the
+/// 808's dark square waves, or the 909's brighter, noisier hats
+fn model_tone(model: KitModel) -> (f32, f32) {
+    match model {
+        KitModel::Tr808 => (0.0, 0.3),
+        KitModel::Tr909 => (0.3, 0.8),
+    }
+}
+
+/// Per-sample coefficient that decays as fast at `sample_rate` as `coeff`
+/// does at the reference rate
+fn rescale(coeff: f32, sample_rate: f32) -> f32 {
+    coeff.powf(REFERENCE_RATE / sample_rate)
+}
 
 /// Closed hihat - short, tight
+#[wasm_bindgen]
 pub struct ClosedHihat {
     sample_rate: f32,
     noise_state: u32,
@@ -16,10 +43,16 @@ pub struct ClosedHihat {
     bp_state1: f32,
     bp_state2: f32,
 
+    // Set by the kit model
+    brightness: f32,
+    noise_level: f32,
+
     active: bool,
 }
 
+#[wasm_bindgen]
 impl ClosedHihat {
+    #[wasm_bindgen(constructor)]
     pub fn new(sample_rate: f32) -> Self {
         // 808-style uses 6 square waves at specific ratios
         // These create the metallic, inharmonic timbre
@@ -37,20 +70,35 @@ impl ClosedHihat {
             sample_rate,
             noise_state: 0xBEEF,
             env: 0.0,
-            decay: 0.9985,
+            decay: rescale(CLOSED_DECAY, sample_rate),
             phases: [0.0; 6],
             freqs,
             bp_state1: 0.0,
             bp_state2: 0.0,
+            brightness: 0.0,
+            noise_level: 0.3,
             active: false,
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.decay = rescale(CLOSED_DECAY, sample_rate);
+    }
+
     pub fn trigger(&mut self) {
         self.env = 1.0;
         self.active = true;
     }
 
+    /// Silence the voice immediately
+    pub fn reset(&mut self) {
+        self.env = 0.0;
+        self.bp_state1 = 0.0;
+        self.bp_state2 = 0.0;
+        self.active = false;
+    }
+
     pub fn process(&mut self) -> f32 {
         if !self.active {
             return 0.0;
@@ -71,12 +119,12 @@ impl ClosedHihat {
         osc_mix /= 6.0;
 
         // Add some noise for extra sizzle
-        let noise = self.generate_noise() * 0.3;
+        let noise = self.generate_noise() * self.noise_level;
         let mixed = osc_mix + noise;
 
         // Highpass filter to remove low frequencies
         // Simple 2-pole bandpass around 8-10kHz
-        let cutoff = 0.4;  // Normalized frequency
+        let cutoff = 0.4 + self.brightness;  // Normalized frequency
         let q = 0.7;
 
         self.bp_state1 += cutoff * (mixed - self.bp_state1 - q * self.bp_state2);
@@ -96,8 +144,15 @@ impl ClosedHihat {
         output * 0.5
     }
 
+    /// Fill `output` with consecutive samples
+    pub fn process_block(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
     fn generate_noise(&mut self) -> f32 {
-        let bit = ((self.noise_state >> 0) ^ (self.noise_state >> 2)
+        let bit = (self.noise_state ^ (self.noise_state >> 2)
                  ^ (self.noise_state >> 3) ^ (self.noise_state >> 5)) & 1;
         self.noise_state = (self.noise_state >> 1) | (bit << 15);
         (self.noise_state as f32 / 32768.0) - 1.0
@@ -105,6 +160,7 @@ impl ClosedHihat {
 }
 
 /// Open hihat - longer, more sustain, can be choked
+#[wasm_bindgen]
 pub struct OpenHihat {
     sample_rate: f32,
     noise_state: u32,
@@ -117,12 +173,18 @@ pub struct OpenHihat {
     bp_state1: f32,
     bp_state2: f32,
 
+    // Set by the kit model
+    brightness: f32,
+    noise_level: f32,
+
     active: bool,
     choking: bool,
     choke_rate: f32,
 }
 
+#[wasm_bindgen]
 impl OpenHihat {
+    #[wasm_bindgen(constructor)]
     pub fn new(sample_rate: f32) -> Self {
         let base = 400.0;
         let freqs = [
@@ -138,23 +200,40 @@ impl OpenHihat {
             sample_rate,
             noise_state: 0xCAFE,
             env: 0.0,
-            decay: 0.9998,  // Longer decay than closed
+            decay: rescale(OPEN_DECAY, sample_rate),
             phases: [0.0; 6],
             freqs,
             bp_state1: 0.0,
             bp_state2: 0.0,
+            brightness: 0.0,
+            noise_level: 0.3,
             active: false,
             choking: false,
-            choke_rate: 0.99,
+            choke_rate: rescale(CHOKE_RATE, sample_rate),
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.decay = rescale(OPEN_DECAY, sample_rate);
+        self.choke_rate = rescale(CHOKE_RATE, sample_rate);
+    }
+
     pub fn trigger(&mut self) {
         self.env = 1.0;
         self.active = true;
         self.choking = false;
     }
 
+    /// Silence the voice immediately
+    pub fn reset(&mut self) {
+        self.env = 0.0;
+        self.bp_state1 = 0.0;
+        self.bp_state2 = 0.0;
+        self.active = false;
+        self.choking = false;
+    }
+
     /// Choke the hihat (when closed hihat plays)
     pub fn choke(&mut self) {
         if self.active {
@@ -180,11 +259,11 @@ impl OpenHihat {
         }
         osc_mix /= 6.0;
 
-        let noise = self.generate_noise() * 0.3;
+        let noise = self.generate_noise() * self.noise_level;
         let mixed = osc_mix + noise;
 
         // Bandpass
-        let cutoff = 0.35;
+        let cutoff = 0.35 + self.brightness;
         let q = 0.6;
 
         self.bp_state1 += cutoff * (mixed - self.bp_state1 - q * self.bp_state2);
@@ -207,14 +286,33 @@ impl OpenHihat {
         output * 0.5
     }
 
+    /// Fill `output` with consecutive samples
+    pub fn process_block(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
     fn generate_noise(&mut self) -> f32 {
-        let bit = ((self.noise_state >> 0) ^ (self.noise_state >> 2)
+        let bit = (self.noise_state ^ (self.noise_state >> 2)
                  ^ (self.noise_state >> 3) ^ (self.noise_state >> 5)) & 1;
         self.noise_state = (self.noise_state >> 1) | (bit << 15);
         (self.noise_state as f32 / 32768.0) - 1.0
     }
 }
 
+impl ClosedHihat {
+    pub fn set_model(&mut self, model: KitModel) {
+        (self.brightness, self.noise_level) = model_tone(model);
+    }
+}
+
+impl OpenHihat {
+    pub fn set_model(&mut self, model: KitModel) {
+        (self.brightness, self.noise_level) = model_tone(model);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,6 +356,23 @@ mod tests {
         assert!(!hh.active);
     }
 
+    #[test]
+    fn test_decay_time_follows_sample_rate() {
+        let ring_seconds = |rate: f32| {
+            let mut hh = ClosedHihat::new(44100.0);
+            hh.set_sample_rate(rate);
+            hh.trigger();
+            let mut count = 0;
+            while hh.active {
+                hh.process();
+                count += 1;
+            }
+            count as f32 / rate
+        };
+        let reference = ring_seconds(44100.0);
+        assert!((ring_seconds(96000.0) - reference).abs() < 0.001);
+    }
+
     #[test]
     fn test_closed_shorter_than_open() {
         let mut closed = ClosedHihat::new(44100.0);
@@ -281,4 +396,18 @@ mod tests {
 
         assert!(closed_count < open_count, "Closed should decay faster than open");
     }
+
+    #[test]
+    fn test_909_hats_brighter() {
+        // Sample-to-sample difference against level measures the high end
+        let brightness = |model: KitModel| {
+            let mut hat = ClosedHihat::new(44100.0);
+            hat.set_model(model);
+            hat.trigger();
+            let out: Vec<f32> = (0..2000).map(|_| hat.process()).collect();
+            let edge: f32 = out.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+            edge / out.iter().map(|s| s * s).sum::<f32>()
+        };
+        assert!(brightness(KitModel::Tr909) > brightness(KitModel::Tr808));
+    }
 }
@@ -1,7 +1,11 @@
 use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
 
-/// 808-style kick drum synthesizer
+use super::kit::KitModel;
+
+/// 808/909-style kick drum synthesizer
 /// Uses a sine wave with pitch envelope for that deep boom
+#[wasm_bindgen]
 pub struct Kick {
     sample_rate: f32,
     phase: f32,
@@ -15,11 +19,33 @@ pub struct Kick {
     pitch_decay: f32,    // How fast pitch drops
     amp_decay: f32,      // How fast amplitude drops
     pitch_amount: f32,   // How much pitch sweeps (in Hz)
+    decay: f32,          // Decay knob, kept to recompute the rates
+    sweep_time: f32,     // Pitch sweep length against the 808's
+    body_time: f32,      // Amplitude decay length against the 808's
+    drive: f32,          // Gain into the soft clipper (0-1)
+
+    // Click: a short burst of highpassed noise on the attack
+    click: f32,          // Click level (0-1)
+    click_env: f32,
+    click_decay: f32,
+    click_hp_state: f32,
+    noise_state: u32,
 
     active: bool,
 }
 
+/// Length of the click to -60dB
+const CLICK_MS: f32 = 3.0;
+
+/// Click level at full click, against the body
+const CLICK_LEVEL: f32 = 0.8;
+
+/// Extra gain into the clipper at full drive
+const MAX_DRIVE: f32 = 4.0;
+
+#[wasm_bindgen]
 impl Kick {
+    #[wasm_bindgen(constructor)]
     pub fn new(sample_rate: f32) -> Self {
         let mut kick = Self {
             sample_rate,
@@ -30,19 +56,43 @@ impl Kick {
             pitch_decay: 0.0,
             amp_decay: 0.0,
             pitch_amount: 150.0,
+            decay: 0.5,
+            sweep_time: 1.0,
+            body_time: 1.0,
+            drive: 0.0,
+            click: 0.0,
+            click_env: 0.0,
+            click_decay: 0.0,
+            click_hp_state: 0.0,
+            noise_state: 0x9E37,
             active: false,
         };
         kick.set_decay(0.5);
         kick
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_decay(self.decay);
+    }
+
     pub fn trigger(&mut self) {
         self.phase = 0.0;
         self.amp_env = 1.0;
         self.pitch_env = 1.0;
+        self.click_env = 1.0;
         self.active = true;
     }
 
+    /// Silence the voice immediately
+    pub fn reset(&mut self) {
+        self.amp_env = 0.0;
+        self.pitch_env = 0.0;
+        self.click_env = 0.0;
+        self.click_hp_state = 0.0;
+        self.active = false;
+    }
+
     pub fn process(&mut self) -> f32 {
         if !self.active {
             return 0.0;
@@ -63,6 +113,12 @@ impl Kick {
         // Apply amplitude envelope
         let output = output * self.amp_env;
 
+        // Click: noise with its lows taken out, so it sits above the body
+        let noise = self.generate_noise();
+        self.click_hp_state += 0.5 * (noise - self.click_hp_state);
+        let output = output + (noise - self.click_hp_state) * self.click_env * self.click * CLICK_LEVEL;
+        self.click_env *= self.click_decay;
+
         // Decay envelopes
         self.amp_env *= self.amp_decay;
         self.pitch_env *= self.pitch_decay;
@@ -72,24 +128,47 @@ impl Kick {
             self.active = false;
         }
 
-        // Soft clip for extra punch
-        soft_clip(output * 1.5)
+        // Soft clip for extra punch, harder with drive
+        soft_clip(output * 1.5 * (1.0 + self.drive * MAX_DRIVE))
+    }
+
+    /// Fill `output` with consecutive samples
+    pub fn process_block(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
     }
 
     /// Set decay time (0.0 = short, 1.0 = long boomy)
     pub fn set_decay(&mut self, decay: f32) {
         let decay = decay.clamp(0.0, 1.0);
+        self.decay = decay;
 
         // Map to useful decay rates
         // Short: ~50ms, Long: ~500ms
-        let amp_ms = 50.0 + decay * 450.0;
-        let pitch_ms = 10.0 + decay * 40.0;
+        let amp_ms = (50.0 + decay * 450.0) * self.body_time;
+        let pitch_ms = (10.0 + decay * 40.0) * self.sweep_time;
 
         let amp_samples = (amp_ms / 1000.0) * self.sample_rate;
         let pitch_samples = (pitch_ms / 1000.0) * self.sample_rate;
 
         self.amp_decay = 0.001_f32.powf(1.0 / amp_samples);
         self.pitch_decay = 0.001_f32.powf(1.0 / pitch_samples);
+        self.click_decay = 0.001_f32.powf(1.0 / (CLICK_MS / 1000.0 * self.sample_rate));
+    }
+
+    /// Set the attack click (0.0 = none, 1.0 = hard 909-style click)
+    pub fn set_click(&mut self, click: f32) {
+        self.click = click.clamp(0.0, 1.0);
+    }
+
+    /// Set the drive into the soft clipper (0.0 = clean, 1.0 = crushed)
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive.clamp(0.0, 1.0);
     }
 
     /// Set base pitch (0.0 = low 40Hz, 1.0 = high 80Hz)
@@ -97,6 +176,47 @@ impl Kick {
         let pitch = pitch.clamp(0.0, 1.0);
         self.base_freq = 40.0 + pitch * 40.0;
     }
+
+    /// Snap the base pitch to the root or fifth of `pitch_class` (0 = C),
+    /// whichever is closer to the current pitch. Returns the new frequency.
+    pub fn tune_to(&mut self, pitch_class: u8) -> f32 {
+        let fold = |semitones: u8| {
+            // Fold the note into the kick's 40-80Hz octave
+            let mut freq = 440.0 * 2.0_f32.powf((semitones % 12) as f32 / 12.0 - 69.0 / 12.0);
+            while freq < 40.0 {
+                freq *= 2.0;
+            }
+            while freq >= 80.0 {
+                freq /= 2.0;
+            }
+            freq
+        };
+        let distance = |freq: f32| (freq / self.base_freq).log2().abs();
+        let root = fold(pitch_class);
+        let fifth = fold(pitch_class + 7);
+        self.base_freq = if distance(fifth) < distance(root) { fifth } else { root };
+        self.base_freq
+    }
+}
+
+impl Kick {
+    /// White noise from a 16-bit LFSR
+    fn generate_noise(&mut self) -> f32 {
+        let bit = (self.noise_state ^ (self.noise_state >> 2)
+                 ^ (self.noise_state >> 3) ^ (self.noise_state >> 5)) & 1;
+        self.noise_state = (self.noise_state >> 1) | (bit << 15);
+        (self.noise_state as f32 / 32768.0) - 1.0
+    }
+
+    /// 808: a long, gentle sweep into a boomy sine. 909: a fast, wide
+    /// sweep for a clicky attack and a tighter body.
+    pub fn set_model(&mut self, model: KitModel) {
+        (self.pitch_amount, self.sweep_time, self.body_time) = match model {
+            KitModel::Tr808 => (150.0, 1.0, 1.0),
+            KitModel::Tr909 => (400.0, 0.35, 0.6),
+        };
+        self.set_decay(self.decay);
+    }
 }
 
 fn soft_clip(x: f32) -> f32 {
@@ -107,6 +227,29 @@ fn soft_clip(x: f32) -> f32 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_tune_to_root_or_fifth() {
+        let mut kick = Kick::new(44100.0);
+        // A: root at 55Hz, fifth (E) at 41.2Hz
+        kick.set_pitch(0.4);
+        assert!((kick.tune_to(9) - 55.0).abs() < 0.01);
+        kick.set_pitch(0.0);
+        assert!((kick.tune_to(9) - 41.2).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_process_block_matches_process() {
+        let mut a = Kick::new(44100.0);
+        let mut b = Kick::new(44100.0);
+        a.trigger();
+        b.trigger();
+        let mut block = [0.0f32; 256];
+        a.process_block(&mut block);
+        for sample in block {
+            assert_eq!(sample, b.process());
+        }
+    }
+
     #[test]
     fn test_kick_creation() {
         let kick = Kick::new(44100.0);
@@ -151,7 +294,49 @@ mod tests {
 
         for _ in 0..1000 {
             let sample = kick.process();
-            assert!(sample >= -1.0 && sample <= 1.0);
+            assert!((-1.0..=1.0).contains(&sample));
         }
     }
+
+    #[test]
+    fn test_909_model_clicks_and_tightens() {
+        let render = |model: KitModel| {
+            let mut kick = Kick::new(44100.0);
+            kick.set_model(model);
+            kick.trigger();
+            (0..44100).map(|_| kick.process()).collect::<Vec<f32>>()
+        };
+        let (tr808, tr909) = (render(KitModel::Tr808), render(KitModel::Tr909));
+        // The wider sweep makes the first 2ms brighter: more sample-to-sample
+        // change against level
+        let brightness = |s: &[f32]| {
+            let edge: f32 = s[..88].windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+            edge / s[..88].iter().map(|x| x * x).sum::<f32>()
+        };
+        assert!(brightness(&tr909) > brightness(&tr808) * 1.5);
+        let length = |s: &[f32]| s.iter().rposition(|x| x.abs() > 0.0).unwrap();
+        assert!(length(&tr909) < length(&tr808));
+    }
+
+    #[test]
+    fn test_click_and_drive() {
+        let render = |click: f32, drive: f32| {
+            let mut kick = Kick::new(44100.0);
+            kick.set_click(click);
+            kick.set_drive(drive);
+            kick.trigger();
+            (0..4410).map(|_| kick.process()).collect::<Vec<f32>>()
+        };
+        let clean = render(0.0, 0.0);
+        let edge = |s: &[f32]| s[..88].windows(2).map(|w| (w[1] - w[0]).powi(2)).sum::<f32>();
+        let clicked = render(1.0, 0.0);
+        assert!(edge(&clicked) > edge(&clean) * 4.0);
+        // The click is over once the attack is
+        assert!(clicked[882..].iter().zip(&clean[882..]).all(|(a, b)| (a - b).abs() < 0.01));
+
+        let energy = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>();
+        let driven = render(0.0, 1.0);
+        assert!(energy(&driven) > energy(&clean) * 1.2);
+        assert!(driven.iter().all(|x| x.abs() <= 1.0));
+    }
 }
@@ -0,0 +1,23 @@
+/// Character of a drum voice, after the machine it imitates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KitModel {
+    /// Boomy sine kick, noisy snare, dark hats, roomy clap
+    Tr808,
+    /// Clicky kick, tonal snare, bright hats, tight clap
+    Tr909,
+}
+
+impl KitModel {
+    /// Model for a UI index: 0 = 808, 1 = 909
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(KitModel::Tr808),
+            1 => Some(KitModel::Tr909),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+}
@@ -1,12 +1,21 @@
 mod kick;
 mod snare;
 mod hihat;
+mod clap;
+mod kit;
 pub mod sequencer;
+mod fill;
+
+use crate::fade::{GainRamp, MUTE_RAMP_MS};
+use crate::pan::pan;
 
 pub use kick::Kick;
 pub use snare::Snare;
 pub use hihat::{ClosedHihat, OpenHihat};
+pub use clap::Clap;
+pub use kit::KitModel;
 pub use sequencer::{DrumSequencer, DrumTrack};
+pub use fill::FillKind;
 pub use sequencer::{BASIC_BEAT, BREAKBEAT, HOUSE_909, MINIMAL, ACID_DRIVE};
 pub use sequencer::{
     INTRO_KICK, INTRO_HATS, BUILD_SNARE, BUILD_ROLL,
@@ -20,57 +29,188 @@ pub struct DrumMachine {
     pub snare: Snare,
     pub closed_hh: ClosedHihat,
     pub open_hh: OpenHihat,
+    pub clap: Clap,
     pub sequencer: DrumSequencer,
 
     // Volumes (0.0 - 1.0)
     kick_vol: f32,
     snare_vol: f32,
     hh_vol: f32,
+    clap_vol: f32,
     master_vol: f32,
+
+    // Boost for voices on accented steps, as a gain
+    accent_gain: f32,
+
+    // Level of each voice's current hit, raised for accented steps
+    voice_gain: [f32; 5],
+
+    // Ramps to 1.0 for voices heard in the mix and to 0.0 for muted ones,
+    // indexed by DrumTrack
+    voice_mix: [GainRamp; 5],
+
+    // Each voice's part of the last sample, indexed by DrumTrack
+    voice_out: [f32; 5],
+
+    // Delay send level per voice, indexed by DrumTrack, and their last sum
+    delay_sends: [f32; 5],
+    delay_send_out: f32,
+
+    // Reverb send level per voice, indexed by DrumTrack, and their last sum
+    reverb_sends: [f32; 5],
+    reverb_send_out: f32,
+
+    // Stereo position per voice, indexed by DrumTrack, and the last frame
+    pans: [f32; 5],
+    stereo_out: (f32, f32),
 }
 
+/// Level boost for voices on an accented step, by default and at full
+/// accent amount
+const ACCENT_GAIN: f32 = 1.4;
+const MAX_ACCENT_GAIN: f32 = 2.0;
+
 impl DrumMachine {
     pub fn new(sample_rate: f32) -> Self {
+        let mut sequencer = DrumSequencer::new();
+        sequencer.set_sample_rate(sample_rate);
         Self {
             kick: Kick::new(sample_rate),
             snare: Snare::new(sample_rate),
             closed_hh: ClosedHihat::new(sample_rate),
             open_hh: OpenHihat::new(sample_rate),
-            sequencer: DrumSequencer::new(),
+            clap: Clap::new(sample_rate),
+            sequencer,
             kick_vol: 0.8,
             snare_vol: 0.7,
             hh_vol: 0.5,
+            clap_vol: 0.6,
             master_vol: 0.8,
+            accent_gain: ACCENT_GAIN,
+            voice_gain: [1.0; 5],
+            voice_mix: [GainRamp::new(sample_rate, MUTE_RAMP_MS); 5],
+            voice_out: [0.0; 5],
+            delay_sends: [0.0; 5],
+            delay_send_out: 0.0,
+            reverb_sends: [0.0; 5],
+            reverb_send_out: 0.0,
+            pans: [0.0; 5],
+            stereo_out: (0.0, 0.0),
         }
     }
 
     /// Process one sample of audio
     pub fn process(&mut self) -> f32 {
-        let kick = self.kick.process() * self.kick_vol;
-        let snare = self.snare.process() * self.snare_vol;
-        let closed = self.closed_hh.process() * self.hh_vol;
-        let open = self.open_hh.process() * self.hh_vol;
+        let [kick_gain, snare_gain, closed_gain, open_gain, clap_gain] = self.voice_gain;
+        let kick = self.kick.process() * self.kick_vol * kick_gain;
+        let snare = self.snare.process() * self.snare_vol * snare_gain;
+        let closed = self.closed_hh.process() * self.hh_vol * closed_gain;
+        let open = self.open_hh.process() * self.hh_vol * open_gain;
+        let clap = self.clap.process() * self.clap_vol * clap_gain;
 
-        (kick + snare + closed + open) * self.master_vol
+        let mut voices = [kick, snare, closed, open, clap];
+        for (voice, mix) in voices.iter_mut().zip(self.voice_mix.iter_mut()) {
+            *voice *= mix.process();
+        }
+        self.voice_out = voices.map(|v| v * self.master_vol);
+        self.delay_send_out = voices.iter().zip(self.delay_sends).map(|(v, send)| v * send).sum::<f32>() * self.master_vol;
+        self.reverb_send_out = voices.iter().zip(self.reverb_sends).map(|(v, send)| v * send).sum::<f32>() * self.master_vol;
+        let (left, right) = voices.iter().zip(self.pans).fold((0.0, 0.0), |(l, r), (&v, p)| {
+            let (vl, vr) = pan(v, p);
+            (l + vl, r + vr)
+        });
+        self.stereo_out = (left * self.master_vol, right * self.master_vol);
+
+        voices.iter().sum::<f32>() * self.master_vol
     }
 
-    /// Tick the sequencer, trigger drums as needed
-    pub fn tick(&mut self) -> Option<usize> {
-        if let Some(step) = self.sequencer.tick() {
-            if step.kick {
-                self.kick.trigger();
-            }
-            if step.snare {
-                self.snare.trigger();
-            }
-            if step.closed_hh {
+    /// Kick contribution to the last processed sample
+    pub fn kick_output(&self) -> f32 {
+        self.voice_output(DrumTrack::Kick)
+    }
+
+    /// One voice's contribution to the last processed sample
+    pub fn voice_output(&self, track: DrumTrack) -> f32 {
+        self.voice_out[track as usize]
+    }
+
+    /// Delay send mix of the last processed sample
+    pub fn delay_send(&self) -> f32 {
+        self.delay_send_out
+    }
+
+    /// Reverb send mix of the last processed sample
+    pub fn reverb_send(&self) -> f32 {
+        self.reverb_send_out
+    }
+
+    /// Stereo mix of the last processed sample, with each voice panned
+    pub fn stereo_output(&self) -> (f32, f32) {
+        self.stereo_out
+    }
+
+    /// Fade one voice out of the mix and its sends, or back in. The voice
+    /// keeps playing, so it comes back in step.
+    pub fn set_muted(&mut self, track: DrumTrack, muted: bool) {
+        self.voice_mix[track as usize].set_target(if muted { 0.0 } else { 1.0 });
+    }
+
+    /// Place one voice in the stereo field (-1.0 left to 1.0 right)
+    pub fn set_pan(&mut self, track: DrumTrack, pan: f32) {
+        self.pans[track as usize] = pan.clamp(-1.0, 1.0);
+    }
+
+    /// How much of one voice goes to the delay bus (0.0 - 1.0)
+    pub fn set_delay_send(&mut self, track: DrumTrack, amount: f32) {
+        self.delay_sends[track as usize] = amount.clamp(0.0, 1.0);
+    }
+
+    /// How much of one voice goes to the reverb bus (0.0 - 1.0)
+    pub fn set_reverb_send(&mut self, track: DrumTrack, amount: f32) {
+        self.reverb_sends[track as usize] = amount.clamp(0.0, 1.0);
+    }
+
+    /// Trigger a single drum voice
+    pub fn trigger(&mut self, track: DrumTrack) {
+        self.trigger_accented(track, false);
+    }
+
+    /// Trigger a single drum voice, louder if `accent` is set
+    pub fn trigger_accented(&mut self, track: DrumTrack, accent: bool) {
+        self.trigger_hit(track, accent, 1.0);
+    }
+
+    /// Trigger a single drum voice at `velocity` (0.0 - 1.0), louder if
+    /// `accent` is set
+    pub fn trigger_hit(&mut self, track: DrumTrack, accent: bool, velocity: f32) {
+        let accent_gain = if accent { self.accent_gain } else { 1.0 };
+        self.voice_gain[track as usize] = velocity.clamp(0.0, 1.0) * accent_gain;
+        match track {
+            DrumTrack::Kick => self.kick.trigger(),
+            DrumTrack::Snare => self.snare.trigger(),
+            DrumTrack::ClosedHH => {
                 // Close open hihat when closed hihat plays
                 self.open_hh.choke();
                 self.closed_hh.trigger();
             }
-            if step.open_hh {
-                self.open_hh.trigger();
+            DrumTrack::OpenHH => self.open_hh.trigger(),
+            DrumTrack::Clap => self.clap.trigger(),
+        }
+    }
+
+    /// Trigger every voice that is active on a step, each at its velocity
+    pub fn trigger_step(&mut self, step: &sequencer::DrumStep) {
+        for track in DrumTrack::ALL {
+            if step.has(track) {
+                self.trigger_hit(track, step.accent, step.gain(track));
             }
+        }
+    }
+
+    /// Tick the sequencer, trigger drums as needed
+    pub fn tick(&mut self) -> Option<usize> {
+        if let Some(step) = self.sequencer.tick() {
+            self.trigger_step(&step);
             return Some(self.sequencer.current_step());
         }
         None
@@ -80,10 +220,23 @@ impl DrumMachine {
         self.sequencer.start();
     }
 
+    pub fn start_at(&mut self, step: usize) {
+        self.sequencer.start_at(step);
+    }
+
     pub fn stop(&mut self) {
         self.sequencer.stop();
     }
 
+    /// Silence all voices immediately
+    pub fn reset(&mut self) {
+        self.kick.reset();
+        self.snare.reset();
+        self.closed_hh.reset();
+        self.open_hh.reset();
+        self.clap.reset();
+    }
+
     pub fn is_playing(&self) -> bool {
         self.sequencer.is_playing()
     }
@@ -105,10 +258,38 @@ impl DrumMachine {
         self.hh_vol = vol.clamp(0.0, 1.0);
     }
 
+    pub fn set_clap_volume(&mut self, vol: f32) {
+        self.clap_vol = vol.clamp(0.0, 1.0);
+    }
+
     pub fn set_master_volume(&mut self, vol: f32) {
         self.master_vol = vol.clamp(0.0, 1.0);
     }
 
+    /// How much accented steps boost every voice (0.0 = no boost, 1.0 =
+    /// twice as loud)
+    pub fn set_accent_amount(&mut self, amount: f32) {
+        self.accent_gain = 1.0 + amount.clamp(0.0, 1.0) * (MAX_ACCENT_GAIN - 1.0);
+    }
+
+    /// Give every voice the character of one machine
+    pub fn set_kit(&mut self, model: KitModel) {
+        for track in DrumTrack::ALL {
+            self.set_voice_model(track, model);
+        }
+    }
+
+    /// Give one voice the character of one machine, for mixed kits
+    pub fn set_voice_model(&mut self, track: DrumTrack, model: KitModel) {
+        match track {
+            DrumTrack::Kick => self.kick.set_model(model),
+            DrumTrack::Snare => self.snare.set_model(model),
+            DrumTrack::ClosedHH => self.closed_hh.set_model(model),
+            DrumTrack::OpenHH => self.open_hh.set_model(model),
+            DrumTrack::Clap => self.clap.set_model(model),
+        }
+    }
+
     // Sound parameter setters
     pub fn set_kick_decay(&mut self, decay: f32) {
         self.kick.set_decay(decay);
@@ -118,6 +299,19 @@ impl DrumMachine {
         self.kick.set_pitch(pitch);
     }
 
+    pub fn set_kick_click(&mut self, click: f32) {
+        self.kick.set_click(click);
+    }
+
+    pub fn set_kick_drive(&mut self, drive: f32) {
+        self.kick.set_drive(drive);
+    }
+
+    /// Tune the kick to a key, see Kick::tune_to()
+    pub fn tune_kick(&mut self, pitch_class: u8) -> f32 {
+        self.kick.tune_to(pitch_class)
+    }
+
     pub fn set_snare_tone(&mut self, tone: f32) {
         self.snare.set_tone(tone);
     }
@@ -125,6 +319,10 @@ impl DrumMachine {
     pub fn set_snare_snap(&mut self, snap: f32) {
         self.snare.set_snap(snap);
     }
+
+    pub fn set_clap_decay(&mut self, decay: f32) {
+        self.clap.set_decay(decay);
+    }
 }
 
 impl Default for DrumMachine {
@@ -1,13 +1,99 @@
-const STEPS: usize = 16;
+use super::fill::{derive_fill, FillKind};
+use crate::rng::Rng;
+use crate::clock::{Clock, Nudger, MAX_NUDGE};
+use crate::sequencer::{Direction, FULL_LEVEL, MAX_RATCHET, MAX_STEPS, STEPS};
+use crate::slots::Variation;
+use crate::trig::Trig;
+
+/// Bytes per step in the packed format: track bits, probability, condition,
+/// ratchet, nudge, then a velocity per track
+pub const DRUM_STEP_BYTES: usize = 5 + DrumTrack::ALL.len();
+/// Rate used until set_sample_rate is called
 const SAMPLE_RATE: f32 = 44100.0;
 
 /// Which drums are active on a step
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct DrumStep {
     pub kick: bool,
     pub snare: bool,
     pub closed_hh: bool,
     pub open_hh: bool,
+    pub clap: bool,
+    /// Play the voices on this step louder
+    pub accent: bool,
+    /// Chance and condition for playing on each pass
+    pub trig: Trig,
+    /// Times the voices are struck within the step (1-4)
+    pub ratchet: u8,
+    /// Timing offset in percent of a step (-50 to 50)
+    pub nudge: i8,
+    /// Level of each track's hit (0-127), indexed by DrumTrack
+    pub velocity: [u8; 5],
+}
+
+impl DrumStep {
+    /// Tracks packed as bits: 1 = kick, 2 = snare, 4 = closed hat,
+    /// 8 = open hat, 32 = clap, plus 16 = accent
+    pub fn bits(&self) -> u8 {
+        self.kick as u8
+            | (self.snare as u8) << 1
+            | (self.closed_hh as u8) << 2
+            | (self.open_hh as u8) << 3
+            | (self.accent as u8) << 4
+            | (self.clap as u8) << 5
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            kick: bits & 1 != 0,
+            snare: bits & 2 != 0,
+            closed_hh: bits & 4 != 0,
+            open_hh: bits & 8 != 0,
+            clap: bits & 32 != 0,
+            accent: bits & 16 != 0,
+            trig: Trig::ALWAYS,
+            ratchet: 1,
+            nudge: 0,
+            velocity: [FULL_LEVEL; 5],
+        }
+    }
+
+    /// Level of `track`'s hit as a gain (0.0 - 1.0)
+    pub fn gain(&self, track: DrumTrack) -> f32 {
+        self.velocity[track as usize].min(FULL_LEVEL) as f32 / FULL_LEVEL as f32
+    }
+
+    /// Number of hits in the step, 0 counting as 1
+    pub fn hits(&self) -> u8 {
+        self.ratchet.clamp(1, MAX_RATCHET)
+    }
+
+    /// Whether `track` plays on this step
+    pub fn has(&self, track: DrumTrack) -> bool {
+        match track {
+            DrumTrack::Kick => self.kick,
+            DrumTrack::Snare => self.snare,
+            DrumTrack::ClosedHH => self.closed_hh,
+            DrumTrack::OpenHH => self.open_hh,
+            DrumTrack::Clap => self.clap,
+        }
+    }
+
+    fn track_mut(&mut self, track: DrumTrack) -> &mut bool {
+        match track {
+            DrumTrack::Kick => &mut self.kick,
+            DrumTrack::Snare => &mut self.snare,
+            DrumTrack::ClosedHH => &mut self.closed_hh,
+            DrumTrack::OpenHH => &mut self.open_hh,
+            DrumTrack::Clap => &mut self.clap,
+        }
+    }
+}
+
+impl Default for DrumStep {
+    fn default() -> Self {
+        Self::from_bits(0)
+    }
 }
 
 /// Which track we're editing
@@ -17,30 +103,98 @@ pub enum DrumTrack {
     Snare,
     ClosedHH,
     OpenHH,
+    Clap,
+}
+
+impl DrumTrack {
+    pub const ALL: [DrumTrack; 5] = [DrumTrack::Kick, DrumTrack::Snare, DrumTrack::ClosedHH, DrumTrack::OpenHH, DrumTrack::Clap];
+
+    /// Track for a UI index: 0 = kick, 1 = snare, 2 = closed hat, 3 = open hat,
+    /// 4 = clap
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(DrumTrack::Kick),
+            1 => Some(DrumTrack::Snare),
+            2 => Some(DrumTrack::ClosedHH),
+            3 => Some(DrumTrack::OpenHH),
+            4 => Some(DrumTrack::Clap),
+            _ => None,
+        }
+    }
 }
 
-/// 16-step drum sequencer with 4 tracks
+/// Drum sequencer with 5 tracks and up to MAX_STEPS steps
 pub struct DrumSequencer {
-    steps: [DrumStep; STEPS],
-    current: usize,
-    sample_counter: u32,
-    samples_per_step: u32,
+    // Pattern steps; only the first `length` are played
+    steps: [DrumStep; MAX_STEPS],
+    length: usize,
     playing: bool,
-    tempo: f32,
+
+    // Loop length of each track, indexed by DrumTrack; a track loops over
+    // the first steps of the pattern when this is shorter than the pattern
+    track_lengths: [usize; 5],
+    // Next step each track plays from
+    track_steps: [usize; 5],
+
+    // Next step to play, and how many have played since the top of the
+    // pattern
+    current: usize,
+    count: u32,
+    direction: Direction,
+
+    // Step timing when not following a shared clock, and where the nudged
+    // steps fall against it
+    clock: Clock,
+    nudger: Nudger,
+
+    // Fill queued for the next bar, and the fill playing in this one
+    queued_fill: Option<[DrumStep; MAX_STEPS]>,
+    bar_fill: Option<[DrumStep; MAX_STEPS]>,
+
+    // Automatic fill on the last bar of every `auto_fill_bars` bars
+    auto_fill: Option<(u32, FillKind)>,
+    bars_started: u32,
+
+    // Completed passes through the pattern since start, for trig conditions
+    passes: u32,
+
+    // The step playing, its number of hits and the next hit due
+    played: DrumStep,
+    hits: u8,
+    next_hit: u8,
+
+    // Alternate pattern played on a bar with probability `variation_chance`,
+    // when no fill is due; always as long as the pattern
+    variation: Option<[DrumStep; MAX_STEPS]>,
+    variation_chance: f32,
+    rng: Rng,
 }
 
 impl DrumSequencer {
     pub fn new() -> Self {
         let mut seq = Self {
-            steps: [DrumStep::default(); STEPS],
-            current: 0,
-            sample_counter: 0,
-            samples_per_step: 0,
+            steps: [DrumStep::default(); MAX_STEPS],
+            length: STEPS,
             playing: false,
-            tempo: 120.0,
+            track_lengths: [MAX_STEPS; 5],
+            track_steps: [0; 5],
+            current: 0,
+            count: 0,
+            direction: Direction::Forward,
+            clock: Clock::new(SAMPLE_RATE),
+            nudger: Nudger::new(),
+            queued_fill: None,
+            bar_fill: None,
+            auto_fill: None,
+            bars_started: 0,
+            passes: 0,
+            played: DrumStep::default(),
+            hits: 1,
+            next_hit: 1,
+            variation: None,
+            variation_chance: 0.0,
+            rng: Rng::new(0xd2),
         };
-        seq.set_tempo(120.0);
-
         // Initialize with a basic 4/4 beat
         seq.load_pattern(&BASIC_BEAT);
 
@@ -48,45 +202,148 @@ impl DrumSequencer {
     }
 
     pub fn set_tempo(&mut self, bpm: f32) {
-        self.tempo = bpm.clamp(60.0, 300.0);
-        let sixteenths_per_second = (self.tempo / 60.0) * 4.0;
-        self.samples_per_step = (SAMPLE_RATE / sixteenths_per_second) as u32;
+        self.clock.set_tempo(bpm);
+    }
+
+    /// Delay every other 16th, matching the synth sequencer's swing
+    pub fn set_swing(&mut self, amount: f32) {
+        self.clock.set_swing(amount);
+    }
+
+    /// Rate `tick` is called at, which the step length is counted in
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.clock.set_sample_rate(sample_rate);
+    }
+
+    /// Restart the random source used for trig chances, variations and
+    /// fills, so a run from here plays the same way every time
+    pub fn set_seed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
     }
 
     pub fn set_step(&mut self, index: usize, track: DrumTrack, active: bool) {
-        if index < STEPS {
-            match track {
-                DrumTrack::Kick => self.steps[index].kick = active,
-                DrumTrack::Snare => self.steps[index].snare = active,
-                DrumTrack::ClosedHH => self.steps[index].closed_hh = active,
-                DrumTrack::OpenHH => self.steps[index].open_hh = active,
-            }
+        if let Some(step) = self.step_mut(index) {
+            *step.track_mut(track) = active;
         }
     }
 
     pub fn toggle_step(&mut self, index: usize, track: DrumTrack) {
-        if index < STEPS {
-            match track {
-                DrumTrack::Kick => self.steps[index].kick = !self.steps[index].kick,
-                DrumTrack::Snare => self.steps[index].snare = !self.steps[index].snare,
-                DrumTrack::ClosedHH => self.steps[index].closed_hh = !self.steps[index].closed_hh,
-                DrumTrack::OpenHH => self.steps[index].open_hh = !self.steps[index].open_hh,
-            }
+        if let Some(step) = self.step_mut(index) {
+            let hit = step.track_mut(track);
+            *hit = !*hit;
+        }
+    }
+
+    pub fn set_accent(&mut self, index: usize, accent: bool) {
+        if let Some(step) = self.step_mut(index) {
+            step.accent = accent;
+        }
+    }
+
+    /// Set when a step plays; applies to all of its voices
+    pub fn set_trig(&mut self, index: usize, trig: Trig) {
+        if let Some(step) = self.step_mut(index) {
+            step.trig = trig;
+        }
+    }
+
+    /// Push a step's voices late (positive) or pull them early (negative)
+    /// by up to 50% of a step
+    pub fn set_nudge(&mut self, index: usize, percent: i8) {
+        if let Some(step) = self.step_mut(index) {
+            step.nudge = percent.clamp(-MAX_NUDGE, MAX_NUDGE);
+        }
+    }
+
+    /// Level of one track's hit on a step (0-127)
+    pub fn set_velocity(&mut self, index: usize, track: DrumTrack, velocity: u8) {
+        if let Some(step) = self.step_mut(index) {
+            step.velocity[track as usize] = velocity.min(FULL_LEVEL);
+        }
+    }
+
+    /// Strike all of a step's voices `hits` times within the step (1-4)
+    pub fn set_ratchet(&mut self, index: usize, hits: u8) {
+        if let Some(step) = self.step_mut(index) {
+            step.ratchet = hits.clamp(1, MAX_RATCHET);
         }
     }
 
     pub fn get_step(&self, index: usize) -> Option<&DrumStep> {
-        self.steps.get(index)
+        self.steps().get(index)
+    }
+
+    fn step_mut(&mut self, index: usize) -> Option<&mut DrumStep> {
+        self.steps[..self.length].get_mut(index)
+    }
+
+    /// Steps in the pattern (1 - MAX_STEPS)
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Loop `track` over the first `steps` steps of the pattern on its own
+    /// (1 - MAX_STEPS), against the pattern's length, so tracks of
+    /// different lengths drift against each other. The other step settings
+    /// follow the pattern.
+    pub fn set_track_length(&mut self, track: DrumTrack, steps: usize) {
+        self.track_lengths[track as usize] = steps.clamp(1, MAX_STEPS);
+        self.track_steps[track as usize] %= self.track_length(track);
+    }
+
+    /// Steps `track` loops over; never longer than the pattern
+    pub fn track_length(&self, track: DrumTrack) -> usize {
+        self.track_lengths[track as usize].min(self.length)
+    }
+
+    /// Step `track` plays next
+    pub fn track_step(&self, track: DrumTrack) -> usize {
+        self.track_steps[track as usize]
+    }
+
+    /// Order the steps play in from the next step on
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
     }
 
     pub fn start(&mut self) {
+        self.start_at(0);
+    }
+
+    /// Start `step` steps into the pattern's order, as if that many steps
+    /// had played since the top, so the pass count trig conditions use and
+    /// the phase of shorter tracks or directions carry on from there
+    pub fn start_at(&mut self, step: usize) {
         self.playing = true;
-        self.current = 0;
-        self.sample_counter = 0;
+        self.count = step as u32;
+        self.place();
+        self.clock.start_at(step % self.length);
+        self.nudger.reset();
+        self.bar_fill = None;
+        self.bars_started = step.div_ceil(self.length) as u32;
+        self.passes = step as u32 / self.length as u32;
+        self.hits = 1;
+        self.next_hit = 1;
+    }
+
+    /// Play a fill derived from the pattern in place of the next bar
+    pub fn queue_fill(&mut self, kind: FillKind) {
+        self.queued_fill = Some(derive_fill(self.steps(), kind));
+    }
+
+    /// Play a fill on the last bar of every `every_bars` bars, or never if
+    /// `every_bars` is 0
+    pub fn set_auto_fill(&mut self, every_bars: u32, kind: FillKind) {
+        self.auto_fill = (every_bars > 0).then_some((every_bars, kind));
     }
 
     pub fn stop(&mut self) {
         self.playing = false;
+        self.clock.stop();
     }
 
     pub fn is_playing(&self) -> bool {
@@ -97,33 +354,297 @@ impl DrumSequencer {
         self.current
     }
 
-    /// Tick the sequencer. Returns Some(DrumStep) when advancing.
+    /// Number of samples between steps at the current tempo, including the
+    /// fraction that is carried from step to step
+    pub fn samples_per_step(&self) -> f64 {
+        self.clock.samples_per_step()
+    }
+
+    pub fn steps_per_bar(&self) -> usize {
+        STEPS
+    }
+
+    /// Average step length in milliseconds
+    pub fn step_duration_ms(&self) -> f32 {
+        60000.0 / self.clock.tempo() / 4.0
+    }
+
+    /// Tick the sequencer. Returns Some(DrumStep) when advancing, and again
+    /// for each further hit of a ratcheted step.
     pub fn tick(&mut self) -> Option<DrumStep> {
-        if !self.playing || self.samples_per_step == 0 {
+        if !self.playing {
             return None;
         }
+        let step_started = self.clock.tick();
+        self.advance(step_started, self.clock.progress())
+    }
 
-        self.sample_counter += 1;
+    /// Tick in step with a shared clock, after the clock's own tick, in
+    /// place of the sequencer's own sample count
+    pub fn tick_with(&mut self, clock: &Clock) -> Option<DrumStep> {
+        if !self.playing {
+            return None;
+        }
+        self.advance(clock.step_started(), clock.progress())
+    }
 
-        if self.sample_counter >= self.samples_per_step {
-            self.sample_counter = 0;
-            let step = self.steps[self.current];
-            self.current = (self.current + 1) % STEPS;
-            Some(step)
+    /// How many steps into a pass of the pattern the next step is; 0 when
+    /// it starts a new pass, whatever the direction
+    pub fn pass_position(&self) -> usize {
+        (self.count % self.length as u32) as usize
+    }
+
+    /// Play the next step once its nudged time comes, or the next hit of a
+    /// ratcheted step when it is due. `progress` is through the grid step.
+    fn advance(&mut self, step_started: bool, progress: f32) -> Option<DrumStep> {
+        let due = self.nudger.due(step_started, progress, self.upcoming().nudge);
+        let elapsed = self.nudger.elapsed(progress);
+        if due {
+            self.nudger.fire(progress);
+            if self.pass_position() == 0 {
+                self.start_bar();
+            }
+            let source = self.bar_fill.as_ref().unwrap_or(&self.steps);
+            let mut step = source[self.current];
+            for track in DrumTrack::ALL {
+                let own = &source[self.track_steps[track as usize]];
+                *step.track_mut(track) = own.has(track);
+                step.velocity[track as usize] = own.velocity[track as usize];
+            }
+            let fires = step.trig.fires(self.passes, &mut self.rng);
+            self.count = self.count.wrapping_add(1);
+            self.place();
+            if self.pass_position() == 0 {
+                self.passes += 1;
+            }
+            // A step whose trig doesn't fire still advances, silently
+            self.played = if fires { step } else { DrumStep::default() };
+            self.hits = self.played.hits();
+            self.next_hit = 1;
+            Some(self.played)
+        } else if self.next_hit < self.hits && elapsed >= self.next_hit as f32 / self.hits as f32 {
+            self.next_hit += 1;
+            Some(self.played)
         } else {
             None
         }
     }
 
-    pub fn load_pattern(&mut self, pattern: &[DrumStep; STEPS]) {
-        self.steps = *pattern;
+    /// Work out the steps the pattern and each track play next from the
+    /// count of steps played
+    fn place(&mut self) {
+        self.current = self.direction.step_at(self.count, self.length, &mut self.rng);
+        for track in DrumTrack::ALL {
+            let length = self.track_length(track);
+            self.track_steps[track as usize] = if length == self.length {
+                self.current
+            } else {
+                self.direction.step_at(self.count, length, &mut self.rng)
+            };
+        }
+    }
+
+    /// Step that plays next. A new bar's fill isn't picked until it starts,
+    /// so its first step is timed by the pattern's own step.
+    fn upcoming(&self) -> &DrumStep {
+        match &self.bar_fill {
+            Some(fill) if self.pass_position() != 0 => &fill[self.current],
+            _ => &self.steps[self.current],
+        }
+    }
+
+    /// Pick what the pass that is starting plays
+    fn start_bar(&mut self) {
+        self.bars_started += 1;
+        self.bar_fill = self.queued_fill.take();
+        if let Some((every, kind)) = self.auto_fill {
+            if self.bar_fill.is_none() && self.bars_started.is_multiple_of(every) {
+                self.bar_fill = Some(derive_fill(self.steps(), kind));
+            }
+        }
+        if self.bar_fill.is_none() && self.rng.chance(self.variation_chance) {
+            self.bar_fill = self.variation;
+        }
+    }
+
+    /// The pattern's steps, `length()` of them
+    pub fn steps(&self) -> &[DrumStep] {
+        &self.steps[..self.length]
+    }
+
+    /// Replace the pattern, taking its length from `pattern` (1 - MAX_STEPS
+    /// steps; longer patterns are cut short). The variation belongs to the
+    /// pattern it was written for and is dropped, along with its chance; a
+    /// queued fill of another length no longer fits and goes too.
+    pub fn load_pattern(&mut self, pattern: &[DrumStep]) {
+        let length = pattern.len().min(MAX_STEPS);
+        if length == 0 {
+            return;
+        }
+        self.steps[..length].copy_from_slice(&pattern[..length]);
+        if length != self.length {
+            self.resize(length);
+        }
+        self.variation = None;
+        self.variation_chance = 0.0;
+    }
+
+    // Change the length, dropping whatever was built for the old one.
+    // The pass in progress carries on from the same step if it still fits.
+    fn resize(&mut self, length: usize) {
+        self.length = length;
+        self.current %= length;
+        for track in DrumTrack::ALL {
+            self.track_steps[track as usize] %= self.track_length(track);
+        }
+        self.variation = None;
+        self.queued_fill = None;
+        self.bar_fill = None;
+    }
+
+    /// The whole pattern as DRUM_STEP_BYTES per step: DrumStep::bits(),
+    /// then the trig's probability and condition, the ratchet, the nudge
+    /// and the velocity of each track
+    pub fn pattern_bytes(&self) -> Vec<u8> {
+        self.steps()
+            .iter()
+            .flat_map(|s| {
+                let [probability, condition] = s.trig.to_bytes();
+                [s.bits(), probability, condition, s.hits(), s.nudge as u8].into_iter().chain(s.velocity)
+            })
+            .collect()
+    }
+
+    /// Replace the whole pattern from the packed format, taking its length
+    /// from `bytes`. Returns false and leaves the pattern untouched if
+    /// `bytes` is not a whole number of steps, 1 - MAX_STEPS of them.
+    pub fn load_pattern_bytes(&mut self, bytes: &[u8]) -> bool {
+        match parse_pattern(bytes) {
+            Some(steps) => {
+                self.load_pattern(&steps);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the alternate pattern from the packed format. Returns false and
+    /// keeps the current variation unless `bytes` holds a pattern as long as
+    /// the one playing.
+    pub fn set_variation_bytes(&mut self, bytes: &[u8]) -> bool {
+        match parse_pattern(bytes) {
+            Some(steps) if steps.len() == self.length => {
+                let mut variation = self.steps;
+                variation[..self.length].copy_from_slice(&steps);
+                self.variation = Some(variation);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn clear_variation(&mut self) {
+        self.variation = None;
+    }
+
+    /// Chance (0.0 - 1.0) that any bar plays the variation
+    pub fn set_variation_chance(&mut self, chance: f32) {
+        self.variation_chance = chance.clamp(0.0, 1.0);
+    }
+
+    /// The variation and its chance, for storing with the pattern
+    pub fn variation(&self) -> Option<Variation<DrumStep>> {
+        self.variation.map(|steps| Variation {
+            steps: steps[..self.length].to_vec(),
+            chance: self.variation_chance,
+        })
+    }
+
+    /// Put back a variation stored with the pattern just loaded. One of
+    /// another length doesn't fit and is ignored.
+    pub fn load_variation(&mut self, variation: Option<&Variation<DrumStep>>) {
+        let Some(variation) = variation.filter(|v| v.steps.len() == self.length) else {
+            return;
+        };
+        let mut steps = self.steps;
+        steps[..self.length].copy_from_slice(&variation.steps);
+        self.variation = Some(steps);
+        self.set_variation_chance(variation.chance);
     }
 
     pub fn clear(&mut self) {
-        self.steps = [DrumStep::default(); STEPS];
+        self.steps = [DrumStep::default(); MAX_STEPS];
+    }
+
+    // Whole-pattern operations
+
+    /// Move every step `n` steps later (negative moves them earlier),
+    /// wrapping round the end of the pattern
+    pub fn rotate(&mut self, n: i32) {
+        let length = self.length;
+        self.steps[..length].rotate_right(n.rem_euclid(length as i32) as usize);
+    }
+
+    /// Play the pattern backwards
+    pub fn reverse(&mut self) {
+        self.steps[..self.length].reverse();
+    }
+
+    /// Replace the second half of the pattern with the first half played
+    /// backwards
+    pub fn mirror(&mut self) {
+        let length = self.length;
+        for i in 0..length / 2 {
+            self.steps[length - 1 - i] = self.steps[i];
+        }
+    }
+
+    /// Accent every unaccented step and clear the accents that were set
+    pub fn invert_accents(&mut self) {
+        for step in self.steps[..self.length].iter_mut() {
+            step.accent = !step.accent;
+        }
+    }
+
+    /// Play the pattern, and its variation, twice over in a pattern of
+    /// twice the length. Returns false if that would be longer than
+    /// MAX_STEPS.
+    pub fn double(&mut self) -> bool {
+        let length = self.length;
+        if length * 2 > MAX_STEPS {
+            return false;
+        }
+        self.steps.copy_within(..length, length);
+        if let Some(variation) = &mut self.variation {
+            variation.copy_within(..length, length);
+        }
+        // A fill is rebuilt from the new pattern when it is next due
+        self.queued_fill = None;
+        self.bar_fill = None;
+        self.length = length * 2;
+        true
     }
 }
 
+/// Unpack a pattern of DRUM_STEP_BYTES per step, 1 - MAX_STEPS steps long
+pub fn parse_pattern(bytes: &[u8]) -> Option<Vec<DrumStep>> {
+    let length = bytes.len() / DRUM_STEP_BYTES;
+    if !bytes.len().is_multiple_of(DRUM_STEP_BYTES) || !(1..=MAX_STEPS).contains(&length) {
+        return None;
+    }
+    let steps = bytes
+        .chunks_exact(DRUM_STEP_BYTES)
+        .map(|b| DrumStep {
+            trig: Trig::from_bytes(b[1], b[2]),
+            ratchet: b[3].clamp(1, MAX_RATCHET),
+            nudge: (b[4] as i8).clamp(-MAX_NUDGE, MAX_NUDGE),
+            velocity: std::array::from_fn(|track| b[5 + track].min(FULL_LEVEL)),
+            ..DrumStep::from_bits(b[0])
+        })
+        .collect();
+    Some(steps)
+}
+
 impl Default for DrumSequencer {
     fn default() -> Self {
         Self::new()
@@ -134,7 +655,15 @@ impl Default for DrumSequencer {
 
 /// Helper to create drum steps
 const fn d(kick: bool, snare: bool, closed_hh: bool, open_hh: bool) -> DrumStep {
-    DrumStep { kick, snare, closed_hh, open_hh }
+    DrumStep { kick, snare, closed_hh, open_hh, clap: false, accent: false, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, velocity: [FULL_LEVEL; 5] }
+}
+
+/// Velocity of ghost notes in the presets
+const GHOST_LEVEL: u8 = 60;
+
+/// Play every voice on a preset step as a ghost note
+const fn ghost(step: DrumStep) -> DrumStep {
+    DrumStep { velocity: [GHOST_LEVEL; 5], ..step }
 }
 
 /// Basic 4/4 house beat
@@ -161,7 +690,7 @@ pub static BASIC_BEAT: [DrumStep; 16] = [
 pub static BREAKBEAT: [DrumStep; 16] = [
     d(true,  false, true,  false),  // 1
     d(false, false, false, true ),
-    d(false, true,  true,  false),
+    ghost(d(false, true,  true,  false)),
     d(false, false, true,  false),
     d(false, false, true,  false),  // 2
     d(false, true,  false, true ),
@@ -169,7 +698,7 @@ pub static BREAKBEAT: [DrumStep; 16] = [
     d(false, false, true,  false),
     d(false, false, true,  false),  // 3
     d(false, false, false, true ),
-    d(false, true,  true,  false),
+    ghost(d(false, true,  true,  false)),
     d(false, false, true,  false),
     d(true,  false, true,  false),  // 4
     d(false, true,  false, true ),
@@ -464,24 +993,25 @@ pub static SHUFFLE: [DrumStep; 16] = [
     d(true,  false, true,  false),
     d(false, false, false, false),
     d(false, false, false, true ),
-    d(false, false, true,  false),
+    ghost(d(false, false, true,  false)),
     d(true,  true,  true,  false),
     d(false, false, false, false),
     d(false, false, false, true ),
-    d(false, false, true,  false),
+    ghost(d(false, false, true,  false)),
     d(true,  false, true,  false),
     d(false, false, false, false),
     d(false, false, false, true ),
-    d(false, false, true,  false),
+    ghost(d(false, false, true,  false)),
     d(true,  true,  true,  false),
     d(false, false, false, false),
     d(false, false, false, true ),
-    d(false, false, true,  false),
+    ghost(d(false, false, true,  false)),
 ];
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::trig::{Condition, ALWAYS_PLAYS};
 
     #[test]
     fn test_sequencer_creation() {
@@ -510,6 +1040,154 @@ mod tests {
         assert!(!seq.steps[0].kick);
     }
 
+    #[test]
+    fn test_auto_fill_replaces_last_bar() {
+        let mut seq = DrumSequencer::new();
+        seq.load_pattern(&BASIC_BEAT);
+        seq.set_auto_fill(2, FillKind::Roll);
+        seq.start();
+
+        let mut played = Vec::new();
+        while played.len() < STEPS * 3 {
+            if let Some(step) = seq.tick() {
+                played.push(step);
+            }
+        }
+        let bar = |n: usize| &played[n * STEPS..(n + 1) * STEPS];
+        assert!(bar(0).iter().zip(&BASIC_BEAT).all(|(a, b)| a.bits() == b.bits()));
+        assert!(bar(1)[12..].iter().all(|s| s.snare));
+        assert!(bar(2).iter().zip(&BASIC_BEAT).all(|(a, b)| a.bits() == b.bits()));
+    }
+
+    #[test]
+    fn test_pattern_bytes_round_trip() {
+        let mut seq = DrumSequencer::new();
+        seq.load_pattern(&BASIC_BEAT);
+        seq.set_accent(4, true);
+        seq.set_velocity(4, DrumTrack::Snare, 200);
+        seq.set_velocity(0, DrumTrack::Kick, 40);
+        let bytes = seq.pattern_bytes();
+        assert_eq!(bytes[0] & 1, 1);
+        assert_eq!(bytes[4 * DRUM_STEP_BYTES] & 16, 16);
+        assert_eq!(&bytes[1..10], &[100, 0, 1, 0, 40, 127, 127, 127, 127]);
+        assert_eq!(bytes[4 * DRUM_STEP_BYTES + 6], 127);
+
+        let mut other = DrumSequencer::new();
+        assert!(other.load_pattern_bytes(&bytes));
+        assert_eq!(other.pattern_bytes(), bytes);
+        assert!(!other.load_pattern_bytes(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn test_polymetric_track_lengths() {
+        let mut seq = DrumSequencer::new();
+        seq.clear();
+        seq.set_step(0, DrumTrack::Kick, true);
+        seq.set_step(0, DrumTrack::ClosedHH, true);
+        seq.set_accent(0, true);
+        seq.set_velocity(0, DrumTrack::Kick, 50);
+        seq.set_track_length(DrumTrack::Kick, 3);
+        assert_eq!(seq.track_length(DrumTrack::Kick), 3);
+        assert_eq!(seq.track_length(DrumTrack::ClosedHH), STEPS);
+        seq.start();
+
+        let mut played = Vec::new();
+        while played.len() < STEPS * 2 {
+            if let Some(step) = seq.tick() {
+                played.push(step);
+            }
+        }
+        let steps_with = |f: fn(&DrumStep) -> bool| (0..played.len()).filter(|&i| f(&played[i])).collect::<Vec<_>>();
+        // The kick loops every 3 steps across the bar line; the hat and the
+        // accent follow the 16-step pattern
+        assert_eq!(steps_with(|s| s.kick), (0..32).step_by(3).collect::<Vec<_>>());
+        assert_eq!(steps_with(|s| s.closed_hh), vec![0, 16]);
+        assert_eq!(steps_with(|s| s.accent), vec![0, 16]);
+        // Each kick keeps the velocity of the step it loops from
+        assert!(played.iter().filter(|s| s.kick).all(|s| s.velocity[DrumTrack::Kick as usize] == 50));
+
+        // A track can't outrun a pattern that gets shorter
+        assert!(seq.load_pattern_bytes(&seq.pattern_bytes()[..2 * DRUM_STEP_BYTES]));
+        assert_eq!(seq.track_length(DrumTrack::Kick), 2);
+        assert!(seq.track_step(DrumTrack::Kick) < 2);
+    }
+
+    #[test]
+    fn test_start_at_matches_playing_through() {
+        let mut seq = DrumSequencer::new();
+        seq.clear();
+        seq.set_step(0, DrumTrack::Kick, true);
+        seq.set_step(4, DrumTrack::Snare, true);
+        seq.set_trig(4, Trig { probability: ALWAYS_PLAYS, condition: Condition::Ratio { pass: 2, cycle: 2 } });
+        seq.set_track_length(DrumTrack::Kick, 3);
+        let play = |seq: &mut DrumSequencer, steps: usize| {
+            let mut played = Vec::new();
+            while played.len() < steps {
+                if let Some(step) = seq.tick() {
+                    played.push((step.kick, step.snare));
+                }
+            }
+            played
+        };
+        seq.start();
+        let through = play(&mut seq, STEPS * 4);
+
+        // A song position partway into the second bar picks up the kick's
+        // phase across the bar line and the snare's pass count
+        for position in [STEPS + 5, STEPS * 2, STEPS * 3 - 1] {
+            seq.start_at(position);
+            assert_eq!(play(&mut seq, STEPS * 4 - position), through[position..], "from step {position}");
+        }
+    }
+
+    #[test]
+    fn test_pattern_operations() {
+        let mut seq = DrumSequencer::new();
+        seq.load_pattern(&BASIC_BEAT);
+        let kicks = |seq: &DrumSequencer| seq.steps().iter().map(|s| s.kick).collect::<Vec<_>>();
+        let original = kicks(&seq);
+
+        seq.rotate(1);
+        assert!(seq.steps()[1].kick && !seq.steps()[0].kick);
+        seq.rotate(-1);
+        seq.reverse();
+        assert!(kicks(&seq).iter().eq(original.iter().rev()));
+        seq.reverse();
+
+        seq.set_accent(0, true);
+        seq.mirror();
+        assert!(seq.steps()[15].accent);
+        seq.invert_accents();
+        assert!(!seq.steps()[0].accent && seq.steps()[1].accent);
+
+        assert!(seq.double());
+        assert_eq!(seq.length(), 32);
+        assert_eq!(seq.pattern_bytes()[..16 * DRUM_STEP_BYTES], seq.pattern_bytes()[16 * DRUM_STEP_BYTES..]);
+        assert!(!seq.double());
+        // A fill keeps the first bar of a two-bar pattern and reworks the last
+        seq.queue_fill(FillKind::Roll);
+        let fill = seq.queued_fill.unwrap();
+        assert_eq!(fill[12].bits(), seq.steps()[12].bits());
+        assert!(fill[28..32].iter().all(|s| s.snare && !s.kick));
+    }
+
+    #[test]
+    fn test_ratchet_repeats_step() {
+        let mut seq = DrumSequencer::new();
+        seq.set_sample_rate(48000.0);
+        seq.set_ratchet(0, 3);
+        seq.start();
+
+        let mut hits = Vec::new();
+        for i in 1..=6000 * 2 {
+            if seq.tick().is_some() {
+                hits.push(i);
+            }
+        }
+        // Step 0 split in three, then step 1 plays once
+        assert_eq!(hits, vec![6000, 8000, 10000, 12000]);
+    }
+
     #[test]
     fn test_basic_beat_has_kicks() {
         let kick_count = BASIC_BEAT.iter().filter(|s| s.kick).count();
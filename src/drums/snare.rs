@@ -1,7 +1,11 @@
 use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
 
-/// 909-style snare drum synthesizer
+use super::kit::KitModel;
+
+/// 808/909-style snare drum synthesizer
 /// Combines a pitched tone with filtered noise for that crisp snap
+#[wasm_bindgen]
 pub struct Snare {
     sample_rate: f32,
 
@@ -22,12 +26,16 @@ pub struct Snare {
 
     // Mix parameters
     tone_mix: f32,     // How much tone vs noise
+    noise_level: f32,  // Level of the wires, set by the kit model
     snap: f32,         // Attack sharpness
+    decay: f32,        // Decay knob, kept to recompute the rates
 
     active: bool,
 }
 
+#[wasm_bindgen]
 impl Snare {
+    #[wasm_bindgen(constructor)]
     pub fn new(sample_rate: f32) -> Self {
         let mut snare = Self {
             sample_rate,
@@ -41,13 +49,20 @@ impl Snare {
             noise_hp_state: 0.0,
             noise_lp_state: 0.0,
             tone_mix: 0.4,
+            noise_level: 1.0,
             snap: 0.7,
+            decay: 0.3,
             active: false,
         };
         snare.set_decay(0.3);
         snare
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_decay(self.decay);
+    }
+
     pub fn trigger(&mut self) {
         self.tone_phase = 0.0;
         self.tone_env = 1.0;
@@ -55,6 +70,15 @@ impl Snare {
         self.active = true;
     }
 
+    /// Silence the voice immediately
+    pub fn reset(&mut self) {
+        self.tone_env = 0.0;
+        self.noise_env = 0.0;
+        self.noise_hp_state = 0.0;
+        self.noise_lp_state = 0.0;
+        self.active = false;
+    }
+
     pub fn process(&mut self) -> f32 {
         if !self.active {
             return 0.0;
@@ -86,7 +110,7 @@ impl Snare {
 
         // === Mix ===
         let tone_out = tone * self.tone_env * self.tone_mix;
-        let noise_out = filtered_noise * self.noise_env * (1.0 - self.tone_mix * 0.5);
+        let noise_out = filtered_noise * self.noise_env * (1.0 - self.tone_mix * 0.5) * self.noise_level;
 
         let output = tone_out + noise_out;
 
@@ -102,10 +126,17 @@ impl Snare {
         soft_clip(output * 2.0) * 0.7
     }
 
+    /// Fill `output` with consecutive samples
+    pub fn process_block(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
     /// Generate white noise using LFSR
     fn generate_noise(&mut self) -> f32 {
         // 16-bit LFSR with taps at 16, 14, 13, 11
-        let bit = ((self.noise_state >> 0) ^ (self.noise_state >> 2)
+        let bit = (self.noise_state ^ (self.noise_state >> 2)
                  ^ (self.noise_state >> 3) ^ (self.noise_state >> 5)) & 1;
         self.noise_state = (self.noise_state >> 1) | (bit << 15);
 
@@ -116,6 +147,7 @@ impl Snare {
     /// Set overall decay (0.0 = tight, 1.0 = long)
     pub fn set_decay(&mut self, decay: f32) {
         let decay = decay.clamp(0.0, 1.0);
+        self.decay = decay;
 
         // Tone decays faster than noise
         let tone_ms = 30.0 + decay * 100.0;
@@ -140,6 +172,17 @@ impl Snare {
     }
 }
 
+impl Snare {
+    /// 808: a higher, thinner tone under loud wires. 909: a lower, fuller
+    /// tone with the wires behind it.
+    pub fn set_model(&mut self, model: KitModel) {
+        (self.tone_freq, self.noise_level) = match model {
+            KitModel::Tr808 => (238.0, 1.6),
+            KitModel::Tr909 => (180.0, 1.0),
+        };
+    }
+}
+
 fn soft_clip(x: f32) -> f32 {
     x.tanh()
 }
@@ -184,7 +227,7 @@ mod tests {
 
         for _ in 0..1000 {
             let sample = snare.process();
-            assert!(sample >= -1.0 && sample <= 1.0);
+            assert!((-1.0..=1.0).contains(&sample));
         }
     }
 
@@ -0,0 +1,174 @@
+/// Feed-forward compressor with fast attack and program-dependent release
+/// Short peaks recover quickly; sustained loud passages release slowly
+pub struct Compressor {
+    sample_rate: f32,
+    enabled: bool,
+
+    // Parameters
+    threshold_db: f32,
+    ratio: f32,
+    makeup: f32,
+
+    // Detector state: a fast and a slow peak follower
+    fast_env: f32,
+    slow_env: f32,
+    attack_coeff: f32,
+    slow_attack_coeff: f32,
+    fast_release_coeff: f32,
+    slow_release_coeff: f32,
+
+    gain_reduction_db: f32,
+}
+
+const ATTACK_MS: f32 = 1.0;
+const SLOW_ATTACK_MS: f32 = 60.0;
+const FAST_RELEASE_MS: f32 = 60.0;
+const SLOW_RELEASE_MS: f32 = 600.0;
+
+impl Compressor {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut comp = Self {
+            sample_rate,
+            enabled: false,
+            threshold_db: -18.0,
+            ratio: 4.0,
+            makeup: 1.0,
+            fast_env: 0.0,
+            slow_env: 0.0,
+            attack_coeff: 0.0,
+            slow_attack_coeff: 0.0,
+            fast_release_coeff: 0.0,
+            slow_release_coeff: 0.0,
+            gain_reduction_db: 0.0,
+        };
+        comp.update_coefficients();
+        comp
+    }
+
+    fn update_coefficients(&mut self) {
+        let sample_rate = self.sample_rate;
+        let coeff = |ms: f32| (-1.0 / (ms / 1000.0 * sample_rate)).exp();
+        self.attack_coeff = coeff(ATTACK_MS);
+        self.slow_attack_coeff = coeff(SLOW_ATTACK_MS);
+        self.fast_release_coeff = coeff(FAST_RELEASE_MS);
+        self.slow_release_coeff = coeff(SLOW_RELEASE_MS);
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.reset();
+        }
+    }
+
+    /// Set threshold in dBFS (-60 to 0)
+    pub fn set_threshold(&mut self, db: f32) {
+        self.threshold_db = db.clamp(-60.0, 0.0);
+    }
+
+    /// Set ratio (1:1 to 20:1)
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.clamp(1.0, 20.0);
+    }
+
+    /// Set makeup gain in dB (0 to 24)
+    pub fn set_makeup(&mut self, db: f32) {
+        self.makeup = 10.0_f32.powf(db.clamp(0.0, 24.0) / 20.0);
+    }
+
+    /// Current gain reduction in dB (positive number), for metering
+    pub fn gain_reduction(&self) -> f32 {
+        self.gain_reduction_db
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if !self.enabled {
+            return input;
+        }
+
+        let level = input.abs();
+        self.fast_env = follow(self.fast_env, level, self.attack_coeff, self.fast_release_coeff);
+        self.slow_env = follow(self.slow_env, level, self.slow_attack_coeff, self.slow_release_coeff);
+        let env = self.fast_env.max(self.slow_env);
+
+        let env_db = 20.0 * env.max(1e-6).log10();
+        let over = env_db - self.threshold_db;
+        self.gain_reduction_db = if over > 0.0 {
+            over * (1.0 - 1.0 / self.ratio)
+        } else {
+            0.0
+        };
+
+        let gain = 10.0_f32.powf(-self.gain_reduction_db / 20.0);
+        input * gain * self.makeup
+    }
+
+    pub fn reset(&mut self) {
+        self.fast_env = 0.0;
+        self.slow_env = 0.0;
+        self.gain_reduction_db = 0.0;
+    }
+}
+
+/// One-pole peak follower with separate attack and release
+fn follow(env: f32, level: f32, attack: f32, release: f32) -> f32 {
+    let coeff = if level > env { attack } else { release };
+    level + (env - level) * coeff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bypassed_by_default() {
+        let mut comp = Compressor::new(44100.0);
+        assert_eq!(comp.process(0.9), 0.9);
+    }
+
+    #[test]
+    fn test_reduces_loud_signal() {
+        let mut comp = Compressor::new(44100.0);
+        comp.set_enabled(true);
+        comp.set_threshold(-20.0);
+        comp.set_ratio(10.0);
+
+        let mut out = 0.0;
+        for _ in 0..4410 {
+            out = comp.process(0.9);
+        }
+        assert!(out < 0.3);
+        assert!(comp.gain_reduction() > 10.0);
+    }
+
+    #[test]
+    fn test_quiet_signal_untouched() {
+        let mut comp = Compressor::new(44100.0);
+        comp.set_enabled(true);
+        comp.set_threshold(-6.0);
+        for _ in 0..4410 {
+            comp.process(0.1);
+        }
+        assert!((comp.process(0.1) - 0.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_release_is_program_dependent() {
+        // Time for gain reduction to recover after a burst of `len` samples
+        let recovery = |len: usize| {
+            let mut comp = Compressor::new(44100.0);
+            comp.set_enabled(true);
+            comp.set_threshold(-30.0);
+            for _ in 0..len {
+                comp.process(0.9);
+            }
+            let mut n = 0;
+            while comp.gain_reduction() > 1.0 && n < 200000 {
+                comp.process(0.0);
+                n += 1;
+            }
+            n
+        };
+        assert!(recovery(100) < recovery(44100));
+    }
+}
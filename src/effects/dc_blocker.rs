@@ -0,0 +1,94 @@
+use std::f32::consts::PI;
+
+/// One-pole DC blocking filter
+/// Removes offset introduced by asymmetric clipping and pitch sweeps
+pub struct DcBlocker {
+    sample_rate: f32,
+    r: f32,
+    x1: f32,
+    y1: f32,
+    enabled: bool,
+}
+
+/// Corner frequency of the blocker, well below anything musical
+const DC_CUTOFF_HZ: f32 = 10.0;
+
+impl DcBlocker {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut blocker = Self {
+            sample_rate,
+            r: 0.0,
+            x1: 0.0,
+            y1: 0.0,
+            enabled: true,
+        };
+        blocker.update_coefficient();
+        blocker
+    }
+
+    fn update_coefficient(&mut self) {
+        self.r = 1.0 - (2.0 * PI * DC_CUTOFF_HZ / self.sample_rate);
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.reset();
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if !self.enabled {
+            return input;
+        }
+        let output = input - self.x1 + self.r * self.y1;
+        self.x1 = input;
+        self.y1 = output;
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removes_offset() {
+        let mut blocker = DcBlocker::new(44100.0);
+        let mut last = 0.0;
+        for _ in 0..44100 {
+            last = blocker.process(0.5);
+        }
+        assert!(last.abs() < 0.001);
+    }
+
+    #[test]
+    fn test_passes_audio() {
+        let mut blocker = DcBlocker::new(44100.0);
+        let mut peak = 0.0f32;
+        for i in 0..44100 {
+            let input = (2.0 * PI * 100.0 * i as f32 / 44100.0).sin();
+            let out = blocker.process(input);
+            if i > 4410 {
+                peak = peak.max(out.abs());
+            }
+        }
+        assert!(peak > 0.95);
+    }
+
+    #[test]
+    fn test_bypass() {
+        let mut blocker = DcBlocker::new(44100.0);
+        blocker.set_enabled(false);
+        assert_eq!(blocker.process(0.5), 0.5);
+    }
+}
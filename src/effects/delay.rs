@@ -0,0 +1,91 @@
+/// Feedback delay for the send bus
+/// A one-pole lowpass in the loop darkens each repeat, like tape echo
+pub struct Delay {
+    buffer: Vec<f32>,
+    write: usize,
+    delay: usize,
+    feedback: f32,
+    damping: f32,
+    lowpass: f32,
+}
+
+/// Longest delay time, a full bar at 60 BPM
+const MAX_DELAY_SECONDS: f32 = 4.0;
+
+/// Corner of the lowpass in the feedback loop
+const DAMPING_HZ: f32 = 3000.0;
+
+impl Delay {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            buffer: vec![0.0; (MAX_DELAY_SECONDS * sample_rate) as usize],
+            write: 0,
+            delay: (sample_rate * 0.375) as usize,
+            feedback: 0.4,
+            damping: 1.0 - (-2.0 * std::f32::consts::PI * DAMPING_HZ / sample_rate).exp(),
+            lowpass: 0.0,
+        }
+    }
+
+    /// Set the delay time in samples
+    pub fn set_time(&mut self, samples: usize) {
+        self.delay = samples.clamp(1, self.buffer.len() - 1);
+    }
+
+    /// Set how much of each repeat feeds the next (0.0 to 0.95)
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback.clamp(0.0, 0.95);
+    }
+
+    /// Returns only the echoes; the dry signal stays on its channel
+    pub fn process(&mut self, input: f32) -> f32 {
+        let len = self.buffer.len();
+        let echo = self.buffer[(self.write + len - self.delay) % len];
+        self.lowpass += self.damping * (echo - self.lowpass);
+        self.buffer[self.write] = input + self.lowpass * self.feedback;
+        self.write = (self.write + 1) % len;
+        echo
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.lowpass = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_arrives_after_delay_time() {
+        let mut delay = Delay::new(44100.0);
+        delay.set_time(100);
+        let out: Vec<f32> = (0..200).map(|i| delay.process(if i == 0 { 1.0 } else { 0.0 })).collect();
+        assert!(out[..100].iter().all(|&s| s == 0.0));
+        assert_eq!(out[100], 1.0);
+    }
+
+    #[test]
+    fn test_repeats_fade() {
+        let mut delay = Delay::new(44100.0);
+        delay.set_time(1000);
+        delay.set_feedback(0.5);
+        let out: Vec<f32> = (0..3500).map(|i| delay.process(if i < 200 { 1.0 } else { 0.0 })).collect();
+        let level = |start: usize| out[start..start + 200].iter().map(|s| s.abs()).sum::<f32>();
+        let (first, second, third) = (level(1000), level(2000), level(3000));
+        assert!(second < first && third < second);
+        assert!(third > 0.0);
+    }
+
+    #[test]
+    fn test_no_feedback_single_echo() {
+        let mut delay = Delay::new(44100.0);
+        delay.set_time(100);
+        delay.set_feedback(0.0);
+        for i in 0..150 {
+            delay.process(if i == 0 { 1.0 } else { 0.0 });
+        }
+        assert!((0..500).all(|_| delay.process(0.0) == 0.0));
+    }
+}
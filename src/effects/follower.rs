@@ -0,0 +1,86 @@
+/// Envelope follower
+/// Tracks the level of a signal so it can be used as a modulation source,
+/// e.g. to duck the synth cutoff on every kick
+pub struct EnvelopeFollower {
+    sample_rate: f32,
+    attack_ms: f32,
+    release_ms: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    env: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut follower = Self {
+            sample_rate,
+            attack_ms: 2.0,
+            release_ms: 150.0,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            env: 0.0,
+        };
+        follower.update_coefficients();
+        follower
+    }
+
+    fn update_coefficients(&mut self) {
+        self.attack_coeff = (-1.0 / (self.attack_ms / 1000.0 * self.sample_rate)).exp();
+        self.release_coeff = (-1.0 / (self.release_ms / 1000.0 * self.sample_rate)).exp();
+    }
+
+    /// Set attack time in milliseconds (0.1 to 100)
+    pub fn set_attack(&mut self, ms: f32) {
+        self.attack_ms = ms.clamp(0.1, 100.0);
+        self.update_coefficients();
+    }
+
+    /// Set release time in milliseconds (5 to 2000)
+    pub fn set_release(&mut self, ms: f32) {
+        self.release_ms = ms.clamp(5.0, 2000.0);
+        self.update_coefficients();
+    }
+
+    /// Feed one sample, returns the current level (0.0 - 1.0)
+    pub fn process(&mut self, input: f32) -> f32 {
+        let level = input.abs().min(1.0);
+        let coeff = if level > self.env { self.attack_coeff } else { self.release_coeff };
+        self.env = level + (self.env - level) * coeff;
+        self.env
+    }
+
+    pub fn level(&self) -> f32 {
+        self.env
+    }
+
+    pub fn reset(&mut self) {
+        self.env = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follows_level() {
+        let mut follower = EnvelopeFollower::new(44100.0);
+        for _ in 0..4410 {
+            follower.process(0.8);
+        }
+        assert!((follower.level() - 0.8).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_releases_to_zero() {
+        let mut follower = EnvelopeFollower::new(44100.0);
+        follower.set_release(10.0);
+        for _ in 0..441 {
+            follower.process(1.0);
+        }
+        for _ in 0..4410 {
+            follower.process(0.0);
+        }
+        assert!(follower.level() < 0.01);
+    }
+}
@@ -0,0 +1,137 @@
+/// Noise gate
+/// Mutes the signal between notes once it falls below the threshold, to
+/// clean up self-oscillation hiss and the distortion noise floor
+pub struct NoiseGate {
+    sample_rate: f32,
+    enabled: bool,
+
+    threshold: f32,
+    release_ms: f32,
+
+    env: f32,
+    gain: f32,
+    env_release_coeff: f32,
+    attack_step: f32,
+    release_coeff: f32,
+}
+
+/// Detector release, short so the gate reacts to note ends
+const DETECTOR_RELEASE_MS: f32 = 10.0;
+/// Opening ramp, just long enough not to click
+const OPEN_MS: f32 = 0.5;
+
+impl NoiseGate {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut gate = Self {
+            sample_rate,
+            enabled: false,
+            threshold: 10.0_f32.powf(-50.0 / 20.0),
+            release_ms: 80.0,
+            env: 0.0,
+            gain: 0.0,
+            env_release_coeff: 0.0,
+            attack_step: 0.0,
+            release_coeff: 0.0,
+        };
+        gate.update_coefficients();
+        gate
+    }
+
+    fn update_coefficients(&mut self) {
+        self.env_release_coeff = (-1.0 / (DETECTOR_RELEASE_MS / 1000.0 * self.sample_rate)).exp();
+        self.attack_step = 1.0 / (OPEN_MS / 1000.0 * self.sample_rate);
+        self.release_coeff = (-1.0 / (self.release_ms / 1000.0 * self.sample_rate)).exp();
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.reset();
+        }
+    }
+
+    /// Set threshold in dBFS (-90 to 0)
+    pub fn set_threshold(&mut self, db: f32) {
+        self.threshold = 10.0_f32.powf(db.clamp(-90.0, 0.0) / 20.0);
+    }
+
+    /// Set release time in milliseconds (5 to 2000)
+    pub fn set_release(&mut self, ms: f32) {
+        self.release_ms = ms.clamp(5.0, 2000.0);
+        self.update_coefficients();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.gain > 0.5
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if !self.enabled {
+            return input;
+        }
+
+        // Peak detector: instant attack, short release
+        let level = input.abs();
+        self.env = if level > self.env {
+            level
+        } else {
+            level + (self.env - level) * self.env_release_coeff
+        };
+
+        if self.env > self.threshold {
+            self.gain = (self.gain + self.attack_step).min(1.0);
+        } else {
+            self.gain *= self.release_coeff;
+        }
+
+        input * self.gain
+    }
+
+    pub fn reset(&mut self) {
+        self.env = 0.0;
+        self.gain = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bypassed_by_default() {
+        let mut gate = NoiseGate::new(44100.0);
+        assert_eq!(gate.process(0.001), 0.001);
+    }
+
+    #[test]
+    fn test_opens_on_loud_signal() {
+        let mut gate = NoiseGate::new(44100.0);
+        gate.set_enabled(true);
+        gate.set_threshold(-40.0);
+        for _ in 0..100 {
+            gate.process(0.5);
+        }
+        assert!(gate.is_open());
+        assert!((gate.process(0.5) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_closes_on_noise_floor() {
+        let mut gate = NoiseGate::new(44100.0);
+        gate.set_enabled(true);
+        gate.set_threshold(-40.0);
+        gate.set_release(20.0);
+        for _ in 0..100 {
+            gate.process(0.5);
+        }
+
+        // Hiss well below the threshold is faded out
+        let mut out = 1.0;
+        for i in 0..44100 {
+            let hiss = if i % 2 == 0 { 0.001 } else { -0.001 };
+            out = gate.process(hiss);
+        }
+        assert!(!gate.is_open());
+        assert!(out.abs() < 1e-6);
+    }
+}
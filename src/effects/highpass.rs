@@ -0,0 +1,102 @@
+use std::f32::consts::PI;
+
+/// 12dB/octave Butterworth highpass for clearing low-end mud
+/// Off until given a cutoff, so inserting it costs nothing by default
+pub struct HighPass {
+    sample_rate: f32,
+    cutoff: f32,
+
+    // Biquad coefficients (a0 normalised out) and transposed direct form
+    // II state
+    b: [f32; 3],
+    a: [f32; 2],
+    z: [f32; 2],
+}
+
+/// Highest cutoff accepted; beyond this it's no longer a mud filter
+const MAX_CUTOFF_HZ: f32 = 1000.0;
+
+/// Butterworth Q for a maximally flat passband
+const Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+impl HighPass {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            cutoff: 0.0,
+            b: [1.0, 0.0, 0.0],
+            a: [0.0, 0.0],
+            z: [0.0; 2],
+        }
+    }
+
+    /// Set the corner frequency in Hz (up to 1000); 0 switches the filter off
+    pub fn set_cutoff(&mut self, freq: f32) {
+        self.cutoff = if freq <= 0.0 { 0.0 } else { freq.clamp(10.0, MAX_CUTOFF_HZ) };
+        if self.cutoff == 0.0 {
+            self.reset();
+            return;
+        }
+        let w = 2.0 * PI * self.cutoff / self.sample_rate;
+        let alpha = w.sin() / (2.0 * Q);
+        let a0 = 1.0 + alpha;
+        let b0 = (1.0 + w.cos()) / 2.0 / a0;
+        self.b = [b0, -2.0 * b0, b0];
+        self.a = [-2.0 * w.cos() / a0, (1.0 - alpha) / a0];
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if self.cutoff == 0.0 {
+            return input;
+        }
+        let y = self.b[0] * input + self.z[0];
+        self.z[0] = self.b[1] * input - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * input - self.a[1] * y;
+        y
+    }
+
+    pub fn reset(&mut self) {
+        self.z = [0.0; 2];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(filter: &mut HighPass, freq: f32) -> f32 {
+        let mut peak = 0.0f32;
+        for i in 0..44100 {
+            let out = filter.process((2.0 * PI * freq * i as f32 / 44100.0).sin());
+            if i > 22050 {
+                peak = peak.max(out.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_cuts_lows_passes_highs() {
+        let mut filter = HighPass::new(44100.0);
+        filter.set_cutoff(150.0);
+        assert!(level(&mut filter, 40.0) < 0.1);
+        filter.reset();
+        assert!(level(&mut filter, 1000.0) > 0.95);
+        filter.reset();
+        // -3dB at the corner
+        assert!((level(&mut filter, 150.0) - 0.707).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_off_by_default() {
+        let mut filter = HighPass::new(44100.0);
+        assert_eq!(filter.process(0.5), 0.5);
+        filter.set_cutoff(100.0);
+        filter.set_cutoff(0.0);
+        assert_eq!(filter.process(0.5), 0.5);
+    }
+}
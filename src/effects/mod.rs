@@ -0,0 +1,31 @@
+mod dc_blocker;
+mod compressor;
+mod gate;
+mod ring_mod;
+mod widener;
+mod transient;
+mod multiband;
+mod follower;
+mod stutter;
+mod tape_stop;
+mod delay;
+mod wow_flutter;
+mod vinyl;
+mod highpass;
+mod reverb;
+
+pub use dc_blocker::DcBlocker;
+pub use compressor::Compressor;
+pub use gate::NoiseGate;
+pub use ring_mod::{RingMod, RingModSource};
+pub use widener::Widener;
+pub use transient::TransientShaper;
+pub use multiband::MultibandDistortion;
+pub use follower::EnvelopeFollower;
+pub use stutter::Stutter;
+pub use tape_stop::TapeStop;
+pub use delay::Delay;
+pub use wow_flutter::WowFlutter;
+pub use vinyl::Vinyl;
+pub use highpass::HighPass;
+pub use reverb::Reverb;
@@ -0,0 +1,175 @@
+use std::f32::consts::PI;
+
+use crate::distortion::Distortion;
+
+/// Three-band distortion
+/// Splits the signal at two crossovers, keeps the low band clean and drives
+/// the mid and high bands separately, so heavy drive doesn't muddy the bass
+pub struct MultibandDistortion {
+    sample_rate: f32,
+    enabled: bool,
+
+    low_freq: f32,
+    high_freq: f32,
+
+    mid_drive: Distortion,
+    high_drive: Distortion,
+
+    low_split: Crossover,
+    high_split: Crossover,
+}
+
+/// Second-order Linkwitz-Riley crossover built from cascaded trapezoidal
+/// one-pole sections. The bands sum to an allpass, so the magnitude stays flat.
+#[derive(Default)]
+struct Crossover {
+    g: f32,
+    lp: [f32; 2],
+    hp: [f32; 2],
+}
+
+impl Crossover {
+    fn set_frequency(&mut self, freq: f32, sample_rate: f32) {
+        let g = (PI * freq / sample_rate).tan();
+        self.g = g / (1.0 + g);
+    }
+
+    /// One-pole lowpass step on `state`
+    fn lowpass(g: f32, state: &mut f32, input: f32) -> f32 {
+        let v = (input - *state) * g;
+        let out = v + *state;
+        *state = out + v;
+        out
+    }
+
+    /// Split into (low, high)
+    fn process(&mut self, input: f32) -> (f32, f32) {
+        let mut low = input;
+        let mut high = input;
+        for i in 0..2 {
+            low = Self::lowpass(self.g, &mut self.lp[i], low);
+            high -= Self::lowpass(self.g, &mut self.hp[i], high);
+        }
+        // Second-order sections are out of phase at the crossover
+        (low, -high)
+    }
+
+    fn reset(&mut self) {
+        self.lp = [0.0; 2];
+        self.hp = [0.0; 2];
+    }
+}
+
+impl MultibandDistortion {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut dist = Self {
+            sample_rate,
+            enabled: false,
+            low_freq: 200.0,
+            high_freq: 2500.0,
+            mid_drive: Distortion::new(),
+            high_drive: Distortion::new(),
+            low_split: Crossover::default(),
+            high_split: Crossover::default(),
+        };
+        dist.mid_drive.set_drive(0.5);
+        dist.high_drive.set_drive(0.5);
+        dist.update_coefficients();
+        dist
+    }
+
+    fn update_coefficients(&mut self) {
+        self.low_split.set_frequency(self.low_freq, self.sample_rate);
+        self.high_split.set_frequency(self.high_freq, self.sample_rate);
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.reset();
+        }
+    }
+
+    /// Set the low/mid and mid/high crossover frequencies in Hz
+    pub fn set_crossovers(&mut self, low: f32, high: f32) {
+        self.low_freq = low.clamp(40.0, 1000.0);
+        self.high_freq = high.clamp(self.low_freq * 2.0, 10000.0);
+        self.update_coefficients();
+    }
+
+    /// Set drive for the mid band (0.0 to 1.0)
+    pub fn set_mid_drive(&mut self, drive: f32) {
+        self.mid_drive.set_drive(drive);
+    }
+
+    /// Set drive for the high band (0.0 to 1.0)
+    pub fn set_high_drive(&mut self, drive: f32) {
+        self.high_drive.set_drive(drive);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if !self.enabled {
+            return input;
+        }
+
+        let (low, rest) = self.low_split.process(input);
+        let (mid, high) = self.high_split.process(rest);
+
+        low + self.mid_drive.process(mid) + self.high_drive.process(high)
+    }
+
+    pub fn reset(&mut self) {
+        self.low_split.reset();
+        self.high_split.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_without_drive() {
+        let mut dist = MultibandDistortion::new(44100.0);
+        dist.set_enabled(true);
+        dist.set_mid_drive(0.0);
+        dist.set_high_drive(0.0);
+
+        // Bands recombine with unity gain across the spectrum
+        for freq in [50.0, 200.0, 1000.0, 2500.0, 8000.0] {
+            dist.reset();
+            let mut peak: f32 = 0.0;
+            for i in 0..44100 {
+                let y = dist.process((2.0 * PI * freq * i as f32 / 44100.0).sin());
+                if i > 22050 {
+                    peak = peak.max(y.abs());
+                }
+            }
+            assert!((peak - 1.0).abs() < 0.1, "{} Hz peak {}", freq, peak);
+        }
+    }
+
+    #[test]
+    fn test_low_band_stays_clean() {
+        let mut dist = MultibandDistortion::new(44100.0);
+        dist.set_enabled(true);
+        dist.set_mid_drive(1.0);
+        dist.set_high_drive(1.0);
+
+        // A 40Hz sine comes through without being squared off
+        let mut peak: f32 = 0.0;
+        for i in 0..44100 {
+            let y = dist.process((2.0 * PI * 40.0 * i as f32 / 44100.0).sin() * 0.5);
+            if i > 4410 {
+                peak = peak.max(y.abs());
+            }
+        }
+        assert!((peak - 0.5).abs() < 0.15, "peak {}", peak);
+    }
+
+    #[test]
+    fn test_disabled_passes_through() {
+        let mut dist = MultibandDistortion::new(44100.0);
+        assert_eq!(dist.process(0.7), 0.7);
+    }
+}
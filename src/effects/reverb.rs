@@ -0,0 +1,217 @@
+/// Freeverb-style reverb for the send bus
+/// Eight lowpassed feedback combs in parallel feed four allpasses in series
+/// on each side. The right side's delays are slightly longer, so the tail
+/// comes out wide from a mono send.
+pub struct Reverb {
+    left: Tank,
+    right: Tank,
+    size: f32,
+    damping: f32,
+}
+
+/// Comb and allpass lengths in samples at 44.1kHz
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+
+/// Extra samples on every right-side delay
+const STEREO_SPREAD: usize = 23;
+
+/// Scales the send so eight summed combs don't overload
+const INPUT_GAIN: f32 = 0.015;
+
+/// Level of the wet output
+const WET_GAIN: f32 = 3.0;
+
+/// Comb feedback at size 0, and how much more size 1 adds
+const ROOM_OFFSET: f32 = 0.7;
+const ROOM_SCALE: f32 = 0.28;
+
+/// Most of each repeat the comb lowpass removes, at damping 1
+const MAX_DAMPING: f32 = 0.4;
+
+const ALLPASS_FEEDBACK: f32 = 0.5;
+
+impl Reverb {
+    pub fn new(sample_rate: f32) -> Self {
+        let scale = sample_rate / 44100.0;
+        let mut reverb = Self {
+            left: Tank::new(scale, 0),
+            right: Tank::new(scale, STEREO_SPREAD),
+            size: 0.5,
+            damping: 0.5,
+        };
+        reverb.update();
+        reverb
+    }
+
+    /// Room size (0.0 - 1.0): how long the tail rings
+    pub fn set_size(&mut self, size: f32) {
+        self.size = size.clamp(0.0, 1.0);
+        self.update();
+    }
+
+    /// High-frequency damping (0.0 - 1.0): higher darkens the tail faster
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping.clamp(0.0, 1.0);
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let feedback = ROOM_OFFSET + ROOM_SCALE * self.size;
+        let damping = MAX_DAMPING * self.damping;
+        for comb in self.left.combs.iter_mut().chain(self.right.combs.iter_mut()) {
+            comb.feedback = feedback;
+            comb.damping = damping;
+        }
+    }
+
+    /// Returns only the reverb as a stereo pair; the dry signal stays on
+    /// its channel
+    pub fn process(&mut self, input: f32) -> (f32, f32) {
+        let input = input * INPUT_GAIN;
+        (self.left.process(input) * WET_GAIN, self.right.process(input) * WET_GAIN)
+    }
+
+    pub fn reset(&mut self) {
+        self.left.reset();
+        self.right.reset();
+    }
+}
+
+/// One side of the reverb
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+    fn new(scale: f32, spread: usize) -> Self {
+        let length = |tuning: usize| (((tuning + spread) as f32 * scale) as usize).max(1);
+        Self {
+            combs: COMB_TUNING.iter().map(|&t| Comb::new(length(t))).collect(),
+            allpasses: ALLPASS_TUNING.iter().map(|&t| Allpass::new(length(t))).collect(),
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let wet = self.combs.iter_mut().map(|comb| comb.process(input)).sum();
+        self.allpasses.iter_mut().fold(wet, |signal, allpass| allpass.process(signal))
+    }
+
+    fn reset(&mut self) {
+        self.combs.iter_mut().for_each(|c| c.reset());
+        self.allpasses.iter_mut().for_each(|a| a.reset());
+    }
+}
+
+/// Feedback comb with a one-pole lowpass in the loop
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    feedback: f32,
+    damping: f32,
+    lowpass: f32,
+}
+
+impl Comb {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length],
+            index: 0,
+            feedback: 0.0,
+            damping: 0.0,
+            lowpass: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.lowpass = output * (1.0 - self.damping) + self.lowpass * self.damping;
+        self.buffer[self.index] = input + self.lowpass * self.feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.lowpass = 0.0;
+    }
+}
+
+/// Schroeder allpass, smearing the comb echoes into a dense tail
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl Allpass {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length],
+            index: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * ALLPASS_FEEDBACK;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - input
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Left side of the response to an impulse
+    fn impulse(reverb: &mut Reverb, len: usize) -> Vec<f32> {
+        (0..len).map(|i| reverb.process(if i == 0 { 1.0 } else { 0.0 }).0).collect()
+    }
+
+    fn energy(samples: &[f32]) -> f32 {
+        samples.iter().map(|s| s * s).sum()
+    }
+
+    #[test]
+    fn test_tail_decays() {
+        let out = impulse(&mut Reverb::new(44100.0), 66150);
+        let (early, late) = (energy(&out[..22050]), energy(&out[44100..]));
+        assert!(early > 0.0);
+        assert!(late < early * 0.01, "{} {}", early, late);
+    }
+
+    #[test]
+    fn test_size_lengthens_tail() {
+        let ring = |size: f32| {
+            let mut reverb = Reverb::new(44100.0);
+            reverb.set_size(size);
+            energy(&impulse(&mut reverb, 66150)[44100..])
+        };
+        assert!(ring(1.0) > ring(0.2) * 10.0);
+    }
+
+    #[test]
+    fn test_damping_darkens_tail() {
+        // Sample-to-sample difference against level measures the high end
+        let brightness = |damping: f32| {
+            let mut reverb = Reverb::new(44100.0);
+            reverb.set_damping(damping);
+            let out = impulse(&mut reverb, 44100);
+            let edge: f32 = out[11025..].windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+            edge / energy(&out[11025..])
+        };
+        assert!(brightness(1.0) < brightness(0.0) * 0.5);
+    }
+
+    #[test]
+    fn test_sides_differ() {
+        let mut reverb = Reverb::new(44100.0);
+        let out: Vec<(f32, f32)> = (0..4410).map(|i| reverb.process(if i == 0 { 1.0 } else { 0.0 })).collect();
+        assert!(out.iter().any(|(l, r)| (l - r).abs() > 1e-4));
+    }
+}
@@ -0,0 +1,123 @@
+use std::f32::consts::TAU;
+
+/// What the input is multiplied with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RingModSource {
+    /// Internal sine carrier
+    Carrier,
+    /// An external signal, e.g. the drum bus
+    External,
+}
+
+/// Ring modulator
+/// Multiplies the input by a carrier for metallic, bell-like tones
+pub struct RingMod {
+    sample_rate: f32,
+    enabled: bool,
+    source: RingModSource,
+
+    freq: f32,
+    mix: f32,
+
+    phase: f32,
+    phase_inc: f32,
+}
+
+impl RingMod {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut ring = Self {
+            sample_rate,
+            enabled: false,
+            source: RingModSource::Carrier,
+            freq: 440.0,
+            mix: 1.0,
+            phase: 0.0,
+            phase_inc: 0.0,
+        };
+        ring.update_coefficients();
+        ring
+    }
+
+    fn update_coefficients(&mut self) {
+        self.phase_inc = self.freq / self.sample_rate;
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn set_source(&mut self, source: RingModSource) {
+        self.source = source;
+    }
+
+    /// Set carrier frequency in Hz (1 to 5000)
+    pub fn set_frequency(&mut self, freq: f32) {
+        self.freq = freq.clamp(1.0, 5000.0);
+        self.update_coefficients();
+    }
+
+    /// Set dry/wet mix (0.0 - 1.0)
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    /// Process one sample; `external` is only used with the External source
+    pub fn process(&mut self, input: f32, external: f32) -> f32 {
+        if !self.enabled {
+            return input;
+        }
+
+        let carrier = match self.source {
+            RingModSource::Carrier => {
+                let c = (self.phase * TAU).sin();
+                self.phase += self.phase_inc;
+                if self.phase >= 1.0 {
+                    self.phase -= 1.0;
+                }
+                c
+            }
+            RingModSource::External => external,
+        };
+
+        input + (input * carrier - input) * self.mix
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_passes_through() {
+        let mut ring = RingMod::new(44100.0);
+        assert_eq!(ring.process(0.5, 0.0), 0.5);
+    }
+
+    #[test]
+    fn test_carrier_modulates() {
+        let mut ring = RingMod::new(44100.0);
+        ring.set_enabled(true);
+        ring.set_frequency(100.0);
+        // Constant input comes out shaped like the carrier
+        let out: Vec<f32> = (0..441).map(|_| ring.process(1.0, 0.0)).collect();
+        let max = out.iter().cloned().fold(f32::MIN, f32::max);
+        let min = out.iter().cloned().fold(f32::MAX, f32::min);
+        assert!(max > 0.99 && min < -0.99);
+    }
+
+    #[test]
+    fn test_external_source() {
+        let mut ring = RingMod::new(44100.0);
+        ring.set_enabled(true);
+        ring.set_source(RingModSource::External);
+        assert_eq!(ring.process(0.5, 0.0), 0.0);
+        assert!((ring.process(0.5, 0.5) - 0.25).abs() < 1e-6);
+
+        ring.set_mix(0.5);
+        assert!((ring.process(0.5, 0.0) - 0.25).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,138 @@
+/// Beat-repeat / stutter
+/// Keeps a running history of its input; when engaged it captures the most
+/// recent slice and loops it until released, for DJ-style builds
+pub struct Stutter {
+    sample_rate: f32,
+    history: Vec<f32>,
+    write: usize,
+    slice: Vec<f32>,
+    slice_len: usize,
+    pos: usize,
+    engaged: bool,
+    wet: f32,
+    wet_step: f32,
+}
+
+/// Longest slice that can be captured, enough for a quarter note at 60 BPM
+const MAX_SLICE_SECONDS: f32 = 1.0;
+
+/// Crossfade between the live signal and the loop, and at the loop edges
+const EDGE_MS: f32 = 2.0;
+
+impl Stutter {
+    pub fn new(sample_rate: f32) -> Self {
+        let capacity = (sample_rate * MAX_SLICE_SECONDS) as usize;
+        Self {
+            sample_rate,
+            history: vec![0.0; capacity],
+            write: 0,
+            slice: vec![0.0; capacity],
+            slice_len: 0,
+            pos: 0,
+            engaged: false,
+            wet: 0.0,
+            wet_step: 1.0 / (EDGE_MS / 1000.0 * sample_rate),
+        }
+    }
+
+    /// Capture the last `samples` of input and start looping them
+    pub fn engage(&mut self, samples: usize) {
+        let len = samples.clamp(1, self.history.len());
+        let start = (self.write + self.history.len() - len) % self.history.len();
+        for (i, s) in self.slice[..len].iter_mut().enumerate() {
+            *s = self.history[(start + i) % self.history.len()];
+        }
+
+        // Short fades at both ends so the loop doesn't click as it wraps
+        let edge = ((EDGE_MS / 1000.0 * self.sample_rate) as usize).min(len / 2);
+        for i in 0..edge {
+            let gain = i as f32 / edge as f32;
+            self.slice[i] *= gain;
+            self.slice[len - 1 - i] *= gain;
+        }
+
+        self.slice_len = len;
+        self.pos = 0;
+        self.engaged = true;
+    }
+
+    /// Return to the live signal
+    pub fn release(&mut self) {
+        self.engaged = false;
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        self.engaged
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.history[self.write] = input;
+        self.write = (self.write + 1) % self.history.len();
+
+        let target = if self.engaged { 1.0 } else { 0.0 };
+        if self.wet == target && target == 0.0 {
+            return input;
+        }
+        self.wet = if self.wet < target {
+            (self.wet + self.wet_step).min(target)
+        } else {
+            (self.wet - self.wet_step).max(target)
+        };
+
+        let looped = self.slice[self.pos];
+        self.pos = (self.pos + 1) % self.slice_len.max(1);
+        input * (1.0 - self.wet) + looped * self.wet
+    }
+
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.engaged = false;
+        self.wet = 0.0;
+        self.pos = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passes_through_when_released() {
+        let mut stutter = Stutter::new(44100.0);
+        for i in 0..100 {
+            let x = i as f32 * 0.01;
+            assert_eq!(stutter.process(x), x);
+        }
+    }
+
+    #[test]
+    fn test_loops_captured_slice() {
+        let mut stutter = Stutter::new(44100.0);
+        for i in 0..1000 {
+            stutter.process((i % 500) as f32 / 500.0);
+        }
+        stutter.engage(500);
+        let first: Vec<f32> = (0..500).map(|_| stutter.process(0.0)).collect();
+        let second: Vec<f32> = (0..500).map(|_| stutter.process(0.0)).collect();
+        // After the fade-in the loop repeats exactly, whatever the input
+        assert_eq!(&first[200..], &second[200..]);
+        assert!(second[250] > 0.4);
+    }
+
+    #[test]
+    fn test_release_returns_to_input() {
+        let mut stutter = Stutter::new(44100.0);
+        for _ in 0..1000 {
+            stutter.process(0.5);
+        }
+        stutter.engage(100);
+        for _ in 0..500 {
+            stutter.process(0.0);
+        }
+        stutter.release();
+        for _ in 0..500 {
+            stutter.process(0.25);
+        }
+        assert_eq!(stutter.process(0.25), 0.25);
+    }
+}
@@ -0,0 +1,164 @@
+/// Tape stop
+/// One-shot effect that slows playback to a halt, dropping pitch with the
+/// speed like a tape machine losing power, then cuts back to the live signal
+pub struct TapeStop {
+    sample_rate: f32,
+    stop_ms: f32,
+
+    buffer: Vec<f32>,
+    write: usize,
+    /// How far the playhead has fallen behind the input, in samples
+    lag: f64,
+
+    state: State,
+    speed_step: f32,
+    resume_step: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Stopping { speed: f32 },
+    Resuming { gain: f32 },
+}
+
+/// Longest stop time, which bounds how far the playhead can lag
+const MAX_STOP_MS: f32 = 2000.0;
+
+/// Fade back into the live signal once stopped
+const RESUME_MS: f32 = 10.0;
+
+/// Speed below which the output fades out so the halt doesn't click
+const FADE_SPEED: f32 = 0.1;
+
+impl TapeStop {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut tape = Self {
+            sample_rate,
+            stop_ms: 500.0,
+            buffer: vec![0.0; (MAX_STOP_MS / 1000.0 * sample_rate) as usize + 2],
+            write: 0,
+            lag: 0.0,
+            state: State::Idle,
+            speed_step: 0.0,
+            resume_step: 1.0 / (RESUME_MS / 1000.0 * sample_rate),
+        };
+        tape.update_coefficients();
+        tape
+    }
+
+    fn update_coefficients(&mut self) {
+        self.speed_step = 1.0 / (self.stop_ms / 1000.0 * self.sample_rate);
+    }
+
+    /// Set how long the slowdown takes in milliseconds (50 to 2000)
+    pub fn set_time(&mut self, ms: f32) {
+        self.stop_ms = ms.clamp(50.0, MAX_STOP_MS);
+        self.update_coefficients();
+    }
+
+    /// Start slowing down; does nothing while a stop is already running
+    pub fn trigger(&mut self) {
+        if self.state == State::Idle {
+            self.lag = 0.0;
+            self.state = State::Stopping { speed: 1.0 };
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state != State::Idle
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let len = self.buffer.len();
+        self.buffer[self.write] = input;
+        self.write = (self.write + 1) % len;
+
+        match self.state {
+            State::Idle => input,
+            State::Stopping { speed } => {
+                self.lag += (1.0 - speed) as f64;
+                let out = self.read(self.lag) * (speed / FADE_SPEED).min(1.0);
+                let speed = speed - self.speed_step;
+                self.state = if speed > 0.0 {
+                    State::Stopping { speed }
+                } else {
+                    State::Resuming { gain: 0.0 }
+                };
+                out
+            }
+            State::Resuming { gain } => {
+                let gain = gain + self.resume_step;
+                self.state = if gain < 1.0 { State::Resuming { gain } } else { State::Idle };
+                input * gain.min(1.0)
+            }
+        }
+    }
+
+    /// Interpolated sample `lag` samples before the newest input
+    fn read(&self, lag: f64) -> f32 {
+        let len = self.buffer.len();
+        let lag = lag.min((len - 2) as f64);
+        let whole = lag as usize;
+        let frac = (lag - whole as f64) as f32;
+        let newest = self.write + len - 1;
+        let a = self.buffer[(newest - whole) % len];
+        let b = self.buffer[(newest - whole - 1 + len) % len];
+        a + (b - a) * frac
+    }
+
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+        self.lag = 0.0;
+        self.buffer.fill(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(i: usize) -> f32 {
+        (i as f32 * 440.0 / 44100.0 * std::f32::consts::TAU).sin()
+    }
+
+    fn crossings(samples: &[f32]) -> usize {
+        samples.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count()
+    }
+
+    #[test]
+    fn test_idle_passes_through() {
+        let mut tape = TapeStop::new(44100.0);
+        assert_eq!(tape.process(0.3), 0.3);
+        assert!(!tape.is_active());
+    }
+
+    #[test]
+    fn test_pitch_drops_while_stopping() {
+        let mut tape = TapeStop::new(44100.0);
+        tape.set_time(1000.0);
+        for i in 0..4410 {
+            tape.process(sine(i));
+        }
+        tape.trigger();
+        let out: Vec<f32> = (4410..4410 + 44100).map(|i| tape.process(sine(i))).collect();
+        // Speed falls linearly, so the first half plays ~3/4 of the cycles
+        // of the live signal and the second half ~1/4
+        let first = crossings(&out[..22050]);
+        let second = crossings(&out[22050..]);
+        assert!(first > second * 2, "first {} second {}", first, second);
+        assert!(first < 200);
+    }
+
+    #[test]
+    fn test_resumes_after_stop() {
+        let mut tape = TapeStop::new(44100.0);
+        tape.set_time(100.0);
+        tape.trigger();
+        for _ in 0..(4410 + 441 + 10) {
+            tape.process(0.5);
+        }
+        assert!(!tape.is_active());
+        assert_eq!(tape.process(0.5), 0.5);
+    }
+}
@@ -0,0 +1,183 @@
+/// Transient shaper
+/// Boosts or cuts the attack and sustain portions of a signal independently
+/// by comparing envelope followers with different time constants
+pub struct TransientShaper {
+    sample_rate: f32,
+    enabled: bool,
+
+    attack: f32,
+    sustain: f32,
+
+    // Attack detection: fast vs slow rise, same release
+    fast_attack: Follower,
+    slow_attack: Follower,
+    // Sustain detection: fast vs slow fall, same attack
+    fast_release: Follower,
+    slow_release: Follower,
+}
+
+/// Largest boost or cut the shaper applies, in dB
+const MAX_GAIN_DB: f32 = 24.0;
+/// Levels below this are treated as silence
+const FLOOR: f32 = 1e-5;
+
+/// Peak envelope follower with separate attack and release
+struct Follower {
+    attack_ms: f32,
+    release_ms: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    env: f32,
+}
+
+impl Follower {
+    fn new(attack_ms: f32, release_ms: f32) -> Self {
+        Self {
+            attack_ms,
+            release_ms,
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            env: 0.0,
+        }
+    }
+
+    fn update_coefficients(&mut self, sample_rate: f32) {
+        self.attack_coeff = (-1.0 / (self.attack_ms / 1000.0 * sample_rate)).exp();
+        self.release_coeff = (-1.0 / (self.release_ms / 1000.0 * sample_rate)).exp();
+    }
+
+    fn process(&mut self, level: f32) -> f32 {
+        let coeff = if level > self.env { self.attack_coeff } else { self.release_coeff };
+        self.env = level + (self.env - level) * coeff;
+        self.env
+    }
+}
+
+impl TransientShaper {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut shaper = Self {
+            sample_rate,
+            enabled: false,
+            attack: 0.0,
+            sustain: 0.0,
+            fast_attack: Follower::new(0.5, 100.0),
+            slow_attack: Follower::new(20.0, 100.0),
+            fast_release: Follower::new(0.5, 20.0),
+            slow_release: Follower::new(0.5, 300.0),
+        };
+        shaper.update_coefficients();
+        shaper
+    }
+
+    fn update_coefficients(&mut self) {
+        for follower in [
+            &mut self.fast_attack,
+            &mut self.slow_attack,
+            &mut self.fast_release,
+            &mut self.slow_release,
+        ] {
+            follower.update_coefficients(self.sample_rate);
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.reset();
+        }
+    }
+
+    /// Set attack emphasis (-1.0 softer to 1.0 punchier)
+    pub fn set_attack(&mut self, amount: f32) {
+        self.attack = amount.clamp(-1.0, 1.0);
+    }
+
+    /// Set sustain emphasis (-1.0 tighter to 1.0 longer)
+    pub fn set_sustain(&mut self, amount: f32) {
+        self.sustain = amount.clamp(-1.0, 1.0);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        if !self.enabled {
+            return input;
+        }
+        input * self.gain(input.abs())
+    }
+
+    /// Process one stereo frame, detecting on the mid signal so both
+    /// channels get the same gain and the image doesn't shift
+    pub fn process_stereo(&mut self, left: f32, right: f32) -> (f32, f32) {
+        if !self.enabled {
+            return (left, right);
+        }
+        let gain = self.gain(((left + right) * 0.5).abs());
+        (left * gain, right * gain)
+    }
+
+    /// Gain for the next sample given its detector `level`
+    fn gain(&mut self, level: f32) -> f32 {
+        let to_db = |env: f32| 20.0 * env.max(FLOOR).log10();
+
+        let attack_diff = to_db(self.fast_attack.process(level)) - to_db(self.slow_attack.process(level));
+        let sustain_diff = to_db(self.slow_release.process(level)) - to_db(self.fast_release.process(level));
+
+        let gain_db = (self.attack * attack_diff.max(0.0) + self.sustain * sustain_diff.max(0.0))
+            .clamp(-MAX_GAIN_DB, MAX_GAIN_DB);
+        10.0_f32.powf(gain_db / 20.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.fast_attack.env = 0.0;
+        self.slow_attack.env = 0.0;
+        self.fast_release.env = 0.0;
+        self.slow_release.env = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decaying 100Hz burst, roughly a drum hit
+    fn hit(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / 44100.0;
+                (t * 100.0 * std::f32::consts::TAU).sin() * (-t * 20.0).exp()
+            })
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |m, s| m.max(s.abs()))
+    }
+
+    #[test]
+    fn test_neutral_settings_pass_through() {
+        let mut shaper = TransientShaper::new(44100.0);
+        shaper.set_enabled(true);
+        for s in hit(4410) {
+            assert!((shaper.process(s) - s).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_attack_boosts_onset() {
+        let dry = hit(4410);
+        let mut shaper = TransientShaper::new(44100.0);
+        shaper.set_enabled(true);
+        shaper.set_attack(1.0);
+        let wet: Vec<f32> = dry.iter().map(|&s| shaper.process(s)).collect();
+        assert!(peak(&wet[..441]) > peak(&dry[..441]) * 1.2);
+    }
+
+    #[test]
+    fn test_sustain_cut_shortens_tail() {
+        let dry = hit(22050);
+        let mut shaper = TransientShaper::new(44100.0);
+        shaper.set_enabled(true);
+        shaper.set_sustain(-1.0);
+        let wet: Vec<f32> = dry.iter().map(|&s| shaper.process(s)).collect();
+        assert!(peak(&wet[8820..]) < peak(&dry[8820..]) * 0.5);
+    }
+}
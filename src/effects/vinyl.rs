@@ -0,0 +1,108 @@
+use crate::rng::Rng;
+
+/// Vinyl ambience
+/// Record hiss plus randomly scattered dust crackles, generated rather than
+/// sampled so it never loops audibly
+pub struct Vinyl {
+    rng: Rng,
+    level: f32,
+    /// Crackles per sample at full density
+    crackle_rate: f32,
+    dust: f32,
+
+    // Hiss is white noise through a one-pole lowpass
+    hiss: f32,
+    hiss_coeff: f32,
+
+    // Current crackle, decaying after each random impulse
+    crackle: f32,
+    crackle_decay: f32,
+}
+
+/// Crackles per second at full dust
+const MAX_CRACKLES_PER_S: f32 = 40.0;
+
+/// Hiss level relative to the crackles
+const HISS_GAIN: f32 = 0.15;
+
+impl Vinyl {
+    pub fn new(sample_rate: f32) -> Self {
+        Self {
+            rng: Rng::new(0x33),
+            level: 0.0,
+            crackle_rate: MAX_CRACKLES_PER_S / sample_rate,
+            dust: 0.3,
+            hiss: 0.0,
+            hiss_coeff: 1.0 - (-std::f32::consts::TAU * 6000.0 / sample_rate).exp(),
+            crackle: 0.0,
+            // Each crackle dies away over about half a millisecond
+            crackle_decay: (-1.0 / (0.0005 * sample_rate)).exp(),
+        }
+    }
+
+    /// Overall level (0.0 = off)
+    pub fn set_level(&mut self, level: f32) {
+        self.level = level.clamp(0.0, 1.0);
+    }
+
+    /// How often crackles occur (0.0 = clean hiss, 1.0 = very dusty)
+    pub fn set_dust(&mut self, dust: f32) {
+        self.dust = dust.clamp(0.0, 1.0);
+    }
+
+    pub fn process(&mut self) -> f32 {
+        if self.level == 0.0 {
+            return 0.0;
+        }
+
+        let white = self.rng.next_f32() * 2.0 - 1.0;
+        self.hiss += (white - self.hiss) * self.hiss_coeff;
+
+        if self.rng.chance(self.dust * self.crackle_rate) {
+            // Random size and polarity, mostly small ticks with the odd pop
+            let size = self.rng.next_f32().powi(3);
+            self.crackle = if self.rng.chance(0.5) { size } else { -size };
+        }
+        let crackle = self.crackle;
+        self.crackle *= self.crackle_decay;
+
+        (self.hiss * HISS_GAIN + crackle) * self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples well above the hiss floor
+    fn crackles(vinyl: &mut Vinyl) -> usize {
+        (0..44100).filter(|_| vinyl.process().abs() > 0.3).count()
+    }
+
+    #[test]
+    fn test_silent_at_zero_level() {
+        let mut vinyl = Vinyl::new(44100.0);
+        assert!((0..1000).all(|_| vinyl.process() == 0.0));
+    }
+
+    #[test]
+    fn test_hiss_without_dust() {
+        let mut vinyl = Vinyl::new(44100.0);
+        vinyl.set_level(1.0);
+        vinyl.set_dust(0.0);
+        let energy: f32 = (0..44100).map(|_| vinyl.process().abs()).sum();
+        assert!(energy > 0.0);
+        assert_eq!(crackles(&mut vinyl), 0);
+    }
+
+    #[test]
+    fn test_dust_adds_crackles() {
+        let mut light = Vinyl::new(44100.0);
+        light.set_level(1.0);
+        light.set_dust(0.1);
+        let mut heavy = Vinyl::new(44100.0);
+        heavy.set_level(1.0);
+        heavy.set_dust(1.0);
+        assert!(crackles(&mut heavy) > crackles(&mut light) * 3);
+    }
+}
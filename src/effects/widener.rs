@@ -0,0 +1,119 @@
+use std::f32::consts::PI;
+
+/// Mid/side stereo widener
+/// Scales the side signal to widen or narrow the image. The mono sum is left
+/// untouched, and side content below the crossover is removed so the bass
+/// stays centred and mono playback never loses low end.
+pub struct Widener {
+    sample_rate: f32,
+    enabled: bool,
+    width: f32,
+
+    // One-pole highpass on the side channel
+    hp_coeff: f32,
+    side_x1: f32,
+    side_y1: f32,
+}
+
+/// Below this frequency the side signal is collapsed to mono
+const MONO_BASS_HZ: f32 = 120.0;
+
+impl Widener {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut widener = Self {
+            sample_rate,
+            enabled: false,
+            width: 1.0,
+            hp_coeff: 0.0,
+            side_x1: 0.0,
+            side_y1: 0.0,
+        };
+        widener.update_coefficients();
+        widener
+    }
+
+    fn update_coefficients(&mut self) {
+        let rc = 1.0 / (2.0 * PI * MONO_BASS_HZ);
+        let dt = 1.0 / self.sample_rate;
+        self.hp_coeff = rc / (rc + dt);
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.reset();
+        }
+    }
+
+    /// Set stereo width: 0.0 = mono, 1.0 = unchanged, 2.0 = widest
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 2.0);
+    }
+
+    /// Process one stereo frame
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        if !self.enabled {
+            return (left, right);
+        }
+
+        let mid = (left + right) * 0.5;
+        let side = (left - right) * 0.5;
+
+        // Keep the bass mono
+        let side_hp = self.hp_coeff * (self.side_y1 + side - self.side_x1);
+        self.side_x1 = side;
+        self.side_y1 = side_hp;
+
+        let side = side_hp * self.width;
+        (mid + side, mid - side)
+    }
+
+    pub fn reset(&mut self) {
+        self.side_x1 = 0.0;
+        self.side_y1 = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mono_sum_preserved() {
+        let mut widener = Widener::new(44100.0);
+        widener.set_enabled(true);
+        widener.set_width(2.0);
+        for i in 0..1000 {
+            let l = (i as f32 * 0.1).sin();
+            let r = (i as f32 * 0.13).sin();
+            let (wl, wr) = widener.process(l, r);
+            assert!((wl + wr - (l + r)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_zero_width_is_mono() {
+        let mut widener = Widener::new(44100.0);
+        widener.set_enabled(true);
+        widener.set_width(0.0);
+        let (l, r) = widener.process(0.8, -0.2);
+        assert_eq!(l, r);
+    }
+
+    #[test]
+    fn test_bass_stays_centred() {
+        let mut widener = Widener::new(44100.0);
+        widener.set_enabled(true);
+        widener.set_width(2.0);
+        // A 40Hz signal only on the left ends up almost entirely in the mid
+        let mut side_peak: f32 = 0.0;
+        for i in 0..44100 {
+            let l = (2.0 * PI * 40.0 * i as f32 / 44100.0).sin();
+            let (wl, wr) = widener.process(l, 0.0);
+            if i > 4410 {
+                side_peak = side_peak.max((wl - wr).abs() * 0.5);
+            }
+        }
+        assert!(side_peak < 0.5);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::rng::Rng;
+use std::f32::consts::TAU;
+
+/// Wow and flutter
+/// Wobbles pitch like a worn cassette deck: a slow random drift (wow) and a
+/// faster flutter, both applied by modulating a short delay
+pub struct WowFlutter {
+    sample_rate: f32,
+    buffer: Vec<f32>,
+    write: usize,
+    depth: f32,
+
+    // Wow glides towards a new random target every WOW_PERIOD_S
+    rng: Rng,
+    wow: f32,
+    wow_target: f32,
+    wow_smooth: f32,
+    wow_countdown: usize,
+
+    flutter_phase: f32,
+    flutter_inc: f32,
+}
+
+/// Largest delay swing at full depth
+const WOW_MS: f32 = 3.0;
+const FLUTTER_MS: f32 = 0.3;
+
+/// Resting delay, leaving room for the swing in both directions
+const CENTER_MS: f32 = WOW_MS + FLUTTER_MS + 0.5;
+
+const WOW_PERIOD_S: f32 = 0.6;
+const FLUTTER_HZ: f32 = 7.5;
+
+impl WowFlutter {
+    pub fn new(sample_rate: f32) -> Self {
+        let len = (2.0 * CENTER_MS / 1000.0 * sample_rate) as usize + 2;
+        Self {
+            sample_rate,
+            buffer: vec![0.0; len],
+            write: 0,
+            depth: 0.0,
+            rng: Rng::new(0x7a9e),
+            wow: 0.0,
+            wow_target: 0.0,
+            wow_smooth: 1.0 / (WOW_PERIOD_S * 0.5 * sample_rate),
+            wow_countdown: 0,
+            flutter_phase: 0.0,
+            flutter_inc: FLUTTER_HZ / sample_rate,
+        }
+    }
+
+    /// Set the amount of pitch wobble (0.0 = off, 1.0 = very worn tape)
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let len = self.buffer.len();
+        self.buffer[self.write] = input;
+        self.write = (self.write + 1) % len;
+        if self.depth == 0.0 {
+            return input;
+        }
+
+        if self.wow_countdown == 0 {
+            self.wow_target = self.rng.next_f32() * 2.0 - 1.0;
+            self.wow_countdown = (WOW_PERIOD_S * self.sample_rate) as usize;
+        }
+        self.wow_countdown -= 1;
+        self.wow += (self.wow_target - self.wow) * self.wow_smooth;
+
+        self.flutter_phase = (self.flutter_phase + self.flutter_inc) % 1.0;
+        let flutter = (self.flutter_phase * TAU).sin();
+
+        let ms = CENTER_MS + self.depth * (self.wow * WOW_MS + flutter * FLUTTER_MS);
+        self.read(ms / 1000.0 * self.sample_rate)
+    }
+
+    /// Interpolated sample `lag` samples before the newest input
+    fn read(&self, lag: f32) -> f32 {
+        let len = self.buffer.len();
+        let whole = lag as usize;
+        let frac = lag - whole as f32;
+        let newest = self.write + len - 1;
+        let a = self.buffer[(newest - whole) % len];
+        let b = self.buffer[(newest - whole - 1 + len) % len];
+        a + (b - a) * frac
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.wow = 0.0;
+        self.wow_countdown = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(i: usize) -> f32 {
+        (i as f32 * 1000.0 / 44100.0 * TAU).sin()
+    }
+
+    /// Spread between the shortest and longest gap between rising zero crossings
+    fn period_spread(samples: &[f32]) -> f32 {
+        let crossings: Vec<f32> = samples
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] < 0.0 && w[1] >= 0.0)
+            .map(|(i, w)| i as f32 + w[0] / (w[0] - w[1]))
+            .collect();
+        let gaps: Vec<f32> = crossings.windows(2).map(|c| c[1] - c[0]).collect();
+        let max = gaps.iter().fold(f32::MIN, |m, &g| m.max(g));
+        let min = gaps.iter().fold(f32::MAX, |m, &g| m.min(g));
+        max - min
+    }
+
+    #[test]
+    fn test_zero_depth_passes_through() {
+        let mut wow = WowFlutter::new(44100.0);
+        for i in 0..1000 {
+            assert_eq!(wow.process(sine(i)), sine(i));
+        }
+    }
+
+    #[test]
+    fn test_depth_wobbles_pitch() {
+        let mut wow = WowFlutter::new(44100.0);
+        wow.set_depth(1.0);
+        let out: Vec<f32> = (0..88200).map(|i| wow.process(sine(i))).collect();
+        let dry: Vec<f32> = (0..88200).map(sine).collect();
+        assert!(period_spread(&dry) < 0.05);
+        assert!(period_spread(&out[4410..]) > 0.2);
+    }
+
+    #[test]
+    fn test_output_stays_bounded() {
+        let mut wow = WowFlutter::new(44100.0);
+        wow.set_depth(1.0);
+        for i in 0..44100 {
+            assert!(wow.process(sine(i)).abs() <= 1.0);
+        }
+    }
+}
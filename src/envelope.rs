@@ -1,47 +1,134 @@
-/// Decay-only envelope generator
-/// The 303 uses a simple decay envelope for the filter
+use wasm_bindgen::prelude::*;
+
+/// Longest attack either envelope accepts
+const MAX_ATTACK_MS: f32 = 50.0;
+
+/// Longest release either envelope accepts
+const MAX_RELEASE_MS: f32 = 5000.0;
+
+/// Per-sample multiplier that falls to 1% over `ms`
+fn fall_rate(ms: f32, sample_rate: f32) -> f32 {
+    let samples = (ms / 1000.0) * sample_rate;
+    0.01_f32.powf(1.0 / samples)
+}
+
+/// Attack-decay-release envelope generator
+/// The 303 uses a simple decay envelope for the filter; the attack is
+/// instant unless set, and ramps up to the peak before decaying. With a
+/// release set, `release` switches the decay to it once the note ends.
+#[wasm_bindgen]
 pub struct Envelope {
     sample_rate: f32,
     value: f32,
+    attack_ms: f32,
+    decay_ms: f32,
     decay_rate: f32,
+    release_ms: f32,
+    release_rate: f32,
     peak: f32,
+    attacking: bool,
+    released: bool,
 }
 
+#[wasm_bindgen]
 impl Envelope {
+    #[wasm_bindgen(constructor)]
     pub fn new(sample_rate: f32) -> Self {
         let mut env = Self {
             sample_rate,
             value: 0.0,
+            attack_ms: 0.0,
+            decay_ms: 0.0,
             decay_rate: 0.0,
+            release_ms: 0.0,
+            release_rate: 1.0,
             peak: 1.0,
+            attacking: false,
+            released: false,
         };
         env.set_decay(200.0); // Default 200ms decay
         env
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_decay(self.decay_ms);
+        self.set_release(self.release_ms);
+    }
+
     /// Set decay time in milliseconds
     pub fn set_decay(&mut self, ms: f32) {
         let ms = ms.clamp(10.0, 5000.0);
+        self.decay_ms = ms;
         // Calculate decay rate for exponential decay
         // After `ms` milliseconds, value should be at ~1% of peak
-        let samples = (ms / 1000.0) * self.sample_rate;
-        // decay^samples = 0.01 (1% remaining)
-        // decay = 0.01^(1/samples)
-        self.decay_rate = 0.01_f32.powf(1.0 / samples);
+        self.decay_rate = fall_rate(ms, self.sample_rate);
+    }
+
+    /// Decay time in milliseconds
+    pub fn decay(&self) -> f32 {
+        self.decay_ms
+    }
+
+    /// Set attack time in milliseconds (0 - 50, 0 = instant)
+    pub fn set_attack(&mut self, ms: f32) {
+        self.attack_ms = ms.clamp(0.0, MAX_ATTACK_MS);
+    }
+
+    /// Attack time in milliseconds
+    pub fn attack(&self) -> f32 {
+        self.attack_ms
     }
 
-    /// Trigger the envelope with optional accent multiplier
+    /// Set release time in milliseconds (0 - 5000). 0 keeps decaying at
+    /// the decay rate after the note ends, as the 303's filter does.
+    pub fn set_release(&mut self, ms: f32) {
+        self.release_ms = ms.clamp(0.0, MAX_RELEASE_MS);
+        if self.release_ms > 0.0 {
+            self.release_rate = fall_rate(self.release_ms, self.sample_rate);
+        }
+    }
+
+    /// Release time in milliseconds
+    pub fn release_time(&self) -> f32 {
+        self.release_ms
+    }
+
+    /// End the note: fall away at the release rate, if one is set
+    pub fn release(&mut self) {
+        if self.release_ms > 0.0 {
+            self.released = true;
+            self.attacking = false;
+        }
+    }
+
+    /// Trigger the envelope with optional accent multiplier. With an
+    /// attack set it rises from wherever it is to the peak.
     pub fn trigger(&mut self, accent_mult: f32) {
+        self.released = false;
         self.peak = accent_mult.clamp(0.5, 2.0);
-        self.value = self.peak;
+        if self.attack_ms > 0.0 {
+            self.attacking = true;
+        } else {
+            self.value = self.peak;
+        }
     }
 
     /// Process one sample
     pub fn process(&mut self) -> f32 {
+        if self.attacking {
+            self.value += self.peak / (self.attack_ms / 1000.0 * self.sample_rate);
+            if self.value >= self.peak {
+                self.value = self.peak;
+                self.attacking = false;
+            }
+            return self.value;
+        }
+
         let output = self.value;
 
-        // Exponential decay
-        self.value *= self.decay_rate;
+        // Exponential decay, or release once the note has ended
+        self.value *= if self.released { self.release_rate } else { self.decay_rate };
 
         // Floor very small values to zero
         if self.value < 0.0001 {
@@ -51,14 +138,144 @@ impl Envelope {
         output
     }
 
+    /// Fill `output` with consecutive samples
+    pub fn process_block(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
     /// Get current envelope value without advancing
     pub fn current(&self) -> f32 {
         self.value
     }
 
+    /// Silence the envelope immediately
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+        self.attacking = false;
+        self.released = false;
+    }
+
     /// Check if envelope is active
     pub fn is_active(&self) -> bool {
-        self.value > 0.0001
+        self.attacking || self.value > 0.0001
+    }
+}
+
+/// Attack of the amp envelope, fixed like the 303's VEG
+const AMP_ATTACK_MS: f32 = 3.0;
+
+/// Shortest release of the amp envelope after the gate closes
+const AMP_RELEASE_MS: f32 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AmpStage {
+    Idle,
+    Attack,
+    Decay,
+    Release,
+}
+
+/// Amplitude envelope for the VCA, separate from the filter's decay
+/// envelope as on the real 303: a short fixed attack, a decay while the
+/// gate is held and a quick release when it closes
+pub struct AmpEnvelope {
+    sample_rate: f32,
+    value: f32,
+    stage: AmpStage,
+    attack_ms: f32,
+    release_coeff: f32,
+    decay_ms: f32,
+    decay_rate: f32,
+}
+
+impl AmpEnvelope {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut env = Self {
+            sample_rate,
+            value: 0.0,
+            stage: AmpStage::Idle,
+            attack_ms: AMP_ATTACK_MS,
+            release_coeff: 0.0,
+            decay_ms: 0.0,
+            decay_rate: 0.0,
+        };
+        env.set_decay(3000.0);
+        env.set_release(AMP_RELEASE_MS);
+        env
+    }
+
+    /// Set decay time in milliseconds, reaching ~1% while the gate is held
+    pub fn set_decay(&mut self, ms: f32) {
+        let ms = ms.clamp(10.0, 5000.0);
+        self.decay_ms = ms;
+        self.decay_rate = fall_rate(ms, self.sample_rate);
+    }
+
+    /// Set release time in milliseconds, never shorter than the 10ms that
+    /// keeps note-offs from clicking (10 - 5000)
+    pub fn set_release(&mut self, ms: f32) {
+        let ms = ms.clamp(AMP_RELEASE_MS, MAX_RELEASE_MS);
+        self.release_coeff = (-1.0 / (ms / 1000.0 * self.sample_rate)).exp();
+    }
+
+    /// Decay time in milliseconds
+    pub fn decay(&self) -> f32 {
+        self.decay_ms
+    }
+
+    /// Set attack time in milliseconds, never shorter than the fixed
+    /// 303 attack (3 - 50)
+    pub fn set_attack(&mut self, ms: f32) {
+        self.attack_ms = ms.clamp(AMP_ATTACK_MS, MAX_ATTACK_MS);
+    }
+
+    /// Open the gate. A retrigger attacks from the current level so it
+    /// doesn't click.
+    pub fn gate_on(&mut self) {
+        self.stage = AmpStage::Attack;
+    }
+
+    /// Close the gate and release to silence
+    pub fn gate_off(&mut self) {
+        if self.stage != AmpStage::Idle {
+            self.stage = AmpStage::Release;
+        }
+    }
+
+    /// Process one sample
+    pub fn process(&mut self) -> f32 {
+        match self.stage {
+            AmpStage::Idle => {}
+            AmpStage::Attack => {
+                self.value += 1.0 / (self.attack_ms / 1000.0 * self.sample_rate);
+                if self.value >= 1.0 {
+                    self.value = 1.0;
+                    self.stage = AmpStage::Decay;
+                }
+            }
+            AmpStage::Decay => self.value *= self.decay_rate,
+            AmpStage::Release => {
+                self.value *= self.release_coeff;
+                if self.value < 0.0001 {
+                    self.value = 0.0;
+                    self.stage = AmpStage::Idle;
+                }
+            }
+        }
+        self.value
+    }
+
+    /// Get current envelope value without advancing
+    pub fn current(&self) -> f32 {
+        self.value
+    }
+
+    /// Silence the envelope immediately
+    pub fn reset(&mut self) {
+        self.value = 0.0;
+        self.stage = AmpStage::Idle;
     }
 }
 
@@ -123,4 +340,92 @@ mod tests {
 
         assert!(accent_peak > normal_peak);
     }
+
+    #[test]
+    fn test_amp_envelope_attack_and_release() {
+        let mut env = AmpEnvelope::new(1000.0);
+        env.gate_on();
+        // 3ms attack at 1kHz
+        env.process();
+        assert!(env.current() < 1.0);
+        for _ in 0..2 {
+            env.process();
+        }
+        assert_eq!(env.current(), 1.0);
+
+        // Held: decays slowly. Released: gone within tens of milliseconds
+        for _ in 0..100 {
+            env.process();
+        }
+        assert!(env.current() > 0.8);
+        env.gate_off();
+        for _ in 0..100 {
+            env.process();
+        }
+        assert_eq!(env.current(), 0.0);
+    }
+
+    #[test]
+    fn test_amp_decay_independent_of_filter_decay() {
+        let mut amp = AmpEnvelope::new(1000.0);
+        amp.set_decay(100.0);
+        assert_eq!(amp.decay(), 100.0);
+        amp.gate_on();
+        for _ in 0..104 {
+            amp.process();
+        }
+        assert!(amp.current() < 0.02);
+        assert_eq!(Envelope::new(1000.0).decay(), 200.0);
+    }
+
+    #[test]
+    fn test_envelope_attack_ramps_to_peak() {
+        let mut env = Envelope::new(1000.0);
+        env.set_attack(10.0);
+        env.trigger(1.0);
+        assert!(env.is_active());
+        let ramp: Vec<f32> = (0..10).map(|_| env.process()).collect();
+        assert!(ramp.windows(2).all(|w| w[1] > w[0]));
+        assert!(ramp[0] < 0.2);
+        assert_eq!(ramp[9], 1.0);
+        // Then decays as before
+        env.process();
+        assert!(env.current() < 1.0);
+
+        env.set_attack(500.0);
+        assert_eq!(env.attack(), 50.0);
+    }
+
+    #[test]
+    fn test_release_after_note_off() {
+        let run = |release: f32| {
+            let mut env = Envelope::new(1000.0);
+            env.set_decay(2000.0);
+            env.set_release(release);
+            env.trigger(1.0);
+            for _ in 0..10 {
+                env.process();
+            }
+            env.release();
+            for _ in 0..50 {
+                env.process();
+            }
+            env.current()
+        };
+        // No release: the decay carries on as if the key were held
+        assert!(run(0.0) > 0.8);
+        assert!(run(20.0) < 0.01);
+
+        let mut amp = AmpEnvelope::new(1000.0);
+        amp.set_release(500.0);
+        amp.gate_on();
+        for _ in 0..10 {
+            amp.process();
+        }
+        amp.gate_off();
+        for _ in 0..50 {
+            amp.process();
+        }
+        assert!(amp.current() > 0.5);
+    }
 }
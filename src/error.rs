@@ -0,0 +1,100 @@
+//! Errors reported by the wasm API
+//!
+//! Setters called from JS don't throw; an invalid argument is recorded
+//! instead and can be read back with `last_error()` and
+//! `last_error_message()` after the call.
+
+/// Why the last checked call was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiError {
+    /// Step index outside the pattern
+    StepIndex = 1,
+    /// No preset or pattern with that index
+    PresetIndex = 2,
+    /// Drum track number isn't 0-4
+    DrumTrack = 3,
+    /// Pattern data doesn't hold exactly one pattern
+    PatternLength = 4,
+    /// Saved state is corrupt or from a newer version
+    StateFormat = 5,
+    /// Pattern slot number out of range
+    SlotIndex = 6,
+    /// Recalling a slot that holds nothing
+    EmptySlot = 7,
+    /// Song chain isn't (synth, drums, repeats) triples with repeats above 0
+    ChainFormat = 8,
+    /// Byte isn't one of the trig conditions
+    TrigCondition = 9,
+    /// Playback direction index out of range
+    Direction = 10,
+    /// Scale index out of range
+    Scale = 11,
+    /// Synth parameter index isn't one of the automation lanes
+    Param = 12,
+    /// Drum kit model index out of range
+    KitModel = 13,
+    /// Mixer track number isn't 0-5
+    MixTrack = 14,
+    /// Pattern generator style index out of range
+    Style = 15,
+}
+
+impl ApiError {
+    /// Numeric code exposed to JS; 0 means no error
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            ApiError::StepIndex => "step index out of range",
+            ApiError::PresetIndex => "preset index out of range",
+            ApiError::DrumTrack => "unknown drum track",
+            ApiError::PatternLength => "pattern data has the wrong length",
+            ApiError::StateFormat => "saved state is corrupt or from a newer version",
+            ApiError::SlotIndex => "pattern slot out of range",
+            ApiError::EmptySlot => "pattern slot is empty",
+            ApiError::ChainFormat => "song chain is malformed or too long",
+            ApiError::TrigCondition => "unknown trig condition",
+            ApiError::Direction => "unknown playback direction",
+            ApiError::Scale => "unknown scale",
+            ApiError::Param => "unknown synth parameter",
+            ApiError::KitModel => "unknown drum kit model",
+            ApiError::MixTrack => "unknown mixer track",
+            ApiError::Style => "unknown pattern style",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_distinct_and_nonzero() {
+        let all = [
+            ApiError::StepIndex,
+            ApiError::PresetIndex,
+            ApiError::DrumTrack,
+            ApiError::PatternLength,
+            ApiError::StateFormat,
+            ApiError::SlotIndex,
+            ApiError::EmptySlot,
+            ApiError::ChainFormat,
+            ApiError::TrigCondition,
+            ApiError::Direction,
+            ApiError::Scale,
+            ApiError::Param,
+            ApiError::KitModel,
+            ApiError::MixTrack,
+            ApiError::Style,
+        ];
+        for (i, a) in all.iter().enumerate() {
+            assert_ne!(a.code(), 0);
+            assert!(!a.message().is_empty());
+            for b in &all[i + 1..] {
+                assert_ne!(a.code(), b.code());
+            }
+        }
+    }
+}
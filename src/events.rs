@@ -0,0 +1,144 @@
+use crate::automation::AutomationParam;
+use crate::drums::DrumTrack;
+
+/// A note scheduled by the host
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteEvent {
+    On { note: f32, accent: bool, slide: bool },
+    Off,
+}
+
+/// An event supplied by the host in host-driven mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostEvent {
+    Note(NoteEvent),
+    Drum(DrumTrack),
+    Param(AutomationParam, f32),
+}
+
+impl HostEvent {
+    /// Decode a packed event: kind 0 = synth note on (a = note, b = flags,
+    /// bit 0 accent, bit 1 slide), 1 = synth note off, 2 = drum trigger
+    /// (a = track), 3 = parameter change (a = parameter, b = value)
+    pub fn decode(kind: f32, a: f32, b: f32) -> Option<Self> {
+        match kind as u32 {
+            0 => {
+                let flags = b as u32;
+                Some(HostEvent::Note(NoteEvent::On {
+                    note: a,
+                    accent: flags & 1 != 0,
+                    slide: flags & 2 != 0,
+                }))
+            }
+            1 => Some(HostEvent::Note(NoteEvent::Off)),
+            2 => DrumTrack::from_index(a as u8).map(HostEvent::Drum),
+            3 => AutomationParam::from_index(a as u8).map(|p| HostEvent::Param(p, b)),
+            _ => None,
+        }
+    }
+}
+
+/// Events queued beyond this are dropped until the queue drains
+const QUEUE_CAPACITY: usize = 256;
+
+/// Events waiting for a sample offset within the upcoming blocks, kept in
+/// offset order. Storage is reserved up front so queuing never allocates.
+pub struct EventQueue<T> {
+    events: Vec<(u32, T)>,
+    head: usize,
+}
+
+impl<T: Copy> EventQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            events: Vec::with_capacity(QUEUE_CAPACITY),
+            head: 0,
+        }
+    }
+
+    /// Queue `event` for `offset` samples into the next block. Events at the
+    /// same offset keep the order they were pushed in.
+    pub fn push(&mut self, offset: u32, event: T) {
+        if self.events.len() >= QUEUE_CAPACITY {
+            return;
+        }
+        let index = self.events.partition_point(|&(o, _)| o <= offset).max(self.head);
+        self.events.insert(index, (offset, event));
+    }
+
+    /// Take the next event due at or before `offset`
+    pub fn pop_due(&mut self, offset: u32) -> Option<T> {
+        match self.events.get(self.head) {
+            Some(&(due, event)) if due <= offset => {
+                self.head += 1;
+                Some(event)
+            }
+            _ => None,
+        }
+    }
+
+    /// Drop played events and move the rest `len` samples earlier
+    pub fn end_block(&mut self, len: u32) {
+        self.events.drain(..self.head);
+        self.head = 0;
+        for (offset, _) in self.events.iter_mut() {
+            *offset = offset.saturating_sub(len);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.head = 0;
+    }
+}
+
+impl<T: Copy> Default for EventQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_come_out_in_offset_order() {
+        let mut queue = EventQueue::new();
+        queue.push(20, 'b');
+        queue.push(5, 'a');
+        queue.push(20, 'c');
+
+        assert_eq!(queue.pop_due(4), None);
+        assert_eq!(queue.pop_due(5), Some('a'));
+        assert_eq!(queue.pop_due(19), None);
+        assert_eq!(queue.pop_due(20), Some('b'));
+        assert_eq!(queue.pop_due(20), Some('c'));
+    }
+
+    #[test]
+    fn test_decode_host_events() {
+        assert_eq!(
+            HostEvent::decode(0.0, 48.0, 3.0),
+            Some(HostEvent::Note(NoteEvent::On { note: 48.0, accent: true, slide: true }))
+        );
+        assert_eq!(HostEvent::decode(2.0, 1.0, 0.0), Some(HostEvent::Drum(DrumTrack::Snare)));
+        assert_eq!(
+            HostEvent::decode(3.0, 0.0, 800.0),
+            Some(HostEvent::Param(AutomationParam::Cutoff, 800.0))
+        );
+        assert_eq!(HostEvent::decode(9.0, 0.0, 0.0), None);
+    }
+
+    #[test]
+    fn test_late_events_carry_into_next_block() {
+        let mut queue = EventQueue::new();
+        queue.push(10, 1);
+        queue.push(150, 2);
+        while queue.pop_due(127).is_some() {}
+        queue.end_block(128);
+
+        assert_eq!(queue.pop_due(21), None);
+        assert_eq!(queue.pop_due(22), Some(2));
+    }
+}
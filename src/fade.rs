@@ -0,0 +1,159 @@
+/// Short linear gain ramp used to soften transport starts and stops
+pub struct Fade {
+    gain: f32,
+    step: f32,
+    state: FadeState,
+    finished: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum FadeState {
+    Idle,
+    In,
+    Out,
+}
+
+impl Fade {
+    pub fn new(sample_rate: f32, ms: f32) -> Self {
+        let samples = (ms / 1000.0) * sample_rate;
+        Self {
+            gain: 1.0,
+            step: 1.0 / samples.max(1.0),
+            state: FadeState::Idle,
+            finished: false,
+        }
+    }
+
+    /// Start ramping up from silence
+    pub fn fade_in(&mut self) {
+        self.gain = 0.0;
+        self.state = FadeState::In;
+        self.finished = false;
+    }
+
+    /// Start ramping down to silence
+    pub fn fade_out(&mut self) {
+        self.state = FadeState::Out;
+        self.finished = false;
+    }
+
+    /// Advance one sample and return the gain to apply
+    pub fn process(&mut self) -> f32 {
+        match self.state {
+            FadeState::Idle => {}
+            FadeState::In => {
+                self.gain += self.step;
+                if self.gain >= 1.0 {
+                    self.gain = 1.0;
+                    self.state = FadeState::Idle;
+                }
+            }
+            FadeState::Out => {
+                self.gain -= self.step;
+                if self.gain <= 0.0 {
+                    // Back to unity once the owner has had a chance to reset
+                    // its voices, so live playing after a stop still sounds
+                    self.gain = 1.0;
+                    self.state = FadeState::Idle;
+                    self.finished = true;
+                    return 0.0;
+                }
+            }
+        }
+        self.gain
+    }
+
+    /// Returns true once after a fade-out has reached silence
+    pub fn take_finished(&mut self) -> bool {
+        std::mem::take(&mut self.finished)
+    }
+}
+
+/// Time for a mixer track to go in or out when muted, soloed or unmuted
+pub const MUTE_RAMP_MS: f32 = 5.0;
+
+/// Gain that ramps linearly to a new level rather than jumping to it, so
+/// switching a part in or out of the mix doesn't click
+#[derive(Clone, Copy)]
+pub struct GainRamp {
+    gain: f32,
+    target: f32,
+    step: f32,
+}
+
+impl GainRamp {
+    pub fn new(sample_rate: f32, ms: f32) -> Self {
+        let samples = (ms / 1000.0) * sample_rate;
+        Self {
+            gain: 1.0,
+            target: 1.0,
+            step: 1.0 / samples.max(1.0),
+        }
+    }
+
+    /// Level to ramp to (1.0 = unity, 0.0 = silent)
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    /// Advance one sample and return the gain to apply
+    pub fn process(&mut self) -> f32 {
+        self.gain += (self.target - self.gain).clamp(-self.step, self.step);
+        self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade_in_ramps_up() {
+        let mut fade = Fade::new(44100.0, 5.0);
+        fade.fade_in();
+        let first = fade.process();
+        assert!(first < 0.1);
+        for _ in 0..1000 {
+            fade.process();
+        }
+        assert_eq!(fade.process(), 1.0);
+    }
+
+    #[test]
+    fn test_fade_out_finishes_once() {
+        let mut fade = Fade::new(44100.0, 5.0);
+        fade.fade_out();
+
+        let mut silent = false;
+        for _ in 0..1000 {
+            if fade.process() == 0.0 {
+                silent = true;
+                break;
+            }
+        }
+        assert!(silent);
+        assert!(fade.take_finished());
+        assert!(!fade.take_finished());
+        assert_eq!(fade.process(), 1.0);
+    }
+
+    #[test]
+    fn test_gain_ramp_reaches_target_without_jumping() {
+        let mut ramp = GainRamp::new(44100.0, 5.0);
+        ramp.set_target(0.0);
+        let mut last = 1.0;
+        for _ in 0..220 {
+            let gain = ramp.process();
+            assert!(last - gain < 0.01);
+            last = gain;
+        }
+        assert!(last > 0.0);
+        for _ in 0..10 {
+            ramp.process();
+        }
+        assert_eq!(ramp.process(), 0.0);
+
+        ramp.set_target(1.0);
+        assert!(ramp.process() < 0.01);
+    }
+}
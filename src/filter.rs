@@ -1,93 +1,514 @@
 use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+
+/// Filter circuit being modelled
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterModel {
+    /// 18dB/octave 3-pole ladder, the original voicing
+    ThreePole,
+    /// TB-303 style 4-pole diode ladder; stages load each other, which
+    /// softens the slope and the resonance peak
+    Diode,
+    /// 24dB/octave Moog style transistor ladder
+    Moog,
+    /// 12dB/octave state-variable filter, lowpass output
+    SvfLowpass,
+    /// State-variable filter, bandpass output
+    SvfBandpass,
+    /// State-variable filter, highpass output
+    SvfHighpass,
+}
+
+impl FilterModel {
+    /// 0 = 3-pole, 1 = diode ladder, 2 = Moog ladder, 3 = SVF lowpass,
+    /// 4 = SVF bandpass, 5 = SVF highpass
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(FilterModel::ThreePole),
+            1 => Some(FilterModel::Diode),
+            2 => Some(FilterModel::Moog),
+            3 => Some(FilterModel::SvfLowpass),
+            4 => Some(FilterModel::SvfBandpass),
+            5 => Some(FilterModel::SvfHighpass),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    fn is_svf(self) -> bool {
+        matches!(self, FilterModel::SvfLowpass | FilterModel::SvfBandpass | FilterModel::SvfHighpass)
+    }
+}
+
+/// Internal oversampling of the filter, trading CPU for cleaner high
+/// resonance near the top of the range
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterQuality {
+    /// Run at the engine rate
+    Standard,
+    /// Run at twice the engine rate
+    X2,
+    /// Run at four times the engine rate
+    X4,
+}
 
-/// 18dB/octave (3-pole) resonant lowpass filter
-/// Emulates the distinctive TB-303 filter sound
+impl FilterQuality {
+    /// 0 = standard, 1 = 2x oversampled, 2 = 4x oversampled
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(FilterQuality::Standard),
+            1 => Some(FilterQuality::X2),
+            2 => Some(FilterQuality::X4),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    fn factor(self) -> f32 {
+        match self {
+            FilterQuality::Standard => 1.0,
+            FilterQuality::X2 => 2.0,
+            FilterQuality::X4 => 4.0,
+        }
+    }
+}
+
+/// Resonant filter, by default the 18dB/octave (3-pole) lowpass that
+/// emulates the distinctive TB-303 filter sound
+#[wasm_bindgen]
 pub struct Filter {
     sample_rate: f32,
     cutoff: f32,
     resonance: f32,
+    model: FilterModel,
+    quality: FilterQuality,
+
+    // Running state, and a second set for a side channel sharing the
+    // coefficients
+    state: FilterState,
+    side: FilterState,
+
+    // Input gain into the saturating input stage, 1.0 when not driven
+    drive_gain: f32,
 
-    // 3 cascaded one-pole filter states
-    s1: f32,
-    s2: f32,
-    s3: f32,
+    // Feedback past the point of oscillation at full resonance, with the
+    // ladder tuned so it sings at the cutoff frequency
+    self_oscillation: bool,
+
+    // Key tracking: how far the cutoff follows the note (0.0 - 1.0) and
+    // the note's frequency
+    key_track: f32,
+    note_freq: f32,
 
     // Coefficients
-    g: f32,  // filter coefficient
+    g: f32,  // filter coefficient: ladder stage gain, or SVF integrator gain
     k: f32,  // resonance coefficient
+
+    // Output gain that offsets the passband loss from resonance
+    gain_compensation: bool,
+    makeup: f32,
 }
 
+/// Makeup gain per unit of resonance feedback; the feedback pulls the
+/// passband down by roughly 1/(1 + k), slightly less once the peak adds back
+const MAKEUP_PER_K: f32 = 0.8;
+
+/// Feedback at full resonance for the diode ladder, higher than the Moog's
+/// because the loaded stages lose more around the loop
+const DIODE_MAX_K: f32 = 10.0;
+
+/// How much each diode ladder stage is loaded by the one after it
+const DIODE_COUPLING: f32 = 0.5;
+
+/// Input gain at full drive (+24dB)
+const MAX_DRIVE_GAIN: f32 = 16.0;
+
+/// Note at which key tracking leaves the cutoff unchanged (middle C), so
+/// at full tracking a cutoff of 261.6 Hz plays the note itself
+const KEY_TRACK_ROOT_HZ: f32 = 261.63;
+
+/// Feedback at full resonance when self-oscillating, as a multiple of the
+/// oscillation threshold; the excess sets how loud the filter sings
+const OSCILLATION_MARGIN: f32 = 1.5;
+
+/// Lowest damping of the state-variable filter, just short of oscillation
+const SVF_MIN_DAMPING: f32 = 0.02;
+
+#[wasm_bindgen]
 impl Filter {
+    #[wasm_bindgen(constructor)]
     pub fn new(sample_rate: f32) -> Self {
         let mut filter = Self {
             sample_rate,
             cutoff: 1000.0,
             resonance: 0.0,
-            s1: 0.0,
-            s2: 0.0,
-            s3: 0.0,
+            model: FilterModel::ThreePole,
+            quality: FilterQuality::Standard,
+            drive_gain: 1.0,
+            self_oscillation: false,
+            key_track: 0.0,
+            note_freq: KEY_TRACK_ROOT_HZ,
+            state: FilterState::new(),
+            side: FilterState::new(),
             g: 0.0,
             k: 0.0,
+            gain_compensation: false,
+            makeup: 1.0,
         };
         filter.update_coefficients();
         filter
     }
 
-    pub fn set_cutoff(&mut self, freq: f32) {
-        self.cutoff = freq.clamp(20.0, self.sample_rate * 0.49);
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.cutoff = self.cutoff.clamp(20.0, sample_rate * 0.49);
         self.update_coefficients();
     }
 
+    /// Set the cutoff in Hz; coefficients are only recomputed when it moves
+    pub fn set_cutoff(&mut self, freq: f32) {
+        let freq = freq.clamp(20.0, self.sample_rate * 0.49);
+        if freq != self.cutoff {
+            self.cutoff = freq;
+            self.update_coefficients();
+        }
+    }
+
     pub fn set_resonance(&mut self, res: f32) {
         self.resonance = res.clamp(0.0, 1.0);
         self.update_coefficients();
     }
 
+    /// Switch the filter circuit, clearing the state of the old one
+    pub fn set_model(&mut self, model: FilterModel) {
+        if model != self.model {
+            self.model = model;
+            self.reset();
+            self.update_coefficients();
+        }
+    }
+
+    pub fn model(&self) -> FilterModel {
+        self.model
+    }
+
+    /// Set the internal oversampling; the coefficients follow the new rate
+    pub fn set_quality(&mut self, quality: FilterQuality) {
+        if quality != self.quality {
+            self.quality = quality;
+            self.reset();
+            self.update_coefficients();
+        }
+    }
+
+    pub fn quality(&self) -> FilterQuality {
+        self.quality
+    }
+
+    /// Gain into the filter (0.0 - 1.0, up to +24dB), pushing the input
+    /// stage into saturation
+    pub fn set_drive(&mut self, amount: f32) {
+        self.drive_gain = 1.0 + amount.clamp(0.0, 1.0) * (MAX_DRIVE_GAIN - 1.0);
+    }
+
+    pub fn drive(&self) -> f32 {
+        (self.drive_gain - 1.0) / (MAX_DRIVE_GAIN - 1.0)
+    }
+
+    /// Let the ladder models self-oscillate at full resonance, tuned so the
+    /// pitch is the cutoff frequency. The diode ladder's loaded stages pull
+    /// it flat as the cutoff rises past the bass range; the state-variable
+    /// models never self-oscillate
+    pub fn set_self_oscillation(&mut self, on: bool) {
+        self.self_oscillation = on;
+        self.update_coefficients();
+    }
+
+    pub fn self_oscillation(&self) -> bool {
+        self.self_oscillation
+    }
+
+    /// How far the cutoff follows the note set by set_note (0.0 - 1.0).
+    /// At 1.0 the cutoff moves an octave per octave around middle C
+    pub fn set_key_track(&mut self, amount: f32) {
+        self.key_track = amount.clamp(0.0, 1.0);
+        self.update_coefficients();
+    }
+
+    pub fn key_track(&self) -> f32 {
+        self.key_track
+    }
+
+    /// Frequency of the note playing, for key tracking
+    pub fn set_note(&mut self, freq: f32) {
+        if freq != self.note_freq {
+            self.note_freq = freq.max(1.0);
+            if self.key_track > 0.0 {
+                self.update_coefficients();
+            }
+        }
+    }
+
+    /// Keep the output level roughly constant as resonance changes
+    pub fn set_gain_compensation(&mut self, on: bool) {
+        self.gain_compensation = on;
+        self.update_coefficients();
+    }
+
     fn update_coefficients(&mut self) {
-        // Compute filter coefficient using tan approximation for stability
-        let wc = 2.0 * PI * self.cutoff / self.sample_rate;
-        self.g = wc.tan();
+        // Coefficients are for the rate the model actually runs at
+        let rate = self.sample_rate * self.quality.factor();
+        let tracking = (self.note_freq / KEY_TRACK_ROOT_HZ).powf(self.key_track);
+        let cutoff = (self.cutoff * tracking).clamp(20.0, self.sample_rate * 0.49);
+        if self.model.is_svf() {
+            // Prewarped integrator gain and damping, 2 (no peak) down to
+            // just above 0 at full resonance
+            self.g = (PI * cutoff / rate).tan();
+            self.k = 2.0 - (2.0 - SVF_MIN_DAMPING) * self.resonance;
+            // The SVF keeps its passband level as resonance rises
+            self.makeup = 1.0;
+            return;
+        }
 
-        // Resonance: map 0-1 to useful range (0 to ~4 for self-oscillation)
-        // The 303 can self-oscillate at high resonance
-        self.k = self.resonance * 4.0;
+        let poles = if self.model == FilterModel::ThreePole { 3 } else { 4 };
+        let w = 2.0 * PI * cutoff / rate;
+        if self.self_oscillation {
+            // Stage gain putting the loop's phase at -180 degrees at the
+            // cutoff, and feedback past the gain needed to oscillate there
+            self.g = tuned_stage_gain(w, poles);
+            let threshold = 1.0 / stage_magnitude(self.g, w).powi(poles);
+            let max_k = if self.model == FilterModel::Diode { DIODE_MAX_K / 4.0 } else { 1.0 };
+            self.k = self.resonance * threshold * max_k * OSCILLATION_MARGIN;
+        } else {
+            // Compute filter coefficient using tan approximation for stability
+            let g = w.tan();
+            self.g = g / (1.0 + g);
+
+            // Resonance: map 0-1 to useful range (0 to ~4 for self-oscillation)
+            // The 303 can self-oscillate at high resonance
+            let max_k = if self.model == FilterModel::Diode { DIODE_MAX_K } else { 4.0 };
+            self.k = self.resonance * max_k;
+        }
+
+        let compensation = self.resonance * 4.0;
+        self.makeup = if self.gain_compensation { 1.0 + compensation * MAKEUP_PER_K } else { 1.0 };
     }
 
     pub fn process(&mut self, input: f32) -> f32 {
-        // 3-pole ladder filter with resonance feedback
-        // Based on simplified Moog ladder topology adapted for 3 poles
+        match self.quality {
+            FilterQuality::Standard => self.process_model(input),
+            FilterQuality::X2 => {
+                let [a, b] = self.state.up[0].interpolate(input);
+                let (a, b) = (self.process_model(a), self.process_model(b));
+                self.state.down[0].decimate(a, b)
+            }
+            FilterQuality::X4 => {
+                let mut halves = [0.0; 2];
+                for (half, x) in halves.iter_mut().zip(self.state.up[0].interpolate(input)) {
+                    let [a, b] = self.state.up[1].interpolate(x);
+                    let (a, b) = (self.process_model(a), self.process_model(b));
+                    *half = self.state.down[1].decimate(a, b);
+                }
+                self.state.down[0].decimate(halves[0], halves[1])
+            }
+        }
+    }
 
-        // Feedback path - take from output of 3rd stage
-        let feedback = self.s3;
+    /// Process a second signal through the same filter settings with its
+    /// own state, e.g. the side channel of a stereo voice
+    pub fn process_side(&mut self, input: f32) -> f32 {
+        std::mem::swap(&mut self.state, &mut self.side);
+        let out = self.process(input);
+        std::mem::swap(&mut self.state, &mut self.side);
+        out
+    }
 
-        // Saturate the feedback for analog-like behavior
-        let saturated_feedback = (self.k * feedback).tanh();
+    /// Process `buffer` in place
+    pub fn process_block(&mut self, buffer: &mut [f32]) {
+        for sample in buffer.iter_mut() {
+            *sample = self.process(*sample);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = FilterState::new();
+        self.side = FilterState::new();
+    }
+}
 
-        // Input with resonance feedback
-        let u = input - saturated_feedback;
+impl Filter {
+    /// Run the selected model for one sample at the internal rate
+    fn process_model(&mut self, input: f32) -> f32 {
+        let input = if self.drive_gain > 1.0 { (input * self.drive_gain).tanh() } else { input };
+        let out = match self.model {
+            FilterModel::ThreePole => self.process_three_pole(input),
+            FilterModel::Diode => self.process_diode(input),
+            FilterModel::Moog => self.process_moog(input),
+            FilterModel::SvfLowpass | FilterModel::SvfBandpass | FilterModel::SvfHighpass => self.process_svf(input),
+        };
+        // Apply soft clipping to prevent harsh clipping at high resonance
+        soft_clip(out * self.makeup)
+    }
+
+    fn process_three_pole(&mut self, input: f32) -> f32 {
+        // 3-pole ladder filter with resonance feedback
+        // Based on simplified Moog ladder topology adapted for 3 poles
+
+        // Feedback path - take from output of 3rd stage, saturated for
+        // analog-like behavior
+        let u = input - (self.k * self.state.s[2]).tanh();
 
         // Cascade of 3 one-pole lowpass filters
         // Each stage: y = g * (x - y) + y, simplified to y += g * (x - y)
-        let g_factor = self.g / (1.0 + self.g);
+        let g = self.g;
+        self.state.s[0] += g * (u - self.state.s[0]);
+        self.state.s[1] += g * (self.state.s[0] - self.state.s[1]);
+        self.state.s[2] += g * (self.state.s[1] - self.state.s[2]);
 
-        // Stage 1
-        self.s1 += g_factor * (u - self.s1);
+        // Output from 3rd pole gives us 18dB/octave
+        self.state.s[2]
+    }
 
-        // Stage 2
-        self.s2 += g_factor * (self.s1 - self.s2);
+    fn process_moog(&mut self, input: f32) -> f32 {
+        // Same ladder with a fourth pole for 24dB/octave
+        let u = input - (self.k * self.state.s[3]).tanh();
+        let g = self.g;
+        self.state.s[0] += g * (u - self.state.s[0]);
+        self.state.s[1] += g * (self.state.s[0] - self.state.s[1]);
+        self.state.s[2] += g * (self.state.s[1] - self.state.s[2]);
+        self.state.s[3] += g * (self.state.s[2] - self.state.s[3]);
+        self.state.s[3]
+    }
 
-        // Stage 3
-        self.s3 += g_factor * (self.s2 - self.s3);
+    fn process_diode(&mut self, input: f32) -> f32 {
+        // Four poles where each capacitor also discharges into the next
+        // stage, as in the 303's diode ladder, with the input driven
+        // through a saturating diode pair
+        let u = (input - (self.k * self.state.s[3]).tanh()).tanh();
+        let g = self.g;
+        let [s1, s2, s3, s4] = self.state.s;
+        self.state.s[0] += g * ((u - s1) - DIODE_COUPLING * (s1 - s2));
+        self.state.s[1] += g * ((s1 - s2) - DIODE_COUPLING * (s2 - s3));
+        self.state.s[2] += g * ((s2 - s3) - DIODE_COUPLING * (s3 - s4));
+        self.state.s[3] += g * (s3 - s4);
+        self.state.s[3]
+    }
 
-        // Output from 3rd pole gives us 18dB/octave
-        // Apply soft clipping to prevent harsh clipping at high resonance
-        soft_clip(self.s3)
+    fn process_svf(&mut self, input: f32) -> f32 {
+        // Trapezoidal state-variable filter, stable at any cutoff
+        let a1 = 1.0 / (1.0 + self.g * (self.g + self.k));
+        let a2 = self.g * a1;
+        let a3 = self.g * a2;
+        let v3 = input - self.state.ic2;
+        let band = a1 * self.state.ic1 + a2 * v3;
+        let low = self.state.ic2 + a2 * self.state.ic1 + a3 * v3;
+        self.state.ic1 = 2.0 * band - self.state.ic1;
+        self.state.ic2 = 2.0 * low - self.state.ic2;
+        match self.model {
+            FilterModel::SvfBandpass => band,
+            FilterModel::SvfHighpass => input - self.k * band - low,
+            _ => low,
+        }
     }
+}
 
-    pub fn reset(&mut self) {
-        self.s1 = 0.0;
-        self.s2 = 0.0;
-        self.s3 = 0.0;
+/// Everything a filter remembers between samples
+struct FilterState {
+    // Ladder stage states; the 3-pole model uses the first three
+    s: [f32; 4],
+
+    // State-variable filter integrator states
+    ic1: f32,
+    ic2: f32,
+
+    // Halfband up- and downsamplers, one pair per doubling
+    up: [Halfband; 2],
+    down: [Halfband; 2],
+}
+
+impl FilterState {
+    fn new() -> Self {
+        Self {
+            s: [0.0; 4],
+            ic1: 0.0,
+            ic2: 0.0,
+            up: [Halfband::new(), Halfband::new()],
+            down: [Halfband::new(), Halfband::new()],
+        }
+    }
+}
+
+/// Gain of a one-pole ladder stage, y += g * (x - y), at which `poles`
+/// stages plus the one-sample feedback delay shift a tone at `w` radians
+/// per sample by exactly 180 degrees
+fn tuned_stage_gain(w: f32, poles: i32) -> f32 {
+    let lag = ((PI - w) / poles as f32).tan();
+    let pole = lag / (w.sin() + lag * w.cos());
+    (1.0 - pole).clamp(1e-6, 1.0)
+}
+
+/// Magnitude response of a one-pole ladder stage with gain `g` at `w`
+fn stage_magnitude(g: f32, w: f32) -> f32 {
+    let pole = 1.0 - g;
+    g / (1.0 - 2.0 * pole * w.cos() + pole * pole).sqrt()
+}
+
+/// Taps of the halfband filter on either side of the 0.5 centre tap, at
+/// odd offsets 1, 3, 5...; the even offsets are zero
+const HALFBAND_TAPS: [f32; 5] = [0.30782851, -0.07809884, 0.02638208, -0.00727146, 0.00115325];
+
+/// Samples of history the halfband filter spans
+const HALFBAND_LEN: usize = 4 * HALFBAND_TAPS.len() - 1;
+
+/// Blackman-windowed 19-tap halfband lowpass at a quarter of its rate, for
+/// doubling or halving the rate
+struct Halfband {
+    history: [f32; HALFBAND_LEN],
+    pos: usize,
+}
+
+impl Halfband {
+    fn new() -> Self {
+        Self { history: [0.0; HALFBAND_LEN], pos: 0 }
+    }
+
+    fn push(&mut self, x: f32) {
+        self.pos = (self.pos + 1) % HALFBAND_LEN;
+        self.history[self.pos] = x;
+    }
+
+    /// Filter output for the newest sample pushed
+    fn output(&self) -> f32 {
+        let at = |delay: usize| self.history[(self.pos + HALFBAND_LEN - delay) % HALFBAND_LEN];
+        let centre = HALFBAND_LEN / 2;
+        HALFBAND_TAPS.iter().enumerate().fold(0.5 * at(centre), |sum, (i, tap)| {
+            let offset = 2 * i + 1;
+            sum + tap * (at(centre - offset) + at(centre + offset))
+        })
+    }
+
+    /// Two samples at double the rate for one input, zero-stuffed and
+    /// filtered, with the gain the zeros take away restored
+    fn interpolate(&mut self, x: f32) -> [f32; 2] {
+        self.push(2.0 * x);
+        let a = self.output();
+        self.push(0.0);
+        [a, self.output()]
+    }
+
+    /// One sample at half the rate from two, filtered before dropping one
+    fn decimate(&mut self, a: f32, b: f32) -> f32 {
+        self.push(a);
+        self.push(b);
+        self.output()
     }
 }
 
@@ -176,6 +597,204 @@ mod tests {
         assert!(max_res > max_no_res);
     }
 
+    #[test]
+    fn test_gain_compensation_evens_level() {
+        let rms = |resonance: f32, compensate: bool| {
+            let mut filter = Filter::new(44100.0);
+            filter.set_cutoff(800.0);
+            filter.set_resonance(resonance);
+            filter.set_gain_compensation(compensate);
+            let mut phase = 0.0f32;
+            let mut sum = 0.0f32;
+            for i in 0..22050 {
+                phase = (phase + 110.0 / 44100.0) % 1.0;
+                let out = filter.process(phase - 0.5);
+                if i >= 4410 {
+                    sum += out * out;
+                }
+            }
+            sum.sqrt()
+        };
+
+        // Without compensation full resonance loses most of the level
+        assert!(rms(1.0, false) < rms(0.0, false) * 0.5);
+        for resonance in [0.25, 0.5, 0.75, 1.0] {
+            let ratio = rms(resonance, true) / rms(0.0, true);
+            assert!((0.75..1.33).contains(&ratio), "resonance {} ratio {}", resonance, ratio);
+        }
+    }
+
+    /// Mean absolute output for a sine at `freq` once the filter has settled
+    fn response(model: FilterModel, cutoff: f32, resonance: f32, freq: f32) -> f32 {
+        let mut filter = Filter::new(44100.0);
+        filter.set_model(model);
+        filter.set_cutoff(cutoff);
+        filter.set_resonance(resonance);
+        let mut sum = 0.0f32;
+        for i in 0..8820 {
+            let out = filter.process((2.0 * PI * freq * (i as f32) / 44100.0).sin());
+            assert!(out.is_finite());
+            if i >= 4410 {
+                sum += out.abs();
+            }
+        }
+        sum / 4410.0
+    }
+
+    #[test]
+    fn test_lowpass_models() {
+        for model in [FilterModel::ThreePole, FilterModel::Diode, FilterModel::Moog, FilterModel::SvfLowpass] {
+            let low = response(model, 1000.0, 0.0, 100.0);
+            let high = response(model, 1000.0, 0.0, 8000.0);
+            assert!(low > 0.4, "{:?} low {}", model, low);
+            assert!(high < low * 0.1, "{:?} high {} low {}", model, high, low);
+        }
+        // Four poles roll off harder than three
+        assert!(response(FilterModel::Moog, 1000.0, 0.0, 8000.0) < response(FilterModel::ThreePole, 1000.0, 0.0, 8000.0));
+    }
+
+    #[test]
+    fn test_svf_outputs() {
+        let band = |freq| response(FilterModel::SvfBandpass, 1000.0, 0.5, freq);
+        assert!(band(1000.0) > band(100.0) * 3.0);
+        assert!(band(1000.0) > band(10000.0) * 3.0);
+
+        let high = |freq| response(FilterModel::SvfHighpass, 1000.0, 0.0, freq);
+        assert!(high(8000.0) > 0.5);
+        assert!(high(100.0) < high(8000.0) * 0.1);
+    }
+
+    #[test]
+    fn test_models_resonate_and_stay_bounded() {
+        for model in [FilterModel::ThreePole, FilterModel::Diode, FilterModel::Moog, FilterModel::SvfLowpass] {
+            let flat = response(model, 1000.0, 0.0, 1000.0);
+            let peaked = response(model, 1000.0, 0.9, 1000.0);
+            assert!(peaked > flat, "{:?} flat {} peaked {}", model, flat, peaked);
+            // Full resonance at the top of the range stays finite
+            response(model, 20000.0, 1.0, 15000.0);
+        }
+    }
+
+    #[test]
+    fn test_oversampling_keeps_the_response() {
+        for quality in [FilterQuality::X2, FilterQuality::X4] {
+            let level = |freq: f32| {
+                let mut filter = Filter::new(44100.0);
+                filter.set_quality(quality);
+                filter.set_cutoff(1000.0);
+                let mut sum = 0.0f32;
+                for i in 0..8820 {
+                    let out = filter.process((2.0 * PI * freq * (i as f32) / 44100.0).sin());
+                    if i >= 4410 {
+                        sum += out.abs();
+                    }
+                }
+                sum / 4410.0
+            };
+            // Passband near unity gain, highs still cut
+            assert!((0.55..0.7).contains(&level(100.0)), "{:?} {}", quality, level(100.0));
+            assert!(level(8000.0) < level(100.0) * 0.1);
+        }
+    }
+
+    #[test]
+    fn test_oversampling_reduces_aliasing() {
+        // Driven hard at 9kHz, the saturation's third harmonic at 27kHz
+        // folds back to 17.1kHz unless the filter runs oversampled
+        let alias = |quality: FilterQuality| {
+            let mut filter = Filter::new(44100.0);
+            filter.set_quality(quality);
+            filter.set_cutoff(9000.0);
+            filter.set_resonance(0.8);
+            let out: Vec<f32> = (0..8192)
+                .map(|i| filter.process(4.0 * (2.0 * PI * 9000.0 * (i as f32) / 44100.0).sin()))
+                .collect();
+            let magnitude = |freq: f32| {
+                let (re, im) = out[4096..].iter().enumerate().fold((0.0f32, 0.0f32), |(re, im), (i, x)| {
+                    let w = 2.0 * PI * freq * (i as f32) / 44100.0;
+                    (re + x * w.cos(), im + x * w.sin())
+                });
+                re.hypot(im)
+            };
+            magnitude(17100.0) / magnitude(9000.0)
+        };
+        let standard = alias(FilterQuality::Standard);
+        assert!(alias(FilterQuality::X2) < standard * 0.25);
+        assert!(alias(FilterQuality::X4) < standard * 0.25);
+    }
+
+    /// Pitch of a self-oscillating filter after a click, from its zero crossings
+    fn ringing_pitch(filter: &mut Filter) -> f32 {
+        let out: Vec<f32> = (0..44100).map(|i| filter.process(if i == 0 { 1.0 } else { 0.0 })).collect();
+        let tail = &out[22050..];
+        assert!(tail.iter().any(|x| x.abs() > 0.01), "filter stopped ringing");
+        let crossings: Vec<usize> = (1..tail.len()).filter(|&i| tail[i - 1] < 0.0 && tail[i] >= 0.0).collect();
+        let span = (crossings[crossings.len() - 1] - crossings[0]) as f32 / 44100.0;
+        (crossings.len() - 1) as f32 / span
+    }
+
+    #[test]
+    fn test_self_oscillation_sings_at_cutoff() {
+        for model in [FilterModel::ThreePole, FilterModel::Moog] {
+            for cutoff in [110.0, 440.0, 1000.0] {
+                let mut filter = Filter::new(44100.0);
+                filter.set_model(model);
+                filter.set_self_oscillation(true);
+                filter.set_resonance(1.0);
+                filter.set_cutoff(cutoff);
+                let pitch = ringing_pitch(&mut filter);
+                assert!((pitch / cutoff - 1.0).abs() < 0.01, "{:?} at {} rings at {}", model, cutoff, pitch);
+            }
+        }
+
+        // Below full resonance the ringing dies away
+        let mut filter = Filter::new(44100.0);
+        filter.set_self_oscillation(true);
+        filter.set_resonance(0.5);
+        let tail: f32 = (0..44100).map(|i| filter.process(if i == 0 { 1.0 } else { 0.0 }).abs()).skip(22050).sum();
+        assert!(tail < 1e-3);
+    }
+
+    #[test]
+    fn test_key_track_follows_note() {
+        let mut filter = Filter::new(44100.0);
+        filter.set_self_oscillation(true);
+        filter.set_resonance(1.0);
+        filter.set_cutoff(KEY_TRACK_ROOT_HZ);
+        filter.set_key_track(1.0);
+        filter.set_note(220.0);
+        let pitch = ringing_pitch(&mut filter);
+        assert!((pitch / 220.0 - 1.0).abs() < 0.01, "rings at {}", pitch);
+
+        // Without tracking the note is ignored
+        filter.set_key_track(0.0);
+        filter.reset();
+        let pitch = ringing_pitch(&mut filter);
+        assert!((pitch / KEY_TRACK_ROOT_HZ - 1.0).abs() < 0.01, "rings at {}", pitch);
+    }
+
+    #[test]
+    fn test_drive_saturates_input() {
+        let peak = |drive: f32| {
+            let mut filter = Filter::new(44100.0);
+            filter.set_cutoff(5000.0);
+            filter.set_drive(drive);
+            (0..4410).map(|i| filter.process(0.1 * (2.0 * PI * 100.0 * (i as f32) / 44100.0).sin()).abs()).fold(0.0f32, f32::max)
+        };
+        assert!(peak(0.5) > peak(0.0) * 4.0);
+        // Fully driven, the input stage flattens out near full scale
+        assert!(peak(1.0) < 1.1);
+    }
+
+    #[test]
+    fn test_model_from_index() {
+        assert_eq!(FilterModel::from_index(1), Some(FilterModel::Diode));
+        assert_eq!(FilterModel::from_index(5).map(FilterModel::index), Some(5));
+        assert_eq!(FilterModel::from_index(6), None);
+        assert_eq!(FilterQuality::from_index(2), Some(FilterQuality::X4));
+        assert_eq!(FilterQuality::from_index(3), None);
+    }
+
     #[test]
     fn test_soft_clip() {
         assert!((soft_clip(0.5) - 0.5).abs() < 0.01);
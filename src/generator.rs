@@ -0,0 +1,180 @@
+//! Random synth pattern generator
+//! Each style profile weights intervals, rests, slides and accents
+//! differently so one call gives a pattern that suits the genre
+
+use crate::rng::Rng;
+use crate::sequencer::{Step, DEFAULT_GATE, FULL_LEVEL};
+use crate::locks::Locks;
+use crate::trig::Trig;
+
+const STEPS: usize = 16;
+
+/// Lowest note of generated patterns before the root is added (C2)
+const BASE_NOTE: u8 = 36;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    ClassicAcid,
+    Electro,
+    Dub,
+    Hoover,
+}
+
+impl Style {
+    /// 0 = classic acid, 1 = electro, 2 = dub/off-beat, 3 = hoover stabs
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Style::ClassicAcid),
+            1 => Some(Style::Electro),
+            2 => Some(Style::Dub),
+            3 => Some(Style::Hoover),
+            _ => None,
+        }
+    }
+
+    fn profile(self) -> Profile {
+        match self {
+            Style::ClassicAcid => Profile {
+                intervals: &[0, 0, 0, 3, 5, 7, 10, 12],
+                rest: 0.2,
+                offbeat_rest: 0.2,
+                repeat: 0.25,
+                octave: 0.3,
+                slide: 0.25,
+                accent: 0.3,
+            },
+            Style::Electro => Profile {
+                intervals: &[0, 0, 7, 10, 12],
+                rest: 0.35,
+                offbeat_rest: 0.35,
+                repeat: 0.4,
+                octave: 0.15,
+                slide: 0.05,
+                accent: 0.45,
+            },
+            // Notes mostly on the off-beat eighths
+            Style::Dub => Profile {
+                intervals: &[0, 0, 0, 7, 10],
+                rest: 0.85,
+                offbeat_rest: 0.1,
+                repeat: 0.5,
+                octave: 0.1,
+                slide: 0.1,
+                accent: 0.2,
+            },
+            // Sparse stabs joined by long slides
+            Style::Hoover => Profile {
+                intervals: &[0, 0, 3, 7, 12],
+                rest: 0.5,
+                offbeat_rest: 0.5,
+                repeat: 0.1,
+                octave: 0.2,
+                slide: 0.6,
+                accent: 0.5,
+            },
+        }
+    }
+}
+
+/// Probabilities that shape a style
+struct Profile {
+    /// Semitones above the root to pick from
+    intervals: &'static [u8],
+    /// Chance of a rest on steps other than the off-beat eighths
+    rest: f32,
+    /// Chance of a rest on the off-beat eighths (steps 2, 6, 10, 14)
+    offbeat_rest: f32,
+    /// Chance of repeating the previous note
+    repeat: f32,
+    /// Chance of jumping up an octave
+    octave: f32,
+    slide: f32,
+    accent: f32,
+}
+
+/// Generate a pattern in `style` rooted on pitch class `root` (0 = C)
+pub fn generate(style: Style, root: u8, rng: &mut Rng) -> [Step; STEPS] {
+    let profile = style.profile();
+    let mut steps = [Step { note: BASE_NOTE, accent: false, slide: false, active: false, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE }; STEPS];
+    let mut previous = BASE_NOTE + root % 12;
+
+    for (i, step) in steps.iter_mut().enumerate() {
+        let rest = if i % 4 == 2 { profile.offbeat_rest } else { profile.rest };
+        if rng.chance(rest) {
+            continue;
+        }
+
+        let note = if rng.chance(profile.repeat) {
+            previous
+        } else {
+            let octave = if rng.chance(profile.octave) { 12 } else { 0 };
+            BASE_NOTE + root % 12 + rng.pick(profile.intervals) + octave
+        };
+        *step = Step {
+            note,
+            accent: rng.chance(profile.accent),
+            slide: rng.chance(profile.slide),
+            active: true,
+            cents: 0,
+            level: FULL_LEVEL,
+            gate: DEFAULT_GATE,
+            trig: Trig::ALWAYS,
+            ratchet: 1,
+            nudge: 0,
+            slide_time: 0,
+            locks: Locks::NONE,
+        };
+        previous = note;
+    }
+
+    // Never hand back a silent pattern
+    if steps.iter().all(|s| !s.active) {
+        let first = if style == Style::Dub { 2 } else { 0 };
+        steps[first].note = BASE_NOTE + root % 12;
+        steps[first].active = true;
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn active_count(steps: &[Step]) -> usize {
+        steps.iter().filter(|s| s.active).count()
+    }
+
+    #[test]
+    fn test_same_seed_same_pattern() {
+        let a = generate(Style::ClassicAcid, 9, &mut Rng::new(5));
+        let b = generate(Style::ClassicAcid, 9, &mut Rng::new(5));
+        assert_eq!(a, b);
+        assert!(active_count(&a) > 0);
+    }
+
+    #[test]
+    fn test_dub_favours_offbeats() {
+        let mut rng = Rng::new(11);
+        let (mut offbeat, mut other) = (0, 0);
+        for _ in 0..50 {
+            let steps = generate(Style::Dub, 0, &mut rng);
+            for (i, _) in steps.iter().enumerate().filter(|(_, s)| s.active) {
+                if i % 4 == 2 { offbeat += 1 } else { other += 1 }
+            }
+        }
+        // 4 off-beat steps per bar against 12 others
+        assert!(offbeat > other, "offbeat {} other {}", offbeat, other);
+    }
+
+    #[test]
+    fn test_hoover_slides_more_than_electro() {
+        let slides = |style| {
+            let mut rng = Rng::new(3);
+            (0..50)
+                .flat_map(|_| generate(style, 0, &mut rng))
+                .filter(|s| s.active && s.slide)
+                .count()
+        };
+        assert!(slides(Style::Hoover) > slides(Style::Electro) * 3);
+    }
+}
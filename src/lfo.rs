@@ -0,0 +1,193 @@
+//! Low-frequency oscillator
+//! Slow periodic movement for the filter, pitch and drive, running free in
+//! Hz or locked to the tempo so a wobble lines up with the pattern
+
+use crate::rng::Rng;
+use std::f32::consts::TAU;
+
+/// Free-running rate range in Hz
+const MIN_HZ: f32 = 0.01;
+const MAX_HZ: f32 = 40.0;
+
+/// Synced cycle length range in 16th-note steps
+const MIN_SYNC_STEPS: f32 = 0.25;
+const MAX_SYNC_STEPS: f32 = 64.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    /// A new random level held for each cycle
+    SampleAndHold,
+}
+
+impl LfoShape {
+    /// 0 = sine, 1 = triangle, 2 = square, 3 = sample & hold
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(LfoShape::Sine),
+            1 => Some(LfoShape::Triangle),
+            2 => Some(LfoShape::Square),
+            3 => Some(LfoShape::SampleAndHold),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+}
+
+/// How fast the LFO cycles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LfoRate {
+    /// Cycles per second
+    Free(f32),
+    /// Length of one cycle in 16th-note steps at the current tempo
+    Synced(f32),
+}
+
+pub struct Lfo {
+    sample_rate: f32,
+    shape: LfoShape,
+    rate: LfoRate,
+    tempo: f32,
+
+    // Position in the cycle (0.0 - 1.0) and its advance per sample
+    phase: f32,
+    inc: f32,
+
+    // Sample & hold level for the current cycle
+    held: f32,
+    rng: Rng,
+}
+
+impl Lfo {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut lfo = Self {
+            sample_rate,
+            shape: LfoShape::Sine,
+            rate: LfoRate::Free(1.0),
+            tempo: 120.0,
+            phase: 0.0,
+            inc: 0.0,
+            held: 0.0,
+            rng: Rng::new(0x1f0),
+        };
+        lfo.update_inc();
+        lfo
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    pub fn shape(&self) -> LfoShape {
+        self.shape
+    }
+
+    /// Run free at `hz` cycles per second (0.01 - 40)
+    pub fn set_rate_hz(&mut self, hz: f32) {
+        self.rate = LfoRate::Free(hz.clamp(MIN_HZ, MAX_HZ));
+        self.update_inc();
+    }
+
+    /// Lock to the tempo, one cycle every `steps` 16th notes (0.25 - 64)
+    pub fn set_synced(&mut self, steps: f32) {
+        self.rate = LfoRate::Synced(steps.clamp(MIN_SYNC_STEPS, MAX_SYNC_STEPS));
+        self.update_inc();
+    }
+
+    pub fn rate(&self) -> LfoRate {
+        self.rate
+    }
+
+    /// Tempo in BPM that synced rates follow
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm.max(1.0);
+        self.update_inc();
+    }
+
+    /// Start the cycle again, so a synced LFO lines up with the downbeat
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+        self.held = self.rng.next_f32() * 2.0 - 1.0;
+    }
+
+    /// Next value (-1.0 - 1.0)
+    pub fn process(&mut self) -> f32 {
+        let value = match self.shape {
+            LfoShape::Sine => (self.phase * TAU).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            LfoShape::Square => if self.phase < 0.5 { 1.0 } else { -1.0 },
+            LfoShape::SampleAndHold => self.held,
+        };
+        self.phase += self.inc;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.held = self.rng.next_f32() * 2.0 - 1.0;
+        }
+        value
+    }
+
+    fn update_inc(&mut self) {
+        let hz = match self.rate {
+            LfoRate::Free(hz) => hz,
+            LfoRate::Synced(steps) => self.tempo / 60.0 * 4.0 / steps,
+        };
+        self.inc = hz / self.sample_rate;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(lfo: &mut Lfo, samples: usize) -> Vec<f32> {
+        (0..samples).map(|_| lfo.process()).collect()
+    }
+
+    #[test]
+    fn test_shapes_stay_in_range() {
+        for index in 0..4 {
+            let mut lfo = Lfo::new(1000.0);
+            lfo.set_shape(LfoShape::from_index(index).unwrap());
+            lfo.set_rate_hz(10.0);
+            let values = cycle(&mut lfo, 1000);
+            assert!(values.iter().all(|v| (-1.0..=1.0).contains(v)));
+            assert!(values.iter().any(|&v| v > 0.2) && values.iter().any(|&v| v < -0.2), "shape {}", index);
+        }
+        assert_eq!(LfoShape::from_index(4), None);
+    }
+
+    #[test]
+    fn test_synced_rate_follows_tempo() {
+        // 120 BPM: 8 steps is one second
+        let mut lfo = Lfo::new(1000.0);
+        lfo.set_shape(LfoShape::Square);
+        lfo.set_synced(8.0);
+        let values = cycle(&mut lfo, 1000);
+        assert_eq!(values[490], 1.0);
+        assert_eq!(values[510], -1.0);
+
+        // Twice the tempo, twice as fast
+        lfo.set_tempo(240.0);
+        lfo.reset();
+        let values = cycle(&mut lfo, 500);
+        assert_eq!(values[240], 1.0);
+        assert_eq!(values[260], -1.0);
+    }
+
+    #[test]
+    fn test_sample_and_hold_holds_each_cycle() {
+        let mut lfo = Lfo::new(1000.0);
+        lfo.set_shape(LfoShape::SampleAndHold);
+        lfo.set_rate_hz(4.0);
+        lfo.reset();
+        let values = cycle(&mut lfo, 500);
+        assert!(values[..240].iter().all(|&v| v == values[0]));
+        assert_ne!(values[260], values[0]);
+    }
+}
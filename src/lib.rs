@@ -1,144 +1,410 @@
 use wasm_bindgen::prelude::*;
 
 mod oscillator;
+mod clock;
 mod filter;
 mod envelope;
 mod sequencer;
 mod distortion;
 mod presets;
 mod drums;
+mod fade;
+mod wav;
+mod loudness;
+mod midi;
+mod accent;
+mod effects;
+mod automation;
+mod resampler;
+mod sampler;
+mod events;
+mod error;
+mod state;
+mod scale;
+mod rng;
+mod generator;
+mod slots;
+mod song;
+mod pan;
+mod trig;
+mod locks;
+mod lfo;
+mod supersaw;
+mod slide;
+#[cfg(test)]
+mod alloc_counter;
 
-pub use oscillator::{Oscillator, Waveform};
-pub use filter::Filter;
-pub use envelope::Envelope;
-pub use sequencer::{Sequencer, Step};
+pub use oscillator::{AntiAlias, Oscillator, Waveform};
+pub use filter::{Filter, FilterModel, FilterQuality};
+pub use envelope::{AmpEnvelope, Envelope};
+pub use sequencer::{Direction, Lane, SeqEvent, Sequencer, Step};
+use sequencer::STEPS;
 pub use distortion::Distortion;
 pub use presets::PRESETS;
-pub use drums::{DrumMachine, DrumSequencer, DrumTrack};
+pub use drums::{Clap, ClosedHihat, DrumMachine, DrumSequencer, DrumTrack, FillKind, Kick, KitModel, OpenHihat, Snare};
+pub use wav::{encode_wav, encode_wav_interleaved, WavFormat};
+pub use loudness::Normalize;
+pub use trig::{Condition, Trig};
+pub use locks::Locks;
+pub use lfo::{Lfo, LfoRate, LfoShape};
+pub use supersaw::SuperSaw;
+pub use slide::{Glide, SlideCurve};
+pub use accent::{AccentCurve, AccentResponse, AccentSweep};
+pub use effects::{Compressor, DcBlocker, Delay, EnvelopeFollower, HighPass, MultibandDistortion, NoiseGate, Reverb, RingMod, RingModSource, Stutter, TapeStop, TransientShaper, Vinyl, Widener, WowFlutter};
+use automation::{Automation, AutomationParam, Sweep, PARAM_COUNT};
+use clock::Clock;
+use fade::{Fade, GainRamp, MUTE_RAMP_MS};
+use midi::{CcMap, HeldKeys, MidiOut};
+use resampler::Resampler;
+use sampler::Sampler;
+use events::{EventQueue, HostEvent, NoteEvent};
+use error::ApiError;
+use state::Session;
+use slots::{DrumSlot, PatternSlot, PatternSlots, Sound, StoredPattern};
+use song::{BarEnd, ChainEntry, Song};
+use drums::sequencer::DrumStep;
+use scale::{Key, Scale};
+use rng::Rng;
+use generator::Style;
+use pan::pan;
 
+/// Engine rate used by `new`; hosts running at another rate either pass
+/// theirs to `new_with_sample_rate` or resample the output
 const SAMPLE_RATE: f32 = 44100.0;
 
+/// Block size used for internal scratch buffers
+const BLOCK_SIZE: usize = 128;
+
 /// Main synthesizer engine - TB-303 style acid synth
 #[wasm_bindgen]
 pub struct Synth {
+    sample_rate: f32,
     oscillator: Oscillator,
+    // Unison saws played in place of the oscillator when on
+    supersaw: SuperSaw,
     filter: Filter,
     envelope: Envelope,
+    amp_envelope: AmpEnvelope,
     sequencer: Sequencer,
     distortion: Distortion,
+    // Drive the oscillator into the filter instead of after the VCA
+    distortion_pre_filter: bool,
+    dc_blocker: DcBlocker,
+    pan: f32,
 
     // Parameters
     cutoff: f32,
     resonance: f32,
     env_mod: f32,
     accent_amount: f32,
+    accent_curve: AccentCurve,
+    accent_sweep: AccentSweep,
+
+    // Knobs held by the playing step's parameter locks, indexed by
+    // AutomationParam: the value before the lock and the value locked to
+    locked: [Option<(f32, f32)>; PARAM_COUNT],
 
     // State
-    current_note: f32,
-    target_note: f32,
-    slide_rate: f32,
-    is_sliding: bool,
+    glide: Glide,
+    slide_time: f32,
+    slide_curve: SlideCurve,
     gate: bool,
+    vca_gain: f32,
+    vca_coeff: f32,
+    accent_gain: f32,
+    // How strongly the playing note charges the accent sweep, 0 unaccented
+    sweep_amount: f32,
+    note_level: f32,
+    // Stereo spread of the unison voice after the filter and VCA, as a
+    // side signal for the last sample rendered
+    side: f32,
+    fade: Fade,
+
+    // Sequencer notes mirrored as MIDI for external gear
+    midi_out: MidiOut,
+
+    // Incoming MIDI: knob assignments, the keys held, the top one sounding,
+    // and the pitch bend in semitones
+    cc_map: CcMap,
+    held_keys: HeldKeys,
+    bend: f32,
+
+    // Whole-instrument tuning in cents and register shift in octaves
+    master_tune: f32,
+    octave: i8,
+
+    // Host notes waiting for their sample offset
+    scheduled: EventQueue<NoteEvent>,
+
+    // LFO and how far it moves each destination (-1.0 - 1.0 of full range)
+    lfo: Lfo,
+    lfo_to_cutoff: f32,
+    lfo_to_pitch: f32,
+    lfo_to_drive: f32,
+    lfo_to_pwm: f32,
+
+    // External cutoff modulation in octaves, read one value per sample
+    cutoff_mod: Vec<f32>,
+    cutoff_mod_pos: usize,
+
+    // Samples left until pitch and cutoff are next recomputed
+    control_countdown: usize,
+
+    // Conversion to the host rate when it differs from sample_rate
+    resampler: Option<Resampler>,
+
+    // Randomness for pattern variations
+    rng: Rng,
+
+    // A/B snapshots of the knobs; the active slot lives in the knobs
+    // themselves and is stored only when switching away
+    ab_slots: [Vec<f32>; 2],
+    ab_active: usize,
+
+    // Outcome of the last checked API call
+    last_error: Option<ApiError>,
 }
 
+/// Length of the fade applied when the transport starts or stops
+const TRANSPORT_FADE_MS: f32 = 5.0;
+
+/// Smoothing time for VCA gain changes, long enough to round off retrigger steps
+const VCA_SMOOTH_MS: f32 = 1.0;
+
+/// Samples between recomputing the oscillator pitch and filter cutoff,
+/// about a third of a millisecond at 44.1kHz; the powf() and tan() behind
+/// them are the costliest part of a sample
+const CONTROL_INTERVAL: usize = 16;
+
+/// Longest cutoff modulation buffer accepted, reserved up front so setting
+/// one from the audio callback never allocates
+const MOD_BUFFER_CAPACITY: usize = 4096;
+
+/// Cutoff and pitch swings of the LFO at full depth
+const LFO_CUTOFF_OCTAVES: f32 = 3.0;
+const LFO_PITCH_SEMITONES: f32 = 12.0;
+
+/// Pulse width swing of the LFO at full depth
+const LFO_PWM_RANGE: f32 = 0.45;
+
+/// Cutoff rise from a fully charged accent sweep
+const ACCENT_SWEEP_OCTAVES: f32 = 2.0;
+
 #[wasm_bindgen]
 impl Synth {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
-        Self {
-            oscillator: Oscillator::new(SAMPLE_RATE),
-            filter: Filter::new(SAMPLE_RATE),
-            envelope: Envelope::new(SAMPLE_RATE),
-            sequencer: Sequencer::new(),
+        Self::new_with_sample_rate(SAMPLE_RATE)
+    }
+
+    /// Create a synth running at `sample_rate`, normally the host's own rate
+    /// (e.g. AudioContext.sampleRate), so pitch and tempo are right without
+    /// resampling
+    #[wasm_bindgen]
+    pub fn new_with_sample_rate(sample_rate: f32) -> Self {
+        let mut sequencer = Sequencer::new();
+        sequencer.set_sample_rate(sample_rate);
+        let mut synth = Self {
+            sample_rate,
+            oscillator: Oscillator::new(sample_rate),
+            supersaw: SuperSaw::new(sample_rate),
+            filter: Filter::new(sample_rate),
+            envelope: Envelope::new(sample_rate),
+            amp_envelope: AmpEnvelope::new(sample_rate),
+            sequencer,
             distortion: Distortion::new(),
+            distortion_pre_filter: false,
+            dc_blocker: DcBlocker::new(sample_rate),
+            pan: 0.0,
 
             cutoff: 1000.0,
             resonance: 0.5,
             env_mod: 0.5,
             accent_amount: 0.7,
+            accent_curve: AccentCurve::Classic,
+            accent_sweep: AccentSweep::new(sample_rate),
+            locked: [None; PARAM_COUNT],
 
-            current_note: 36.0, // C2
-            target_note: 36.0,
-            slide_rate: 0.001,
-            is_sliding: false,
+            glide: Glide::new(36.0), // C2
+            slide_time: 1000.0 / 44.1, // ~23ms
+            slide_curve: SlideCurve::Exponential,
             gate: false,
-        }
+            vca_gain: 0.0,
+            vca_coeff: 1.0 - (-1.0 / (VCA_SMOOTH_MS / 1000.0 * sample_rate)).exp(),
+            accent_gain: 1.0,
+            sweep_amount: 0.0,
+            note_level: 1.0,
+            side: 0.0,
+            fade: Fade::new(sample_rate, TRANSPORT_FADE_MS),
+            midi_out: MidiOut::new(),
+            cc_map: CcMap::new(),
+            held_keys: HeldKeys::new(),
+            bend: 0.0,
+            master_tune: 0.0,
+            octave: 0,
+            scheduled: EventQueue::new(),
+            lfo: Lfo::new(sample_rate),
+            lfo_to_cutoff: 0.0,
+            lfo_to_pitch: 0.0,
+            lfo_to_drive: 0.0,
+            lfo_to_pwm: 0.0,
+            cutoff_mod: Vec::with_capacity(MOD_BUFFER_CAPACITY),
+            cutoff_mod_pos: 0,
+            control_countdown: 0,
+            resampler: None,
+            rng: Rng::new(0x303),
+            ab_slots: [Vec::new(), Vec::new()],
+            ab_active: 0,
+            last_error: None,
+        };
+        synth.set_resonance(synth.resonance);
+        synth
     }
 
     /// Process a block of audio samples
     #[wasm_bindgen]
     pub fn process(&mut self, output: &mut [f32]) {
-        for sample in output.iter_mut() {
-            // Handle note sliding (portamento)
-            if self.is_sliding {
-                if (self.current_note - self.target_note).abs() > 0.01 {
-                    self.current_note += (self.target_note - self.current_note) * self.slide_rate;
-                } else {
-                    self.current_note = self.target_note;
-                    self.is_sliding = false;
-                }
-            }
-
-            // Convert MIDI note to frequency
-            let freq = midi_to_freq(self.current_note);
-            self.oscillator.set_frequency(freq);
-
-            // Generate oscillator
-            let osc_out = self.oscillator.process();
-
-            // Get envelope value
-            let env = self.envelope.process();
-
-            // Calculate filter cutoff with envelope modulation
-            let env_scaled = env * self.env_mod * 10000.0;
-            let filter_freq = (self.cutoff + env_scaled).clamp(20.0, 20000.0);
-            self.filter.set_cutoff(filter_freq);
+        if let Some(mut resampler) = self.resampler.take() {
+            resampler.process(output, |block| self.render_block(block));
+            self.resampler = Some(resampler);
+        } else {
+            self.render_block(output);
+        }
+    }
 
-            // Apply filter
-            let filtered = self.filter.process(osc_out);
+    /// Process into an interleaved buffer with `channels` channels per frame
+    #[wasm_bindgen]
+    pub fn process_interleaved(&mut self, buffer: &mut [f32], channels: usize) {
+        process_interleaved_with(buffer, channels, |block| self.process(block));
+    }
 
-            // Apply VCA (envelope also controls amplitude)
-            let vca_out = filtered * (0.3 + env * 0.7);
+    /// Process a block into separate left and right buffers, placed by the
+    /// pan setting and widened by the unison spread
+    #[wasm_bindgen]
+    pub fn process_stereo(&mut self, left: &mut [f32], right: &mut [f32]) {
+        if let Some(mut resampler) = self.resampler.take() {
+            resampler.process_stereo(left, right, |l, r| self.render_stereo_block(l, r));
+            self.resampler = Some(resampler);
+        } else {
+            self.render_stereo_block(left, right);
+        }
+    }
 
-            // Apply distortion
-            let distorted = self.distortion.process(vca_out);
+    /// Stereo position for process_stereo (-1.0 left to 1.0 right)
+    #[wasm_bindgen]
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
 
-            *sample = distorted * 0.5; // Master volume
+    /// Process a block and fill `gate` (0 or 1) and `pitch` (1.0 per octave,
+    /// 0 at C4) alongside it, for driving modular-style modules or scopes
+    #[wasm_bindgen]
+    pub fn process_with_cv(&mut self, output: &mut [f32], gate: &mut [f32], pitch: &mut [f32]) {
+        if let Some(mut resampler) = self.resampler.take() {
+            resampler.process_with_cv(output, gate, pitch, |block, gate, pitch| {
+                self.render_block_with(block, |offset, cv| write_cv(gate, pitch, offset, cv))
+            });
+            self.resampler = Some(resampler);
+        } else {
+            self.render_block_with(output, |offset, cv| write_cv(gate, pitch, offset, cv));
         }
     }
 
     /// Trigger a note
     #[wasm_bindgen]
     pub fn note_on(&mut self, note: f32, accent: bool, slide: bool) {
+        self.play_note(note, accent, slide, self.slide_time);
+    }
+
+    /// Trigger a note, sliding over `slide_ms` if it slides
+    fn play_note(&mut self, note: f32, accent: bool, slide: bool, slide_ms: f32) {
         if slide && self.gate {
             // Slide to new note
-            self.target_note = note;
-            self.is_sliding = true;
+            self.glide.start(note, self.slide_curve, slide_ms / 1000.0 * self.sample_rate);
         } else {
             // Immediate note change
-            self.current_note = note;
-            self.target_note = note;
-            self.is_sliding = false;
+            self.glide.set(note);
         }
 
         self.gate = true;
+        // Pick up the new pitch on the next sample
+        self.control_countdown = 0;
 
-        // Trigger envelope with accent
-        let accent_mult = if accent { 1.0 + self.accent_amount } else { 1.0 };
-        self.envelope.trigger(accent_mult);
-
-        // Accent also boosts resonance temporarily
-        if accent {
-            self.filter.set_resonance((self.resonance + 0.2).min(1.0));
+        // Accent shapes envelope peak, sweep and level per the curve
+        let response = if accent {
+            self.accent_curve.response(self.accent_amount)
         } else {
-            self.filter.set_resonance(self.resonance);
-        }
+            AccentResponse::NONE
+        };
+        self.envelope.trigger(response.env_peak);
+        self.amp_envelope.gate_on();
+        self.sweep_amount = response.sweep;
+        self.accent_gain = response.vca_gain;
+        self.note_level = 1.0;
     }
 
     /// Release a note
     #[wasm_bindgen]
     pub fn note_off(&mut self) {
         self.gate = false;
+        self.envelope.release();
+        self.amp_envelope.gate_off();
+    }
+
+    /// Handle a raw MIDI message on any channel. Velocity of ACCENT_VELOCITY
+    /// or more plays accented, and a key pressed while another is held
+    /// slides to it; letting it go slides back to the last key still held.
+    /// Pitch bend covers two semitones; CCs move the knobs
+    /// in the default CC map (74 cutoff, 71 resonance, 70 env mod, 75
+    /// decay, 76 accent, 77 distortion).
+    #[wasm_bindgen]
+    pub fn handle_midi(&mut self, status: u8, data1: u8, data2: u8) {
+        self.handle_midi_pitched(status, data1, data2, |pitch| pitch);
+    }
+
+    /// handle_midi(), playing each key at the pitch `pitch` gives for it
+    fn handle_midi_pitched(&mut self, status: u8, data1: u8, data2: u8, pitch: impl Fn(f32) -> f32) {
+        let note = data1 & 0x7F;
+        match status & 0xF0 {
+            midi::NOTE_ON if data2 > 0 => {
+                let legato = self.held_keys.top().is_some();
+                self.note_on(pitch(note as f32), data2 >= midi::ACCENT_VELOCITY, legato);
+                self.held_keys.press(note);
+            }
+            midi::NOTE_ON | midi::NOTE_OFF if self.held_keys.top() == Some(note) => {
+                self.held_keys.release(note);
+                match self.held_keys.top() {
+                    Some(held) => {
+                        let samples = self.slide_time / 1000.0 * self.sample_rate;
+                        self.glide.start(pitch(held as f32), self.slide_curve, samples);
+                    }
+                    None => self.note_off(),
+                }
+            }
+            // Releasing a key that was slid away from keeps the note
+            midi::NOTE_ON | midi::NOTE_OFF => self.held_keys.release(note),
+            midi::PITCH_BEND => self.bend = midi::bend_semitones(data1, data2),
+            midi::CONTROL_CHANGE => {
+                if let Some((param, value)) = self.cc_map.handle(data1, data2) {
+                    self.apply_param(param, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Trigger a note `offset` samples into the next process() call
+    #[wasm_bindgen]
+    pub fn note_on_at(&mut self, offset: u32, note: f32, accent: bool, slide: bool) {
+        let offset = internal_offset(&self.resampler, offset);
+        self.scheduled.push(offset, NoteEvent::On { note, accent, slide });
+    }
+
+    /// Release the note `offset` samples into the next process() call
+    #[wasm_bindgen]
+    pub fn note_off_at(&mut self, offset: u32) {
+        let offset = internal_offset(&self.resampler, offset);
+        self.scheduled.push(offset, NoteEvent::Off);
     }
 
     // Parameter setters
@@ -148,11 +414,58 @@ impl Synth {
         self.oscillator.set_waveform(if saw { Waveform::Saw } else { Waveform::Square });
     }
 
+    /// Select any waveform: 0 = saw, 1 = square, 2 = triangle, 3 = pulse,
+    /// 4 = noise
+    #[wasm_bindgen]
+    pub fn set_waveform_type(&mut self, waveform: u8) {
+        if let Some(waveform) = Waveform::from_index(waveform) {
+            self.oscillator.set_waveform(waveform);
+        }
+    }
+
+    /// Duty cycle of the pulse waveform (0.05 - 0.95)
+    #[wasm_bindgen]
+    pub fn set_pulse_width(&mut self, width: f32) {
+        self.oscillator.set_pulse_width(width);
+    }
+
+    /// Stack 2 - 5 detuned saws in place of the oscillator; 1 returns to
+    /// the single waveform
+    #[wasm_bindgen]
+    pub fn set_unison_voices(&mut self, voices: u8) {
+        self.supersaw.set_voices(voices as usize);
+    }
+
+    /// Detune of the unison saws (0.0 - 1.0, up to 50 cents each way)
+    #[wasm_bindgen]
+    pub fn set_unison_detune(&mut self, amount: f32) {
+        self.supersaw.set_detune(amount);
+    }
+
+    /// Stereo spread of the unison saws (0.0 - 1.0), heard on the stereo
+    /// outputs; the mono output is the same at any spread
+    #[wasm_bindgen]
+    pub fn set_unison_spread(&mut self, amount: f32) {
+        self.supersaw.set_spread(amount);
+    }
+
     #[wasm_bindgen]
     pub fn set_cutoff(&mut self, freq: f32) {
         self.cutoff = freq.clamp(20.0, 20000.0);
     }
 
+    /// Modulate the cutoff from an external signal, in octaves per unit.
+    /// The buffer is read one value per sample by the following `process`
+    /// calls; once it runs out the modulation returns to zero. Values past
+    /// MOD_BUFFER_CAPACITY are ignored.
+    #[wasm_bindgen]
+    pub fn set_cutoff_mod_buffer(&mut self, buffer: &[f32]) {
+        let len = buffer.len().min(MOD_BUFFER_CAPACITY);
+        self.cutoff_mod.clear();
+        self.cutoff_mod.extend_from_slice(&buffer[..len]);
+        self.cutoff_mod_pos = 0;
+    }
+
     #[wasm_bindgen]
     pub fn set_resonance(&mut self, res: f32) {
         self.resonance = res.clamp(0.0, 1.0);
@@ -164,575 +477,6064 @@ impl Synth {
         self.env_mod = depth.clamp(0.0, 1.0);
     }
 
+    /// Filter envelope (MEG) decay in milliseconds
     #[wasm_bindgen]
     pub fn set_decay(&mut self, ms: f32) {
         self.envelope.set_decay(ms);
     }
 
+    /// Attack of both envelopes in milliseconds (0 - 50). The amp envelope
+    /// keeps its short fixed attack as a floor so notes never click.
     #[wasm_bindgen]
-    pub fn set_accent(&mut self, amount: f32) {
-        self.accent_amount = amount.clamp(0.0, 1.0);
+    pub fn set_attack(&mut self, ms: f32) {
+        self.envelope.set_attack(ms);
+        self.amp_envelope.set_attack(ms);
     }
 
+    /// Release of both envelopes after a note-off, in milliseconds
+    /// (0 - 5000). 0 lets the filter envelope decay on as if still held,
+    /// and leaves the amp envelope its short default release.
     #[wasm_bindgen]
-    pub fn set_slide_time(&mut self, ms: f32) {
-        let samples = (ms / 1000.0) * SAMPLE_RATE;
-        self.slide_rate = 1.0 / samples.max(1.0);
+    pub fn set_release(&mut self, ms: f32) {
+        self.envelope.set_release(ms);
+        self.amp_envelope.set_release(ms);
     }
 
+    /// Amp envelope (VEG) decay in milliseconds while the gate is held
     #[wasm_bindgen]
-    pub fn set_distortion(&mut self, amount: f32) {
-        self.distortion.set_drive(amount);
+    pub fn set_amp_decay(&mut self, ms: f32) {
+        self.amp_envelope.set_decay(ms);
     }
 
-    // Sequencer controls
-
     #[wasm_bindgen]
-    pub fn set_step(&mut self, index: usize, note: u8, accent: bool, slide: bool, active: bool) {
-        self.sequencer.set_step(index, Step { note, accent, slide, active });
+    pub fn set_accent(&mut self, amount: f32) {
+        self.accent_amount = amount.clamp(0.0, 1.0);
     }
 
+    /// How long the accent sweep takes to drain between accents, in
+    /// milliseconds (10 - 5000). Longer lets runs of accents build higher.
     #[wasm_bindgen]
-    pub fn set_tempo(&mut self, bpm: f32) {
-        self.sequencer.set_tempo(bpm);
+    pub fn set_accent_decay(&mut self, ms: f32) {
+        self.accent_sweep.set_decay(ms);
     }
 
+    /// Set the accent response curve: 0 = classic, 1 = linear,
+    /// 2 = exponential, 3 = hardware-matched
     #[wasm_bindgen]
-    pub fn tick(&mut self) -> i32 {
-        if let Some(step) = self.sequencer.tick() {
-            if step.active {
-                self.note_on(step.note as f32, step.accent, step.slide);
-            }
-            return self.sequencer.current_step() as i32;
+    pub fn set_accent_curve(&mut self, curve: u8) {
+        if let Some(curve) = AccentCurve::from_index(curve) {
+            self.accent_curve = curve;
         }
-        -1
     }
 
+    /// Select the filter model: 0 = 3-pole ladder, 1 = diode ladder,
+    /// 2 = Moog ladder, 3 = SVF lowpass, 4 = SVF bandpass, 5 = SVF highpass
     #[wasm_bindgen]
-    pub fn start(&mut self) {
-        self.sequencer.start();
+    pub fn set_filter_type(&mut self, model: u8) {
+        if let Some(model) = FilterModel::from_index(model) {
+            self.filter.set_model(model);
+        }
     }
 
+    /// Set the filter oversampling: 0 = off, 1 = 2x, 2 = 4x. Cleans up
+    /// self-oscillation and high-resonance squelch at the cost of CPU
     #[wasm_bindgen]
-    pub fn stop(&mut self) {
-        self.sequencer.stop();
-        self.note_off();
+    pub fn set_filter_quality(&mut self, quality: u8) {
+        if let Some(quality) = FilterQuality::from_index(quality) {
+            self.filter.set_quality(quality);
+        }
     }
 
+    /// Gain into the filter (0.0 - 1.0), saturating its input stage
     #[wasm_bindgen]
-    pub fn is_playing(&self) -> bool {
-        self.sequencer.is_playing()
+    pub fn set_filter_drive(&mut self, amount: f32) {
+        self.filter.set_drive(amount);
     }
 
-    /// Load a preset pattern by index
+    /// Let the filter self-oscillate at full resonance, in tune with its
+    /// cutoff, so it can be played as a sine-like voice
     #[wasm_bindgen]
-    pub fn load_preset(&mut self, index: usize) {
-        if let Some(preset) = PRESETS.get(index) {
-            for (i, step) in preset.steps.iter().enumerate() {
-                self.sequencer.set_step(i, *step);
-            }
-            self.set_tempo(preset.tempo);
-            self.set_cutoff(preset.cutoff);
-            self.set_resonance(preset.resonance);
-            self.set_env_mod(preset.env_mod);
-            self.set_decay(preset.decay);
-            self.set_waveform(preset.saw);
-        }
+    pub fn set_filter_self_oscillation(&mut self, on: bool) {
+        self.filter.set_self_oscillation(on);
     }
 
-    /// Get number of available presets
+    /// How far the cutoff follows the played note (0.0 - 1.0). At 1.0 with
+    /// the cutoff at 261.6 Hz a self-oscillating filter plays the note
     #[wasm_bindgen]
-    pub fn preset_count() -> usize {
-        PRESETS.len()
+    pub fn set_filter_key_track(&mut self, amount: f32) {
+        self.filter.set_key_track(amount);
     }
 
-    /// Get preset name
+    /// Raise the output as resonance goes up so the level stays roughly
+    /// constant while sweeping it
     #[wasm_bindgen]
-    pub fn preset_name(index: usize) -> String {
-        PRESETS.get(index)
-            .map(|p| p.name.to_string())
-            .unwrap_or_default()
-    }
-}
-
-impl Default for Synth {
-    fn default() -> Self {
-        Self::new()
+    pub fn set_resonance_compensation(&mut self, on: bool) {
+        self.filter.set_gain_compensation(on);
     }
-}
-
-/// Convert MIDI note number to frequency in Hz
-fn midi_to_freq(note: f32) -> f32 {
-    440.0 * 2.0_f32.powf((note - 69.0) / 12.0)
-}
-
-// ============== STUDIO (Synth + Drums Combined) ==============
-
-/// Complete studio with 303 bass synth and 808/909 drum machine
-#[wasm_bindgen]
-pub struct Studio {
-    synth: Synth,
-    drums: DrumMachine,
-
-    // Mixer levels
-    synth_vol: f32,
-    drum_vol: f32,
-    master_vol: f32,
-
-    // Sync state
-    playing: bool,
-    tempo: f32,
-
-    // Step tracking for UI
-    last_synth_step: i32,
-    last_drum_step: i32,
-    synth_step_changed: bool,
-    drum_step_changed: bool,
-}
 
-#[wasm_bindgen]
-impl Studio {
-    #[wasm_bindgen(constructor)]
-    pub fn new() -> Self {
-        Self {
-            synth: Synth::new(),
-            drums: DrumMachine::new(SAMPLE_RATE),
-            synth_vol: 0.7,
-            drum_vol: 0.8,
-            master_vol: 0.8,
-            playing: false,
-            tempo: 120.0,
-            last_synth_step: -1,
-            last_drum_step: -1,
-            synth_step_changed: false,
-            drum_step_changed: false,
+    /// Set the oscillator anti-aliasing: 0 = PolyBLEP, 1 = four-point BLEP,
+    /// 2 = 8x oversampled. Higher settings cost more CPU for cleaner highs
+    #[wasm_bindgen]
+    pub fn set_anti_alias(&mut self, mode: u8) {
+        if let Some(mode) = AntiAlias::from_index(mode) {
+            self.oscillator.set_anti_alias(mode);
         }
     }
 
-    /// Process audio - combines synth and drums with integrated sequencer timing
+    /// Fine-tune the whole instrument in cents (-100 - 100), e.g. to match
+    /// a record or another synth
     #[wasm_bindgen]
-    pub fn process(&mut self, output: &mut [f32]) {
-        // Reset step change flags at start of buffer
-        self.synth_step_changed = false;
-        self.drum_step_changed = false;
-
-        for sample in output.iter_mut() {
-            // Tick sequencers if playing
-            if self.playing {
-                // Synth sequencer
-                if let Some(step) = self.synth.sequencer.tick() {
-                    let new_step = self.synth.sequencer.current_step() as i32;
-                    if new_step != self.last_synth_step {
-                        self.last_synth_step = new_step;
-                        self.synth_step_changed = true;
-                    }
-                    if step.active {
-                        self.synth.note_on(step.note as f32, step.accent, step.slide);
-                    }
-                }
-
-                // Drum sequencer
-                if let Some(step) = self.drums.sequencer.tick() {
-                    let new_step = self.drums.sequencer.current_step() as i32;
-                    if new_step != self.last_drum_step {
-                        self.last_drum_step = new_step;
-                        self.drum_step_changed = true;
-                    }
-                    // Trigger drum sounds
-                    if step.kick {
-                        self.drums.kick.trigger();
-                    }
-                    if step.snare {
-                        self.drums.snare.trigger();
-                    }
-                    if step.closed_hh {
-                        self.drums.open_hh.choke();
-                        self.drums.closed_hh.trigger();
-                    }
-                    if step.open_hh {
-                        self.drums.open_hh.trigger();
-                    }
-                }
-            }
-
-            // Handle synth note sliding
-            if self.synth.is_sliding {
-                if (self.synth.current_note - self.synth.target_note).abs() > 0.01 {
-                    self.synth.current_note += (self.synth.target_note - self.synth.current_note) * self.synth.slide_rate;
-                } else {
-                    self.synth.current_note = self.synth.target_note;
-                    self.synth.is_sliding = false;
-                }
-            }
-
-            let freq = midi_to_freq(self.synth.current_note);
-            self.synth.oscillator.set_frequency(freq);
-
-            let osc_out = self.synth.oscillator.process();
-            let env = self.synth.envelope.process();
-
-            let env_scaled = env * self.synth.env_mod * 10000.0;
-            let filter_freq = (self.synth.cutoff + env_scaled).clamp(20.0, 20000.0);
-            self.synth.filter.set_cutoff(filter_freq);
+    pub fn set_master_tune(&mut self, cents: f32) {
+        self.master_tune = cents.clamp(-100.0, 100.0);
+    }
 
-            let filtered = self.synth.filter.process(osc_out);
-            let vca_out = filtered * (0.3 + env * 0.7);
-            let synth_sample = self.synth.distortion.process(vca_out);
+    /// Shift everything played by whole octaves (-2 - 2) without touching
+    /// the pattern notes
+    #[wasm_bindgen]
+    pub fn set_octave(&mut self, octave: i8) {
+        self.octave = octave.clamp(-2, 2);
+    }
 
-            // Process drums (sound generation)
-            let drum_sample = self.drums.process();
+    #[wasm_bindgen]
+    pub fn set_slide_time(&mut self, ms: f32) {
+        self.slide_time = ms.max(0.0);
+    }
 
-            // Mix and output
-            let mixed = (synth_sample * self.synth_vol) + (drum_sample * self.drum_vol);
-            *sample = mixed * self.master_vol;
+    /// Shape of the glide between slid notes: 0 = exponential, 1 = linear
+    /// in pitch, 2 = the 303's RC curve
+    #[wasm_bindgen]
+    pub fn set_slide_curve(&mut self, curve: u8) {
+        if let Some(curve) = SlideCurve::from_index(curve) {
+            self.slide_curve = curve;
         }
     }
 
-    /// Get current synth step (for UI), returns -1 if stopped
     #[wasm_bindgen]
-    pub fn get_synth_step(&self) -> i32 {
-        if self.playing { self.last_synth_step } else { -1 }
+    pub fn set_distortion(&mut self, amount: f32) {
+        self.distortion.set_drive(amount);
     }
 
-    /// Get current drum step (for UI), returns -1 if stopped
+    /// Put the distortion before the filter, so the resonance sweeps the
+    /// clipped harmonics, or after the VCA (the default)
     #[wasm_bindgen]
-    pub fn get_drum_step(&self) -> i32 {
-        if self.playing { self.last_drum_step } else { -1 }
+    pub fn set_distortion_routing(&mut self, pre_filter: bool) {
+        self.distortion_pre_filter = pre_filter;
     }
 
-    /// Check if synth step changed during last process() call
+    /// Enable or bypass the DC blocker on the output
     #[wasm_bindgen]
-    pub fn synth_step_changed(&self) -> bool {
-        self.synth_step_changed
+    pub fn set_dc_blocker(&mut self, enabled: bool) {
+        self.dc_blocker.set_enabled(enabled);
     }
 
-    /// Check if drum step changed during last process() call
+    /// LFO waveform: 0 = sine, 1 = triangle, 2 = square, 3 = sample & hold
     #[wasm_bindgen]
-    pub fn drum_step_changed(&self) -> bool {
-        self.drum_step_changed
+    pub fn set_lfo_shape(&mut self, shape: u8) {
+        if let Some(shape) = LfoShape::from_index(shape) {
+            self.lfo.set_shape(shape);
+        }
     }
 
-    // ===== Transport =====
-
+    /// Run the LFO free at `hz` cycles per second (0.01 - 40)
     #[wasm_bindgen]
-    pub fn start(&mut self) {
-        self.playing = true;
-        self.synth.sequencer.start();
-        self.drums.start();
+    pub fn set_lfo_rate(&mut self, hz: f32) {
+        self.lfo.set_rate_hz(hz);
     }
 
+    /// Lock the LFO to the tempo, one cycle every `steps` 16th notes
+    /// (0.25 - 64). It restarts with the transport.
     #[wasm_bindgen]
-    pub fn stop(&mut self) {
-        self.playing = false;
-        self.synth.sequencer.stop();
-        self.synth.note_off();
-        self.drums.stop();
+    pub fn set_lfo_sync(&mut self, steps: f32) {
+        self.lfo.set_synced(steps);
     }
 
+    /// LFO depth on the cutoff (-1.0 - 1.0, full depth = 3 octaves each way)
     #[wasm_bindgen]
-    pub fn is_playing(&self) -> bool {
-        self.playing
+    pub fn set_lfo_to_cutoff(&mut self, depth: f32) {
+        self.lfo_to_cutoff = depth.clamp(-1.0, 1.0);
     }
 
+    /// LFO depth on the pitch (-1.0 - 1.0, full depth = an octave each way)
     #[wasm_bindgen]
-    pub fn set_tempo(&mut self, bpm: f32) {
-        self.tempo = bpm.clamp(60.0, 300.0);
-        self.synth.sequencer.set_tempo(self.tempo);
-        self.drums.set_tempo(self.tempo);
+    pub fn set_lfo_to_pitch(&mut self, depth: f32) {
+        self.lfo_to_pitch = depth.clamp(-1.0, 1.0);
     }
 
-    // ===== Mixer =====
-
+    /// LFO depth on the distortion drive (-1.0 - 1.0)
     #[wasm_bindgen]
-    pub fn set_synth_volume(&mut self, vol: f32) {
-        self.synth_vol = vol.clamp(0.0, 1.0);
+    pub fn set_lfo_to_drive(&mut self, depth: f32) {
+        self.lfo_to_drive = depth.clamp(-1.0, 1.0);
     }
 
+    /// LFO depth on the pulse width (-1.0 - 1.0, full depth = 0.45 each way)
     #[wasm_bindgen]
-    pub fn set_drum_volume(&mut self, vol: f32) {
-        self.drum_vol = vol.clamp(0.0, 1.0);
+    pub fn set_lfo_to_pwm(&mut self, depth: f32) {
+        self.lfo_to_pwm = depth.clamp(-1.0, 1.0);
     }
 
+    /// Set the host output rate; audio is rendered at the engine's own
+    /// rate and resampled when the rates differ
     #[wasm_bindgen]
-    pub fn set_master_volume(&mut self, vol: f32) {
-        self.master_vol = vol.clamp(0.0, 1.0);
+    pub fn set_output_sample_rate(&mut self, rate: f32) {
+        // Stereo, or mono with gate and pitch CV
+        self.resampler = output_resampler(self.sample_rate, rate, 3);
     }
 
-    // ===== Synth controls (delegated) =====
+    // Sequencer controls
 
     #[wasm_bindgen]
-    pub fn synth_note_on(&mut self, note: f32, accent: bool, slide: bool) {
-        self.synth.note_on(note, accent, slide);
+    pub fn set_step(&mut self, index: usize, note: u8, accent: bool, slide: bool, active: bool) {
+        let result = self.try_set_step(index, note, accent, slide, active);
+        self.last_error = result.err();
     }
 
+    /// Detune a step by `cents` (-100 to 100) on top of its note
     #[wasm_bindgen]
-    pub fn synth_note_off(&mut self) {
-        self.synth.note_off();
+    pub fn set_step_cents(&mut self, index: usize, cents: i32) {
+        let result = self.try_set_step_cents(index, cents);
+        self.last_error = result.err();
     }
 
+    /// Level of a step (0-127) applied to the VCA on top of accent, for
+    /// ghost notes and dynamics
     #[wasm_bindgen]
-    pub fn set_synth_waveform(&mut self, saw: bool) {
-        self.synth.set_waveform(saw);
+    pub fn set_step_level(&mut self, index: usize, level: u8) {
+        let result = self.try_set_step_level(index, level);
+        self.last_error = result.err();
     }
 
+    /// How long a step's gate stays open, in percent of the step (1-100,
+    /// default 50). At 100 the note is held until the next step starts.
     #[wasm_bindgen]
-    pub fn set_synth_cutoff(&mut self, freq: f32) {
-        self.synth.set_cutoff(freq);
+    pub fn set_step_gate(&mut self, index: usize, gate: u8) {
+        let result = self.try_set_step_gate(index, gate);
+        self.last_error = result.err();
     }
 
+    /// Strike a step's note `hits` times within the step (1-4), each hit
+    /// gated for its share of the step
     #[wasm_bindgen]
-    pub fn set_synth_resonance(&mut self, res: f32) {
-        self.synth.set_resonance(res);
+    pub fn set_step_ratchet(&mut self, index: usize, hits: u8) {
+        let result = self.try_set_step_ratchet(index, hits);
+        self.last_error = result.err();
     }
 
+    /// Push a step late (positive) or pull it early (negative) by up to 50%
+    /// of a step, for groove
     #[wasm_bindgen]
-    pub fn set_synth_env_mod(&mut self, depth: f32) {
-        self.synth.set_env_mod(depth);
+    pub fn set_step_nudge(&mut self, index: usize, percent: i8) {
+        let result = self.try_set_step_nudge(index, percent);
+        self.last_error = result.err();
     }
 
+    /// Slide into a step over `ms` milliseconds (5 - 1275) instead of the
+    /// synth's slide time, for long swoops among snappy slides. 0 goes
+    /// back to the synth's slide time.
     #[wasm_bindgen]
-    pub fn set_synth_decay(&mut self, ms: f32) {
-        self.synth.set_decay(ms);
+    pub fn set_step_slide_time(&mut self, index: usize, ms: f32) {
+        let result = self.try_set_step_slide_time(index, ms);
+        self.last_error = result.err();
     }
 
+    /// Lock a knob to a position (0.0 - 1.0) for as long as a step plays:
+    /// 0 = cutoff, 1 = resonance, 2 = env mod, 3 = decay, 4 = accent,
+    /// 5 = distortion. The knob goes back when the next step starts.
     #[wasm_bindgen]
-    pub fn set_synth_accent(&mut self, amount: f32) {
-        self.synth.set_accent(amount);
+    pub fn set_step_lock(&mut self, index: usize, param: u8, position: f32) {
+        let result = self.try_set_step_locks(index, param, |locks, param| locks.set(param, position));
+        self.last_error = result.err();
     }
 
     #[wasm_bindgen]
-    pub fn set_synth_slide_time(&mut self, ms: f32) {
-        self.synth.set_slide_time(ms);
+    pub fn clear_step_lock(&mut self, index: usize, param: u8) {
+        let result = self.try_set_step_locks(index, param, |locks, param| locks.clear(param));
+        self.last_error = result.err();
     }
 
+    /// Chance (0-100%) that an active step plays each time it comes round
     #[wasm_bindgen]
-    pub fn set_synth_distortion(&mut self, amount: f32) {
-        self.synth.set_distortion(amount);
+    pub fn set_step_probability(&mut self, index: usize, percent: u8) {
+        let result = self.try_set_step_trig(index, |trig| trig.probability = percent.min(trig::ALWAYS_PLAYS));
+        self.last_error = result.err();
     }
 
+    /// Which passes of the pattern a step plays on: 0 = always, 1 = first
+    /// pass only, 2 = all but the first, or cycle * 16 + pass for "pass of
+    /// cycle" (0x21 = 1:2, 0x43 = 3:4)
     #[wasm_bindgen]
-    pub fn set_synth_step(&mut self, index: usize, note: u8, accent: bool, slide: bool, active: bool) {
-        self.synth.set_step(index, note, accent, slide, active);
+    pub fn set_step_condition(&mut self, index: usize, condition: u8) {
+        let result = Condition::from_byte(condition)
+            .ok_or(ApiError::TrigCondition)
+            .and_then(|condition| self.try_set_step_trig(index, |trig| trig.condition = condition));
+        self.last_error = result.err();
     }
 
+    /// Whole pattern as 16 bytes per step: note, flags (1 = accent,
+    /// 2 = slide, 4 = active), cents as a signed byte, level (0-127),
+    /// gate length (1-100), probability (0-100), condition as in
+    /// set_step_condition(), ratchet (1-4), nudge as a signed byte, slide
+    /// time in 5 ms units (0 = the synth's own), then a lock per
+    /// set_step_lock() parameter (0 = none, 1-255 = position)
     #[wasm_bindgen]
-    pub fn load_synth_preset(&mut self, index: usize) {
-        self.synth.load_preset(index);
+    pub fn get_pattern(&self) -> Vec<u8> {
+        self.sequencer.pattern_bytes()
     }
 
-    // ===== Drum controls =====
+    /// Replace the whole pattern from the get_pattern() format, taking the
+    /// pattern length from the input (1-32 steps). Anything else is ignored.
+    #[wasm_bindgen]
+    pub fn set_pattern(&mut self, bytes: &[u8]) {
+        let result = self.try_set_pattern(bytes);
+        self.last_error = result.err();
+    }
 
-    /// Set a drum step with all 4 tracks at once
+    /// Load a whole pattern in one call from per-step notes and flags
+    /// (1 = accent, 2 = slide, 4 = active), so the sequencer never plays a
+    /// half-updated pattern. Ignored unless both arrays have one entry per
+    /// step.
     #[wasm_bindgen]
-    pub fn set_drum_step(&mut self, index: usize, kick: bool, snare: bool, closed_hh: bool, open_hh: bool) {
-        self.drums.sequencer.set_step(index, drums::DrumTrack::Kick, kick);
-        self.drums.sequencer.set_step(index, drums::DrumTrack::Snare, snare);
-        self.drums.sequencer.set_step(index, drums::DrumTrack::ClosedHH, closed_hh);
-        self.drums.sequencer.set_step(index, drums::DrumTrack::OpenHH, open_hh);
+    pub fn load_pattern_bytes(&mut self, notes: &[u8], flags: &[u8]) {
+        let result = self.try_load_pattern_bytes(notes, flags);
+        self.last_error = result.err();
     }
 
-    /// Set a single drum track step
     #[wasm_bindgen]
-    pub fn set_drum_track_step(&mut self, index: usize, track: u8, active: bool) {
-        let track = match track {
-            0 => drums::DrumTrack::Kick,
-            1 => drums::DrumTrack::Snare,
-            2 => drums::DrumTrack::ClosedHH,
-            3 => drums::DrumTrack::OpenHH,
-            _ => return,
-        };
-        self.drums.sequencer.set_step(index, track, active);
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.sequencer.set_tempo(bpm);
+        self.lfo.set_tempo(bpm);
     }
 
+    /// Delay every other 16th by up to 75% of a step (0.0 = straight)
     #[wasm_bindgen]
-    pub fn toggle_drum_step(&mut self, index: usize, track: u8) {
-        let track = match track {
-            0 => drums::DrumTrack::Kick,
-            1 => drums::DrumTrack::Snare,
-            2 => drums::DrumTrack::ClosedHH,
-            3 => drums::DrumTrack::OpenHH,
-            _ => return,
-        };
-        self.drums.sequencer.toggle_step(index, track);
+    pub fn set_swing(&mut self, amount: f32) {
+        self.sequencer.set_swing(amount);
     }
 
-    /// Get drum step data (all 4 tracks) for a specific step index
+    /// Shift the whole pattern by `semitones`, snapping into the scale if
+    /// one is set
     #[wasm_bindgen]
-    pub fn get_drum_step_data(&self, index: usize) -> Vec<u8> {
-        if let Some(step) = self.drums.sequencer.get_step(index) {
-            vec![
-                step.kick as u8,
-                step.snare as u8,
-                step.closed_hh as u8,
-                step.open_hh as u8,
-            ]
-        } else {
-            vec![0, 0, 0, 0]
+    pub fn transpose(&mut self, semitones: i8) {
+        self.sequencer.transpose(semitones);
+    }
+
+    /// Quantize the pattern to a scale: `root` is a pitch class (0 = C),
+    /// `scale` is as in Studio::set_key(). The pattern is snapped now and
+    /// notes set afterwards are snapped as they are edited.
+    #[wasm_bindgen]
+    pub fn set_scale(&mut self, root: u8, scale: u8) {
+        let result = Scale::from_index(scale).ok_or(ApiError::Scale);
+        self.last_error = result.err();
+        if let Ok(scale) = result {
+            self.sequencer.set_scale(Key::new(root, scale));
         }
     }
 
+    /// Stop snapping edited notes to a scale
     #[wasm_bindgen]
-    pub fn set_kick_volume(&mut self, vol: f32) {
-        self.drums.set_kick_volume(vol);
+    pub fn clear_scale(&mut self) {
+        self.sequencer.clear_scale();
     }
 
+    /// Order the steps play in: 0 = forward, 1 = reverse, 2 = ping-pong,
+    /// 3 = random. Takes effect from the next step.
     #[wasm_bindgen]
-    pub fn set_snare_volume(&mut self, vol: f32) {
-        self.drums.set_snare_volume(vol);
+    pub fn set_direction(&mut self, mode: u8) {
+        let result = Direction::from_index(mode).ok_or(ApiError::Direction);
+        self.last_error = result.err();
+        if let Ok(direction) = result {
+            self.sequencer.set_direction(direction);
+        }
     }
 
+    // Accent and slide lanes, edited independently of the notes
+
+    /// Move every accent `n` steps later (negative moves them earlier)
     #[wasm_bindgen]
-    pub fn set_hihat_volume(&mut self, vol: f32) {
-        self.drums.set_hihat_volume(vol);
+    pub fn rotate_accents(&mut self, n: i32) {
+        self.sequencer.rotate_lane(Lane::Accent, n);
     }
 
     #[wasm_bindgen]
-    pub fn set_kick_decay(&mut self, decay: f32) {
-        self.drums.set_kick_decay(decay);
+    pub fn rotate_slides(&mut self, n: i32) {
+        self.sequencer.rotate_lane(Lane::Slide, n);
     }
 
+    /// Re-roll accents so each step has a `density` (0.0 - 1.0) chance
     #[wasm_bindgen]
-    pub fn set_kick_pitch(&mut self, pitch: f32) {
-        self.drums.set_kick_pitch(pitch);
+    pub fn randomize_accents(&mut self, density: f32) {
+        self.sequencer.randomize_lane(Lane::Accent, density, &mut self.rng);
     }
 
     #[wasm_bindgen]
-    pub fn set_snare_tone(&mut self, tone: f32) {
-        self.drums.set_snare_tone(tone);
+    pub fn randomize_slides(&mut self, density: f32) {
+        self.sequencer.randomize_lane(Lane::Slide, density, &mut self.rng);
     }
 
     #[wasm_bindgen]
-    pub fn set_snare_snap(&mut self, snap: f32) {
-        self.drums.set_snare_snap(snap);
+    pub fn clear_accents(&mut self) {
+        self.sequencer.clear_lane(Lane::Accent);
     }
 
     #[wasm_bindgen]
-    pub fn load_drum_pattern(&mut self, index: usize) {
-        let pattern = match index {
-            // Main patterns
-            0 => &drums::BASIC_BEAT,
-            1 => &drums::BREAKBEAT,
-            2 => &drums::HOUSE_909,
-            3 => &drums::MINIMAL,
-            4 => &drums::ACID_DRIVE,
-            // Arrangement patterns
-            5 => &drums::INTRO_KICK,
-            6 => &drums::INTRO_HATS,
-            7 => &drums::BUILD_SNARE,
-            8 => &drums::BUILD_ROLL,
-            9 => &drums::BREAKDOWN,
-            10 => &drums::BREAKDOWN_KICK,
-            11 => &drums::FILL_SNARE,
-            12 => &drums::FILL_STOMP,
-            13 => &drums::FILL_OPEN_HAT,
-            14 => &drums::DROP_FULL,
-            15 => &drums::OFFBEAT_HOUSE,
-            16 => &drums::SHUFFLE,
-            _ => &drums::BASIC_BEAT,
-        };
-        self.drums.sequencer.load_pattern(pattern);
+    pub fn clear_slides(&mut self) {
+        self.sequencer.clear_lane(Lane::Slide);
     }
 
+    /// Samples between sequencer steps at the current tempo, with the
+    /// fraction the sequencer carries from step to step
     #[wasm_bindgen]
-    pub fn drum_pattern_count() -> usize {
-        17
+    pub fn samples_per_step(&self) -> f64 {
+        self.sequencer.samples_per_step()
     }
 
     #[wasm_bindgen]
-    pub fn drum_pattern_name(index: usize) -> String {
-        match index {
-            // Main patterns
-            0 => "Basic 4/4".to_string(),
-            1 => "Breakbeat".to_string(),
-            2 => "House 909".to_string(),
-            3 => "Minimal Techno".to_string(),
-            4 => "Acid Drive".to_string(),
-            // Arrangement patterns
-            5 => "-- INTRO --".to_string(),
-            6 => "Intro + Hats".to_string(),
-            7 => "Build (Snare)".to_string(),
-            8 => "Build (Roll)".to_string(),
-            9 => "Breakdown".to_string(),
-            10 => "Breakdown + Kick".to_string(),
-            11 => "Fill: Snare".to_string(),
-            12 => "Fill: Stomp".to_string(),
-            13 => "Fill: Open Hat".to_string(),
-            14 => "DROP!".to_string(),
-            15 => "Offbeat House".to_string(),
-            16 => "Shuffle".to_string(),
-            _ => "Unknown".to_string(),
+    pub fn steps_per_bar(&self) -> usize {
+        self.sequencer.steps_per_bar()
+    }
+
+    /// Step length in milliseconds as actually played
+    #[wasm_bindgen]
+    pub fn step_duration_ms(&self) -> f32 {
+        self.sequencer.step_duration_ms()
+    }
+
+    /// Advance the sequencer one sample. Returns the new step index when a
+    /// step starts, -1 otherwise.
+    #[wasm_bindgen]
+    pub fn tick(&mut self) -> i32 {
+        if let Some(event) = self.sequencer.tick() {
+            self.play_event(&event, 0);
+            if event.starts_step() {
+                return self.sequencer.current_step() as i32;
+            }
         }
+        -1
     }
 
-    // ===== Presets =====
+    /// Start the sequencer from a clean voice with a short fade-in
+    #[wasm_bindgen]
+    pub fn start(&mut self) {
+        self.reset_voice();
+        self.fade.fade_in();
+        self.sequencer.start();
+    }
 
+    /// Stop the sequencer, fading out and then resetting the voice
     #[wasm_bindgen]
-    pub fn synth_preset_count() -> usize {
-        Synth::preset_count()
+    pub fn stop(&mut self) {
+        self.sequencer.stop();
+        self.note_off();
+        self.scheduled.clear();
+        self.midi_out.release(0);
+        self.fade.fade_out();
     }
 
+    // MIDI output
+
+    /// Enable mirroring sequencer notes into the outgoing MIDI queue
     #[wasm_bindgen]
-    pub fn synth_preset_name(index: usize) -> String {
-        Synth::preset_name(index)
+    pub fn set_midi_out_enabled(&mut self, enabled: bool) {
+        self.midi_out.set_enabled(enabled);
     }
-}
 
-impl Default for Studio {
-    fn default() -> Self {
-        Self::new()
+    /// Set the channel (0-15) used for outgoing MIDI notes
+    #[wasm_bindgen]
+    pub fn set_midi_out_channel(&mut self, channel: u8) {
+        self.midi_out.set_channel(channel);
+    }
+
+    /// Take queued MIDI events as [offset, status, data1, data2, ...], where
+    /// offset is the sample position within the block that produced them
+    #[wasm_bindgen]
+    pub fn drain_midi_out(&mut self) -> Vec<u32> {
+        self.midi_out.drain()
+    }
+
+    /// Like drain_midi_out, but writes into `out` without allocating so it
+    /// can be polled every block. Returns the number of values written;
+    /// events that don't fit stay queued.
+    #[wasm_bindgen]
+    pub fn drain_midi_out_into(&mut self, out: &mut [u32]) -> usize {
+        self.midi_out.drain_into(out)
+    }
+
+    #[wasm_bindgen]
+    pub fn is_playing(&self) -> bool {
+        self.sequencer.is_playing()
+    }
+
+    /// Load a preset pattern by index
+    #[wasm_bindgen]
+    pub fn load_preset(&mut self, index: usize) {
+        let result = self.try_load_preset(index);
+        self.last_error = result.err();
+    }
+
+    /// Switch between the A and B knob snapshots, keeping the current
+    /// tweaks in the slot being left. B starts as a copy of A. Returns the
+    /// now active slot: 0 = A, 1 = B
+    #[wasm_bindgen]
+    pub fn toggle_ab(&mut self) -> u8 {
+        let current = self.params();
+        let other = 1 - self.ab_active;
+        if !self.ab_slots[other].is_empty() {
+            self.set_params(&self.ab_slots[other].clone());
+        }
+        self.ab_slots[self.ab_active] = current;
+        self.ab_active = other;
+        self.ab_active as u8
+    }
+
+    /// Overwrite B with A, taking A from the knobs when it's active
+    #[wasm_bindgen]
+    pub fn copy_a_to_b(&mut self) {
+        if self.ab_active == 0 {
+            self.ab_slots[1] = self.params();
+        } else if !self.ab_slots[0].is_empty() {
+            self.ab_slots[1] = self.ab_slots[0].clone();
+            self.set_params(&self.ab_slots[1].clone());
+        }
+    }
+
+    /// Active A/B slot: 0 = A, 1 = B
+    #[wasm_bindgen]
+    pub fn get_ab_slot(&self) -> u8 {
+        self.ab_active as u8
+    }
+
+    /// Error code from the last pattern or preset call, 0 if it succeeded:
+    /// 1 = bad step index, 2 = bad preset index, 3 = bad drum track,
+    /// 4 = pattern data of the wrong length, 5 = unreadable saved state,
+    /// 6 = bad pattern slot, 7 = empty pattern slot
+    #[wasm_bindgen]
+    pub fn last_error(&self) -> u8 {
+        self.last_error.map_or(0, ApiError::code)
+    }
+
+    /// Description of last_error(), empty if the call succeeded
+    #[wasm_bindgen]
+    pub fn last_error_message(&self) -> String {
+        self.last_error.map(|e| e.message().to_string()).unwrap_or_default()
+    }
+
+    /// Get number of available presets
+    #[wasm_bindgen]
+    pub fn preset_count() -> usize {
+        PRESETS.len()
+    }
+
+    /// Get preset name
+    #[wasm_bindgen]
+    pub fn preset_name(index: usize) -> String {
+        PRESETS.get(index)
+            .map(|p| p.name.to_string())
+            .unwrap_or_default()
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Synth {
+    /// Current value of a knob, as apply_param() takes it
+    fn param_value(&self, param: AutomationParam) -> f32 {
+        match param {
+            AutomationParam::Cutoff => self.cutoff,
+            AutomationParam::Resonance => self.resonance,
+            AutomationParam::EnvMod => self.env_mod,
+            AutomationParam::Decay => self.envelope.decay(),
+            AutomationParam::Accent => self.accent_amount,
+            AutomationParam::Distortion => self.distortion.drive(),
+        }
+    }
 
-    #[test]
-    fn test_midi_to_freq() {
-        assert!((midi_to_freq(69.0) - 440.0).abs() < 0.01);
-        assert!((midi_to_freq(57.0) - 220.0).abs() < 0.01);
-        assert!((midi_to_freq(60.0) - 261.63).abs() < 0.1);
+    /// Apply a step's parameter locks, handing back those of the step before
+    fn lock_params(&mut self, locks: Locks) {
+        self.release_locks();
+        for (param, value) in locks.values() {
+            let before = self.param_value(param);
+            self.apply_param(param, value);
+            self.locked[param.index()] = Some((before, self.param_value(param)));
+        }
     }
 
-    #[test]
-    fn test_synth_creation() {
-        let synth = Synth::new();
-        assert!(!synth.is_playing());
+    /// Put locked knobs back, except any moved while the lock was held,
+    /// which keep their new setting
+    fn release_locks(&mut self) {
+        for param in AutomationParam::ALL {
+            if let Some((before, locked)) = self.locked[param.index()].take() {
+                if self.param_value(param) == locked {
+                    self.apply_param(param, before);
+                }
+            }
+        }
     }
 
-    #[test]
-    fn test_synth_process() {
-        let mut synth = Synth::new();
-        let mut buffer = [0.0f32; 128];
-        synth.note_on(48.0, false, false);
-        synth.process(&mut buffer);
-        assert!(buffer.iter().any(|&s| s.abs() > 0.001));
+    /// Set a knob by automation parameter
+    fn apply_param(&mut self, param: AutomationParam, value: f32) {
+        match param {
+            AutomationParam::Cutoff => self.set_cutoff(value),
+            AutomationParam::Resonance => self.set_resonance(value),
+            AutomationParam::EnvMod => self.set_env_mod(value),
+            AutomationParam::Decay => self.set_decay(value),
+            AutomationParam::Accent => self.set_accent(value),
+            AutomationParam::Distortion => self.set_distortion(value),
+        }
     }
 
-    #[test]
-    fn test_presets_exist() {
-        assert!(Synth::preset_count() > 0);
-        assert!(!Synth::preset_name(0).is_empty());
+    fn try_set_step(&mut self, index: usize, note: u8, accent: bool, slide: bool, active: bool) -> Result<(), ApiError> {
+        let note = self.sequencer.snap(note);
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        // Keep the step's micro-tuning, level and gate, which are set separately
+        *step = Step { note, accent, slide, active, ..*step };
+        Ok(())
     }
 
-    #[test]
-    fn test_studio_creation() {
-        let studio = Studio::new();
-        assert!(!studio.is_playing());
+    fn try_set_step_level(&mut self, index: usize, level: u8) -> Result<(), ApiError> {
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        step.level = level.min(sequencer::FULL_LEVEL);
+        Ok(())
     }
 
-    #[test]
-    fn test_studio_process() {
-        let mut studio = Studio::new();
-        let mut buffer = [0.0f32; 128];
-        studio.synth_note_on(48.0, false, false);
-        studio.process(&mut buffer);
-        assert!(buffer.iter().any(|&s| s.abs() > 0.001));
+    fn try_set_step_ratchet(&mut self, index: usize, hits: u8) -> Result<(), ApiError> {
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        step.ratchet = hits.clamp(1, sequencer::MAX_RATCHET);
+        Ok(())
     }
 
-    #[test]
-    fn test_drum_patterns_exist() {
-        assert!(Studio::drum_pattern_count() > 0);
-        assert!(!Studio::drum_pattern_name(0).is_empty());
+    fn try_set_step_nudge(&mut self, index: usize, percent: i8) -> Result<(), ApiError> {
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        step.nudge = percent.clamp(-clock::MAX_NUDGE, clock::MAX_NUDGE);
+        Ok(())
+    }
+
+    fn try_set_step_slide_time(&mut self, index: usize, ms: f32) -> Result<(), ApiError> {
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        step.slide_time = if ms > 0.0 { (ms / sequencer::SLIDE_TIME_UNIT_MS).round().clamp(1.0, 255.0) as u8 } else { 0 };
+        Ok(())
+    }
+
+    fn try_set_step_locks(&mut self, index: usize, param: u8, change: impl FnOnce(&mut Locks, AutomationParam)) -> Result<(), ApiError> {
+        let param = AutomationParam::from_index(param).ok_or(ApiError::Param)?;
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        change(&mut step.locks, param);
+        Ok(())
+    }
+
+    fn try_set_step_trig(&mut self, index: usize, change: impl FnOnce(&mut Trig)) -> Result<(), ApiError> {
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        change(&mut step.trig);
+        Ok(())
+    }
+
+    fn try_set_step_gate(&mut self, index: usize, gate: u8) -> Result<(), ApiError> {
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        step.gate = gate.clamp(1, sequencer::TIE_GATE);
+        Ok(())
+    }
+
+    fn try_set_step_cents(&mut self, index: usize, cents: i32) -> Result<(), ApiError> {
+        let step = self.sequencer.get_step_mut(index).ok_or(ApiError::StepIndex)?;
+        step.cents = cents.clamp(-100, 100) as i8;
+        Ok(())
+    }
+
+    fn try_set_pattern(&mut self, bytes: &[u8]) -> Result<(), ApiError> {
+        if !self.sequencer.load_pattern_bytes(bytes) {
+            return Err(ApiError::PatternLength);
+        }
+        Ok(())
+    }
+
+    fn try_load_pattern_bytes(&mut self, notes: &[u8], flags: &[u8]) -> Result<(), ApiError> {
+        if !self.sequencer.load_notes_and_flags(notes, flags) {
+            return Err(ApiError::PatternLength);
+        }
+        Ok(())
+    }
+
+    /// Knob values saved in a session, in a fixed order that new knobs are
+    /// only ever appended to
+    fn params(&self) -> Vec<f32> {
+        vec![
+            self.cutoff,
+            self.resonance,
+            self.env_mod,
+            self.envelope.decay(),
+            self.accent_amount,
+            self.distortion.drive(),
+            (self.oscillator.waveform() == Waveform::Saw) as u8 as f32,
+            self.lfo.shape().index() as f32,
+            // Free rate in Hz, or synced cycle length as negative steps
+            match self.lfo.rate() {
+                LfoRate::Free(hz) => hz,
+                LfoRate::Synced(steps) => -steps,
+            },
+            self.lfo_to_cutoff,
+            self.lfo_to_pitch,
+            self.lfo_to_drive,
+            self.amp_envelope.decay(),
+            self.accent_sweep.decay(),
+            self.envelope.attack(),
+            self.envelope.release_time(),
+            self.filter.model().index() as f32,
+            self.filter.quality().index() as f32,
+            self.filter.drive(),
+            self.filter.self_oscillation() as u8 as f32,
+            self.filter.key_track(),
+            self.oscillator.waveform().index() as f32,
+            self.oscillator.pulse_width(),
+            self.lfo_to_pwm,
+            self.supersaw.voices() as f32,
+            self.supersaw.detune(),
+            self.supersaw.spread(),
+            self.master_tune,
+            self.octave as f32,
+            self.slide_curve.index() as f32,
+            self.distortion_pre_filter as u8 as f32,
+        ]
+    }
+
+    /// Restore knobs from params(); missing trailing values are left alone
+    fn set_params(&mut self, params: &[f32]) {
+        let setters: [fn(&mut Synth, f32); 31] = [
+            Synth::set_cutoff,
+            Synth::set_resonance,
+            Synth::set_env_mod,
+            Synth::set_decay,
+            Synth::set_accent,
+            Synth::set_distortion,
+            |synth, saw| synth.set_waveform(saw >= 0.5),
+            |synth, shape| synth.set_lfo_shape(shape as u8),
+            |synth, rate| if rate < 0.0 { synth.set_lfo_sync(-rate) } else { synth.set_lfo_rate(rate) },
+            Synth::set_lfo_to_cutoff,
+            Synth::set_lfo_to_pitch,
+            Synth::set_lfo_to_drive,
+            Synth::set_amp_decay,
+            Synth::set_accent_decay,
+            Synth::set_attack,
+            Synth::set_release,
+            |synth, model| synth.set_filter_type(model as u8),
+            |synth, quality| synth.set_filter_quality(quality as u8),
+            Synth::set_filter_drive,
+            |synth, on| synth.set_filter_self_oscillation(on >= 0.5),
+            Synth::set_filter_key_track,
+            |synth, waveform| synth.set_waveform_type(waveform as u8),
+            Synth::set_pulse_width,
+            Synth::set_lfo_to_pwm,
+            |synth, voices| synth.set_unison_voices(voices as u8),
+            Synth::set_unison_detune,
+            Synth::set_unison_spread,
+            Synth::set_master_tune,
+            |synth, octave| synth.set_octave(octave as i8),
+            |synth, curve| synth.set_slide_curve(curve as u8),
+            |synth, pre_filter| synth.set_distortion_routing(pre_filter != 0.0),
+        ];
+        for (set, &value) in setters.iter().zip(params) {
+            set(self, value);
+        }
+    }
+
+    fn try_load_preset(&mut self, index: usize) -> Result<(), ApiError> {
+        let preset = PRESETS.get(index).ok_or(ApiError::PresetIndex)?;
+        self.sequencer.load_pattern(&preset.steps);
+        self.set_tempo(preset.tempo);
+        self.set_cutoff(preset.cutoff);
+        self.set_resonance(preset.resonance);
+        self.set_env_mod(preset.env_mod);
+        self.set_decay(preset.decay);
+        self.set_waveform(preset.saw);
+        Ok(())
+    }
+
+    /// Render a block at the internal sample rate
+    fn render_block(&mut self, output: &mut [f32]) {
+        self.render_block_with(output, |_, _| {});
+    }
+
+    /// Render a block, handing the gate and pitch CV after each sample to `cv`
+    fn render_block_with(&mut self, output: &mut [f32], mut cv: impl FnMut(usize, (f32, f32))) {
+        for (offset, sample) in output.iter_mut().enumerate() {
+            (*sample, _) = self.next_output(offset);
+            cv(offset, self.cv());
+        }
+        self.scheduled.end_block(output.len() as u32);
+    }
+
+    /// Render a stereo block at the internal sample rate
+    fn render_stereo_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let len = left.len().min(right.len());
+        for (offset, (l, r)) in left[..len].iter_mut().zip(right.iter_mut()).enumerate() {
+            let (sample, side) = self.next_output(offset);
+            let (pan_left, pan_right) = pan(sample, self.pan);
+            (*l, *r) = (pan_left - side, pan_right + side);
+        }
+        self.scheduled.end_block(len as u32);
+    }
+
+    /// Next output sample and its unison side signal, `offset` samples
+    /// into the block
+    fn next_output(&mut self, offset: usize) -> (f32, f32) {
+        // Transport fade; once a stop has faded out, clear the voice
+        let fade = self.fade.process();
+        if self.fade.take_finished() {
+            self.reset_voice();
+        }
+
+        self.run_scheduled(offset as u32);
+
+        let voice = self.render_sample(0.0, 0.0);
+
+        // Remove any DC offset left by the nonlinear stages
+        let blocked = self.dc_blocker.process(voice);
+
+        let gain = 0.5 * fade; // Master volume
+        (blocked * gain, self.side * gain)
+    }
+
+    /// Run the voice for one sample, up to and including distortion, with
+    /// extra cutoff modulation in octaves and drive modulation from outside
+    /// the synth. Transport fade, scheduled notes and output gain are left
+    /// to the caller, so Synth and Studio share everything in between.
+    fn render_sample(&mut self, cutoff_octaves: f32, drive_mod: f32) -> f32 {
+        // Handle note sliding (portamento)
+        let note = self.glide.process();
+
+        let lfo = self.lfo.process();
+        let env = self.envelope.process();
+        let sweep = self.accent_sweep.process(env * self.sweep_amount);
+
+        if self.control_tick() {
+            // Convert MIDI note to frequency
+            let tuning = self.master_tune / 100.0 + self.octave as f32 * 12.0;
+            let freq = midi_to_freq(note + tuning + self.bend + lfo * self.lfo_to_pitch * LFO_PITCH_SEMITONES);
+            self.oscillator.set_frequency(freq);
+            self.supersaw.set_frequency(freq);
+            self.oscillator.set_pulse_width_mod(lfo * self.lfo_to_pwm * LFO_PWM_RANGE);
+            self.filter.set_note(freq);
+
+            // Calculate filter cutoff with envelope modulation
+            let env_scaled = env * self.env_mod * 10000.0;
+            let octaves = cutoff_octaves
+                + sweep * ACCENT_SWEEP_OCTAVES
+                + lfo * self.lfo_to_cutoff * LFO_CUTOFF_OCTAVES
+                + self.next_cutoff_mod();
+            let cutoff = self.cutoff * octaves.exp2();
+            let filter_freq = (cutoff + env_scaled).clamp(20.0, 20000.0);
+            self.filter.set_cutoff(filter_freq);
+        }
+
+        // Generate oscillator, or the unison saws and their stereo spread
+        let (osc_out, side) = if self.supersaw.is_active() {
+            self.supersaw.process()
+        } else {
+            (self.oscillator.process(), 0.0)
+        };
+
+        self.distortion.set_drive_mod(drive_mod + lfo * self.lfo_to_drive);
+        let osc_out = if self.distortion_pre_filter {
+            self.distortion.process(osc_out)
+        } else {
+            osc_out
+        };
+
+        // Apply filter
+        let filtered = self.filter.process(osc_out);
+
+        // Apply VCA from its own amp envelope, smoothed so retriggers
+        // don't click
+        let vca = self.vca();
+        let vca_out = filtered * vca;
+
+        // The spread follows the voice through the filter and VCA but
+        // skips the distortion, which would fold it back into the centre
+        self.side = if side != 0.0 { self.filter.process_side(side) * vca } else { 0.0 };
+
+        // Apply distortion
+        if self.distortion_pre_filter {
+            vca_out
+        } else {
+            self.distortion.process(vca_out)
+        }
+    }
+
+    /// Whether pitch and cutoff are due to be recomputed this sample: every
+    /// CONTROL_INTERVAL samples, or every sample while an external cutoff
+    /// modulation buffer is playing so it stays audio rate
+    fn control_tick(&mut self) -> bool {
+        let due = self.control_countdown == 0 || self.cutoff_mod_pos < self.cutoff_mod.len();
+        self.control_countdown = if due { CONTROL_INTERVAL - 1 } else { self.control_countdown - 1 };
+        due
+    }
+
+    /// Next external cutoff modulation value, zero once the buffer runs out
+    fn next_cutoff_mod(&mut self) -> f32 {
+        let Some(&value) = self.cutoff_mod.get(self.cutoff_mod_pos) else {
+            return 0.0;
+        };
+        self.cutoff_mod_pos += 1;
+        value
+    }
+
+    /// Gate (0 or 1) and pitch CV (1.0 per octave, 0 at C4)
+    fn cv(&self) -> (f32, f32) {
+        let gate = if self.gate { 1.0 } else { 0.0 };
+        (gate, (self.glide.pitch() - 60.0) / 12.0)
+    }
+
+    /// VCA gain from the amp envelope, which attacks and decays while the
+    /// gate is open and releases to silence once it closes
+    fn vca(&mut self) -> f32 {
+        let amp = self.amp_envelope.process();
+        self.smooth_vca(amp * self.accent_gain * self.note_level)
+    }
+
+    /// Move the VCA gain towards `target` with a short one-pole ramp
+    fn smooth_vca(&mut self, target: f32) -> f32 {
+        self.vca_gain += (target - self.vca_gain) * self.vca_coeff;
+        self.vca_gain
+    }
+
+    /// Play host-scheduled notes that are due at `offset`
+    fn run_scheduled(&mut self, offset: u32) {
+        while let Some(event) = self.scheduled.pop_due(offset) {
+            match event {
+                NoteEvent::On { note, accent, slide } => self.note_on(note, accent, slide),
+                NoteEvent::Off => self.note_off(),
+            }
+        }
+    }
+
+    /// Play a sequencer event on the voice and mirror it to MIDI out
+    fn play_event(&mut self, event: &SeqEvent, offset: u32) {
+        self.apply_event(event);
+        match event {
+            SeqEvent::NoteOn(step) | SeqEvent::Ratchet(step) => self.midi_out.note(offset, step.note, step.accent, step.slide),
+            SeqEvent::Tie(step) => self.midi_out.note(offset, step.note, step.accent, true),
+            SeqEvent::NoteOff | SeqEvent::Rest => self.midi_out.release(offset),
+        }
+    }
+
+    /// Play a sequencer event on the voice only
+    fn apply_event(&mut self, event: &SeqEvent) {
+        // Locks hold from the start of their step to the start of the next
+        match event {
+            SeqEvent::NoteOn(step) | SeqEvent::Tie(step) => self.lock_params(step.locks),
+            SeqEvent::Rest => self.release_locks(),
+            SeqEvent::Ratchet(_) | SeqEvent::NoteOff => {}
+        }
+        match event {
+            SeqEvent::NoteOn(step) | SeqEvent::Ratchet(step) => {
+                let slide_ms = step.slide_ms().unwrap_or(self.slide_time);
+                self.play_note(step.pitch(), step.accent, step.slide, slide_ms);
+                self.note_level = step.gain();
+            }
+            // A tie keeps the note and its envelope going at the new level
+            SeqEvent::Tie(step) => self.note_level = step.gain(),
+            // Closes the gate of a note held through the step before
+            SeqEvent::Rest | SeqEvent::NoteOff => self.note_off(),
+        }
+    }
+
+    /// Silence the voice and clear filter state so nothing stale rings on
+    fn reset_voice(&mut self) {
+        self.release_locks();
+        self.lfo.reset();
+        self.filter.reset();
+        self.envelope.reset();
+        self.amp_envelope.reset();
+        self.accent_sweep.reset();
+        self.vca_gain = 0.0;
+        self.side = 0.0;
+        self.gate = false;
+        self.glide.settle();
+    }
+}
+
+impl Default for Synth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Render mono blocks with `render` and copy each sample to every channel
+/// of an interleaved buffer
+fn process_interleaved_with<F: FnMut(&mut [f32])>(buffer: &mut [f32], channels: usize, mut render: F) {
+    if channels == 0 {
+        return;
+    }
+    let mut scratch = [0.0f32; BLOCK_SIZE];
+    for frames in buffer.chunks_mut(BLOCK_SIZE * channels) {
+        let count = frames.len() / channels;
+        render(&mut scratch[..count]);
+        for (frame, &sample) in frames.chunks_exact_mut(channels).zip(scratch.iter()) {
+            frame.fill(sample);
+        }
+    }
+}
+
+/// Resampler for `channels` channels from the engine rate to `rate`, or
+/// None if they match
+fn output_resampler(sample_rate: f32, rate: f32, channels: usize) -> Option<Resampler> {
+    let rate = rate.clamp(8000.0, 192000.0);
+    if (rate - sample_rate).abs() < 0.5 {
+        None
+    } else {
+        Some(Resampler::new(sample_rate, rate, channels))
+    }
+}
+
+/// Offset at the internal rate of output sample `offset` of the next
+/// process() call, through `resampler` if there is one
+fn internal_offset(resampler: &Option<Resampler>, offset: u32) -> u32 {
+    resampler.as_ref().map_or(offset, |resampler| resampler.input_offset(offset))
+}
+
+/// Store one sample of gate and pitch CV, ignoring buffers that are too short
+fn write_cv(gate: &mut [f32], pitch: &mut [f32], offset: usize, (g, p): (f32, f32)) {
+    if let Some(slot) = gate.get_mut(offset) {
+        *slot = g;
+    }
+    if let Some(slot) = pitch.get_mut(offset) {
+        *slot = p;
+    }
+}
+
+/// Convert MIDI note number to frequency in Hz
+fn midi_to_freq(note: f32) -> f32 {
+    440.0 * 2.0_f32.powf((note - 69.0) / 12.0)
+}
+
+// ============== STUDIO (Synth + Drums Combined) ==============
+
+/// Cutoff range swept by the envelope follower at full depth
+const FOLLOWER_CUTOFF_OCTAVES: f32 = 4.0;
+
+/// Channels written by Studio::process_multi(): the synth, then the kick,
+/// snare, closed hat, open hat and clap
+const MULTI_OUTPUTS: usize = 6;
+
+/// Mixer tracks that can be muted or soloed: the drum tracks by DrumTrack
+/// index, then the synth bus
+const MIX_TRACKS: usize = 6;
+const SYNTH_TRACK: usize = 5;
+
+/// Complete studio with 303 bass synth and 808/909 drum machine
+#[wasm_bindgen]
+pub struct Studio {
+    sample_rate: f32,
+    synth: Synth,
+    drums: DrumMachine,
+
+    // Mixer levels; drum voice pans live in the drum machine
+    synth_vol: f32,
+    drum_vol: f32,
+    master_vol: f32,
+    synth_pan: f32,
+    headroom_gain: f32,
+
+    // Mute and solo per mixer track, and the synth bus gain they leave
+    track_muted: [bool; MIX_TRACKS],
+    track_soloed: [bool; MIX_TRACKS],
+    synth_mix: GainRamp,
+
+    // Master effects run once per channel, left then right
+    dc_blocker: [DcBlocker; 2],
+    master_highpass: [HighPass; 2],
+    widener: Widener,
+
+    // Synth channel inserts
+    synth_highpass: HighPass,
+    synth_gate: NoiseGate,
+    synth_ring: RingMod,
+    synth_comp: Compressor,
+
+    // Drum bus inserts
+    drum_shaper: TransientShaper,
+
+    // Multiband distortion, on the synth channel (left instance only) or
+    // the master
+    multiband: [MultibandDistortion; 2],
+    multiband_on_master: bool,
+
+    // Beat-repeat on the master, looping 1/stutter_division of a bar
+    stutter: [Stutter; 2],
+    stutter_division: u32,
+    // Engage or release waiting for the next division on the clock
+    stutter_pending: Option<bool>,
+
+    // One-shot tape stop on the master
+    tape_stop: [TapeStop; 2],
+    wow_flutter: [WowFlutter; 2],
+    vinyl: Vinyl,
+
+    // Delay send bus: time in sequencer steps, return level and synth send;
+    // drum sends are per voice in the drum machine
+    delay: Delay,
+    delay_steps: f32,
+    delay_return: f32,
+    synth_delay_send: f32,
+    reverb: Reverb,
+    reverb_return: f32,
+    synth_reverb_send: f32,
+
+    // Drum envelope follower modulating the synth
+    follower: EnvelopeFollower,
+    follower_kick_only: bool,
+
+    // Accents shared between the synth and drum sequencers
+    synth_accents_to_drums: bool,
+    drum_accents_to_synth: bool,
+    follower_to_cutoff: f32,
+    follower_to_drive: f32,
+
+    // Output metering for the last process() call
+    clip_count: u32,
+    block_peak: f32,
+    // Each part of the last frame on its own, for process_multi()
+    stems: [f32; MULTI_OUTPUTS],
+
+    // Sync state; the clock steps both sequencers together
+    playing: bool,
+    tempo: f32,
+    clock: Clock,
+
+    // Step tracking for UI
+    last_synth_step: i32,
+    last_drum_step: i32,
+    synth_step_changed: bool,
+    drum_step_changed: bool,
+
+    // Transport position since the last start
+    steps_elapsed: u64,
+    elapsed_samples: u64,
+
+    // MIDI clock slave: position in steps kept from Song Position Pointer
+    // and clock ticks, and the timing of the last beat to derive the tempo
+    midi_clock_sync: bool,
+    song_position: u32,
+    clock_ticks: u32,
+    clock_beat_start: Option<u64>,
+    rendered_samples: u64,
+
+    fade: Fade,
+    resampler: Option<Resampler>,
+
+    // Synth pattern bounced to audio, played instead of the live voice
+    synth_sampler: Sampler,
+    synth_frozen: bool,
+
+    // Host-driven mode: sequencers bypassed, events supplied per block
+    host_mode: bool,
+    host_events: EventQueue<HostEvent>,
+
+    // User pattern memory
+    synth_slots: PatternSlots,
+    drum_slots: PatternSlots<DrumSlot>,
+
+    // Pattern chain stepped through at bar boundaries while song mode is on
+    song: Song,
+    song_mode: bool,
+
+    // Knob recording
+    automation: Automation,
+    last_automation_point: Option<usize>,
+
+    // One-shot cutoff sweep and the knob value it returns to
+    sweep: Option<Sweep>,
+    sweep_knob: f32,
+
+    // Key lock for presets and played notes, None when off
+    key: Option<Key>,
+
+    // Incoming CC assignments
+    cc_map: CcMap,
+
+    // Export
+    export_normalize: Normalize,
+    render_seed: u32,
+
+    // Outcome of the last checked API call
+    last_error: Option<ApiError>,
+}
+
+#[wasm_bindgen]
+impl Studio {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::new_with_sample_rate(SAMPLE_RATE)
+    }
+
+    /// Create a studio running at `sample_rate`, normally the host's own
+    /// rate, so all timing and filter coefficients use the real rate
+    #[wasm_bindgen]
+    pub fn new_with_sample_rate(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            synth: Synth::new_with_sample_rate(sample_rate),
+            drums: DrumMachine::new(sample_rate),
+            clock: Clock::new(sample_rate),
+            synth_vol: 0.7,
+            drum_vol: 0.8,
+            master_vol: 0.8,
+            synth_pan: 0.0,
+            track_muted: [false; MIX_TRACKS],
+            track_soloed: [false; MIX_TRACKS],
+            synth_mix: GainRamp::new(sample_rate, MUTE_RAMP_MS),
+            headroom_gain: 1.0,
+            dc_blocker: std::array::from_fn(|_| DcBlocker::new(sample_rate)),
+            master_highpass: std::array::from_fn(|_| HighPass::new(sample_rate)),
+            widener: Widener::new(sample_rate),
+            synth_highpass: HighPass::new(sample_rate),
+            synth_gate: NoiseGate::new(sample_rate),
+            synth_ring: RingMod::new(sample_rate),
+            synth_comp: Compressor::new(sample_rate),
+            drum_shaper: TransientShaper::new(sample_rate),
+            multiband: std::array::from_fn(|_| MultibandDistortion::new(sample_rate)),
+            multiband_on_master: false,
+            stutter: std::array::from_fn(|_| Stutter::new(sample_rate)),
+            stutter_division: 8,
+            stutter_pending: None,
+            tape_stop: std::array::from_fn(|_| TapeStop::new(sample_rate)),
+            wow_flutter: std::array::from_fn(|_| WowFlutter::new(sample_rate)),
+            vinyl: Vinyl::new(sample_rate),
+            delay: Delay::new(sample_rate),
+            delay_steps: 3.0,
+            delay_return: 0.5,
+            synth_delay_send: 0.0,
+            reverb: Reverb::new(sample_rate),
+            reverb_return: 0.5,
+            synth_reverb_send: 0.0,
+            follower: EnvelopeFollower::new(sample_rate),
+            follower_kick_only: false,
+            synth_accents_to_drums: false,
+            drum_accents_to_synth: false,
+            follower_to_cutoff: 0.0,
+            follower_to_drive: 0.0,
+            clip_count: 0,
+            block_peak: 0.0,
+            stems: [0.0; MULTI_OUTPUTS],
+            playing: false,
+            tempo: 120.0,
+            last_synth_step: -1,
+            last_drum_step: -1,
+            synth_step_changed: false,
+            drum_step_changed: false,
+            steps_elapsed: 0,
+            elapsed_samples: 0,
+            midi_clock_sync: false,
+            song_position: 0,
+            clock_ticks: 0,
+            clock_beat_start: None,
+            rendered_samples: 0,
+            fade: Fade::new(sample_rate, TRANSPORT_FADE_MS),
+            resampler: None,
+            synth_sampler: Sampler::new(),
+            synth_frozen: false,
+            host_mode: false,
+            host_events: EventQueue::new(),
+            synth_slots: PatternSlots::new(),
+            drum_slots: PatternSlots::new(),
+            song: Song::new(),
+            song_mode: false,
+            automation: Automation::new(),
+            last_automation_point: None,
+            sweep: None,
+            sweep_knob: 0.0,
+            key: None,
+            cc_map: CcMap::new(),
+            export_normalize: Normalize::Off,
+            render_seed: 0,
+            last_error: None,
+        }
+    }
+
+    /// Process audio - combines synth and drums with integrated sequencer timing
+    #[wasm_bindgen]
+    pub fn process(&mut self, output: &mut [f32]) {
+        self.begin_block();
+        self.process_mono(output);
+    }
+
+    /// Process into an interleaved buffer with `channels` channels per frame.
+    /// The meters and step flags cover the whole buffer.
+    #[wasm_bindgen]
+    pub fn process_interleaved(&mut self, buffer: &mut [f32], channels: usize) {
+        self.begin_block();
+        process_interleaved_with(buffer, channels, |block| self.process_mono(block));
+    }
+
+    /// Process into separate left and right buffers, with the synth bus and
+    /// drum voices placed by their pan settings. `process` returns the mono
+    /// fold-down of the same mix.
+    #[wasm_bindgen]
+    pub fn process_stereo(&mut self, left: &mut [f32], right: &mut [f32]) {
+        self.begin_block();
+        if let Some(mut resampler) = self.resampler.take() {
+            resampler.process_stereo(left, right, |l, r| self.render_stereo_block(l, r));
+            self.resampler = Some(resampler);
+        } else {
+            self.render_stereo_block(left, right);
+        }
+    }
+
+    /// Process each part into its own channel, so hosts can route them to
+    /// separate effects. `output` holds six equal runs of samples: the
+    /// synth, then the kick, snare, closed hat, open hat and clap. Each part is
+    /// taken after its volume and inserts, without pan, sends or master
+    /// processing.
+    #[wasm_bindgen]
+    pub fn process_multi(&mut self, output: &mut [f32]) {
+        self.begin_block();
+        if let Some(mut resampler) = self.resampler.take() {
+            resampler.process_planar(output, |block| self.render_multi_block(block));
+            self.resampler = Some(resampler);
+        } else {
+            self.render_multi_block(output);
+        }
+    }
+
+    /// Process a block and fill `gate` (0 or 1) and `pitch` (1.0 per octave,
+    /// 0 at C4) for the synth voice alongside it
+    #[wasm_bindgen]
+    pub fn process_with_cv(&mut self, output: &mut [f32], gate: &mut [f32], pitch: &mut [f32]) {
+        self.begin_block();
+        if let Some(mut resampler) = self.resampler.take() {
+            resampler.process_with_cv(output, gate, pitch, |block, gate, pitch| {
+                self.render_block_with(block, |offset, cv| write_cv(gate, pitch, offset, cv))
+            });
+            self.resampler = Some(resampler);
+        } else {
+            self.render_block_with(output, |offset, cv| write_cv(gate, pitch, offset, cv));
+        }
+    }
+
+    /// Get current synth step (for UI), returns -1 if stopped
+    #[wasm_bindgen]
+    pub fn get_synth_step(&self) -> i32 {
+        if self.playing { self.last_synth_step } else { -1 }
+    }
+
+    /// Get current drum step (for UI), returns -1 if stopped
+    #[wasm_bindgen]
+    pub fn get_drum_step(&self) -> i32 {
+        if self.playing { self.last_drum_step } else { -1 }
+    }
+
+    // ===== Timing =====
+
+    #[wasm_bindgen]
+    pub fn synth_samples_per_step(&self) -> f64 {
+        self.synth.sequencer.samples_per_step()
+    }
+
+    #[wasm_bindgen]
+    pub fn drum_samples_per_step(&self) -> f64 {
+        self.drums.sequencer.samples_per_step()
+    }
+
+    #[wasm_bindgen]
+    pub fn synth_steps_per_bar(&self) -> usize {
+        self.synth.sequencer.steps_per_bar()
+    }
+
+    #[wasm_bindgen]
+    pub fn drum_steps_per_bar(&self) -> usize {
+        self.drums.sequencer.steps_per_bar()
+    }
+
+    /// Synth step length in milliseconds as actually played
+    #[wasm_bindgen]
+    pub fn synth_step_duration_ms(&self) -> f32 {
+        self.synth.sequencer.step_duration_ms()
+    }
+
+    /// Drum step length in milliseconds as actually played
+    #[wasm_bindgen]
+    pub fn drum_step_duration_ms(&self) -> f32 {
+        self.drums.sequencer.step_duration_ms()
+    }
+
+    /// Playback position in steps since start, including progress through
+    /// the current step
+    #[wasm_bindgen]
+    pub fn get_position(&self) -> f64 {
+        match self.steps_elapsed {
+            0 => 0.0,
+            n => (n - 1) as f64 + self.get_step_fraction() as f64,
+        }
+    }
+
+    /// Bar number since start, counting from 0
+    #[wasm_bindgen]
+    pub fn get_bar(&self) -> u32 {
+        self.get_position() as u32 / self.steps_in_bar()
+    }
+
+    /// Beat within the bar, counting from 0
+    #[wasm_bindgen]
+    pub fn get_beat(&self) -> u32 {
+        self.get_step_in_bar() / self.steps_in_beat()
+    }
+
+    /// Step within the bar, counting from 0
+    #[wasm_bindgen]
+    pub fn get_step_in_bar(&self) -> u32 {
+        self.get_position() as u32 % self.steps_in_bar()
+    }
+
+    /// Progress through the current step (0.0 - 1.0)
+    #[wasm_bindgen]
+    pub fn get_step_fraction(&self) -> f32 {
+        if self.steps_elapsed == 0 {
+            0.0
+        } else {
+            self.synth.sequencer.position().fract()
+        }
+    }
+
+    /// Samples rendered since the transport started
+    #[wasm_bindgen]
+    pub fn get_elapsed_samples(&self) -> f64 {
+        self.elapsed_samples as f64
+    }
+
+    /// Check if synth step changed during last process() call
+    #[wasm_bindgen]
+    pub fn synth_step_changed(&self) -> bool {
+        self.synth_step_changed
+    }
+
+    /// Check if drum step changed during last process() call
+    #[wasm_bindgen]
+    pub fn drum_step_changed(&self) -> bool {
+        self.drum_step_changed
+    }
+
+    // ===== Transport =====
+
+    /// Start both sequencers from clean voices with a short fade-in
+    #[wasm_bindgen]
+    pub fn start(&mut self) {
+        self.reset_voices();
+        self.fade.fade_in();
+        self.start_sequencers();
+    }
+
+    /// Stop both sequencers, fading out and then resetting all voices
+    #[wasm_bindgen]
+    pub fn stop(&mut self) {
+        self.halt_sequencers();
+        self.fade.fade_out();
+    }
+
+    #[wasm_bindgen]
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    #[wasm_bindgen]
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.tempo = bpm.clamp(60.0, 300.0);
+        self.clock.set_tempo(self.tempo);
+        self.synth.set_tempo(self.tempo);
+        self.drums.set_tempo(self.tempo);
+        self.update_delay_time();
+    }
+
+    /// Shuffle both sequencers together: every other 16th is delayed by up
+    /// to 75% of a step (0.0 = straight, 1.0 = hardest)
+    #[wasm_bindgen]
+    pub fn set_swing(&mut self, amount: f32) {
+        self.clock.set_swing(amount);
+        self.synth.sequencer.set_swing(amount);
+        self.drums.sequencer.set_swing(amount);
+    }
+
+    /// Order the synth steps play in, see Synth::set_direction()
+    #[wasm_bindgen]
+    pub fn set_synth_direction(&mut self, mode: u8) {
+        self.synth.set_direction(mode);
+        self.last_error = self.synth.last_error;
+    }
+
+    /// Order the drum steps play in, coded as in Synth::set_direction()
+    #[wasm_bindgen]
+    pub fn set_drum_direction(&mut self, mode: u8) {
+        let result = Direction::from_index(mode).ok_or(ApiError::Direction);
+        self.last_error = result.err();
+        if let Ok(direction) = result {
+            self.drums.sequencer.set_direction(direction);
+        }
+    }
+
+    /// Loop one drum track (0 = kick, 1 = snare, 2 = closed hat, 3 = open
+    /// hat, 4 = clap) over the first `steps` steps of the pattern (1-32) for
+    /// polymetric grooves. A track as long as the pattern or longer just
+    /// follows it.
+    #[wasm_bindgen]
+    pub fn set_drum_track_length(&mut self, track: u8, steps: usize) {
+        let result = DrumTrack::from_index(track).ok_or(ApiError::DrumTrack);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.drums.sequencer.set_track_length(track, steps);
+        }
+    }
+
+    /// Steps a drum track loops over, or 0 for an invalid track
+    #[wasm_bindgen]
+    pub fn get_drum_track_length(&self, track: u8) -> usize {
+        DrumTrack::from_index(track).map_or(0, |track| self.drums.sequencer.track_length(track))
+    }
+
+    #[wasm_bindgen]
+    pub fn get_swing(&self) -> f32 {
+        self.clock.swing()
+    }
+
+    // ===== Mixer =====
+
+    #[wasm_bindgen]
+    pub fn set_synth_volume(&mut self, vol: f32) {
+        self.synth_vol = vol.clamp(0.0, 1.0);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_drum_volume(&mut self, vol: f32) {
+        self.drum_vol = vol.clamp(0.0, 1.0);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_master_volume(&mut self, vol: f32) {
+        self.master_vol = vol.clamp(0.0, 1.0);
+    }
+
+    /// Enable or bypass the DC blocker on the mix bus
+    #[wasm_bindgen]
+    pub fn set_dc_blocker(&mut self, enabled: bool) {
+        self.dc_blocker.iter_mut().for_each(|d| d.set_enabled(enabled));
+    }
+
+    /// High-pass the whole mix at `freq` Hz (up to 1000) to clear mud
+    /// between the bassline and kick; 0 turns it off
+    #[wasm_bindgen]
+    pub fn set_master_highpass(&mut self, freq: f32) {
+        self.master_highpass.iter_mut().for_each(|h| h.set_cutoff(freq));
+    }
+
+    /// High-pass the synth channel alone at `freq` Hz (up to 1000), leaving
+    /// the kick its low end; 0 turns it off
+    #[wasm_bindgen]
+    pub fn set_synth_highpass(&mut self, freq: f32) {
+        self.synth_highpass.set_cutoff(freq);
+    }
+
+    /// Mute one mixer track: 0 = kick, 1 = snare, 2 = closed hat,
+    /// 3 = open hat, 4 = clap, 5 = synth. Muted tracks fade out over a few
+    /// ms and keep playing silently, so they drop back in on the beat.
+    #[wasm_bindgen]
+    pub fn mute_track(&mut self, track: u8, muted: bool) {
+        let result = Self::mix_track(track);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.track_muted[track] = muted;
+            self.update_track_mix();
+        }
+    }
+
+    /// Solo one mixer track, numbered as in mute_track(). While any track
+    /// is soloed only the soloed tracks are heard, muted or not.
+    #[wasm_bindgen]
+    pub fn solo_track(&mut self, track: u8, soloed: bool) {
+        let result = Self::mix_track(track);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.track_soloed[track] = soloed;
+            self.update_track_mix();
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn is_track_muted(&self, track: u8) -> bool {
+        Self::mix_track(track).is_ok_and(|track| self.track_muted[track])
+    }
+
+    #[wasm_bindgen]
+    pub fn is_track_soloed(&self, track: u8) -> bool {
+        Self::mix_track(track).is_ok_and(|track| self.track_soloed[track])
+    }
+
+    /// Stereo position of the synth bus (-1.0 left to 1.0 right)
+    #[wasm_bindgen]
+    pub fn set_synth_pan(&mut self, pan: f32) {
+        self.synth_pan = pan.clamp(-1.0, 1.0);
+    }
+
+    /// Stereo position of one drum voice: 0 = kick, 1 = snare,
+    /// 2 = closed hat, 3 = open hat, 4 = clap
+    #[wasm_bindgen]
+    pub fn set_drum_pan(&mut self, track: u8, pan: f32) {
+        let result = DrumTrack::from_index(track).ok_or(ApiError::DrumTrack);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.drums.set_pan(track, pan);
+        }
+    }
+
+    /// Width of the master image: 0.0 = mono, 1.0 = as panned, 2.0 = widest.
+    /// Bass stays centred at any width.
+    #[wasm_bindgen]
+    pub fn set_stereo_width(&mut self, width: f32) {
+        self.widener.set_width(width);
+        self.widener.set_enabled(width != 1.0);
+    }
+
+    /// Set the host output rate; audio is rendered at the engine's own
+    /// rate and resampled when the rates differ
+    #[wasm_bindgen]
+    pub fn set_output_sample_rate(&mut self, rate: f32) {
+        self.resampler = output_resampler(self.sample_rate, rate, MULTI_OUTPUTS);
+    }
+
+    /// Reserve headroom on the mix bus (0-24 dB of attenuation before the
+    /// master fader) to absorb hot drive, resonance and drum levels
+    #[wasm_bindgen]
+    pub fn set_headroom(&mut self, db: f32) {
+        self.headroom_gain = 10.0_f32.powf(-db.clamp(0.0, 24.0) / 20.0);
+    }
+
+    /// Number of output samples beyond full scale during the last process() call
+    #[wasm_bindgen]
+    pub fn get_clip_count(&self) -> u32 {
+        self.clip_count
+    }
+
+    /// Highest absolute output sample during the last process() call
+    #[wasm_bindgen]
+    pub fn get_peak_level(&self) -> f32 {
+        self.block_peak
+    }
+
+    // ===== Delay send =====
+
+    /// Delay time in sixteenth steps (0.25 - 16), following the tempo;
+    /// 3 is a dotted eighth
+    #[wasm_bindgen]
+    pub fn set_delay_time(&mut self, steps: f32) {
+        self.delay_steps = steps.clamp(0.25, 16.0);
+        self.update_delay_time();
+    }
+
+    #[wasm_bindgen]
+    pub fn set_delay_feedback(&mut self, feedback: f32) {
+        self.delay.set_feedback(feedback);
+    }
+
+    /// Level of the delay bus in the mix
+    #[wasm_bindgen]
+    pub fn set_delay_return(&mut self, level: f32) {
+        self.delay_return = level.clamp(0.0, 1.0);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_delay_send(&mut self, amount: f32) {
+        self.synth_delay_send = amount.clamp(0.0, 1.0);
+    }
+
+    /// Delay send for one drum voice: 0 = kick, 1 = snare, 2 = closed hat,
+    /// 3 = open hat, 4 = clap
+    #[wasm_bindgen]
+    pub fn set_drum_delay_send(&mut self, track: u8, amount: f32) {
+        let result = DrumTrack::from_index(track).ok_or(ApiError::DrumTrack);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.drums.set_delay_send(track, amount);
+        }
+    }
+
+    // ===== Reverb send =====
+
+    /// Room size (0.0 - 1.0): how long the reverb tail rings
+    #[wasm_bindgen]
+    pub fn set_reverb_size(&mut self, size: f32) {
+        self.reverb.set_size(size);
+    }
+
+    /// How quickly the reverb tail loses its top end (0.0 - 1.0)
+    #[wasm_bindgen]
+    pub fn set_reverb_damping(&mut self, damping: f32) {
+        self.reverb.set_damping(damping);
+    }
+
+    /// Level of the reverb bus in the mix
+    #[wasm_bindgen]
+    pub fn set_reverb_return(&mut self, level: f32) {
+        self.reverb_return = level.clamp(0.0, 1.0);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_reverb_send(&mut self, amount: f32) {
+        self.synth_reverb_send = amount.clamp(0.0, 1.0);
+    }
+
+    /// Reverb send for one drum voice: 0 = kick, 1 = snare, 2 = closed hat,
+    /// 3 = open hat, 4 = clap
+    #[wasm_bindgen]
+    pub fn set_drum_reverb_send(&mut self, track: u8, amount: f32) {
+        let result = DrumTrack::from_index(track).ok_or(ApiError::DrumTrack);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.drums.set_reverb_send(track, amount);
+        }
+    }
+
+    // ===== Synth channel gate =====
+
+    #[wasm_bindgen]
+    pub fn set_synth_gate(&mut self, enabled: bool) {
+        self.synth_gate.set_enabled(enabled);
+    }
+
+    /// Gate threshold in dBFS (-90 to 0)
+    #[wasm_bindgen]
+    pub fn set_synth_gate_threshold(&mut self, db: f32) {
+        self.synth_gate.set_threshold(db);
+    }
+
+    /// Gate release time in milliseconds (5 to 2000)
+    #[wasm_bindgen]
+    pub fn set_synth_gate_release(&mut self, ms: f32) {
+        self.synth_gate.set_release(ms);
+    }
+
+    // ===== Synth channel ring modulator =====
+
+    #[wasm_bindgen]
+    pub fn set_synth_ring_mod(&mut self, enabled: bool) {
+        self.synth_ring.set_enabled(enabled);
+    }
+
+    /// Ring mod carrier: 0 = internal sine, 1 = drum bus
+    #[wasm_bindgen]
+    pub fn set_synth_ring_source(&mut self, source: u8) {
+        self.synth_ring.set_source(match source {
+            1 => RingModSource::External,
+            _ => RingModSource::Carrier,
+        });
+    }
+
+    /// Internal carrier frequency in Hz (1 to 5000)
+    #[wasm_bindgen]
+    pub fn set_synth_ring_freq(&mut self, freq: f32) {
+        self.synth_ring.set_frequency(freq);
+    }
+
+    /// Ring mod dry/wet mix (0.0 - 1.0)
+    #[wasm_bindgen]
+    pub fn set_synth_ring_mix(&mut self, mix: f32) {
+        self.synth_ring.set_mix(mix);
+    }
+
+    // ===== Synth channel compressor =====
+
+    #[wasm_bindgen]
+    pub fn set_synth_compressor(&mut self, enabled: bool) {
+        self.synth_comp.set_enabled(enabled);
+    }
+
+    /// Compressor threshold in dBFS (-60 to 0)
+    #[wasm_bindgen]
+    pub fn set_synth_comp_threshold(&mut self, db: f32) {
+        self.synth_comp.set_threshold(db);
+    }
+
+    /// Compressor ratio (1 to 20)
+    #[wasm_bindgen]
+    pub fn set_synth_comp_ratio(&mut self, ratio: f32) {
+        self.synth_comp.set_ratio(ratio);
+    }
+
+    /// Compressor makeup gain in dB (0 to 24)
+    #[wasm_bindgen]
+    pub fn set_synth_comp_makeup(&mut self, db: f32) {
+        self.synth_comp.set_makeup(db);
+    }
+
+    /// Current synth compressor gain reduction in dB, for metering
+    #[wasm_bindgen]
+    pub fn get_synth_comp_reduction(&self) -> f32 {
+        self.synth_comp.gain_reduction()
+    }
+
+    // ===== Drum bus transient shaper =====
+
+    #[wasm_bindgen]
+    pub fn set_drum_transient(&mut self, enabled: bool) {
+        self.drum_shaper.set_enabled(enabled);
+    }
+
+    /// Attack emphasis (-1.0 softer to 1.0 punchier)
+    #[wasm_bindgen]
+    pub fn set_drum_transient_attack(&mut self, amount: f32) {
+        self.drum_shaper.set_attack(amount);
+    }
+
+    /// Sustain emphasis (-1.0 tighter to 1.0 longer)
+    #[wasm_bindgen]
+    pub fn set_drum_transient_sustain(&mut self, amount: f32) {
+        self.drum_shaper.set_sustain(amount);
+    }
+
+    // ===== Multiband distortion =====
+
+    #[wasm_bindgen]
+    pub fn set_multiband(&mut self, enabled: bool) {
+        self.multiband.iter_mut().for_each(|m| m.set_enabled(enabled));
+    }
+
+    /// Insert point: false = synth channel, true = master
+    #[wasm_bindgen]
+    pub fn set_multiband_on_master(&mut self, on_master: bool) {
+        if on_master != self.multiband_on_master {
+            self.multiband_on_master = on_master;
+            self.multiband.iter_mut().for_each(|m| m.reset());
+        }
+    }
+
+    /// Crossover frequencies in Hz (low 40 to 1000, high up to 10000)
+    #[wasm_bindgen]
+    pub fn set_multiband_crossovers(&mut self, low: f32, high: f32) {
+        self.multiband.iter_mut().for_each(|m| m.set_crossovers(low, high));
+    }
+
+    #[wasm_bindgen]
+    pub fn set_multiband_mid_drive(&mut self, drive: f32) {
+        self.multiband.iter_mut().for_each(|m| m.set_mid_drive(drive));
+    }
+
+    #[wasm_bindgen]
+    pub fn set_multiband_high_drive(&mut self, drive: f32) {
+        self.multiband.iter_mut().for_each(|m| m.set_high_drive(drive));
+    }
+
+    // ===== Stutter =====
+
+    /// Length of the stutter loop as a note value: 4 = quarter note,
+    /// 8 = eighth, 16 = sixteenth
+    #[wasm_bindgen]
+    pub fn set_stutter_division(&mut self, division: u32) {
+        if matches!(division, 4 | 8 | 16) {
+            self.stutter_division = division;
+        }
+    }
+
+    /// Engage to capture the last note value of the master and loop it,
+    /// release to return to the live mix
+    #[wasm_bindgen]
+    pub fn set_stutter(&mut self, engaged: bool) {
+        if engaged {
+            let steps = 16 / self.stutter_division;
+            let samples = (self.synth.sequencer.samples_per_step() * steps as f64).round() as usize;
+            self.stutter.iter_mut().for_each(|s| s.engage(samples));
+        } else {
+            self.stutter.iter_mut().for_each(|s| s.release());
+        }
+    }
+
+    /// Loop the last 1/`division` of a bar (4, 8 or 16) from the next
+    /// division on the clock, so the repeat lands in time. Stopped or
+    /// following a host, it engages straight away.
+    #[wasm_bindgen]
+    pub fn stutter_on(&mut self, division: u32) {
+        self.set_stutter_division(division);
+        self.queue_stutter(true);
+    }
+
+    /// Return to the live mix at the next division on the clock
+    #[wasm_bindgen]
+    pub fn stutter_off(&mut self) {
+        self.queue_stutter(false);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_stutter_engaged(&self) -> bool {
+        self.stutter[0].is_engaged()
+    }
+
+    // ===== Tape stop =====
+
+    /// Slow the master down to a halt like a tape machine losing power,
+    /// then cut back to the live mix
+    #[wasm_bindgen]
+    pub fn trigger_tape_stop(&mut self) {
+        self.tape_stop.iter_mut().for_each(|t| t.trigger());
+    }
+
+    /// Set how long the slowdown takes in milliseconds (50 to 2000)
+    #[wasm_bindgen]
+    pub fn set_tape_stop_time(&mut self, ms: f32) {
+        self.tape_stop.iter_mut().for_each(|t| t.set_time(ms));
+    }
+
+    #[wasm_bindgen]
+    pub fn is_tape_stopping(&self) -> bool {
+        self.tape_stop[0].is_active()
+    }
+
+    // ===== Wow / flutter =====
+
+    /// Tape-style pitch wobble on the master (0.0 = off, 1.0 = very worn)
+    #[wasm_bindgen]
+    pub fn set_wow_flutter(&mut self, depth: f32) {
+        self.wow_flutter.iter_mut().for_each(|w| w.set_depth(depth));
+    }
+
+    // ===== Vinyl ambience =====
+
+    /// Level of the hiss and crackle layer on the master (0.0 = off)
+    #[wasm_bindgen]
+    pub fn set_vinyl_level(&mut self, level: f32) {
+        self.vinyl.set_level(level);
+    }
+
+    /// Crackle density (0.0 = hiss only, 1.0 = very dusty)
+    #[wasm_bindgen]
+    pub fn set_vinyl_dust(&mut self, dust: f32) {
+        self.vinyl.set_dust(dust);
+    }
+
+    // ===== Envelope follower =====
+
+    /// Follower input: false = whole drum bus, true = kick only
+    #[wasm_bindgen]
+    pub fn set_follower_kick_only(&mut self, kick_only: bool) {
+        self.follower_kick_only = kick_only;
+    }
+
+    /// Follower attack in milliseconds (0.1 to 100)
+    #[wasm_bindgen]
+    pub fn set_follower_attack(&mut self, ms: f32) {
+        self.follower.set_attack(ms);
+    }
+
+    /// Follower release in milliseconds (5 to 2000)
+    #[wasm_bindgen]
+    pub fn set_follower_release(&mut self, ms: f32) {
+        self.follower.set_release(ms);
+    }
+
+    /// Follower to synth cutoff depth (-1.0 ducks, 1.0 opens, up to 4 octaves)
+    #[wasm_bindgen]
+    pub fn set_follower_to_cutoff(&mut self, depth: f32) {
+        self.follower_to_cutoff = depth.clamp(-1.0, 1.0);
+    }
+
+    /// Follower to synth drive depth (-1.0 to 1.0)
+    #[wasm_bindgen]
+    pub fn set_follower_to_drive(&mut self, depth: f32) {
+        self.follower_to_drive = depth.clamp(-1.0, 1.0);
+    }
+
+    /// Current follower level (0.0 - 1.0), for UI meters
+    #[wasm_bindgen]
+    pub fn get_follower_level(&self) -> f32 {
+        self.follower.level()
+    }
+
+    // ===== Synth controls (delegated) =====
+
+    #[wasm_bindgen]
+    pub fn synth_note_on(&mut self, note: f32, accent: bool, slide: bool) {
+        self.synth.note_on(self.lock_note(note), accent, slide);
+    }
+
+    #[wasm_bindgen]
+    pub fn synth_note_off(&mut self) {
+        self.synth.note_off();
+    }
+
+    #[wasm_bindgen]
+    pub fn synth_note_on_at(&mut self, offset: u32, note: f32, accent: bool, slide: bool) {
+        let offset = internal_offset(&self.resampler, offset);
+        let note = self.lock_note(note);
+        self.synth.scheduled.push(offset, NoteEvent::On { note, accent, slide });
+    }
+
+    #[wasm_bindgen]
+    pub fn synth_note_off_at(&mut self, offset: u32) {
+        let offset = internal_offset(&self.resampler, offset);
+        self.synth.scheduled.push(offset, NoteEvent::Off);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_waveform(&mut self, saw: bool) {
+        self.synth.set_waveform(saw);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_waveform_type(&mut self, waveform: u8) {
+        self.synth.set_waveform_type(waveform);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_pulse_width(&mut self, width: f32) {
+        self.synth.set_pulse_width(width);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_unison_voices(&mut self, voices: u8) {
+        self.synth.set_unison_voices(voices);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_unison_detune(&mut self, amount: f32) {
+        self.synth.set_unison_detune(amount);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_unison_spread(&mut self, amount: f32) {
+        self.synth.set_unison_spread(amount);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_cutoff(&mut self, freq: f32) {
+        self.apply_param(AutomationParam::Cutoff, freq);
+        self.record_automation(AutomationParam::Cutoff, freq);
+    }
+
+    /// Modulate the synth cutoff from an external signal, in octaves per
+    /// unit, read one value per sample by the following `process` calls
+    #[wasm_bindgen]
+    pub fn set_synth_cutoff_mod_buffer(&mut self, buffer: &[f32]) {
+        self.synth.set_cutoff_mod_buffer(buffer);
+    }
+
+    /// Sweep the cutoff from `start_cutoff` to `end_cutoff` Hz over `bars`
+    /// bars at the current tempo, then return to the knob value
+    #[wasm_bindgen]
+    pub fn trigger_sweep(&mut self, start_cutoff: f32, end_cutoff: f32, bars: f32) {
+        let length = (bars.max(0.0) * self.samples_per_bar() as f32) as u32;
+        if self.sweep.is_none() {
+            self.sweep_knob = self.synth.cutoff;
+        }
+        self.sweep = Some(Sweep::new(start_cutoff, end_cutoff, length));
+    }
+
+    #[wasm_bindgen]
+    pub fn is_sweeping(&self) -> bool {
+        self.sweep.is_some()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_resonance(&mut self, res: f32) {
+        self.synth.set_resonance(res);
+        self.record_automation(AutomationParam::Resonance, res);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_env_mod(&mut self, depth: f32) {
+        self.synth.set_env_mod(depth);
+        self.record_automation(AutomationParam::EnvMod, depth);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_decay(&mut self, ms: f32) {
+        self.synth.set_decay(ms);
+        self.record_automation(AutomationParam::Decay, ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_attack(&mut self, ms: f32) {
+        self.synth.set_attack(ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_release(&mut self, ms: f32) {
+        self.synth.set_release(ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_amp_decay(&mut self, ms: f32) {
+        self.synth.set_amp_decay(ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_accent(&mut self, amount: f32) {
+        self.synth.set_accent(amount);
+        self.record_automation(AutomationParam::Accent, amount);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_accent_decay(&mut self, ms: f32) {
+        self.synth.set_accent_decay(ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_accent_curve(&mut self, curve: u8) {
+        self.synth.set_accent_curve(curve);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_filter_type(&mut self, model: u8) {
+        self.synth.set_filter_type(model);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_filter_quality(&mut self, quality: u8) {
+        self.synth.set_filter_quality(quality);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_filter_drive(&mut self, amount: f32) {
+        self.synth.set_filter_drive(amount);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_filter_self_oscillation(&mut self, on: bool) {
+        self.synth.set_filter_self_oscillation(on);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_filter_key_track(&mut self, amount: f32) {
+        self.synth.set_filter_key_track(amount);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_resonance_compensation(&mut self, on: bool) {
+        self.synth.set_resonance_compensation(on);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_anti_alias(&mut self, mode: u8) {
+        self.synth.set_anti_alias(mode);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_master_tune(&mut self, cents: f32) {
+        self.synth.set_master_tune(cents);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_octave(&mut self, octave: i8) {
+        self.synth.set_octave(octave);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_slide_time(&mut self, ms: f32) {
+        self.synth.set_slide_time(ms);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_slide_curve(&mut self, curve: u8) {
+        self.synth.set_slide_curve(curve);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_distortion(&mut self, amount: f32) {
+        self.synth.set_distortion(amount);
+        self.record_automation(AutomationParam::Distortion, amount);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_distortion_routing(&mut self, pre_filter: bool) {
+        self.synth.set_distortion_routing(pre_filter);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_lfo_shape(&mut self, shape: u8) {
+        self.synth.set_lfo_shape(shape);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_lfo_rate(&mut self, hz: f32) {
+        self.synth.set_lfo_rate(hz);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_lfo_sync(&mut self, steps: f32) {
+        self.synth.set_lfo_sync(steps);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_lfo_to_cutoff(&mut self, depth: f32) {
+        self.synth.set_lfo_to_cutoff(depth);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_lfo_to_pitch(&mut self, depth: f32) {
+        self.synth.set_lfo_to_pitch(depth);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_lfo_to_drive(&mut self, depth: f32) {
+        self.synth.set_lfo_to_drive(depth);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_lfo_to_pwm(&mut self, depth: f32) {
+        self.synth.set_lfo_to_pwm(depth);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_step(&mut self, index: usize, note: u8, accent: bool, slide: bool, active: bool) {
+        let result = self.synth.try_set_step(index, note, accent, slide, active);
+        self.last_error = result.err();
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_step_cents(&mut self, index: usize, cents: i32) {
+        let result = self.synth.try_set_step_cents(index, cents);
+        self.last_error = result.err();
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_step_level(&mut self, index: usize, level: u8) {
+        let result = self.synth.try_set_step_level(index, level);
+        self.last_error = result.err();
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_step_gate(&mut self, index: usize, gate: u8) {
+        let result = self.synth.try_set_step_gate(index, gate);
+        self.last_error = result.err();
+    }
+
+    /// See Synth::set_step_ratchet()
+    #[wasm_bindgen]
+    pub fn set_synth_step_ratchet(&mut self, index: usize, hits: u8) {
+        let result = self.synth.try_set_step_ratchet(index, hits);
+        self.last_error = result.err();
+    }
+
+    /// See Synth::set_step_nudge()
+    #[wasm_bindgen]
+    pub fn set_synth_step_nudge(&mut self, index: usize, percent: i8) {
+        let result = self.synth.try_set_step_nudge(index, percent);
+        self.last_error = result.err();
+    }
+
+    /// See Synth::set_step_slide_time()
+    #[wasm_bindgen]
+    pub fn set_synth_step_slide_time(&mut self, index: usize, ms: f32) {
+        let result = self.synth.try_set_step_slide_time(index, ms);
+        self.last_error = result.err();
+    }
+
+    /// See Synth::set_step_lock()
+    #[wasm_bindgen]
+    pub fn set_synth_step_lock(&mut self, index: usize, param: u8, position: f32) {
+        self.synth.set_step_lock(index, param, position);
+        self.last_error = self.synth.last_error;
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_synth_step_lock(&mut self, index: usize, param: u8) {
+        self.synth.clear_step_lock(index, param);
+        self.last_error = self.synth.last_error;
+    }
+
+    /// See Synth::set_step_probability()
+    #[wasm_bindgen]
+    pub fn set_synth_step_probability(&mut self, index: usize, percent: u8) {
+        self.synth.set_step_probability(index, percent);
+        self.last_error = self.synth.last_error;
+    }
+
+    /// See Synth::set_step_condition()
+    #[wasm_bindgen]
+    pub fn set_synth_step_condition(&mut self, index: usize, condition: u8) {
+        self.synth.set_step_condition(index, condition);
+        self.last_error = self.synth.last_error;
+    }
+
+    /// Synth pattern as 16 bytes per step, see Synth::get_pattern()
+    #[wasm_bindgen]
+    pub fn get_synth_pattern(&self) -> Vec<u8> {
+        self.synth.get_pattern()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_synth_pattern(&mut self, bytes: &[u8]) {
+        let result = self.synth.try_set_pattern(bytes);
+        self.last_error = result.err();
+    }
+
+    #[wasm_bindgen]
+    pub fn load_synth_pattern_bytes(&mut self, notes: &[u8], flags: &[u8]) {
+        let result = self.synth.try_load_pattern_bytes(notes, flags);
+        self.last_error = result.err();
+    }
+
+    #[wasm_bindgen]
+    pub fn rotate_synth_accents(&mut self, n: i32) {
+        self.synth.rotate_accents(n);
+    }
+
+    #[wasm_bindgen]
+    pub fn rotate_synth_slides(&mut self, n: i32) {
+        self.synth.rotate_slides(n);
+    }
+
+    #[wasm_bindgen]
+    pub fn randomize_synth_accents(&mut self, density: f32) {
+        self.synth.randomize_accents(density);
+    }
+
+    #[wasm_bindgen]
+    pub fn randomize_synth_slides(&mut self, density: f32) {
+        self.synth.randomize_slides(density);
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_synth_accents(&mut self) {
+        self.synth.clear_accents();
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_synth_slides(&mut self) {
+        self.synth.clear_slides();
+    }
+
+    #[wasm_bindgen]
+    pub fn load_synth_preset(&mut self, index: usize) {
+        let result = self.synth.try_load_preset(index);
+        self.last_error = result.err();
+        if result.is_ok() {
+            self.fit_pattern_to_key();
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn toggle_synth_ab(&mut self) -> u8 {
+        self.synth.toggle_ab()
+    }
+
+    #[wasm_bindgen]
+    pub fn copy_synth_a_to_b(&mut self) {
+        self.synth.copy_a_to_b();
+    }
+
+    #[wasm_bindgen]
+    pub fn get_synth_ab_slot(&self) -> u8 {
+        self.synth.get_ab_slot()
+    }
+
+    // ===== Key lock =====
+
+    /// Lock to a key: `root` is a pitch class (0 = C, 11 = B), `scale` is
+    /// 0 = chromatic, 1 = major, 2 = minor, 3 = dorian, 4 = phrygian,
+    /// 5 = minor pentatonic, 6 = harmonic minor, 7 = phrygian dominant.
+    /// Presets loaded afterwards are transposed to the key and live notes
+    /// are snapped into it.
+    #[wasm_bindgen]
+    pub fn set_key(&mut self, root: u8, scale: u8) {
+        let result = Scale::from_index(scale).ok_or(ApiError::Scale);
+        self.last_error = result.err();
+        if let Ok(scale) = result {
+            self.key = Some(Key::new(root, scale));
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_key(&mut self) {
+        self.key = None;
+    }
+
+    /// Generate a synth pattern in a style: 0 = classic acid, 1 = electro,
+    /// 2 = dub/off-beat, 3 = hoover stabs. The same seed always gives the
+    /// same pattern. Notes are rooted on and kept in the locked key.
+    #[wasm_bindgen]
+    pub fn generate_synth_pattern(&mut self, style: u8, seed: u32) {
+        let result = Style::from_index(style).ok_or(ApiError::Style);
+        self.last_error = result.err();
+        let Ok(style) = result else {
+            return;
+        };
+        let root = self.key.map_or(0, |key| key.root());
+        let mut steps = generator::generate(style, root, &mut Rng::new(seed));
+        if let Some(key) = self.key {
+            for step in steps.iter_mut() {
+                step.note = key.snap(step.note);
+            }
+        }
+        self.synth.sequencer.load_pattern(&steps);
+    }
+
+    /// Transpose the current synth pattern to the key and snap its notes
+    #[wasm_bindgen]
+    pub fn fit_pattern_to_key(&mut self) {
+        let Some(key) = self.key else {
+            return;
+        };
+        let Some(root) = self.synth.sequencer.root_note() else {
+            return;
+        };
+        let shift = key.transpose_from(root);
+        for i in 0..self.synth.sequencer.length() {
+            if let Some(step) = self.synth.sequencer.get_step_mut(i) {
+                let note = (step.note as i32 + shift).clamp(0, 127) as u8;
+                step.note = key.snap(note);
+            }
+        }
+    }
+
+    /// See Synth::transpose()
+    #[wasm_bindgen]
+    pub fn transpose_synth_pattern(&mut self, semitones: i8) {
+        self.synth.transpose(semitones);
+    }
+
+    /// See Synth::set_scale()
+    #[wasm_bindgen]
+    pub fn set_synth_scale(&mut self, root: u8, scale: u8) {
+        self.synth.set_scale(root, scale);
+        self.last_error = self.synth.last_error;
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_synth_scale(&mut self) {
+        self.synth.clear_scale();
+    }
+
+    /// Shift the synth pattern `n` steps later (negative = earlier),
+    /// wrapping round its end
+    #[wasm_bindgen]
+    pub fn rotate_synth_pattern(&mut self, n: i32) {
+        self.synth.sequencer.rotate(n);
+    }
+
+    /// Play the synth pattern backwards, keeping slides between the same
+    /// notes
+    #[wasm_bindgen]
+    pub fn reverse_synth_pattern(&mut self) {
+        self.synth.sequencer.reverse();
+    }
+
+    /// Overwrite the second half of the synth pattern with the first half
+    /// backwards
+    #[wasm_bindgen]
+    pub fn mirror_synth_pattern(&mut self) {
+        self.synth.sequencer.mirror();
+    }
+
+    #[wasm_bindgen]
+    pub fn invert_synth_accents(&mut self) {
+        self.synth.sequencer.invert_accents();
+    }
+
+    /// Double the synth pattern's length by playing it twice (16 steps
+    /// become 32). Patterns already over 16 steps are left alone.
+    #[wasm_bindgen]
+    pub fn double_synth_pattern(&mut self) {
+        let doubled = self.synth.sequencer.double();
+        self.last_error = (!doubled).then_some(ApiError::PatternLength);
+    }
+
+    /// Steps in the synth pattern (1-32)
+    #[wasm_bindgen]
+    pub fn get_synth_pattern_length(&self) -> usize {
+        self.synth.sequencer.length()
+    }
+
+    // ===== Drum controls =====
+
+    /// Set the kick, snare and hat tracks of a drum step at once; the clap
+    /// is set with set_drum_track_step()
+    #[wasm_bindgen]
+    pub fn set_drum_step(&mut self, index: usize, kick: bool, snare: bool, closed_hh: bool, open_hh: bool) {
+        self.last_error = self.check_drum_step(index).err();
+        self.drums.sequencer.set_step(index, drums::DrumTrack::Kick, kick);
+        self.drums.sequencer.set_step(index, drums::DrumTrack::Snare, snare);
+        self.drums.sequencer.set_step(index, drums::DrumTrack::ClosedHH, closed_hh);
+        self.drums.sequencer.set_step(index, drums::DrumTrack::OpenHH, open_hh);
+    }
+
+    /// Set a single drum track step
+    #[wasm_bindgen]
+    pub fn set_drum_track_step(&mut self, index: usize, track: u8, active: bool) {
+        let result = self.check_drum_track_step(index, track);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.drums.sequencer.set_step(index, track, active);
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn toggle_drum_step(&mut self, index: usize, track: u8) {
+        let result = self.check_drum_track_step(index, track);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.drums.sequencer.toggle_step(index, track);
+        }
+    }
+
+    /// Get drum step data for a specific step index: kick, snare, closed
+    /// hat, open hat, accent and clap
+    #[wasm_bindgen]
+    pub fn get_drum_step_data(&self, index: usize) -> Vec<u8> {
+        if let Some(step) = self.drums.sequencer.get_step(index) {
+            vec![
+                step.kick as u8,
+                step.snare as u8,
+                step.closed_hh as u8,
+                step.open_hh as u8,
+                step.accent as u8,
+                step.clap as u8,
+            ]
+        } else {
+            vec![0, 0, 0, 0, 0, 0]
+        }
+    }
+
+    // ===== Pattern slots =====
+
+    /// Store the synth pattern in user slot `slot` (0-7). With `with_sound`
+    /// the tempo and synth knobs are stored too, to be restored on recall.
+    #[wasm_bindgen]
+    pub fn store_synth_slot(&mut self, slot: usize, with_sound: bool) {
+        let sound = with_sound.then(|| Sound {
+            tempo: self.tempo,
+            params: self.synth.params(),
+        });
+        let pattern = PatternSlot {
+            steps: self.synth.sequencer.steps().to_vec(),
+            variation: self.synth.sequencer.variation(),
+            sound,
+        };
+        self.last_error = self.synth_slots.store(slot, pattern).err();
+    }
+
+    /// Load the synth pattern from user slot `slot`, along with its tempo
+    /// and knobs if it has them, unless `pattern_only` is set
+    #[wasm_bindgen]
+    pub fn recall_synth_slot(&mut self, slot: usize, pattern_only: bool) {
+        let result = self.try_recall_synth_slot(slot, pattern_only);
+        self.last_error = result.err();
+    }
+
+    #[wasm_bindgen]
+    pub fn is_synth_slot_filled(&self, slot: usize) -> bool {
+        self.synth_slots.is_filled(slot)
+    }
+
+    /// Copy synth slot `from` over slot `to`
+    #[wasm_bindgen]
+    pub fn copy_synth_slot(&mut self, from: usize, to: usize) {
+        self.last_error = self.synth_slots.copy(from, to).err();
+    }
+
+    /// Store the drum pattern and its variation in user slot `slot` (0-7)
+    #[wasm_bindgen]
+    pub fn store_drum_slot(&mut self, slot: usize) {
+        let pattern = self.stored_drum_pattern();
+        self.last_error = self.drum_slots.store(slot, pattern).err();
+    }
+
+    #[wasm_bindgen]
+    pub fn recall_drum_slot(&mut self, slot: usize) {
+        let result = self.drum_slots.get(slot).map(|pattern| {
+            self.drums.sequencer.load_pattern(&pattern.steps);
+            self.drums.sequencer.load_variation(pattern.variation.as_ref());
+        });
+        self.last_error = result.err();
+    }
+
+    #[wasm_bindgen]
+    pub fn is_drum_slot_filled(&self, slot: usize) -> bool {
+        self.drum_slots.is_filled(slot)
+    }
+
+    /// Copy drum slot `from` over slot `to`
+    #[wasm_bindgen]
+    pub fn copy_drum_slot(&mut self, from: usize, to: usize) {
+        self.last_error = self.drum_slots.copy(from, to).err();
+    }
+
+    /// Store the synth and drum patterns together in slot `slot` (0-7) of
+    /// each sequencer, without the synth's sound
+    #[wasm_bindgen]
+    pub fn save_to_slot(&mut self, slot: usize) {
+        self.store_synth_slot(slot, false);
+        if self.last_error.is_none() {
+            self.store_drum_slot(slot);
+        }
+    }
+
+    /// Load both patterns from slot `slot`, keeping the tempo and knobs.
+    /// Nothing changes unless both sequencers' slots are filled.
+    #[wasm_bindgen]
+    pub fn load_from_slot(&mut self, slot: usize) {
+        let result = self.try_load_from_slot(slot);
+        self.last_error = result.err();
+    }
+
+    /// Copy slot `from` over slot `to` for both sequencers, so a variation
+    /// can be worked up from a copy. Nothing changes unless both of the
+    /// `from` slots are filled.
+    #[wasm_bindgen]
+    pub fn copy_pattern(&mut self, from: usize, to: usize) {
+        let result = self
+            .drum_slots
+            .get(from)
+            .and_then(|_| self.synth_slots.copy(from, to))
+            .and_then(|_| self.drum_slots.copy(from, to));
+        self.last_error = result.err();
+    }
+
+    // ===== Song mode =====
+
+    /// Store the current synth and drum patterns as song pattern `index`
+    /// (0-15)
+    #[wasm_bindgen]
+    pub fn store_song_pattern(&mut self, index: usize) {
+        let synth = StoredPattern {
+            steps: self.synth.sequencer.steps().to_vec(),
+            variation: self.synth.sequencer.variation(),
+        };
+        let drums = self.stored_drum_pattern();
+        let result = self.song.set_synth_pattern(index, synth).and_then(|_| self.song.set_drum_pattern(index, drums));
+        self.last_error = result.err();
+    }
+
+    /// Set song synth pattern `index` from bytes in get_synth_pattern format
+    #[wasm_bindgen]
+    pub fn set_song_synth_pattern(&mut self, index: usize, bytes: &[u8]) {
+        let result = sequencer::parse_pattern(bytes)
+            .ok_or(ApiError::PatternLength)
+            .and_then(|steps| self.song.set_synth_pattern(index, StoredPattern { steps, variation: None }));
+        self.last_error = result.err();
+    }
+
+    /// Set song drum pattern `index` from bytes in get_drum_pattern format
+    #[wasm_bindgen]
+    pub fn set_song_drum_pattern(&mut self, index: usize, bytes: &[u8]) {
+        let result = drums::sequencer::parse_pattern(bytes)
+            .ok_or(ApiError::PatternLength)
+            .and_then(|steps| self.song.set_drum_pattern(index, StoredPattern { steps, variation: None }));
+        self.last_error = result.err();
+    }
+
+    /// Set the chain as [synth pattern, drum pattern, bars, ...] triples,
+    /// e.g. [0, 0, 4, 1, 1, 2, 1, 2, 1] plays A for 4 bars, B for 2, then a
+    /// fill bar. An entry whose pattern was never stored keeps what's playing.
+    #[wasm_bindgen]
+    pub fn set_song_chain(&mut self, chain: &[u32]) {
+        let result = if chain.len().is_multiple_of(3) && chain.len() / 3 <= song::MAX_CHAIN {
+            let mut entries = [ChainEntry { synth: 0, drums: 0, repeats: 0 }; song::MAX_CHAIN];
+            for (entry, c) in entries.iter_mut().zip(chain.chunks_exact(3)) {
+                *entry = ChainEntry { synth: c[0] as usize, drums: c[1] as usize, repeats: c[2] };
+            }
+            self.song.set_chain(&entries[..chain.len() / 3])
+        } else {
+            Err(ApiError::ChainFormat)
+        };
+        self.last_error = result.err();
+    }
+
+    /// While on, the chain replaces the patterns at each bar, from the top
+    /// of the chain at the next bar or on start
+    #[wasm_bindgen]
+    pub fn set_song_mode(&mut self, enabled: bool) {
+        self.song_mode = enabled;
+        self.song.rewind();
+    }
+
+    /// Loop back to the first entry at the end of the chain; when off the
+    /// transport stops instead
+    #[wasm_bindgen]
+    pub fn set_song_loop(&mut self, looping: bool) {
+        self.song.set_looping(looping);
+    }
+
+    #[wasm_bindgen]
+    pub fn get_song_length(&self) -> usize {
+        self.song.chain().len()
+    }
+
+    /// Chain entry playing, or -1 when song mode is off or hasn't begun
+    #[wasm_bindgen]
+    pub fn get_song_entry(&self) -> i32 {
+        match self.song.entry() {
+            Some(entry) if self.song_mode => entry as i32,
+            _ => -1,
+        }
+    }
+
+    // ===== Pattern variations =====
+
+    /// Alternate synth pattern in the get_synth_pattern() format, played on
+    /// a loop instead of the main pattern with the variation chance
+    #[wasm_bindgen]
+    pub fn set_synth_variation(&mut self, bytes: &[u8]) {
+        let loaded = self.synth.sequencer.set_variation_bytes(bytes);
+        self.last_error = (!loaded).then_some(ApiError::PatternLength);
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_synth_variation(&mut self) {
+        self.synth.sequencer.clear_variation();
+    }
+
+    /// Chance (0.0 - 1.0) that a loop of the synth plays its variation
+    #[wasm_bindgen]
+    pub fn set_synth_variation_chance(&mut self, chance: f32) {
+        self.synth.sequencer.set_variation_chance(chance);
+    }
+
+    #[wasm_bindgen]
+    pub fn is_synth_variation_playing(&self) -> bool {
+        self.synth.sequencer.is_playing_variation()
+    }
+
+    /// Alternate drum pattern in the get_drum_pattern() format, played on a
+    /// bar instead of the main pattern with the variation chance. Fills
+    /// take priority.
+    #[wasm_bindgen]
+    pub fn set_drum_variation(&mut self, bytes: &[u8]) {
+        let loaded = self.drums.sequencer.set_variation_bytes(bytes);
+        self.last_error = (!loaded).then_some(ApiError::PatternLength);
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_drum_variation(&mut self) {
+        self.drums.sequencer.clear_variation();
+    }
+
+    /// Chance (0.0 - 1.0) that a bar of the drums plays its variation
+    #[wasm_bindgen]
+    pub fn set_drum_variation_chance(&mut self, chance: f32) {
+        self.drums.sequencer.set_variation_chance(chance);
+    }
+
+    /// Accent the drum voices on a step
+    #[wasm_bindgen]
+    pub fn set_drum_accent(&mut self, index: usize, accent: bool) {
+        self.last_error = self.check_drum_step(index).err();
+        self.drums.sequencer.set_accent(index, accent);
+    }
+
+    /// How much accented drum steps boost every voice, like the 909's
+    /// accent knob (0.0 = no boost, 1.0 = twice as loud, default 0.4)
+    #[wasm_bindgen]
+    pub fn set_drum_accent_amount(&mut self, amount: f32) {
+        self.drums.set_accent_amount(amount);
+    }
+
+    /// Strike all of a drum step's voices `hits` times within the step
+    /// (1-4), for rolls and hat ratchets
+    #[wasm_bindgen]
+    pub fn set_drum_step_ratchet(&mut self, index: usize, hits: u8) {
+        self.last_error = self.check_drum_step(index).err();
+        self.drums.sequencer.set_ratchet(index, hits);
+    }
+
+    /// Push a drum step late (positive) or pull it early (negative) by up
+    /// to 50% of a step
+    #[wasm_bindgen]
+    pub fn set_drum_step_nudge(&mut self, index: usize, percent: i8) {
+        self.last_error = self.check_drum_step(index).err();
+        self.drums.sequencer.set_nudge(index, percent);
+    }
+
+    /// How hard one track is hit on a drum step (0-127, default 127), for
+    /// ghost notes and dynamics. Track: 0 = kick, 1 = snare,
+    /// 2 = closed hat, 3 = open hat, 4 = clap
+    #[wasm_bindgen]
+    pub fn set_drum_step_velocity(&mut self, index: usize, track: u8, velocity: u8) {
+        let result = self.check_drum_track_step(index, track);
+        self.last_error = result.err();
+        if let Ok(track) = result {
+            self.drums.sequencer.set_velocity(index, track, velocity);
+        }
+    }
+
+    /// Chance (0-100%) that a drum step plays each time it comes round
+    #[wasm_bindgen]
+    pub fn set_drum_step_probability(&mut self, index: usize, percent: u8) {
+        let result = self.check_drum_step(index).map(|_| {
+            let trig = self.drums.sequencer.get_step(index).map(|s| s.trig).unwrap_or_default();
+            Trig { probability: percent.min(trig::ALWAYS_PLAYS), ..trig }
+        });
+        self.last_error = result.err();
+        if let Ok(trig) = result {
+            self.drums.sequencer.set_trig(index, trig);
+        }
+    }
+
+    /// Which passes a drum step plays on, coded as in
+    /// Synth::set_step_condition()
+    #[wasm_bindgen]
+    pub fn set_drum_step_condition(&mut self, index: usize, condition: u8) {
+        let result = self.check_drum_step(index).and_then(|_| {
+            let condition = Condition::from_byte(condition).ok_or(ApiError::TrigCondition)?;
+            let trig = self.drums.sequencer.get_step(index).map(|s| s.trig).unwrap_or_default();
+            Ok(Trig { condition, ..trig })
+        });
+        self.last_error = result.err();
+        if let Ok(trig) = result {
+            self.drums.sequencer.set_trig(index, trig);
+        }
+    }
+
+    /// Seed for the trig chances and pattern variations in offline
+    /// renders, so the same seed always renders the same audio
+    #[wasm_bindgen]
+    pub fn set_render_seed(&mut self, seed: u32) {
+        self.render_seed = seed;
+    }
+
+    /// Let accented synth steps also accent the drums on the same step
+    #[wasm_bindgen]
+    pub fn set_synth_accents_to_drums(&mut self, linked: bool) {
+        self.synth_accents_to_drums = linked;
+    }
+
+    /// Let accented drum steps also accent the synth note on the same step
+    #[wasm_bindgen]
+    pub fn set_drum_accents_to_synth(&mut self, linked: bool) {
+        self.drum_accents_to_synth = linked;
+    }
+
+    /// Drum pattern as 10 bytes per step: tracks (1 = kick, 2 = snare,
+    /// 4 = closed hat, 8 = open hat, 32 = clap, 16 = accent), probability
+    /// (0-100), condition as in Synth::set_step_condition(), ratchet (1-4),
+    /// nudge as a signed byte, then the velocity of each track (0-127)
+    #[wasm_bindgen]
+    pub fn get_drum_pattern(&self) -> Vec<u8> {
+        self.drums.sequencer.pattern_bytes()
+    }
+
+    /// Replace the drum pattern from the get_drum_pattern() format, taking
+    /// the pattern length from the input (1-32 steps). Anything else is
+    /// ignored.
+    #[wasm_bindgen]
+    pub fn set_drum_pattern(&mut self, bytes: &[u8]) {
+        let loaded = self.drums.sequencer.load_pattern_bytes(bytes);
+        self.last_error = (!loaded).then_some(ApiError::PatternLength);
+    }
+
+    /// Shift the drum pattern `n` steps later (negative = earlier),
+    /// wrapping round its end
+    #[wasm_bindgen]
+    pub fn rotate_drum_pattern(&mut self, n: i32) {
+        self.drums.sequencer.rotate(n);
+    }
+
+    #[wasm_bindgen]
+    pub fn reverse_drum_pattern(&mut self) {
+        self.drums.sequencer.reverse();
+    }
+
+    /// Overwrite the second half of the drum pattern with the first half
+    /// backwards
+    #[wasm_bindgen]
+    pub fn mirror_drum_pattern(&mut self) {
+        self.drums.sequencer.mirror();
+    }
+
+    #[wasm_bindgen]
+    pub fn invert_drum_accents(&mut self) {
+        self.drums.sequencer.invert_accents();
+    }
+
+    /// Double the drum pattern's length by playing it twice (16 steps
+    /// become 32). Patterns already over 16 steps are left alone.
+    #[wasm_bindgen]
+    pub fn double_drum_pattern(&mut self) {
+        let doubled = self.drums.sequencer.double();
+        self.last_error = (!doubled).then_some(ApiError::PatternLength);
+    }
+
+    /// Steps in the drum pattern (1-32)
+    #[wasm_bindgen]
+    pub fn get_drum_pattern_length(&self) -> usize {
+        self.drums.sequencer.length()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_kick_volume(&mut self, vol: f32) {
+        self.drums.set_kick_volume(vol);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_snare_volume(&mut self, vol: f32) {
+        self.drums.set_snare_volume(vol);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_hihat_volume(&mut self, vol: f32) {
+        self.drums.set_hihat_volume(vol);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_clap_volume(&mut self, vol: f32) {
+        self.drums.set_clap_volume(vol);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_kick_decay(&mut self, decay: f32) {
+        self.drums.set_kick_decay(decay);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_kick_pitch(&mut self, pitch: f32) {
+        self.drums.set_kick_pitch(pitch);
+    }
+
+    /// Noise click on the kick's attack, so it cuts through the mix
+    /// (0.0 = none, 1.0 = hard)
+    #[wasm_bindgen]
+    pub fn set_kick_click(&mut self, click: f32) {
+        self.drums.set_kick_click(click);
+    }
+
+    /// Drive the kick into its soft clipper for a fatter, punchier body
+    /// (0.0 = clean, 1.0 = crushed)
+    #[wasm_bindgen]
+    pub fn set_kick_drive(&mut self, drive: f32) {
+        self.drums.set_kick_drive(drive);
+    }
+
+    /// Snap the kick pitch to the root or fifth of the synth pattern's key
+    /// so they don't clash. Returns the kick frequency in Hz, or 0 if the
+    /// pattern has no active steps and the kick was left alone.
+    #[wasm_bindgen]
+    pub fn tune_kick_to_pattern(&mut self) -> f32 {
+        match self.synth.sequencer.root_note() {
+            Some(root) => self.drums.tune_kick(root),
+            None => 0.0,
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn set_snare_tone(&mut self, tone: f32) {
+        self.drums.set_snare_tone(tone);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_snare_snap(&mut self, snap: f32) {
+        self.drums.set_snare_snap(snap);
+    }
+
+    /// Length of the clap's tail (0.0 = dry, 1.0 = roomy)
+    #[wasm_bindgen]
+    pub fn set_clap_decay(&mut self, decay: f32) {
+        self.drums.set_clap_decay(decay);
+    }
+
+    /// Give the whole drum kit one machine's character: 0 = 808, 1 = 909
+    #[wasm_bindgen]
+    pub fn set_drum_kit(&mut self, model: u8) {
+        let result = KitModel::from_index(model).ok_or(ApiError::KitModel);
+        self.last_error = result.err();
+        if let Ok(model) = result {
+            self.drums.set_kit(model);
+        }
+    }
+
+    /// Give one drum voice (0 = kick, 1 = snare, 2 = closed hat,
+    /// 3 = open hat, 4 = clap) one machine's character: 0 = 808, 1 = 909
+    #[wasm_bindgen]
+    pub fn set_drum_voice_model(&mut self, track: u8, model: u8) {
+        let result = DrumTrack::from_index(track)
+            .ok_or(ApiError::DrumTrack)
+            .and_then(|track| Ok((track, KitModel::from_index(model).ok_or(ApiError::KitModel)?)));
+        self.last_error = result.err();
+        if let Ok((track, model)) = result {
+            self.drums.set_voice_model(track, model);
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn load_drum_pattern(&mut self, index: usize) {
+        let pattern = match index {
+            // Main patterns
+            0 => &drums::BASIC_BEAT,
+            1 => &drums::BREAKBEAT,
+            2 => &drums::HOUSE_909,
+            3 => &drums::MINIMAL,
+            4 => &drums::ACID_DRIVE,
+            // Arrangement patterns
+            5 => &drums::INTRO_KICK,
+            6 => &drums::INTRO_HATS,
+            7 => &drums::BUILD_SNARE,
+            8 => &drums::BUILD_ROLL,
+            9 => &drums::BREAKDOWN,
+            10 => &drums::BREAKDOWN_KICK,
+            11 => &drums::FILL_SNARE,
+            12 => &drums::FILL_STOMP,
+            13 => &drums::FILL_OPEN_HAT,
+            14 => &drums::DROP_FULL,
+            15 => &drums::OFFBEAT_HOUSE,
+            16 => &drums::SHUFFLE,
+            _ => &drums::BASIC_BEAT,
+        };
+        self.drums.sequencer.load_pattern(pattern);
+        self.last_error = (index >= Self::drum_pattern_count()).then_some(ApiError::PresetIndex);
+    }
+
+    /// Play a fill built from the current drum pattern in place of the
+    /// next bar: 0 = snare build, 1 = roll on the last beat, 2 = dropped kick
+    #[wasm_bindgen]
+    pub fn queue_drum_fill(&mut self, kind: u8) {
+        if let Some(kind) = FillKind::from_index(kind) {
+            self.drums.sequencer.queue_fill(kind);
+        }
+    }
+
+    /// Play a fill of `kind` (see queue_drum_fill) on the last bar of every
+    /// `every_bars` bars; 0 turns auto-fill off
+    #[wasm_bindgen]
+    pub fn set_drum_auto_fill(&mut self, every_bars: u32, kind: u8) {
+        if let Some(kind) = FillKind::from_index(kind) {
+            self.drums.sequencer.set_auto_fill(every_bars, kind);
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn drum_pattern_count() -> usize {
+        17
+    }
+
+    #[wasm_bindgen]
+    pub fn drum_pattern_name(index: usize) -> String {
+        match index {
+            // Main patterns
+            0 => "Basic 4/4".to_string(),
+            1 => "Breakbeat".to_string(),
+            2 => "House 909".to_string(),
+            3 => "Minimal Techno".to_string(),
+            4 => "Acid Drive".to_string(),
+            // Arrangement patterns
+            5 => "-- INTRO --".to_string(),
+            6 => "Intro + Hats".to_string(),
+            7 => "Build (Snare)".to_string(),
+            8 => "Build (Roll)".to_string(),
+            9 => "Breakdown".to_string(),
+            10 => "Breakdown + Kick".to_string(),
+            11 => "Fill: Snare".to_string(),
+            12 => "Fill: Stomp".to_string(),
+            13 => "Fill: Open Hat".to_string(),
+            14 => "DROP!".to_string(),
+            15 => "Offbeat House".to_string(),
+            16 => "Shuffle".to_string(),
+            _ => "Unknown".to_string(),
+        }
+    }
+
+    // ===== MIDI =====
+
+    /// Handle a raw MIDI message. Notes on channel 10 play the drum voices
+    /// using the General MIDI percussion map; other channels play the synth
+    /// as Synth::handle_midi() does. CCs on any channel move the synth knobs
+    /// assigned in the CC map.
+    #[wasm_bindgen]
+    pub fn handle_midi(&mut self, status: u8, data1: u8, data2: u8) {
+        if status >= 0xF0 {
+            self.handle_midi_system(status, data1, data2);
+            return;
+        }
+        if status & 0xF0 == midi::CONTROL_CHANGE {
+            if let Some((param, value)) = self.cc_map.handle(data1, data2) {
+                self.apply_param(param, value);
+                self.record_automation(param, value);
+            }
+            return;
+        }
+
+        let channel = status & 0x0F;
+        let is_note_on = status & 0xF0 == midi::NOTE_ON && data2 > 0;
+
+        if channel == midi::DRUM_CHANNEL {
+            if is_note_on {
+                if let Some(track) = midi::gm_drum(data1) {
+                    self.drums.trigger(track);
+                }
+            }
+        } else {
+            // Keys are held to the locked key like every other synth note
+            let key = self.key;
+            self.synth.handle_midi_pitched(status, data1, data2, |note| key.map_or(note, |key| key.snap_pitch(note)));
+        }
+    }
+
+    /// Follow incoming MIDI clock: Start, Stop, Continue and Song Position
+    /// Pointer control the transport and clock ticks set the tempo
+    #[wasm_bindgen]
+    pub fn set_midi_clock_sync(&mut self, enabled: bool) {
+        self.midi_clock_sync = enabled;
+        self.clock_ticks = 0;
+        self.clock_beat_start = None;
+    }
+
+    /// Song position in sixteenth steps, as set by Song Position Pointer
+    /// and advanced by clock ticks while playing
+    #[wasm_bindgen]
+    pub fn get_song_position(&self) -> u32 {
+        self.song_position
+    }
+
+    /// Assign the next CC received to a synth knob: 0 = cutoff,
+    /// 1 = resonance, 2 = env mod, 3 = decay, 4 = accent, 5 = distortion
+    #[wasm_bindgen]
+    pub fn start_midi_learn(&mut self, param: u8) {
+        if let Some(param) = AutomationParam::from_index(param) {
+            self.cc_map.start_learn(param);
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn cancel_midi_learn(&mut self) {
+        self.cc_map.cancel_learn();
+    }
+
+    #[wasm_bindgen]
+    pub fn is_midi_learning(&self) -> bool {
+        self.cc_map.is_learning()
+    }
+
+    /// CC assignments as [cc, knob] byte pairs, knobs numbered as in
+    /// start_midi_learn()
+    #[wasm_bindgen]
+    pub fn get_cc_map(&self) -> Vec<u8> {
+        self.cc_map.to_bytes()
+    }
+
+    /// Replace the CC assignments from get_cc_map() pairs
+    #[wasm_bindgen]
+    pub fn set_cc_map(&mut self, bytes: &[u8]) {
+        let loaded = self.cc_map.load_bytes(bytes);
+        self.last_error = (!loaded).then_some(ApiError::StateFormat);
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_cc_map(&mut self) {
+        self.cc_map.clear();
+    }
+
+    /// Enable mirroring synth sequencer notes into the outgoing MIDI queue
+    #[wasm_bindgen]
+    pub fn set_midi_out_enabled(&mut self, enabled: bool) {
+        self.synth.set_midi_out_enabled(enabled);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_midi_out_channel(&mut self, channel: u8) {
+        self.synth.set_midi_out_channel(channel);
+    }
+
+    /// Take queued MIDI events as [offset, status, data1, data2, ...]
+    #[wasm_bindgen]
+    pub fn drain_midi_out(&mut self) -> Vec<u32> {
+        self.synth.drain_midi_out()
+    }
+
+    /// Non-allocating drain_midi_out; returns the number of values written
+    #[wasm_bindgen]
+    pub fn drain_midi_out_into(&mut self, out: &mut [u32]) -> usize {
+        self.synth.drain_midi_out_into(out)
+    }
+
+    // ===== Host-driven mode =====
+
+    /// Bypass the internal sequencers and play only events from the host
+    #[wasm_bindgen]
+    pub fn set_host_mode(&mut self, enabled: bool) {
+        self.host_mode = enabled;
+        self.host_events.clear();
+        if enabled {
+            self.halt_sequencers();
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn is_host_mode(&self) -> bool {
+        self.host_mode
+    }
+
+    /// Queue events for the next process() call, packed as
+    /// [offset, kind, a, b, ...]. Kinds: 0 = synth note on (a = note,
+    /// b = flags, bit 0 accent, bit 1 slide), 1 = synth note off,
+    /// 2 = drum trigger (a = track: 0 = kick, 1 = snare, 2 = closed hat,
+    /// 3 = open hat, 4 = clap), 3 = parameter (a = automation lane index,
+    /// b = value). Unknown kinds are skipped.
+    #[wasm_bindgen]
+    pub fn queue_host_events(&mut self, events: &[f32]) {
+        for chunk in events.chunks_exact(4) {
+            if let Some(event) = HostEvent::decode(chunk[1], chunk[2], chunk[3]) {
+                let offset = internal_offset(&self.resampler, chunk[0].max(0.0) as u32);
+                self.host_events.push(offset, event);
+            }
+        }
+    }
+
+    // ===== Automation =====
+
+    /// While recording and playing, synth knob changes are stored against
+    /// the pattern position and replayed on every following loop
+    #[wasm_bindgen]
+    pub fn set_automation_record(&mut self, enabled: bool) {
+        self.automation.set_recording(enabled);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_automation_playback(&mut self, enabled: bool) {
+        self.automation.set_playback(enabled);
+    }
+
+    /// Clear one lane: 0 = cutoff, 1 = resonance, 2 = env mod, 3 = decay,
+    /// 4 = accent, 5 = distortion
+    #[wasm_bindgen]
+    pub fn clear_automation_lane(&mut self, param: u8) {
+        if let Some(param) = AutomationParam::from_index(param) {
+            self.automation.clear_lane(param);
+        }
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_automation(&mut self) {
+        self.automation.clear();
+    }
+
+    #[wasm_bindgen]
+    pub fn has_automation(&self, param: u8) -> bool {
+        AutomationParam::from_index(param).is_some_and(|p| self.automation.has_data(p))
+    }
+
+    // ===== Freeze =====
+
+    /// Bounce the synth pattern to audio and play that back in place of the
+    /// live voice, which then costs no CPU. Stops the transport.
+    #[wasm_bindgen]
+    pub fn freeze_synth(&mut self) {
+        let frozen = self.render_synth_loop();
+        self.synth_sampler.load(frozen);
+        self.synth_frozen = true;
+    }
+
+    /// Return to the live synth voice and free the frozen audio
+    #[wasm_bindgen]
+    pub fn unfreeze_synth(&mut self) {
+        self.synth_frozen = false;
+        self.synth_sampler.clear();
+    }
+
+    #[wasm_bindgen]
+    pub fn is_synth_frozen(&self) -> bool {
+        self.synth_frozen
+    }
+
+    // ===== Offline rendering =====
+
+    /// Number of samples in one bar at the current tempo
+    #[wasm_bindgen]
+    pub fn samples_per_bar(&self) -> usize {
+        (self.clock.samples_per_step() * self.synth.sequencer.steps_per_bar() as f64).round() as usize
+    }
+
+    /// Set export normalization: 0 = off, 1 = peak (target in dBFS),
+    /// 2 = integrated loudness (target in LUFS, peak held below -1 dBFS)
+    #[wasm_bindgen]
+    pub fn set_export_normalize(&mut self, mode: u8, target_db: f32) {
+        self.export_normalize = match mode {
+            1 => Normalize::Peak(target_db.min(0.0)),
+            2 => Normalize::Lufs(target_db.min(0.0)),
+            _ => Normalize::Off,
+        };
+    }
+
+    /// Render `bars` bars of the current patterns offline, from step 0
+    #[wasm_bindgen]
+    pub fn render(&mut self, bars: u32) -> Vec<f32> {
+        let mut out = self.render_pass(bars);
+        loudness::normalize(&mut out, self.sample_rate, self.export_normalize);
+        out
+    }
+
+    /// Render `bars` bars as a seamless loop: one extra bar is rendered with
+    /// the sequencers stopped and that tail is folded back onto the loop start,
+    /// so decays and effects ringing past the end carry over when repeated
+    #[wasm_bindgen]
+    pub fn render_loop(&mut self, bars: u32) -> Vec<f32> {
+        let mut out = self.render_pass(bars);
+        let tail = self.render_samples(self.samples_per_bar().min(out.len()));
+        for (sample, t) in out.iter_mut().zip(tail.iter()) {
+            *sample += t;
+        }
+        loudness::normalize(&mut out, self.sample_rate, self.export_normalize);
+        out
+    }
+
+    /// Render whatever is still ringing after a render for `bars` more bars,
+    /// without the sequencers running (for exporting the tail separately).
+    /// The tail is not normalized, since its level only makes sense relative
+    /// to the render it follows.
+    #[wasm_bindgen]
+    pub fn render_tail(&mut self, bars: u32) -> Vec<f32> {
+        self.halt_sequencers();
+        self.render_samples(self.samples_per_bar() * bars as usize)
+    }
+
+    /// Render `bars` bars as a 16-bit WAV file, with optional TPDF dither
+    #[wasm_bindgen]
+    pub fn render_wav(&mut self, bars: u32, dither: bool) -> Vec<u8> {
+        let samples = self.render(bars);
+        encode_wav(&samples, self.sample_rate as u32, WavFormat::Pcm16, dither)
+    }
+
+    /// Render `bars` bars in stereo as interleaved [left, right, ...] frames.
+    /// Both channels are normalized together so the image is kept.
+    #[wasm_bindgen]
+    pub fn render_stereo(&mut self, bars: u32) -> Vec<f32> {
+        let (mut left, mut right) = self.render_pass_stereo(bars);
+        loudness::normalize_stereo(&mut left, &mut right, self.sample_rate, self.export_normalize);
+        left.iter().zip(&right).flat_map(|(&l, &r)| [l, r]).collect()
+    }
+
+    /// Render `bars` bars as a 16-bit stereo WAV file, with optional TPDF dither
+    #[wasm_bindgen]
+    pub fn render_wav_stereo(&mut self, bars: u32, dither: bool) -> Vec<u8> {
+        let samples = self.render_stereo(bars);
+        encode_wav_interleaved(&samples, 2, self.sample_rate as u32, WavFormat::Pcm16, dither)
+    }
+
+    // ===== Presets =====
+
+    #[wasm_bindgen]
+    pub fn synth_preset_count() -> usize {
+        Synth::preset_count()
+    }
+
+    #[wasm_bindgen]
+    pub fn synth_preset_name(index: usize) -> String {
+        Synth::preset_name(index)
+    }
+
+    /// Save tempo, synth knobs, mixer levels, both patterns and the CC map
+    /// as a versioned blob for sessions and share codes
+    #[wasm_bindgen]
+    pub fn export_state(&self) -> Vec<u8> {
+        Session {
+            tempo: Some(self.tempo),
+            synth: self.synth.params(),
+            mixer: vec![self.synth_vol, self.drum_vol, self.master_vol],
+            synth_pattern: Some(self.synth.get_pattern()),
+            drum_pattern: Some(self.drums.sequencer.pattern_bytes()),
+            cc_map: Some(self.cc_map.to_bytes()),
+        }
+        .encode()
+    }
+
+    /// Restore a blob from export_state(), including ones saved by older
+    /// versions. Nothing changes if the blob can't be read.
+    #[wasm_bindgen]
+    pub fn import_state(&mut self, bytes: &[u8]) {
+        let result = self.try_import_state(bytes);
+        self.last_error = result.err();
+    }
+
+    /// Error code from the last pattern, preset, step or state call, 0 if
+    /// it succeeded; codes match Synth::last_error()
+    #[wasm_bindgen]
+    pub fn last_error(&self) -> u8 {
+        self.last_error.map_or(0, ApiError::code)
+    }
+
+    /// Description of last_error(), empty if the call succeeded
+    #[wasm_bindgen]
+    pub fn last_error_message(&self) -> String {
+        self.last_error.map(|e| e.message().to_string()).unwrap_or_default()
+    }
+}
+
+impl Studio {
+    fn try_import_state(&mut self, bytes: &[u8]) -> Result<(), ApiError> {
+        let session = Session::decode(bytes)?;
+
+        // Check both patterns before touching anything
+        let synth_ok = session.synth_pattern.as_ref().is_none_or(|p| sequencer::parse_pattern(p).is_some());
+        let drums_ok = session.drum_pattern.as_ref().is_none_or(|p| drums::sequencer::parse_pattern(p).is_some());
+        if !synth_ok || !drums_ok {
+            return Err(ApiError::StateFormat);
+        }
+        let mut cc_map = CcMap::new();
+        if let Some(bytes) = &session.cc_map {
+            if !cc_map.load_bytes(bytes) {
+                return Err(ApiError::StateFormat);
+            }
+            self.cc_map = cc_map;
+        }
+
+        if let Some(tempo) = session.tempo {
+            self.set_tempo(tempo);
+        }
+        self.synth.set_params(&session.synth);
+        let mixer: [fn(&mut Studio, f32); 3] = [
+            Studio::set_synth_volume,
+            Studio::set_drum_volume,
+            Studio::set_master_volume,
+        ];
+        for (set, &value) in mixer.iter().zip(&session.mixer) {
+            set(self, value);
+        }
+        if let Some(pattern) = &session.synth_pattern {
+            self.synth.sequencer.load_pattern_bytes(pattern);
+        }
+        if let Some(pattern) = &session.drum_pattern {
+            self.drums.sequencer.load_pattern_bytes(pattern);
+        }
+        Ok(())
+    }
+
+    fn stored_drum_pattern(&self) -> StoredPattern<DrumStep> {
+        StoredPattern {
+            steps: self.drums.sequencer.steps().to_vec(),
+            variation: self.drums.sequencer.variation(),
+        }
+    }
+
+    fn check_drum_step(&self, index: usize) -> Result<(), ApiError> {
+        self.drums.sequencer.get_step(index).map(|_| ()).ok_or(ApiError::StepIndex)
+    }
+
+    fn try_load_from_slot(&mut self, slot: usize) -> Result<(), ApiError> {
+        let drums = self.drum_slots.get(slot)?;
+        let synth = self.synth_slots.get(slot)?;
+        self.synth.sequencer.load_pattern(&synth.steps);
+        self.synth.sequencer.load_variation(synth.variation.as_ref());
+        self.drums.sequencer.load_pattern(&drums.steps);
+        self.drums.sequencer.load_variation(drums.variation.as_ref());
+        Ok(())
+    }
+
+    fn try_recall_synth_slot(&mut self, slot: usize, pattern_only: bool) -> Result<(), ApiError> {
+        let slot = self.synth_slots.get(slot)?.clone();
+        self.synth.sequencer.load_pattern(&slot.steps);
+        self.synth.sequencer.load_variation(slot.variation.as_ref());
+        if let Some(sound) = slot.sound.filter(|_| !pattern_only) {
+            self.set_tempo(sound.tempo);
+            self.synth.set_params(&sound.params);
+        }
+        Ok(())
+    }
+
+    /// Load the patterns for the next bar of the song
+    fn end_song_bar(&mut self) {
+        let end = self.song.end_bar();
+        self.load_song_bar(end);
+    }
+
+    /// Load the patterns of a chain entry the song has moved on to
+    fn load_song_bar(&mut self, end: BarEnd) {
+        match end {
+            BarEnd::Same | BarEnd::Finished => {}
+            BarEnd::Next(entry) => {
+                if let Some(pattern) = self.song.synth_pattern(entry.synth) {
+                    self.synth.sequencer.load_pattern(&pattern.steps);
+                    self.synth.sequencer.load_variation(pattern.variation.as_ref());
+                }
+                if let Some(pattern) = self.song.drum_pattern(entry.drums) {
+                    self.drums.sequencer.load_pattern(&pattern.steps);
+                    self.drums.sequencer.load_variation(pattern.variation.as_ref());
+                }
+            }
+        }
+    }
+
+    /// Share accents between the synth and drum steps starting together
+    fn link_accents(&self, synth_event: &mut Option<SeqEvent>, drum_step: &mut Option<DrumStep>) {
+        let (Some(SeqEvent::NoteOn(note) | SeqEvent::Tie(note)), Some(drums)) = (synth_event, drum_step) else {
+            return;
+        };
+        let synth_accent = note.accent;
+        if self.drum_accents_to_synth && drums.accent {
+            note.accent = true;
+        }
+        if self.synth_accents_to_drums && synth_accent {
+            drums.accent = true;
+        }
+    }
+
+    fn update_delay_time(&mut self) {
+        let samples = self.synth.sequencer.samples_per_step() as f32 * self.delay_steps;
+        self.delay.set_time(samples as usize);
+    }
+
+    fn mix_track(track: u8) -> Result<usize, ApiError> {
+        let track = track as usize;
+        if track < MIX_TRACKS {
+            Ok(track)
+        } else {
+            Err(ApiError::MixTrack)
+        }
+    }
+
+    /// Apply mutes and solos to the synth bus and drum voices
+    fn update_track_mix(&mut self) {
+        let any_solo = self.track_soloed.contains(&true);
+        let audible = |track: usize| if any_solo { self.track_soloed[track] } else { !self.track_muted[track] };
+        self.synth_mix.set_target(if audible(SYNTH_TRACK) { 1.0 } else { 0.0 });
+        for track in DrumTrack::ALL {
+            self.drums.set_muted(track, !audible(track as usize));
+        }
+    }
+
+    fn check_drum_track_step(&self, index: usize, track: u8) -> Result<DrumTrack, ApiError> {
+        self.check_drum_step(index)?;
+        DrumTrack::from_index(track).ok_or(ApiError::DrumTrack)
+    }
+
+    /// Fill a mono block at the host rate, without resetting the meters
+    fn process_mono(&mut self, output: &mut [f32]) {
+        if let Some(mut resampler) = self.resampler.take() {
+            resampler.process(output, |block| self.render_block(block));
+            self.resampler = Some(resampler);
+        } else {
+            self.render_block(output);
+        }
+    }
+
+    /// Steps the bar length holds at the current step length
+    fn steps_in_bar(&self) -> u32 {
+        (self.samples_per_bar() as f64 / self.clock.samples_per_step()).round().max(1.0) as u32
+    }
+
+    /// Steps in a quarter-note beat at the current step length
+    fn steps_in_beat(&self) -> u32 {
+        let beat = 60.0 * self.sample_rate as f64 / self.clock.tempo() as f64;
+        (beat / self.clock.samples_per_step()).round().max(1.0) as u32
+    }
+
+    /// Reset the per-block step flags and meters
+    fn begin_block(&mut self) {
+        self.synth_step_changed = false;
+        self.drum_step_changed = false;
+        self.clip_count = 0;
+        self.block_peak = 0.0;
+    }
+
+    /// Render a block at the internal sample rate
+    fn render_block(&mut self, output: &mut [f32]) {
+        self.render_block_with(output, |_, _| {});
+    }
+
+    /// Render a mono block, handing the synth gate and pitch CV after each
+    /// sample to `cv`
+    fn render_block_with(&mut self, output: &mut [f32], cv: impl FnMut(usize, (f32, f32))) {
+        self.render_frames(output.len(), |offset, (left, right), _| output[offset] = (left + right) * 0.5, cv);
+    }
+
+    /// Render a stereo block at the internal sample rate
+    fn render_stereo_block(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let len = left.len().min(right.len());
+        self.render_frames(
+            len,
+            |offset, (l, r), _| {
+                left[offset] = l;
+                right[offset] = r;
+            },
+            |_, _| {},
+        );
+    }
+
+    /// Render MULTI_OUTPUTS planar channels at the internal sample rate
+    fn render_multi_block(&mut self, output: &mut [f32]) {
+        let len = output.len() / MULTI_OUTPUTS;
+        self.render_frames(
+            len,
+            |offset, _, stems| {
+                for (channel, &sample) in stems.iter().enumerate() {
+                    output[channel * len + offset] = sample;
+                }
+            },
+            |_, _| {},
+        );
+    }
+
+    /// Render `len` stereo frames, handing each to `write` along with the
+    /// parts that went into it, and the synth gate and pitch CV after it to
+    /// `cv`
+    fn render_frames(
+        &mut self,
+        len: usize,
+        mut write: impl FnMut(usize, (f32, f32), &[f32; MULTI_OUTPUTS]),
+        mut cv: impl FnMut(usize, (f32, f32)),
+    ) {
+        for offset in 0..len {
+            let fade = self.fade.process();
+            if self.fade.take_finished() {
+                self.reset_voices();
+            }
+
+            self.synth.run_scheduled(offset as u32);
+
+            if self.host_mode {
+                while let Some(event) = self.host_events.pop_due(offset as u32) {
+                    self.apply_host_event(event);
+                }
+            }
+
+            self.rendered_samples += 1;
+            if self.playing {
+                self.elapsed_samples += 1;
+            }
+
+            // Tick sequencers if playing
+            if self.playing && !self.host_mode {
+                self.clock.tick();
+                let mut synth_event = self.synth.sequencer.tick_with(&self.clock);
+                let mut drum_step = self.drums.sequencer.tick_with(&self.clock);
+                self.link_accents(&mut synth_event, &mut drum_step);
+                let step_started = synth_event.is_some_and(|e| e.starts_step());
+                let bar_ending = step_started && self.synth.sequencer.pass_position().is_multiple_of(STEPS);
+                if step_started && self.song_mode && self.song.is_finished() {
+                    // The song's last bar has played out
+                    self.stop();
+                    synth_event = None;
+                    drum_step = None;
+                }
+
+                // Synth sequencer
+                if let Some(event) = synth_event {
+                    if event.starts_step() {
+                        self.steps_elapsed += 1;
+                        self.run_stutter_queue();
+                        let new_step = self.synth.sequencer.current_step() as i32;
+                        if new_step != self.last_synth_step {
+                            self.last_synth_step = new_step;
+                            self.synth_step_changed = true;
+                        }
+                        // Restart the frozen loop as the pattern's first step
+                        // starts (1 % 1 = 0 for a one-step pattern)
+                        if self.synth_frozen && self.synth.sequencer.pass_position() == 1 % self.synth.sequencer.length() {
+                            self.synth_sampler.trigger();
+                        }
+                    }
+                    if !self.synth_frozen {
+                        self.synth.play_event(&event, offset as u32);
+                    }
+                }
+
+                // Drum sequencer
+                if let Some(step) = drum_step {
+                    let new_step = self.drums.sequencer.current_step() as i32;
+                    if new_step != self.last_drum_step {
+                        self.last_drum_step = new_step;
+                        self.drum_step_changed = true;
+                    }
+                    // Trigger drum sounds
+                    self.drums.trigger_step(&step);
+                }
+
+                self.play_automation();
+
+                // Switch patterns as the last step starts, before the next
+                // bar's first step is read
+                if bar_ending && self.song_mode {
+                    self.end_song_bar();
+                }
+            }
+
+            if let Some(sweep) = self.sweep.as_mut() {
+                match sweep.next_value() {
+                    Some(cutoff) => self.synth.set_cutoff(cutoff),
+                    None => {
+                        self.sweep = None;
+                        self.synth.set_cutoff(self.sweep_knob);
+                    }
+                }
+            }
+
+            // Process drums first so the follower can modulate the synth
+            let drum_sample = self.drums.process();
+            let follow = self.follower.process(if self.follower_kick_only {
+                self.drums.kick_output()
+            } else {
+                drum_sample
+            });
+
+            let synth_sample = if self.synth_frozen {
+                self.synth_sampler.process()
+            } else {
+                self.synth_voice(follow)
+            };
+
+            // Synth channel inserts
+            let synth_sample = self.synth_highpass.process(synth_sample);
+            let synth_sample = self.synth_gate.process(synth_sample);
+            let synth_sample = self.synth_ring.process(synth_sample, drum_sample);
+            let synth_sample = self.synth_comp.process(synth_sample);
+            let synth_sample = if self.multiband_on_master {
+                synth_sample
+            } else {
+                self.multiband[0].process(synth_sample)
+            };
+            let synth_mix = self.synth_mix.process();
+            let synth_sample = synth_sample * synth_mix;
+
+            // Drum bus inserts
+            let (drum_left, drum_right) = self.drums.stereo_output();
+            let (drum_left, drum_right) = self.drum_shaper.process_stereo(drum_left, drum_right);
+
+            // Each part on its own for multi-out, post-fader like the sends
+            self.stems[0] = synth_sample * self.synth_vol * fade;
+            for (stem, track) in self.stems[1..].iter_mut().zip(DrumTrack::ALL) {
+                *stem = self.drums.voice_output(track) * self.drum_vol * fade;
+            }
+
+            // Sends are post-fader
+            let delay_send = synth_sample * self.synth_delay_send * self.synth_vol
+                + self.drums.delay_send() * self.drum_vol;
+            let echoes = self.delay.process(delay_send) * self.delay_return;
+            let reverb_send = synth_sample * self.synth_reverb_send * self.synth_vol
+                + self.drums.reverb_send() * self.drum_vol;
+            let (room_left, room_right) = self.reverb.process(reverb_send);
+
+            // Mix, with the echoes and vinyl noise in the centre, the room
+            // spread wide and the unison spread around the synth's position
+            let (synth_left, synth_right) = pan(synth_sample * self.synth_vol, self.synth_pan);
+            let synth_side = if self.synth_frozen { 0.0 } else { self.synth.side * self.synth_vol * synth_mix };
+            let mut frame = [
+                synth_left - synth_side + (drum_left * self.drum_vol) + echoes + room_left * self.reverb_return,
+                synth_right + synth_side + (drum_right * self.drum_vol) + echoes + room_right * self.reverb_return,
+            ];
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let mixed = if self.multiband_on_master {
+                    self.multiband[channel].process(*sample)
+                } else {
+                    *sample
+                };
+                let mixed = self.dc_blocker[channel].process(mixed);
+                let mixed = self.master_highpass[channel].process(mixed);
+                let mixed = self.stutter[channel].process(mixed);
+                let mixed = self.tape_stop[channel].process(mixed);
+                *sample = self.wow_flutter[channel].process(mixed);
+            }
+            let hiss = self.vinyl.process();
+            let (left, right) = self.widener.process(frame[0] + hiss, frame[1] + hiss);
+            let left = left * self.headroom_gain * self.master_vol * fade;
+            let right = right * self.headroom_gain * self.master_vol * fade;
+
+            // Meter the final output so UIs can warn about overloads
+            let level = left.abs().max(right.abs());
+            self.block_peak = self.block_peak.max(level);
+            if level > 1.0 {
+                self.clip_count += 1;
+            }
+            write(offset, (left, right), &self.stems);
+            cv(offset, self.synth.cv());
+        }
+        self.synth.scheduled.end_block(len as u32);
+        self.host_events.end_block(len as u32);
+    }
+
+    /// Run the synth voice for one sample, modulated by the drum follower
+    fn synth_voice(&mut self, follow: f32) -> f32 {
+        let cutoff_octaves = follow * self.follower_to_cutoff * FOLLOWER_CUTOFF_OCTAVES;
+        self.synth.render_sample(cutoff_octaves, follow * self.follower_to_drive)
+    }
+
+    fn start_sequencers(&mut self) {
+        self.start_sequencers_at(0);
+    }
+
+    /// Start both sequencers with `step` as the first step played
+    fn start_sequencers_at(&mut self, step: u32) {
+        self.playing = true;
+        self.steps_elapsed = step as u64;
+        self.elapsed_samples = 0;
+        self.last_automation_point = None;
+        if self.song_mode {
+            // Song bars are counted in 16ths, whatever the pattern lengths
+            let end = self.song.seek(step / STEPS as u32);
+            self.load_song_bar(end);
+        }
+        self.clock.start_at(step as usize);
+        self.synth.sequencer.start_at(step as usize);
+        self.drums.start_at(step as usize);
+    }
+
+    /// MIDI clock and transport messages, only followed when synced
+    fn handle_midi_system(&mut self, status: u8, data1: u8, data2: u8) {
+        if !self.midi_clock_sync {
+            return;
+        }
+        match status {
+            midi::TIMING_CLOCK => {
+                self.clock_ticks += 1;
+                if self.playing && self.clock_ticks.is_multiple_of(midi::CLOCKS_PER_STEP) {
+                    self.song_position += 1;
+                }
+                if self.clock_ticks.is_multiple_of(midi::CLOCKS_PER_BEAT) {
+                    if let Some(start) = self.clock_beat_start {
+                        let samples = (self.rendered_samples - start).max(1) as f32;
+                        self.set_tempo(60.0 * self.sample_rate / samples);
+                    }
+                    self.clock_beat_start = Some(self.rendered_samples);
+                }
+            }
+            midi::START => {
+                self.song_position = 0;
+                self.clock_ticks = 0;
+                self.reset_voices();
+                self.fade.fade_in();
+                self.start_sequencers();
+            }
+            midi::CONTINUE => {
+                self.clock_ticks = 0;
+                self.reset_voices();
+                self.fade.fade_in();
+                self.start_sequencers_at(self.song_position);
+            }
+            midi::STOP => {
+                self.halt_sequencers();
+                self.fade.fade_out();
+            }
+            midi::SONG_POSITION => {
+                // Counted in MIDI beats, which are sixteenth notes
+                self.song_position = (data1 as u32 & 0x7F) | (data2 as u32 & 0x7F) << 7;
+                if self.playing {
+                    self.start_sequencers_at(self.song_position);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Stop the sequencers and release the synth, leaving tails ringing
+    fn halt_sequencers(&mut self) {
+        // Nothing is left to quantize to
+        if let Some(engaged) = self.stutter_pending.take() {
+            self.set_stutter(engaged);
+        }
+        self.playing = false;
+        self.clock.stop();
+        self.synth.sequencer.stop();
+        self.synth.note_off();
+        self.synth.scheduled.clear();
+        self.synth.midi_out.release(0);
+        self.drums.stop();
+    }
+
+    fn queue_stutter(&mut self, engaged: bool) {
+        if self.playing && !self.host_mode {
+            self.stutter_pending = Some(engaged);
+        } else {
+            self.stutter_pending = None;
+            self.set_stutter(engaged);
+        }
+    }
+
+    /// Apply a queued stutter change if the step that just started begins
+    /// a division
+    fn run_stutter_queue(&mut self) {
+        let steps = (16 / self.stutter_division) as u64;
+        if (self.steps_elapsed - 1).is_multiple_of(steps) {
+            if let Some(engaged) = self.stutter_pending.take() {
+                self.set_stutter(engaged);
+            }
+        }
+    }
+
+    /// Store a knob movement at the current time into the pattern
+    fn record_automation(&mut self, param: AutomationParam, value: f32) {
+        if self.playing && self.automation.is_recording() {
+            let sequencer = &self.synth.sequencer;
+            self.automation.record(param, sequencer.pass_time(), sequencer.length(), value);
+        }
+    }
+
+    /// Apply recorded values when the pattern reaches a new automation point
+    fn play_automation(&mut self) {
+        if !self.automation.is_playback() {
+            return;
+        }
+        let point = automation::point_at(self.synth.sequencer.pass_time(), self.synth.sequencer.length());
+        if self.last_automation_point == Some(point) {
+            return;
+        }
+        self.last_automation_point = Some(point);
+
+        for param in AutomationParam::ALL {
+            if let Some(value) = self.automation.value_at(param, point) {
+                self.apply_param(param, value);
+            }
+        }
+    }
+
+    /// Set a synth parameter without recording it as automation
+    fn apply_param(&mut self, param: AutomationParam, value: f32) {
+        self.synth.apply_param(param, value);
+        if param == AutomationParam::Cutoff && self.sweep.is_some() {
+            // Takes effect when the sweep releases
+            self.sweep_knob = self.synth.cutoff;
+        }
+    }
+
+    /// Snap a played note into the locked key
+    fn lock_note(&self, note: f32) -> f32 {
+        self.key.map_or(note, |key| key.snap_pitch(note))
+    }
+
+    fn apply_host_event(&mut self, event: HostEvent) {
+        match event {
+            HostEvent::Note(NoteEvent::On { note, accent, slide }) => {
+                self.synth.note_on(self.lock_note(note), accent, slide)
+            }
+            HostEvent::Note(NoteEvent::Off) => self.synth.note_off(),
+            HostEvent::Drum(track) => self.drums.trigger(track),
+            HostEvent::Param(param, value) => self.apply_param(param, value),
+        }
+    }
+
+    fn reset_voices(&mut self) {
+        self.synth.reset_voice();
+        self.synth_sampler.stop();
+        self.drums.reset();
+        self.synth_highpass.reset();
+        self.synth_gate.reset();
+        self.synth_ring.reset();
+        self.synth_comp.reset();
+        self.drum_shaper.reset();
+        self.follower.reset();
+        self.multiband.iter_mut().for_each(|m| m.reset());
+        self.stutter.iter_mut().for_each(|s| s.reset());
+        self.tape_stop.iter_mut().for_each(|t| t.reset());
+        self.wow_flutter.iter_mut().for_each(|w| w.reset());
+        self.widener.reset();
+        self.delay.reset();
+        self.reverb.reset();
+    }
+
+    /// Render one loop of the synth pattern, starting at the sample where
+    /// step 0 fires. A loop is rendered first so tails from the end of the
+    /// pattern ring into the start, as they do when looping live. Steps are
+    /// timed by the shared clock, as they are when playing.
+    fn render_synth_loop(&mut self) -> Vec<f32> {
+        let samples_per_step = self.clock.samples_per_step();
+        let len = (samples_per_step * self.synth.sequencer.length() as f64).round() as usize;
+        let skip = len + samples_per_step.ceil() as usize - 1;
+        let mut out = Vec::with_capacity(len);
+
+        self.halt_sequencers();
+        self.reset_voices();
+        self.clock.start_at(0);
+        self.synth.sequencer.start();
+        for i in 0..skip + len {
+            self.clock.tick();
+            if let Some(event) = self.synth.sequencer.tick_with(&self.clock) {
+                self.synth.apply_event(&event);
+            }
+            let sample = self.synth_voice(0.0);
+            if i >= skip {
+                out.push(sample);
+            }
+        }
+        self.halt_sequencers();
+        self.reset_voices();
+        out
+    }
+
+    /// Render `bars` bars from step 0 without any post-processing
+    fn render_pass(&mut self, bars: u32) -> Vec<f32> {
+        let len = self.begin_render_pass(bars);
+        let out = self.render_samples(len);
+        self.halt_sequencers();
+        out
+    }
+
+    /// Stereo render_pass(), returning the left and right channels
+    fn render_pass_stereo(&mut self, bars: u32) -> (Vec<f32>, Vec<f32>) {
+        let len = self.begin_render_pass(bars);
+        let mut left = vec![0.0; len];
+        let mut right = vec![0.0; len];
+        for (l, r) in left.chunks_mut(BLOCK_SIZE).zip(right.chunks_mut(BLOCK_SIZE)) {
+            self.render_stereo_block(l, r);
+        }
+        self.halt_sequencers();
+        (left, right)
+    }
+
+    /// Restart from step 0 with silent voices and the render seed, returning
+    /// the length of `bars` bars
+    fn begin_render_pass(&mut self, bars: u32) -> usize {
+        self.halt_sequencers();
+        self.reset_voices();
+        self.synth.sequencer.set_seed(self.render_seed);
+        self.drums.sequencer.set_seed(self.render_seed);
+        self.start_sequencers();
+        self.samples_per_bar() * bars as usize
+    }
+
+    /// Run the engine for `len` samples in fixed-size blocks
+    fn render_samples(&mut self, len: usize) -> Vec<f32> {
+        let mut out = vec![0.0; len];
+        for block in out.chunks_mut(BLOCK_SIZE) {
+            self.render_block(block);
+        }
+        out
+    }
+}
+
+impl Default for Studio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midi_to_freq() {
+        assert!((midi_to_freq(69.0) - 440.0).abs() < 0.01);
+        assert!((midi_to_freq(57.0) - 220.0).abs() < 0.01);
+        assert!((midi_to_freq(60.0) - 261.63).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_synth_creation() {
+        let synth = Synth::new();
+        assert!(!synth.is_playing());
+    }
+
+    #[test]
+    fn test_synth_process() {
+        let mut synth = Synth::new();
+        let mut buffer = [0.0f32; 128];
+        synth.note_on(48.0, false, false);
+        synth.process(&mut buffer);
+        assert!(buffer.iter().any(|&s| s.abs() > 0.001));
+    }
+
+    #[test]
+    fn test_retrigger_is_click_free() {
+        let mut synth = Synth::new();
+        synth.set_distortion(0.0);
+        synth.set_cutoff(20000.0);
+        synth.set_env_mod(0.0);
+        let mut buffer = [0.0f32; 4096];
+        synth.note_on(36.0, false, false);
+        synth.process(&mut buffer);
+
+        // An accented retrigger mid-decay jumps the envelope to its peak
+        let before = synth.vca_gain;
+        synth.note_on(36.0, true, false);
+        synth.process(&mut buffer[..1]);
+        assert!(synth.vca_gain - before < 0.1);
+    }
+
+    #[test]
+    fn test_accent_curve_hardware_boosts_level() {
+        let mut synth = Synth::new();
+        synth.set_accent_curve(3);
+        synth.note_on(36.0, true, false);
+        assert!(synth.accent_gain > 1.0);
+        synth.note_on(36.0, false, false);
+        assert_eq!(synth.accent_gain, 1.0);
+
+        // Unknown curve indices are ignored
+        synth.set_accent_curve(42);
+        assert_eq!(synth.accent_curve, AccentCurve::Hardware);
+    }
+
+    #[test]
+    fn test_accent_sweep_builds_on_runs_of_accents() {
+        let render = |decay: f32| {
+            let mut synth = Synth::new();
+            for i in 0..STEPS {
+                synth.set_step(i, 36, true, false, true);
+            }
+            synth.set_accent_decay(decay);
+            synth.start();
+            let mut sample = [0.0f32; 1];
+            (0..44100)
+                .map(|_| {
+                    synth.tick();
+                    synth.process(&mut sample);
+                    sample[0]
+                })
+                .collect::<Vec<f32>>()
+        };
+        // A slow drain lets the sweep stack up and changes the sound
+        assert_ne!(render(10.0), render(5000.0));
+
+        let mut synth = Synth::new();
+        synth.note_on(36.0, true, false);
+        assert_eq!(synth.sweep_amount, 1.0);
+        synth.note_on(36.0, false, false);
+        assert_eq!(synth.sweep_amount, 0.0);
+    }
+
+    #[test]
+    fn test_ab_slots_keep_both_tweaks() {
+        let mut synth = Synth::new();
+        synth.set_cutoff(500.0);
+        assert_eq!(synth.toggle_ab(), 1);
+        // B starts as a copy of A
+        assert_eq!(synth.cutoff, 500.0);
+        synth.set_cutoff(2000.0);
+
+        assert_eq!(synth.toggle_ab(), 0);
+        assert_eq!(synth.cutoff, 500.0);
+        assert_eq!(synth.toggle_ab(), 1);
+        assert_eq!(synth.cutoff, 2000.0);
+
+        // Copying while on B replaces the knobs with A
+        synth.copy_a_to_b();
+        assert_eq!(synth.cutoff, 500.0);
+        synth.toggle_ab();
+        assert_eq!(synth.cutoff, 500.0);
+    }
+
+    #[test]
+    fn test_filter_type_changes_sound_and_is_a_knob() {
+        let render = |model: u8| {
+            let mut synth = Synth::new();
+            synth.set_filter_type(model);
+            synth.note_on(36.0, false, false);
+            let mut buffer = vec![0.0f32; 4410];
+            synth.process(&mut buffer);
+            buffer
+        };
+        assert_ne!(render(0), render(2));
+        assert_ne!(render(2), render(5));
+
+        // An unknown model leaves the filter alone
+        let mut synth = Synth::new();
+        synth.set_filter_type(9);
+        assert_eq!(synth.filter.model(), FilterModel::ThreePole);
+
+        synth.set_filter_type(1);
+        synth.toggle_ab();
+        synth.set_filter_type(3);
+        synth.toggle_ab();
+        assert_eq!(synth.filter.model(), FilterModel::Diode);
+    }
+
+    #[test]
+    fn test_filter_key_track_opens_for_high_notes() {
+        let energy = |track: f32| {
+            let mut synth = Synth::new();
+            synth.set_cutoff(300.0);
+            synth.set_env_mod(0.0);
+            synth.set_resonance(0.0);
+            synth.set_filter_key_track(track);
+            synth.note_on(84.0, false, false);
+            let mut buffer = vec![0.0f32; 4410];
+            synth.process(&mut buffer);
+            buffer.iter().map(|s| s * s).sum::<f32>()
+        };
+        assert!(energy(1.0) > energy(0.0) * 2.0);
+    }
+
+    #[test]
+    fn test_output_has_no_dc() {
+        let mut synth = Synth::new();
+        synth.set_waveform(false);
+        synth.set_distortion(1.0);
+        let mut buffer = [0.0f32; 44100];
+        synth.note_on(36.0, false, false);
+        synth.process(&mut buffer);
+        let mean: f32 = buffer[22050..].iter().sum::<f32>() / 22050.0;
+        assert!(mean.abs() < 0.01);
+    }
+
+    #[test]
+    fn test_invalid_calls_report_errors() {
+        let mut studio = Studio::new();
+        studio.set_synth_step(16, 36, false, false, true);
+        assert_eq!(studio.last_error(), 1);
+        assert!(!studio.last_error_message().is_empty());
+
+        studio.set_synth_step(0, 36, false, false, true);
+        assert_eq!(studio.last_error(), 0);
+
+        studio.load_synth_preset(usize::MAX);
+        assert_eq!(studio.last_error(), 2);
+        studio.set_drum_track_step(0, 7, true);
+        assert_eq!(studio.last_error(), 3);
+        studio.set_drum_pattern(&[0; 3]);
+        assert_eq!(studio.last_error(), 4);
+
+        let mut synth = Synth::new();
+        synth.set_step_cents(99, 10);
+        assert_eq!(synth.last_error(), 1);
+        assert_eq!(synth.last_error_message(), "step index out of range");
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(1);
+        studio.load_drum_pattern(2);
+        studio.set_tempo(133.0);
+        studio.set_synth_cutoff(640.0);
+        studio.set_synth_decay(420.0);
+        studio.set_synth_waveform(false);
+        studio.set_drum_volume(0.5);
+        let blob = studio.export_state();
+
+        let mut restored = Studio::new();
+        restored.import_state(&blob);
+        assert_eq!(restored.last_error(), 0);
+        assert_eq!(restored.export_state(), blob);
+        assert_eq!(restored.tempo, 133.0);
+
+        // A pattern exported before sessions existed still loads
+        let mut legacy = Studio::new();
+        let old_format: Vec<u8> = studio.get_synth_pattern().chunks(16).flat_map(|s| s[..3].to_vec()).collect();
+        legacy.import_state(&old_format);
+        assert_eq!(legacy.get_synth_pattern(), studio.get_synth_pattern());
+
+        legacy.import_state(&[0xff; 7]);
+        assert_eq!(legacy.last_error(), 5);
+    }
+
+    #[test]
+    fn test_key_lock_transposes_presets() {
+        let mut studio = Studio::new();
+        studio.set_key(2, 2); // D minor
+        studio.load_synth_preset(0);
+
+        let key = Key::new(2, Scale::Minor);
+        let active: Vec<u8> = (0..16)
+            .filter_map(|i| studio.synth.sequencer.get_step(i))
+            .filter(|s| s.active)
+            .map(|s| s.note)
+            .collect();
+        assert!(active.iter().all(|&n| key.contains(n)));
+        assert_eq!(studio.synth.sequencer.root_note(), Some(2));
+
+        assert_eq!(studio.lock_note(40.0), 40.0); // E is in D minor
+        assert_eq!(studio.lock_note(42.0), 41.0); // F# snaps to F
+
+        // Keys played from a MIDI keyboard are held to it too, and still
+        // release by the key pressed
+        studio.handle_midi(0x90, 42, 100);
+        assert_eq!(studio.synth.glide.target(), 41.0);
+        studio.handle_midi(0x80, 42, 0);
+        assert!(!studio.synth.gate);
+
+        studio.clear_key();
+        assert_eq!(studio.lock_note(42.0), 42.0);
+
+        studio.set_key(2, 99);
+        assert_eq!(studio.last_error(), ApiError::Scale.code());
+        assert_eq!(studio.lock_note(42.0), 42.0);
+    }
+
+    #[test]
+    fn test_generated_pattern_follows_key() {
+        let mut studio = Studio::new();
+        studio.set_key(4, 5); // E minor pentatonic
+        studio.generate_synth_pattern(0, 1234);
+        let first = studio.get_synth_pattern();
+
+        let key = Key::new(4, Scale::MinorPentatonic);
+        assert!((0..16)
+            .filter_map(|i| studio.synth.sequencer.get_step(i))
+            .filter(|s| s.active)
+            .all(|s| key.contains(s.note)));
+
+        studio.generate_synth_pattern(0, 1234);
+        assert_eq!(studio.get_synth_pattern(), first);
+        assert_eq!(studio.last_error(), 0);
+
+        // An unknown style leaves the pattern alone
+        studio.generate_synth_pattern(9, 1234);
+        assert_eq!(studio.last_error(), ApiError::Style.code());
+        assert_eq!(studio.get_synth_pattern(), first);
+    }
+
+    #[test]
+    fn test_midi_learn_moves_knob() {
+        let mut studio = Studio::new();
+        studio.start_midi_learn(5); // distortion
+        assert!(studio.is_midi_learning());
+        studio.handle_midi(0xB0, 21, 0);
+        assert!(!studio.is_midi_learning());
+
+        studio.handle_midi(0xB3, 21, 127);
+        assert_eq!(studio.synth.distortion.drive(), 1.0);
+        studio.handle_midi(0xB0, 21, 0);
+        assert_eq!(studio.synth.distortion.drive(), 0.0);
+
+        // The mapping survives a session round trip
+        let mut other = Studio::new();
+        other.import_state(&studio.export_state());
+        assert_eq!(other.get_cc_map(), studio.get_cc_map());
+    }
+
+    #[test]
+    fn test_sweep_releases_to_knob() {
+        let mut studio = Studio::new();
+        studio.set_synth_cutoff(900.0);
+        studio.trigger_sweep(200.0, 4000.0, 0.25);
+        let quarter = studio.samples_per_bar() / 4;
+
+        let mut buffer = vec![0.0f32; quarter / 2];
+        studio.process(&mut buffer);
+        assert!(studio.is_sweeping());
+        assert!(studio.synth.cutoff > 800.0 && studio.synth.cutoff < 1000.0);
+
+        // Moving the knob mid-sweep changes where it lands
+        studio.set_synth_cutoff(600.0);
+        studio.process(&mut buffer);
+        studio.process(&mut buffer[..4]);
+        assert!(!studio.is_sweeping());
+        assert_eq!(studio.synth.cutoff, 600.0);
+
+        // So does a CC assigned to the cutoff
+        studio.start_midi_learn(0);
+        studio.handle_midi(0xB0, 21, 64);
+        studio.trigger_sweep(200.0, 4000.0, 0.25);
+        studio.process(&mut buffer);
+        studio.handle_midi(0xB0, 21, 0);
+        let knob = studio.synth.cutoff;
+        assert!(knob < 600.0);
+        studio.process(&mut buffer);
+        studio.process(&mut buffer[..4]);
+        assert!(!studio.is_sweeping());
+        assert_eq!(studio.synth.cutoff, knob);
+    }
+
+    #[test]
+    fn test_presets_exist() {
+        assert!(Synth::preset_count() > 0);
+        assert!(!Synth::preset_name(0).is_empty());
+    }
+
+    #[test]
+    fn test_studio_creation() {
+        let studio = Studio::new();
+        assert!(!studio.is_playing());
+    }
+
+    #[test]
+    fn test_studio_process() {
+        let mut studio = Studio::new();
+        let mut buffer = [0.0f32; 128];
+        studio.synth_note_on(48.0, false, false);
+        studio.process(&mut buffer);
+        assert!(buffer.iter().any(|&s| s.abs() > 0.001));
+    }
+
+    #[test]
+    fn test_drum_patterns_exist() {
+        assert!(Studio::drum_pattern_count() > 0);
+        assert!(!Studio::drum_pattern_name(0).is_empty());
+    }
+
+    #[test]
+    fn test_process_interleaved() {
+        let mut synth = Synth::new();
+        let mut buffer = [0.0f32; 512];
+        synth.note_on(48.0, false, false);
+        synth.process_interleaved(&mut buffer, 2);
+        assert!(buffer.iter().any(|&s| s.abs() > 0.001));
+        for frame in buffer.chunks_exact(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+    }
+
+    #[test]
+    fn test_stop_fades_and_resets() {
+        let mut studio = Studio::new();
+        let mut buffer = [0.0f32; 4096];
+        studio.start();
+        studio.synth_note_on(36.0, true, false);
+        studio.process(&mut buffer);
+        studio.stop();
+        studio.process(&mut buffer);
+
+        // Fade-out starts from the previous level rather than cutting
+        assert!(buffer[0].abs() > 0.0);
+        // After the fade the voices have been cleared
+        assert_eq!(studio.synth.envelope.current(), 0.0);
+        assert!(!studio.drums.kick.is_active());
+        assert!(buffer[4095].abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_note_off_silences_voice() {
+        let mut synth = Synth::new();
+        let mut buffer = [0.0f32; 4410];
+        synth.note_on(36.0, false, false);
+        synth.process(&mut buffer);
+        assert!(buffer.iter().any(|s| s.abs() > 0.01));
+
+        // 100ms after release nothing is left
+        synth.note_off();
+        synth.process(&mut buffer);
+        assert!(buffer[4000..].iter().all(|s| s.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_synth_start_fades_in() {
+        let mut synth = Synth::new();
+        let mut buffer = [0.0f32; 64];
+        synth.note_on(36.0, true, false);
+        synth.process(&mut buffer);
+        synth.start();
+        assert_eq!(synth.vca_gain, 0.0);
+        synth.note_on(36.0, true, false);
+        synth.process(&mut buffer);
+        assert!(buffer[0].abs() < 0.01);
+    }
+
+    #[test]
+    fn test_midi_drum_channel() {
+        let mut studio = Studio::new();
+        // Note on, channel 10, GM kick
+        studio.handle_midi(0x99, 36, 100);
+        assert!(studio.drums.kick.is_active());
+
+        let mut buffer = [0.0f32; 128];
+        studio.process(&mut buffer);
+        assert!(buffer.iter().any(|&s| s.abs() > 0.001));
+    }
+
+    #[test]
+    fn test_midi_synth_channel() {
+        let mut studio = Studio::new();
+        studio.handle_midi(0x90, 48, 100);
+        assert!(studio.synth.gate);
+        assert_eq!(studio.synth.glide.pitch(), 48.0);
+        studio.handle_midi(0x90, 48, 0);
+        assert!(!studio.synth.gate);
+    }
+
+    #[test]
+    fn test_synth_handle_midi() {
+        let mut synth = Synth::new();
+        synth.accent_curve = AccentCurve::Linear;
+        synth.handle_midi(0x90, 48, 120);
+        assert!(synth.gate);
+        assert!(synth.accent_gain > 1.0);
+
+        // Legato slides, and releasing the first key keeps the second held
+        synth.handle_midi(0x90, 55, 64);
+        assert!(synth.glide.is_active());
+        assert_eq!(synth.accent_gain, 1.0);
+        synth.handle_midi(0x80, 48, 0);
+        assert!(synth.gate);
+        synth.handle_midi(0x90, 55, 0);
+        assert!(!synth.gate);
+
+        synth.handle_midi(0xE0, 127, 127);
+        assert!((synth.bend - 2.0).abs() < 0.01);
+        synth.handle_midi(0xB0, 74, 0);
+        assert_eq!(synth.cutoff, 20.0);
+    }
+
+    #[test]
+    fn test_midi_legato_returns_to_held_key() {
+        let mut synth = Synth::new();
+        synth.handle_midi(0x90, 48, 64);
+        synth.handle_midi(0x90, 55, 64);
+        let mut buffer = [0.0f32; 16384];
+        synth.process(&mut buffer);
+        assert!((synth.glide.pitch() - 55.0).abs() < 0.01);
+
+        // Letting go of B glides back to A, still held, without a new gate
+        synth.handle_midi(0x80, 55, 0);
+        assert!(synth.gate);
+        assert!(synth.glide.is_active());
+        assert_eq!(synth.glide.target(), 48.0);
+        synth.process(&mut buffer);
+        assert!((synth.glide.pitch() - 48.0).abs() < 0.01);
+
+        synth.handle_midi(0x80, 48, 0);
+        assert!(!synth.gate);
+    }
+
+    #[test]
+    fn test_sequencer_midi_out() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.set_midi_out_enabled(true);
+        studio.start();
+
+        let mut buffer = [0.0f32; 128];
+        let mut events = Vec::new();
+        while events.is_empty() {
+            studio.process(&mut buffer);
+            events.extend(studio.drain_midi_out());
+        }
+        // First step of "Acid Tracks" is an accented C2
+        assert_eq!(events[1], 0x90);
+        assert_eq!(events[2], 36);
+        assert_eq!(events[3], 127);
+        assert!(events[0] < 128);
+
+        // Stopping while the gate is open releases the note
+        studio.stop();
+        let off = studio.drain_midi_out();
+        assert_eq!(off[1], 0x80);
+    }
+
+    #[test]
+    fn test_clip_count_and_headroom() {
+        let mut studio = Studio::new();
+        studio.set_master_volume(1.0);
+        studio.set_synth_volume(1.0);
+        studio.set_drum_volume(1.0);
+        studio.set_kick_volume(1.0);
+        studio.set_synth_distortion(1.0);
+
+        let mut buffer = [0.0f32; 2048];
+        let mut clipped = 0;
+        for _ in 0..8 {
+            studio.synth_note_on(36.0, true, false);
+            studio.handle_midi(0x99, 36, 127);
+            studio.process(&mut buffer);
+            clipped += studio.get_clip_count();
+        }
+        assert!(clipped > 0);
+        assert!(studio.get_peak_level() > 0.0);
+
+        studio.set_headroom(24.0);
+        studio.synth_note_on(36.0, true, false);
+        studio.process(&mut buffer);
+        assert_eq!(studio.get_clip_count(), 0);
+    }
+
+    #[test]
+    fn test_meters_cover_whole_interleaved_buffer() {
+        let mut studio = Studio::new();
+        studio.set_headroom(0.0);
+        studio.set_synth_distortion(1.0);
+        studio.synth_note_on(36.0, true, false);
+        studio.synth_note_off_at(64);
+
+        // Only the first of the buffer's BLOCK_SIZE chunks is loud
+        let mut buffer = [0.0f32; 8 * BLOCK_SIZE * 2];
+        studio.process_interleaved(&mut buffer, 2);
+        let peak = buffer.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak > 0.0);
+        assert_eq!(studio.get_peak_level(), peak);
+        let clipped = buffer.iter().step_by(2).filter(|s| s.abs() > 1.0).count();
+        assert_eq!(studio.get_clip_count(), clipped as u32);
+    }
+
+    #[test]
+    fn test_synth_compressor_evens_accents() {
+        let mut studio = Studio::new();
+        studio.set_synth_compressor(true);
+        studio.set_synth_comp_threshold(-30.0);
+        studio.set_synth_comp_ratio(8.0);
+
+        let mut buffer = [0.0f32; 1024];
+        studio.synth_note_on(36.0, true, false);
+        studio.process(&mut buffer);
+        assert!(studio.get_synth_comp_reduction() > 0.0);
+    }
+
+    #[test]
+    fn test_synth_gate_opens_for_notes() {
+        let mut studio = Studio::new();
+        studio.set_synth_gate(true);
+        studio.set_synth_gate_threshold(-40.0);
+
+        let mut buffer = [0.0f32; 1024];
+        studio.synth_note_on(36.0, true, false);
+        studio.process(&mut buffer);
+        assert!(studio.synth_gate.is_open());
+    }
+
+    #[test]
+    fn test_automation_record_and_replay() {
+        let mut studio = Studio::new();
+        let mut buffer = [0.0f32; 1024];
+
+        // Knob moves while stopped are not recorded
+        studio.set_automation_record(true);
+        studio.set_synth_cutoff(800.0);
+        assert!(!studio.has_automation(0));
+
+        studio.start();
+        studio.process(&mut buffer);
+        studio.set_synth_cutoff(500.0);
+        assert!(studio.has_automation(0));
+
+        // After the pattern loops the recorded value comes back
+        studio.set_automation_record(false);
+        studio.set_synth_cutoff(3000.0);
+        let bar = studio.samples_per_bar();
+        studio.render_samples(bar);
+        assert_eq!(studio.synth.cutoff, 500.0);
+
+        studio.clear_automation_lane(0);
+        assert!(!studio.has_automation(0));
+    }
+
+    #[test]
+    fn test_automation_follows_pass_time() {
+        // A two-bar pattern played backwards still replays in time order
+        let mut studio = Studio::new();
+        studio.double_synth_pattern();
+        studio.set_synth_direction(1);
+        studio.set_automation_record(true);
+        studio.start();
+        let bar = studio.samples_per_bar();
+        studio.render_samples(bar + 1024);
+        studio.set_synth_cutoff(500.0);
+        studio.set_automation_record(false);
+        studio.set_synth_cutoff(3000.0);
+
+        // Nothing was recorded over the first bar of the pass
+        studio.render_samples(bar + bar / 2 - 1024);
+        assert_eq!(studio.synth.cutoff, 3000.0);
+        studio.render_samples(bar);
+        assert_eq!(studio.synth.cutoff, 500.0);
+    }
+
+    #[test]
+    fn test_highpass_clears_low_end() {
+        let kick_energy = |master: f32, synth: f32| {
+            let mut studio = Studio::new();
+            studio.set_master_highpass(master);
+            studio.set_synth_highpass(synth);
+            studio.drums.trigger(DrumTrack::Kick);
+            let mut buffer = vec![0.0f32; 8820];
+            studio.process(&mut buffer);
+            buffer.iter().map(|s| s * s).sum::<f32>()
+        };
+        assert!(kick_energy(300.0, 0.0) < kick_energy(0.0, 0.0) * 0.5);
+        // The synth channel filter leaves the kick alone
+        assert_eq!(kick_energy(0.0, 300.0), kick_energy(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_drum_delay_send_per_voice() {
+        // Energy well after the snare has died, where only echoes remain
+        let tail = |track: u8, amount: f32| {
+            let mut studio = Studio::new();
+            studio.set_delay_time(6.0);
+            studio.set_drum_delay_send(track, amount);
+            studio.drums.trigger(DrumTrack::Snare);
+            let mut buffer = vec![0.0f32; 44100];
+            studio.process(&mut buffer);
+            buffer[33000..].iter().map(|s| s.abs()).sum::<f32>()
+        };
+        assert!(tail(1, 1.0) > tail(1, 0.0) * 10.0);
+        // Sending the kick doesn't echo the snare
+        assert!(tail(0, 1.0) < tail(1, 1.0) * 0.1);
+
+        let mut studio = Studio::new();
+        studio.set_drum_delay_send(9, 1.0);
+        assert_eq!(studio.last_error(), ApiError::DrumTrack.code());
+    }
+
+    #[test]
+    fn test_stutter_waits_for_the_division() {
+        let mut studio = Studio::new();
+        studio.start();
+        let steps = studio.synth.sequencer.samples_per_step() as usize;
+        let mut buffer = vec![0.0f32; steps * 5 + steps / 2];
+        studio.process(&mut buffer);
+
+        // Partway into a quarter note, the repeat waits for the next one
+        // and so does the release
+        let mut buffer = [0.0f32; 64];
+        for engaged in [true, false] {
+            let asked_at = studio.steps_elapsed;
+            if engaged {
+                studio.stutter_on(4);
+            } else {
+                studio.stutter_off();
+            }
+            while studio.is_stutter_engaged() != engaged {
+                studio.process(&mut buffer);
+            }
+            assert!(studio.steps_elapsed > asked_at);
+            assert_eq!(studio.steps_elapsed % 4, 1);
+        }
+
+        // Stopped, there is no clock to wait for
+        studio.stop();
+        studio.stutter_on(16);
+        assert!(studio.is_stutter_engaged());
+    }
+
+    #[test]
+    fn test_multi_out_separates_parts() {
+        let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+        for rate in [44100.0, 48000.0] {
+            let mut studio = Studio::new();
+            studio.set_output_sample_rate(rate);
+            studio.synth.note_on(36.0, false, false);
+            studio.drums.trigger(DrumTrack::Snare);
+            let mut output = vec![0.0f32; MULTI_OUTPUTS * 2048];
+            studio.process_multi(&mut output);
+            let parts: Vec<f32> = output.chunks(2048).map(energy).collect();
+            assert!(parts[0] > 1.0 && parts[2] > 1.0, "{:?}", parts);
+            assert!(parts[1] < 1e-6 && parts[3] < 1e-6 && parts[4] < 1e-6, "{:?}", parts);
+        }
+
+        // A hat's channel is the hat alone
+        let mut studio = Studio::new();
+        studio.drums.trigger(DrumTrack::ClosedHH);
+        let mut output = vec![0.0f32; MULTI_OUTPUTS * 512];
+        studio.process_multi(&mut output);
+        assert!(energy(&output[3 * 512..4 * 512]) > 0.0);
+        assert_eq!(energy(&output[..3 * 512]) + energy(&output[4 * 512..]), 0.0);
+    }
+
+    #[test]
+    fn test_clap_track() {
+        let mut studio = Studio::new();
+        let mut pattern = studio.get_drum_pattern();
+        pattern.iter_mut().step_by(drums::sequencer::DRUM_STEP_BYTES).for_each(|tracks| *tracks = 0);
+        studio.set_drum_pattern(&pattern);
+        studio.set_drum_track_step(0, 4, true);
+        assert_eq!(studio.get_drum_step_data(0), vec![0, 0, 0, 0, 0, 1]);
+        assert_eq!(studio.get_drum_pattern()[0], 32);
+
+        // The step plays the clap, on the last channel only
+        studio.start();
+        let mut output = vec![0.0f32; MULTI_OUTPUTS * 8192];
+        studio.process_multi(&mut output);
+        let parts: Vec<f32> = output.chunks(8192).map(|c| c.iter().map(|s| s * s).sum()).collect();
+        assert!(parts[5] > 1.0, "{:?}", parts);
+        assert_eq!(parts[1..5].iter().sum::<f32>(), 0.0, "{:?}", parts);
+
+        studio.set_clap_volume(0.0);
+        studio.drums.trigger(DrumTrack::Clap);
+        studio.process_multi(&mut output);
+        assert_eq!(output[5 * 8192..].iter().map(|s| s.abs()).sum::<f32>(), 0.0);
+    }
+
+    #[test]
+    fn test_mute_and_solo() {
+        let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+        // Energy of each multi-out channel with the synth, kick and snare
+        // all sounding, once any mute has faded in
+        let parts = |setup: &dyn Fn(&mut Studio)| {
+            let mut studio = Studio::new();
+            setup(&mut studio);
+            studio.synth.note_on(36.0, false, false);
+            studio.drums.trigger(DrumTrack::Kick);
+            studio.drums.trigger(DrumTrack::Snare);
+            let mut output = vec![0.0f32; MULTI_OUTPUTS * 2048];
+            studio.process_multi(&mut output);
+            output.chunks(2048).map(|channel| energy(&channel[512..])).collect::<Vec<f32>>()
+        };
+        let all = parts(&|_| {});
+        assert!(all[..3].iter().all(|&e| e > 1.0), "{:?}", all);
+
+        let muted = parts(&|studio| {
+            studio.mute_track(5, true);
+            studio.mute_track(1, true);
+        });
+        assert_eq!(muted[0] + muted[2], 0.0);
+        assert_eq!(muted[1], all[1]);
+
+        // A solo silences everything else, mutes included
+        let soloed = parts(&|studio| {
+            studio.mute_track(0, true);
+            studio.solo_track(0, true);
+        });
+        assert!(soloed[1] > 1.0);
+        assert_eq!(soloed[0] + soloed[2], 0.0);
+
+        let mut studio = Studio::new();
+        studio.solo_track(2, true);
+        studio.solo_track(2, false);
+        assert!(!studio.is_track_soloed(2));
+        studio.mute_track(6, true);
+        assert_eq!(studio.last_error(), ApiError::MixTrack.code());
+        assert!(!studio.is_track_muted(6));
+
+        // Muting ramps the track down rather than cutting it dead, here
+        // against a twin left unmuted
+        let mut studio = Studio::new();
+        let mut unmuted = Studio::new();
+        let mut output = vec![0.0f32; MULTI_OUTPUTS * 64];
+        for studio in [&mut studio, &mut unmuted] {
+            studio.synth.note_on(36.0, false, false);
+            studio.process_multi(&mut output);
+        }
+        studio.mute_track(5, true);
+        studio.process_multi(&mut output);
+        let ramp = energy(&output[..64]);
+        unmuted.process_multi(&mut output);
+        let full = energy(&output[..64]);
+        assert!(ramp > 0.1 * full && ramp < 0.9 * full, "{} {}", ramp, full);
+        for _ in 0..8 {
+            studio.process_multi(&mut output);
+        }
+        assert_eq!(energy(&output[..64]), 0.0);
+    }
+
+    #[test]
+    fn test_drum_kit_models() {
+        // Each multi-out channel with every drum voice struck at once
+        let render = |setup: &dyn Fn(&mut Studio)| {
+            let mut studio = Studio::new();
+            setup(&mut studio);
+            for track in DrumTrack::ALL {
+                studio.drums.trigger(track);
+            }
+            let mut output = vec![0.0f32; MULTI_OUTPUTS * 4096];
+            studio.process_multi(&mut output);
+            output.chunks(4096).map(|channel| channel.to_vec()).collect::<Vec<_>>()
+        };
+        let changed = |setup: &dyn Fn(&mut Studio)| {
+            let default = render(&|_| {});
+            render(setup).iter().zip(&default).map(|(a, b)| a != b).collect::<Vec<_>>()
+        };
+        // The default kit has the 808's kick and hats and the 909's snare
+        // and clap, so a 909 kit changes just the kick and hats
+        assert_eq!(changed(&|studio| studio.set_drum_kit(1)), [false, true, false, true, true, false]);
+        // A voice's model changes that voice alone
+        assert_eq!(changed(&|studio| studio.set_drum_voice_model(1, 0)), [false, false, true, false, false, false]);
+        assert_eq!(changed(&|studio| studio.set_drum_voice_model(3, 1)), [false, false, false, false, true, false]);
+        assert!(!changed(&|studio| studio.set_drum_voice_model(3, 0)).contains(&true));
+
+        let mut studio = Studio::new();
+        studio.set_drum_kit(2);
+        assert_eq!(studio.last_error(), ApiError::KitModel.code());
+        studio.set_drum_voice_model(5, 0);
+        assert_eq!(studio.last_error(), ApiError::DrumTrack.code());
+        studio.set_drum_voice_model(0, 1);
+        assert_eq!(studio.last_error(), 0);
+    }
+
+    #[test]
+    fn test_reverb_send_per_channel() {
+        // Energy after the dry snare and synth note have died away
+        let tail = |setup: &dyn Fn(&mut Studio)| {
+            let mut studio = Studio::new();
+            studio.set_reverb_size(0.9);
+            setup(&mut studio);
+            studio.drums.trigger(DrumTrack::Snare);
+            let mut buffer = vec![0.0f32; 44100];
+            studio.process(&mut buffer);
+            buffer[33000..].iter().map(|s| s.abs()).sum::<f32>()
+        };
+        let dry = tail(&|_| {});
+        assert!(tail(&|studio| studio.set_drum_reverb_send(1, 1.0)) > dry * 10.0);
+        // Sending the hats doesn't put the snare in the room
+        assert!(tail(&|studio| studio.set_drum_reverb_send(2, 1.0)) < dry * 2.0 + 1e-3);
+        // Nor does closing the return
+        let muted = tail(&|studio| {
+            studio.set_drum_reverb_send(1, 1.0);
+            studio.set_reverb_return(0.0);
+        });
+        assert!(muted < dry * 2.0 + 1e-3);
+
+        let mut studio = Studio::new();
+        studio.set_drum_reverb_send(9, 1.0);
+        assert_eq!(studio.last_error(), ApiError::DrumTrack.code());
+    }
+
+    #[test]
+    fn test_synth_accents_push_drums() {
+        let kick_peak = |linked: bool| {
+            let mut studio = Studio::new();
+            studio.set_synth_step(0, 36, true, false, true);
+            studio.set_drum_step(0, true, false, false, false);
+            studio.set_synth_accents_to_drums(linked);
+            studio.start();
+            let mut peak = 0.0f32;
+            let mut buffer = [0.0f32; 1];
+            for _ in 0..8000 {
+                studio.process(&mut buffer);
+                peak = peak.max(studio.drums.kick_output().abs());
+            }
+            peak
+        };
+        let plain = kick_peak(false);
+        let linked = kick_peak(true);
+        assert!(linked > plain * 1.2, "plain {} linked {}", plain, linked);
+    }
+
+    #[test]
+    fn test_song_position_and_continue() {
+        let mut studio = Studio::new();
+        studio.set_midi_clock_sync(true);
+        let mut buffer = [0.0f32; 128];
+
+        // Jump to bar 2, step 5 (MIDI beat 21) and continue from there
+        studio.handle_midi(midi::SONG_POSITION, 21, 0);
+        studio.handle_midi(midi::CONTINUE, 0, 0);
+        assert!(studio.is_playing());
+        while studio.get_synth_step() < 0 || !studio.synth_step_changed {
+            studio.process(&mut buffer);
+        }
+        assert_eq!(studio.get_synth_step(), 6);
+        assert_eq!(studio.get_drum_step(), 6);
+        assert_eq!(studio.get_bar(), 1);
+
+        // Clock ticks move the song position on and set the tempo
+        for _ in 0..4 {
+            for _ in 0..midi::CLOCKS_PER_BEAT {
+                studio.handle_midi(midi::TIMING_CLOCK, 0, 0);
+                for _ in 0..7 {
+                    studio.process(&mut buffer);
+                }
+            }
+        }
+        assert_eq!(studio.get_song_position(), 21 + 16);
+        // 24 * 7 * 128 samples per beat
+        assert!((studio.tempo - 60.0 * 44100.0 / 21504.0).abs() < 0.1);
+
+        studio.handle_midi(midi::STOP, 0, 0);
+        assert!(!studio.is_playing());
+    }
+
+    #[test]
+    fn test_song_position_in_song_mode() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.store_song_pattern(0);
+        studio.load_synth_preset(1);
+        studio.store_song_pattern(1);
+        let b = studio.song.synth_pattern(1).map(|p| p.steps.clone());
+        studio.load_synth_preset(0);
+        studio.set_song_chain(&[0, 0, 2, 1, 1, 3]);
+        studio.set_song_mode(true);
+        studio.set_midi_clock_sync(true);
+
+        // Bar 3 is the second of entry 1's three, so one more bar of B
+        // follows before the chain loops back round
+        studio.handle_midi(midi::SONG_POSITION, 48 + 5, 0);
+        studio.handle_midi(midi::CONTINUE, 0, 0);
+        assert_eq!(studio.get_song_entry(), 1);
+        assert_eq!(Some(studio.synth.sequencer.steps().to_vec()), b);
+
+        let mut buffer = vec![0.0f32; studio.samples_per_bar()];
+        studio.process(&mut buffer);
+        assert_eq!(studio.get_song_entry(), 1);
+        studio.process(&mut buffer);
+        assert_eq!(studio.get_song_entry(), 0);
+    }
+
+    #[test]
+    fn test_slot_recalls_sound_unless_pattern_only() {
+        let mut studio = Studio::new();
+        studio.set_tempo(140.0);
+        studio.set_synth_cutoff(600.0);
+        studio.set_synth_step(0, 40, false, false, true);
+        studio.store_synth_slot(2, true);
+
+        studio.set_tempo(100.0);
+        studio.set_synth_cutoff(3000.0);
+        studio.set_synth_step(0, 50, false, false, true);
+        studio.recall_synth_slot(2, true);
+        assert_eq!(studio.synth.sequencer.get_step(0).unwrap().note, 40);
+        assert_eq!(studio.tempo, 100.0);
+
+        studio.recall_synth_slot(2, false);
+        assert_eq!(studio.tempo, 140.0);
+        assert_eq!(studio.synth.cutoff, 600.0);
+
+        studio.recall_synth_slot(5, false);
+        assert_eq!(studio.last_error(), ApiError::EmptySlot.code());
+        studio.store_synth_slot(8, false);
+        assert_eq!(studio.last_error(), ApiError::SlotIndex.code());
+    }
+
+    #[test]
+    fn test_drum_track_lengths() {
+        let mut studio = Studio::new();
+        studio.set_drum_track_length(2, 12);
+        assert_eq!(studio.last_error(), 0);
+        assert_eq!(studio.get_drum_track_length(2), 12);
+        assert_eq!(studio.get_drum_track_length(0), 16);
+        studio.double_drum_pattern();
+        assert_eq!(studio.get_drum_track_length(0), 32);
+        assert_eq!(studio.get_drum_track_length(2), 12);
+
+        studio.set_drum_track_length(5, 12);
+        assert_eq!(studio.last_error(), ApiError::DrumTrack.code());
+        assert_eq!(studio.get_drum_track_length(5), 0);
+    }
+
+    #[test]
+    fn test_slots_hold_both_patterns() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.load_drum_pattern(0);
+        studio.save_to_slot(0);
+        assert_eq!(studio.last_error(), 0);
+        let (synth, drums) = (studio.get_synth_pattern(), studio.get_drum_pattern());
+
+        studio.copy_pattern(0, 7);
+        studio.load_synth_preset(1);
+        studio.load_drum_pattern(2);
+        studio.double_drum_pattern();
+        studio.load_from_slot(7);
+        assert_eq!(studio.last_error(), 0);
+        assert_eq!(studio.get_synth_pattern(), synth);
+        assert_eq!(studio.get_drum_pattern(), drums);
+
+        // A slot with only a synth pattern can't be loaded or copied whole
+        studio.store_synth_slot(3, false);
+        studio.load_from_slot(3);
+        assert_eq!(studio.last_error(), ApiError::EmptySlot.code());
+        studio.copy_pattern(3, 4);
+        assert_eq!(studio.last_error(), ApiError::EmptySlot.code());
+        assert!(!studio.is_synth_slot_filled(4));
+
+        studio.copy_drum_slot(0, 3);
+        studio.recall_drum_slot(3);
+        assert_eq!(studio.last_error(), 0);
+        studio.copy_pattern(0, 8);
+        assert_eq!(studio.last_error(), ApiError::SlotIndex.code());
+    }
+
+    #[test]
+    fn test_slots_keep_their_variations() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.load_drum_pattern(0);
+        let (synth, drums) = (studio.get_synth_pattern(), studio.get_drum_pattern());
+        studio.set_synth_variation(&synth);
+        studio.set_synth_variation_chance(0.25);
+        studio.set_drum_variation(&drums);
+        studio.set_drum_variation_chance(0.5);
+        studio.save_to_slot(0);
+        let synth = studio.synth.sequencer.variation();
+        let drums = studio.drums.sequencer.variation();
+        assert_eq!(synth.as_ref().map(|v| v.chance), Some(0.25));
+        assert_eq!(drums.as_ref().map(|v| v.chance), Some(0.5));
+
+        // A new pattern doesn't inherit the variation, even at the same length
+        studio.load_synth_preset(1);
+        studio.load_drum_pattern(2);
+        assert_eq!(studio.synth.sequencer.variation(), None);
+        assert_eq!(studio.drums.sequencer.variation(), None);
+        studio.save_to_slot(1);
+
+        studio.load_from_slot(0);
+        assert_eq!(studio.synth.sequencer.variation(), synth);
+        assert_eq!(studio.drums.sequencer.variation(), drums);
+        studio.load_from_slot(1);
+        assert_eq!(studio.synth.sequencer.variation(), None);
+        assert_eq!(studio.drums.sequencer.variation(), None);
+        studio.recall_synth_slot(0, true);
+        studio.recall_drum_slot(0);
+        assert_eq!(studio.synth.sequencer.variation(), synth);
+        assert_eq!(studio.drums.sequencer.variation(), drums);
+    }
+
+    #[test]
+    fn test_follower_ducks_on_kick() {
+        let mut studio = Studio::new();
+        studio.set_follower_kick_only(true);
+        studio.set_follower_to_cutoff(-1.0);
+
+        let mut buffer = [0.0f32; 512];
+        studio.process(&mut buffer);
+        assert_eq!(studio.get_follower_level(), 0.0);
+
+        studio.handle_midi(0x99, 36, 100);
+        studio.process(&mut buffer);
+        assert!(studio.get_follower_level() > 0.1);
+
+        // Stopping clears the level, so a restart doesn't duck on a stale one
+        studio.start();
+        studio.handle_midi(0x99, 36, 100);
+        studio.process(&mut buffer);
+        studio.stop();
+        studio.process(&mut buffer);
+        assert_eq!(studio.get_follower_level(), 0.0);
+    }
+
+    #[test]
+    fn test_output_sample_rate_keeps_tempo() {
+        // At 48kHz output a bar should take proportionally more samples
+        let mut studio = Studio::new();
+        studio.set_output_sample_rate(48000.0);
+        studio.start();
+        let mut buffer = [0.0f32; 128];
+        let bar = (studio.samples_per_bar() as f32 * 48000.0 / SAMPLE_RATE) as usize;
+        let mut steps = 0;
+        let mut heard = false;
+        for _ in 0..bar / 128 {
+            studio.process(&mut buffer);
+            if studio.synth_step_changed() {
+                steps += 1;
+            }
+            heard |= buffer.iter().any(|s| s.abs() > 0.001);
+        }
+        assert!((15..=16).contains(&steps), "steps {}", steps);
+        assert!(heard);
+    }
+
+    #[test]
+    fn test_native_sample_rate_keeps_pitch_and_tempo() {
+        let studio = Studio::new_with_sample_rate(48000.0);
+        assert_eq!(studio.synth.sequencer.samples_per_step(), 6000.0);
+        assert_eq!(studio.drums.sequencer.samples_per_step(), 6000.0);
+
+        // One second of A3 crosses zero about 220 times at either rate
+        let mut synth = Synth::new_with_sample_rate(48000.0);
+        synth.set_cutoff(5000.0);
+        synth.set_resonance(0.0);
+        synth.note_on_at(0, 57.0, false, false);
+        let mut buffer = vec![0.0f32; 48000];
+        synth.process(&mut buffer);
+        let crossings = buffer.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        assert!((218..=222).contains(&crossings), "crossings {}", crossings);
+    }
+
+    #[test]
+    fn test_stereo_mix_pans_and_folds_to_mono() {
+        // Centred, both channels carry exactly the mono mix
+        let mut mono_studio = Studio::new();
+        let mut stereo_studio = Studio::new();
+        mono_studio.load_synth_preset(0);
+        stereo_studio.load_synth_preset(0);
+        mono_studio.start();
+        stereo_studio.start();
+        let mut mono = vec![0.0f32; 8192];
+        let mut left = vec![0.0f32; 8192];
+        let mut right = vec![0.0f32; 8192];
+        mono_studio.process(&mut mono);
+        stereo_studio.process_stereo(&mut left, &mut right);
+        assert_eq!(left, mono);
+        assert_eq!(right, mono);
+
+        // Synth hard left, every drum voice hard right
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.set_synth_pan(-1.0);
+        for track in 0..5 {
+            studio.set_drum_pan(track, 1.0);
+        }
+        studio.set_drum_volume(0.0);
+        studio.start();
+        studio.process_stereo(&mut left, &mut right);
+        assert!(left.iter().any(|s| s.abs() > 0.01));
+        assert!(right.iter().all(|s| s.abs() < 1e-6));
+
+        let mut studio = Studio::new();
+        for track in 0..5 {
+            studio.set_drum_pan(track, 1.0);
+        }
+        studio.set_synth_volume(0.0);
+        studio.start();
+        studio.process_stereo(&mut left, &mut right);
+        assert!(right.iter().any(|s| s.abs() > 0.01));
+        assert!(left.iter().all(|s| s.abs() < 1e-6));
+
+        studio.set_drum_pan(5, 0.0);
+        assert_eq!(studio.last_error(), 3);
+    }
+
+    #[test]
+    fn test_swing_keeps_sequencers_locked() {
+        let mut studio = Studio::new();
+        studio.set_swing(0.8);
+        studio.start();
+        let mut buffer = [0.0f32; 100];
+        for _ in 0..2000 {
+            studio.process(&mut buffer);
+            assert_eq!(studio.get_synth_step(), studio.get_drum_step());
+        }
+        assert_eq!(studio.get_swing(), 0.8);
+    }
+
+    #[test]
+    fn test_shared_clock_holds_exact_tempo() {
+        // 5378.05 samples per step, so whole-sample steps would drift
+        let mut studio = Studio::new();
+        studio.set_tempo(123.0);
+        studio.start();
+        let len = studio.samples_per_bar() * 8;
+        let mut buffer = [0.0f32; BLOCK_SIZE];
+        for _ in 0..len / BLOCK_SIZE {
+            studio.process(&mut buffer);
+            assert_eq!(studio.get_synth_step(), studio.get_drum_step());
+        }
+        let expected = ((len / BLOCK_SIZE * BLOCK_SIZE) as f64 / studio.clock.samples_per_step()) as u64;
+        assert_eq!(studio.steps_elapsed, expected);
+    }
+
+    #[test]
+    fn test_song_chain_switches_patterns_at_bars() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.store_song_pattern(0);
+        studio.load_synth_preset(1);
+        studio.load_drum_pattern(2);
+        studio.store_song_pattern(1);
+        let a = studio.song.synth_pattern(0).map(|p| p.steps.clone());
+        let b = studio.song.synth_pattern(1).map(|p| p.steps.clone());
+        assert_ne!(a, b);
+
+        studio.set_song_chain(&[0, 0, 2, 1, 1, 1]);
+        studio.set_song_loop(false);
+        studio.set_song_mode(true);
+        studio.start();
+        assert_eq!(studio.get_song_length(), 2);
+
+        let bar = studio.samples_per_bar();
+        let mut buffer = vec![0.0f32; bar];
+        let mut entries = Vec::new();
+        for _ in 0..3 {
+            entries.push(studio.get_song_entry());
+            assert_eq!(&Some(studio.synth.sequencer.steps().to_vec()), if entries.last() == Some(&0) { &a } else { &b });
+            studio.process(&mut buffer);
+        }
+        assert_eq!(entries, vec![0, 0, 1]);
+
+        // The chain doesn't loop, so the transport stops after its last bar
+        assert!(studio.is_playing());
+        studio.process(&mut buffer);
+        assert!(!studio.is_playing());
+
+        studio.set_song_chain(&[0, 0]);
+        assert_eq!(studio.last_error(), ApiError::ChainFormat.code());
+    }
+
+    #[test]
+    fn test_freeze_matches_live() {
+        // The preset retimes the synth's own clock, but the Studio clock leads
+        let mut studio = Studio::new();
+        studio.set_drum_volume(0.0);
+        studio.load_synth_preset(0);
+        let bar = studio.samples_per_bar();
+        let step = bar / 16;
+
+        // Second bar of a render is the steady-state loop
+        let live = studio.render_pass(2);
+        studio.freeze_synth();
+        assert!(studio.is_synth_frozen());
+        let frozen = studio.render_pass(2);
+
+        // Oscillator phase differs between passes, so compare level per step
+        let rms = |s: &[f32]| (s.iter().map(|x| x * x).sum::<f32>() / s.len() as f32).sqrt();
+        for i in 0..16 {
+            let range = bar + i * step..bar + (i + 1) * step;
+            let (a, b) = (rms(&live[range.clone()]), rms(&frozen[range]));
+            assert!((a - b).abs() <= 0.1 * a.max(b) + 1e-4, "step {}: {} vs {}", i, a, b);
+        }
+
+        studio.unfreeze_synth();
+        assert!(studio.synth_sampler.is_empty());
+    }
+
+    #[test]
+    fn test_freeze_captures_whole_pattern() {
+        // Two bars with the second one silent, so a loop cut to one bar or
+        // out of phase shows up
+        let mut studio = Studio::new();
+        studio.set_drum_volume(0.0);
+        studio.load_synth_preset(0);
+        studio.double_synth_pattern();
+        for i in 16..32 {
+            studio.set_synth_step(i, 36, false, false, false);
+        }
+        let bar = studio.samples_per_bar();
+        let step = bar / 16;
+
+        // The second pass of the pattern is the steady-state loop
+        let live = studio.render_pass(4);
+        studio.freeze_synth();
+        let frozen = studio.render_pass(4);
+
+        let rms = |s: &[f32]| (s.iter().map(|x| x * x).sum::<f32>() / s.len() as f32).sqrt();
+        for i in 0..32 {
+            let range = 2 * bar + i * step..2 * bar + (i + 1) * step;
+            let (a, b) = (rms(&live[range.clone()]), rms(&frozen[range]));
+            assert!((a - b).abs() <= 0.1 * a.max(b) + 1e-4, "step {}: {} vs {}", i, a, b);
+        }
+        assert!(rms(&frozen[2 * bar..3 * bar]) > 0.01);
+    }
+
+    #[test]
+    fn test_stop_silences_frozen_loop() {
+        let mut studio = Studio::new();
+        studio.set_drum_volume(0.0);
+        studio.load_synth_preset(0);
+        studio.freeze_synth();
+        studio.start();
+        let mut buffer = vec![0.0f32; 16384];
+        studio.process(&mut buffer);
+        assert!(buffer.iter().any(|s| s.abs() > 0.01));
+
+        // Once the stop has faded out the loop is gone
+        studio.stop();
+        studio.process(&mut buffer);
+        studio.process(&mut buffer);
+        assert!(buffer.iter().all(|s| s.abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_step_cents_detune_note() {
+        let mut synth = Synth::new();
+        synth.set_step(0, 48, false, false, true);
+        synth.set_step_cents(0, 25);
+        // Re-entering the note keeps the tuning
+        synth.set_step(0, 50, false, false, true);
+        synth.start();
+        while synth.tick() < 0 {}
+        assert_eq!(synth.glide.pitch(), 50.25);
+    }
+
+    #[test]
+    fn test_step_slide_time_overrides_synth() {
+        // Pitch 50ms into a slide from C2 up to C3
+        let slide = |ms: f32, curve: u8| {
+            let mut synth = Synth::new();
+            synth.set_slide_curve(curve);
+            synth.set_step(0, 36, false, false, true);
+            synth.set_step(1, 48, false, true, true);
+            synth.set_step_slide_time(1, ms);
+            synth.start();
+            while synth.tick() != 2 {}
+            let mut buffer = vec![0.0f32; 2205];
+            synth.process(&mut buffer);
+            synth.glide.pitch()
+        };
+        assert!(slide(0.0, 0) > 46.0);
+        assert!(slide(500.0, 0) < 38.0);
+        let linear = slide(500.0, 1);
+        assert!((linear - 37.2).abs() < 0.1, "linear {}", linear);
+
+        let mut synth = Synth::new();
+        synth.set_step_slide_time(1, 500.0);
+        assert_eq!(synth.get_pattern()[sequencer::STEP_BYTES + 9], 100);
+        synth.set_step_slide_time(1, 0.0);
+        assert_eq!(synth.get_pattern()[sequencer::STEP_BYTES + 9], 0);
+        synth.set_step_slide_time(99, 500.0);
+        assert_eq!(synth.last_error, Some(ApiError::StepIndex));
+    }
+
+    #[test]
+    fn test_step_level_scales_note() {
+        let render = |level: u8| {
+            let mut synth = Synth::new();
+            synth.set_step(0, 36, false, false, true);
+            synth.set_step_level(0, level);
+            synth.start();
+            while synth.tick() < 0 {}
+            let mut buffer = vec![0.0f32; 2048];
+            synth.process(&mut buffer);
+            buffer.iter().fold(0.0f32, |m, s| m.max(s.abs()))
+        };
+        let full = render(127);
+        let ghost = render(32);
+        assert!(ghost > 0.0 && ghost < full * 0.5, "full {} ghost {}", full, ghost);
+    }
+
+    #[test]
+    fn test_cutoff_mod_buffer_opens_filter() {
+        let render = |modulation: Option<f32>| {
+            let mut synth = Synth::new();
+            synth.set_cutoff(200.0);
+            synth.set_env_mod(0.0);
+            synth.note_on(48.0, false, false);
+            if let Some(octaves) = modulation {
+                synth.set_cutoff_mod_buffer(&[octaves; 4096]);
+            }
+            let mut buffer = vec![0.0f32; 4096];
+            synth.process(&mut buffer);
+            // Brightness: sample-to-sample change relative to level
+            let tail = &buffer[256..2048];
+            let diff: f32 = tail.windows(2).map(|w| (w[1] - w[0]).abs()).sum();
+            diff / tail.iter().map(|s| s.abs()).sum::<f32>()
+        };
+        let dry = render(None);
+        let opened = render(Some(4.0));
+        assert!(opened > dry * 1.3, "dry {} opened {}", dry, opened);
+    }
+
+    #[test]
+    fn test_pitch_and_cutoff_update_at_control_rate() {
+        let mut synth = Synth::new();
+        let ticks = |synth: &mut Synth| (0..CONTROL_INTERVAL * 10).filter(|_| synth.control_tick()).count();
+        assert_eq!(ticks(&mut synth), 10);
+
+        // A note-on is picked up straight away
+        synth.control_tick();
+        synth.note_on(48.0, false, false);
+        assert!(synth.control_tick());
+
+        // External modulation stays audio rate while its buffer lasts
+        synth.set_cutoff_mod_buffer(&[1.0; 64]);
+        assert!((0..64).all(|_| {
+            let due = synth.control_tick();
+            synth.next_cutoff_mod();
+            due
+        }));
+        assert!(!synth.control_tick());
+    }
+
+    #[test]
+    fn test_studio_voice_shares_synth_rendering() {
+        // Pitch bend reaches the Studio's synth, as it does a bare Synth
+        let render = |bend: bool| {
+            let mut studio = Studio::new();
+            studio.synth_note_on(48.0, false, false);
+            if bend {
+                studio.handle_midi(0xE0, 0x7F, 0x7F);
+            }
+            let mut buffer = vec![0.0f32; 2048];
+            studio.process(&mut buffer);
+            buffer
+        };
+        assert_ne!(render(false), render(true));
+    }
+
+    #[test]
+    fn test_cv_follows_gate_and_pitch() {
+        let mut synth = Synth::new();
+        synth.note_on_at(64, 72.0, false, false);
+
+        let mut buffer = [0.0f32; 128];
+        let mut gate = [0.0f32; 128];
+        let mut pitch = [0.0f32; 128];
+        synth.process_with_cv(&mut buffer, &mut gate, &mut pitch);
+        assert!(gate[..64].iter().all(|&g| g == 0.0));
+        assert!(gate[64..].iter().all(|&g| g == 1.0));
+        assert_eq!(pitch[127], 1.0);
+
+        // Buffers shorter than the block are filled as far as they go
+        let mut short = [0.0f32; 16];
+        synth.process_with_cv(&mut buffer, &mut short, &mut pitch);
+        assert!(short.iter().all(|&g| g == 1.0));
+    }
+
+    #[test]
+    fn test_cv_follows_gate_when_resampling() {
+        let mut synth = Synth::new();
+        let mut studio = Studio::new();
+        synth.set_waveform(false);
+        studio.set_synth_waveform(false);
+        synth.set_output_sample_rate(48000.0);
+        studio.set_output_sample_rate(48000.0);
+        synth.note_on_at(32, 72.0, false, false);
+        synth.note_off_at(96);
+        studio.synth_note_on_at(32, 72.0, false, false);
+        studio.synth_note_off_at(96);
+
+        let (mut synth_out, mut synth_gate, mut synth_pitch) = ([0.0f32; 128], [0.0f32; 128], [0.0f32; 128]);
+        let (mut studio_out, mut studio_gate, mut studio_pitch) = ([0.0f32; 128], [0.0f32; 128], [0.0f32; 128]);
+        synth.process_with_cv(&mut synth_out, &mut synth_gate, &mut synth_pitch);
+        studio.process_with_cv(&mut studio_out, &mut studio_gate, &mut studio_pitch);
+        for (output, gate, pitch) in [(synth_out, synth_gate, synth_pitch), (studio_out, studio_gate, studio_pitch)] {
+            // The gate opens and closes within the block, with the audio it
+            // goes with a few samples of attack behind it
+            let open = gate.iter().position(|&g| g == 1.0).unwrap();
+            let closed = open + gate[open..].iter().position(|&g| g == 0.0).unwrap();
+            let sound = output.iter().position(|s| s.abs() > 1e-3).unwrap();
+            assert!(gate[..open].iter().all(|&g| g == 0.0) && gate[closed..].iter().all(|&g| g == 0.0));
+            assert!((closed - open).abs_diff(64) <= 1, "open {open}, closed {closed}");
+            assert!((open..=open + 4).contains(&sound), "gate {open}, sound {sound}");
+            assert_eq!(pitch[open], 1.0);
+        }
+    }
+
+    #[test]
+    fn test_scheduled_note_starts_on_offset() {
+        let mut synth = Synth::new();
+        synth.note_on_at(200, 36.0, false, false);
+        synth.note_off_at(100);
+
+        let mut buffer = [0.0f32; 128];
+        synth.process(&mut buffer);
+        assert!(!synth.gate);
+        assert!(buffer.iter().all(|s| s.abs() < 1e-6));
+
+        synth.process(&mut buffer);
+        assert!(synth.gate);
+        assert!(buffer[..72].iter().all(|s| s.abs() < 1e-6));
+        assert!(buffer[72..].iter().any(|s| s.abs() > 1e-4));
+    }
+
+    #[test]
+    fn test_scheduled_notes_land_on_offset_when_resampling() {
+        // Square waves, which start at full swing whatever their phase
+        let first_sound = |buffer: &[f32]| buffer.iter().position(|s| s.abs() > 1e-3).unwrap();
+        let synth = |offset: Option<u32>| {
+            let mut synth = Synth::new();
+            synth.set_waveform(false);
+            synth.set_output_sample_rate(48000.0);
+            let mut buffer = vec![0.0f32; 300];
+            synth.process(&mut buffer);
+            match offset {
+                Some(offset) => synth.note_on_at(offset, 36.0, false, false),
+                None => synth.note_on(36.0, false, false),
+            }
+            let mut buffer = vec![0.0f32; 512];
+            synth.process(&mut buffer);
+            first_sound(&buffer)
+        };
+        let studio = |offset: Option<u32>, queued: bool| {
+            let mut studio = Studio::new();
+            studio.set_synth_waveform(false);
+            studio.set_output_sample_rate(48000.0);
+            studio.set_host_mode(queued);
+            let mut buffer = vec![0.0f32; 300];
+            studio.process(&mut buffer);
+            match offset {
+                Some(offset) if queued => studio.queue_host_events(&[offset as f32, 0.0, 36.0, 0.0]),
+                Some(offset) => studio.synth_note_on_at(offset, 36.0, false, false),
+                None => studio.synth_note_on(36.0, false, false),
+            }
+            let mut buffer = vec![0.0f32; 512];
+            studio.process(&mut buffer);
+            first_sound(&buffer)
+        };
+
+        // Everything through the resampler is equally late, so scheduled
+        // notes sound `offset` host samples after one played at once
+        let (now, studio_now) = (synth(None), studio(None, false));
+        for offset in [0, 32, 64, 100] {
+            let expected = now + offset as usize;
+            assert!(synth(Some(offset)).abs_diff(expected) <= 2, "synth {}", offset);
+            let expected = studio_now + offset as usize;
+            assert!(studio(Some(offset), false).abs_diff(expected) <= 2, "studio {}", offset);
+            assert!(studio(Some(offset), true).abs_diff(expected) <= 2, "host event {}", offset);
+        }
+    }
+
+    #[test]
+    fn test_host_mode_plays_queued_events() {
+        let mut studio = Studio::new();
+        studio.set_host_mode(true);
+        studio.start();
+        studio.queue_host_events(&[
+            10.0, 2.0, 0.0, 0.0,    // kick
+            20.0, 3.0, 0.0, 600.0,  // cutoff
+            30.0, 0.0, 48.0, 1.0,   // accented C3
+        ]);
+
+        let mut buffer = [0.0f32; 128];
+        studio.process(&mut buffer);
+        assert!(studio.drums.kick.is_active());
+        assert_eq!(studio.synth.cutoff, 600.0);
+        assert_eq!(studio.synth.glide.pitch(), 48.0);
+
+        // The internal sequencers stay put
+        assert_eq!(studio.get_synth_step(), -1);
+    }
+
+    #[test]
+    fn test_transport_position() {
+        // Bars and beats follow the step length, whole or not
+        for tempo in [120.0, 173.0] {
+            let mut studio = Studio::new();
+            studio.set_tempo(tempo);
+            let step = studio.clock.samples_per_step();
+            studio.start();
+            assert_eq!(studio.get_position(), 0.0);
+
+            // Steps 0-20 have fired, halfway into step 20: bar 1, beat 1
+            let len = (step * 21.5).round() as usize;
+            let mut buffer = vec![0.0f32; len];
+            studio.process(&mut buffer);
+            assert_eq!(studio.get_bar(), 1, "at {tempo} bpm");
+            assert_eq!(studio.get_beat(), 1, "at {tempo} bpm");
+            assert_eq!(studio.get_step_in_bar(), 4, "at {tempo} bpm");
+            assert!((studio.get_step_fraction() - 0.5).abs() < 0.01);
+            assert_eq!(studio.get_elapsed_samples(), len as f64);
+        }
+    }
+
+    #[test]
+    fn test_trigs_and_render_seed() {
+        let render = |seed: u32| {
+            let mut studio = Studio::new();
+            studio.load_drum_pattern(0);
+            for step in 0..16 {
+                studio.set_drum_step_probability(step, 50);
+            }
+            studio.set_render_seed(seed);
+            studio.render(2)
+        };
+        assert_eq!(render(7), render(7));
+        assert_ne!(render(7), render(8));
+
+        let mut studio = Studio::new();
+        studio.set_synth_step_condition(0, 0x21);
+        assert_eq!(studio.last_error(), 0);
+        assert_eq!(&studio.get_synth_pattern()[5..7], &[100, 0x21]);
+        studio.set_synth_step_condition(0, 0x23);
+        assert_eq!(studio.last_error(), 9);
+        studio.set_drum_step_probability(16, 50);
+        assert_eq!(studio.last_error(), 1);
+        studio.set_drum_step_condition(3, 1);
+        assert_eq!(&studio.get_drum_pattern()[31..33], &[100, 1]);
+    }
+
+    #[test]
+    fn test_step_ratchets() {
+        let mut studio = Studio::new();
+        studio.set_synth_step_ratchet(2, 9);
+        assert_eq!(studio.get_synth_pattern()[2 * 16 + 7], 4);
+        studio.set_drum_step_ratchet(5, 2);
+        assert_eq!(studio.get_drum_pattern()[5 * 10 + 3], 2);
+        studio.set_drum_step_ratchet(16, 2);
+        assert_eq!(studio.last_error(), 1);
+
+        // The extra hits don't count as steps
+        studio.load_synth_preset(0);
+        for step in 0..16 {
+            studio.set_synth_step_ratchet(step, 3);
+            studio.set_drum_step_ratchet(step, 4);
+        }
+        studio.start();
+        let mut buffer = [0.0f32; BLOCK_SIZE];
+        let blocks = studio.samples_per_bar() / BLOCK_SIZE;
+        for _ in 0..blocks {
+            studio.process(&mut buffer);
+            assert_eq!(studio.get_synth_step(), studio.get_drum_step());
+        }
+        let expected = ((blocks * BLOCK_SIZE) as f64 / studio.clock.samples_per_step()) as u64;
+        assert_eq!(studio.steps_elapsed, expected);
+    }
+
+    #[test]
+    fn test_drum_step_velocity() {
+        // Peak of the kick on the first step, with the rest of the kit silent
+        let peak = |velocity: u8| {
+            let mut studio = Studio::new();
+            studio.set_synth_volume(0.0);
+            let mut pattern = studio.get_drum_pattern();
+            pattern.iter_mut().step_by(drums::sequencer::DRUM_STEP_BYTES).for_each(|tracks| *tracks = 0);
+            studio.set_drum_pattern(&pattern);
+            studio.set_drum_track_step(0, 0, true);
+            studio.set_drum_step_velocity(0, 0, velocity);
+            assert_eq!(studio.last_error(), 0);
+            studio.start();
+            let mut buffer = vec![0.0f32; 8192];
+            studio.process(&mut buffer);
+            buffer.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+        };
+        let (full, ghost) = (peak(127), peak(40));
+        assert!(ghost > 0.0 && ghost < full * 0.6, "{} {}", ghost, full);
+        assert_eq!(peak(0), 0.0);
+
+        let mut studio = Studio::new();
+        studio.set_drum_step_velocity(0, 5, 64);
+        assert_eq!(studio.last_error(), ApiError::DrumTrack.code());
+    }
+
+    #[test]
+    fn test_drum_accent_amount() {
+        // Peak of the first step's kick, accented
+        let peak = |amount: Option<f32>| {
+            let mut studio = Studio::new();
+            studio.set_synth_volume(0.0);
+            let mut pattern = studio.get_drum_pattern();
+            pattern.iter_mut().step_by(drums::sequencer::DRUM_STEP_BYTES).for_each(|tracks| *tracks = 0);
+            studio.set_drum_pattern(&pattern);
+            studio.set_drum_track_step(0, 0, true);
+            if let Some(amount) = amount {
+                studio.set_drum_accent(0, true);
+                studio.set_drum_accent_amount(amount);
+            }
+            studio.start();
+            let mut buffer = vec![0.0f32; 8192];
+            studio.process(&mut buffer);
+            buffer.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+        };
+        let plain = peak(None);
+        assert!((peak(Some(0.0)) - plain).abs() < 1e-6);
+        assert!(peak(Some(0.4)) > plain * 1.1);
+        assert!(peak(Some(1.0)) > peak(Some(0.4)));
+    }
+
+    #[test]
+    fn test_step_nudges() {
+        let mut studio = Studio::new();
+        studio.set_synth_step_nudge(3, -80);
+        assert_eq!(studio.get_synth_pattern()[3 * 16 + 8] as i8, -50);
+        studio.set_drum_step_nudge(4, 25);
+        assert_eq!(studio.get_drum_pattern()[4 * 10 + 4], 25);
+        studio.set_synth_step_nudge(16, 10);
+        assert_eq!(studio.last_error(), 1);
+    }
+
+    #[test]
+    fn test_parameter_locks() {
+        let mut studio = Studio::new();
+        studio.set_synth_step_lock(0, 0, 0.0);
+        studio.set_synth_step_lock(0, 1, 1.0);
+        assert_eq!(studio.last_error(), 0);
+        assert_eq!(&studio.get_synth_pattern()[10..12], &[1, 255]);
+        studio.set_synth_step_lock(0, 6, 0.5);
+        assert_eq!(studio.last_error(), ApiError::Param.code());
+        studio.clear_synth_step_lock(16, 0);
+        assert_eq!(studio.last_error(), ApiError::StepIndex.code());
+
+        let synth = &mut studio.synth;
+        synth.set_cutoff(1000.0);
+        synth.set_resonance(0.2);
+        let step = *synth.sequencer.get_step(0).unwrap();
+        synth.apply_event(&SeqEvent::NoteOn(step));
+        assert_eq!(synth.cutoff, 20.0);
+        assert_eq!(synth.resonance, 1.0);
+        // A knob turned during the step keeps its new setting
+        synth.set_resonance(0.6);
+        synth.apply_event(&SeqEvent::Rest);
+        assert_eq!(synth.cutoff, 1000.0);
+        assert_eq!(synth.resonance, 0.6);
+    }
+
+    #[test]
+    fn test_lfo_modulates_voice() {
+        let render = |depth: f32| {
+            let mut studio = Studio::new();
+            studio.load_synth_preset(0);
+            studio.set_synth_lfo_shape(2);
+            studio.set_synth_lfo_sync(4.0);
+            studio.set_synth_lfo_to_cutoff(depth);
+            studio.set_synth_lfo_to_pitch(depth);
+            studio.start();
+            let mut buffer = [0.0f32; BLOCK_SIZE];
+            let mut out = Vec::new();
+            for _ in 0..studio.samples_per_bar() / BLOCK_SIZE {
+                studio.process(&mut buffer);
+                out.extend_from_slice(&buffer);
+            }
+            out
+        };
+        assert_eq!(render(0.0), render(0.0));
+        assert_ne!(render(0.0), render(1.0));
+
+        // LFO settings travel with the synth params
+        let mut synth = Synth::new();
+        synth.set_lfo_shape(3);
+        synth.set_lfo_sync(8.0);
+        synth.set_lfo_to_drive(-0.5);
+        let mut copy = Synth::new();
+        copy.set_params(&synth.params());
+        assert_eq!(copy.lfo.shape(), LfoShape::SampleAndHold);
+        assert_eq!(copy.lfo.rate(), LfoRate::Synced(8.0));
+        assert_eq!(copy.lfo_to_drive, -0.5);
+    }
+
+    #[test]
+    fn test_waveform_types_and_pwm() {
+        let render = |setup: &dyn Fn(&mut Synth)| {
+            let mut synth = Synth::new();
+            setup(&mut synth);
+            synth.note_on(36.0, false, false);
+            let mut buffer = vec![0.0f32; 8820];
+            synth.process(&mut buffer);
+            buffer
+        };
+        let pulse = render(&|synth| {
+            synth.set_waveform_type(3);
+            synth.set_pulse_width(0.2);
+        });
+        assert_ne!(pulse, render(&|synth| synth.set_waveform(false)));
+        assert_ne!(render(&|synth| synth.set_waveform_type(2)), render(&|_| {}));
+        let modulated = render(&|synth| {
+            synth.set_waveform_type(3);
+            synth.set_pulse_width(0.2);
+            synth.set_lfo_rate(5.0);
+            synth.set_lfo_to_pwm(1.0);
+        });
+        assert_ne!(pulse, modulated);
+
+        // An unknown waveform is ignored; the choice travels with the params
+        let mut synth = Synth::new();
+        synth.set_waveform_type(4);
+        synth.set_waveform_type(9);
+        synth.set_pulse_width(0.3);
+        let mut copy = Synth::new();
+        copy.set_params(&synth.params());
+        assert_eq!(copy.oscillator.waveform(), Waveform::Noise);
+        assert_eq!(copy.oscillator.pulse_width(), 0.3);
+    }
+
+    #[test]
+    fn test_unison_spreads_in_stereo_only() {
+        let synth = |spread: f32| {
+            let mut synth = Synth::new();
+            synth.set_unison_voices(5);
+            synth.set_unison_detune(0.5);
+            synth.set_unison_spread(spread);
+            synth.note_on(48.0, false, false);
+            synth
+        };
+        let mut mono = vec![0.0f32; 4410];
+        synth(1.0).process(&mut mono);
+        let (mut left, mut right) = (vec![0.0f32; 4410], vec![0.0f32; 4410]);
+        synth(1.0).process_stereo(&mut left, &mut right);
+        assert_ne!(left, right);
+        // The stereo pair folds back to the mono output
+        for ((l, r), m) in left.iter().zip(&right).zip(&mono) {
+            assert!(((l + r) * 0.5 - m).abs() < 1e-5);
+        }
+
+        // The spread has no effect on the mono output, and none at all at 0
+        let mut narrow = vec![0.0f32; 4410];
+        synth(0.0).process(&mut narrow);
+        assert_eq!(mono, narrow);
+        synth(0.0).process_stereo(&mut left, &mut right);
+        assert_eq!(left, right);
+
+        // Unison sounds different from the single saw
+        let mut single = Synth::new();
+        single.note_on(48.0, false, false);
+        let mut plain = vec![0.0f32; 4410];
+        single.process(&mut plain);
+        assert_ne!(plain, mono);
+
+        // The Studio mix carries the spread too
+        let mut studio = Studio::new();
+        studio.set_synth_unison_voices(3);
+        studio.set_synth_unison_spread(1.0);
+        studio.synth_note_on(48.0, false, false);
+        studio.process_stereo(&mut left, &mut right);
+        assert_ne!(left, right);
+    }
+
+    #[test]
+    fn test_distortion_before_filter_is_darker() {
+        // Sample-to-sample difference against level measures the high end
+        let brightness = |pre_filter: bool| {
+            let mut synth = Synth::new();
+            synth.set_cutoff(400.0);
+            synth.set_env_mod(0.0);
+            synth.set_resonance(0.0);
+            synth.set_distortion(1.0);
+            synth.set_distortion_routing(pre_filter);
+            synth.note_on(36.0, false, false);
+            let mut buffer = vec![0.0f32; 8192];
+            synth.process(&mut buffer);
+            let tail = &buffer[2048..];
+            let edge: f32 = tail.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+            edge / tail.iter().map(|s| s * s).sum::<f32>()
+        };
+        // The filter smooths off the clipped edges when it comes second
+        assert!(brightness(true) < brightness(false) * 0.5);
+    }
+
+    #[test]
+    fn test_master_tune_and_octave_shift_pitch() {
+        // Zero crossings over one second of A3 track the played pitch
+        let crossings = |setup: &dyn Fn(&mut Synth)| {
+            let mut synth = Synth::new();
+            synth.set_cutoff(5000.0);
+            synth.set_resonance(0.0);
+            setup(&mut synth);
+            synth.note_on_at(0, 57.0, false, false);
+            let mut buffer = vec![0.0f32; 44100];
+            synth.process(&mut buffer);
+            buffer.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count()
+        };
+        let base = crossings(&|_| {});
+        assert!((218..=222).contains(&base), "base {}", base);
+        let up = crossings(&|synth| synth.set_octave(1));
+        assert!((438..=442).contains(&up), "octave up {}", up);
+        let down = crossings(&|synth| synth.set_octave(-5));
+        assert!((53..=57).contains(&down), "two octaves down {}", down);
+        let sharp = crossings(&|synth| synth.set_master_tune(50.0));
+        assert!((224..=229).contains(&sharp), "quarter tone up {}", sharp);
+
+        // The pattern notes are left alone
+        let mut synth = Synth::new();
+        synth.set_octave(2);
+        synth.note_on(45.0, false, false);
+        assert_eq!(synth.glide.pitch(), 45.0);
+    }
+
+    #[test]
+    fn test_playback_direction() {
+        let mut studio = Studio::new();
+        studio.set_synth_direction(4);
+        assert_eq!(studio.last_error(), 10);
+        studio.set_synth_direction(1);
+        studio.set_drum_direction(1);
+        assert_eq!(studio.last_error(), 0);
+
+        // Reversed sequencers stay in step with each other
+        studio.start();
+        let mut buffer = [0.0f32; BLOCK_SIZE];
+        for _ in 0..studio.samples_per_bar() * 2 / BLOCK_SIZE {
+            studio.process(&mut buffer);
+            assert_eq!(studio.get_synth_step(), studio.get_drum_step());
+        }
+        assert_eq!(studio.synth.sequencer.direction(), Direction::Reverse);
+        assert_eq!(studio.drums.sequencer.direction(), Direction::Reverse);
+    }
+
+    #[test]
+    fn test_synth_scale_snaps_edits() {
+        let mut studio = Studio::new();
+        studio.set_synth_scale(9, 6); // A harmonic minor
+        assert_eq!(studio.last_error(), 0);
+        studio.set_synth_step(0, 55, false, false, true); // G
+        assert_eq!(studio.synth.sequencer.get_step(0).unwrap().note, 56);
+        studio.transpose_synth_pattern(12);
+        assert_eq!(studio.synth.sequencer.get_step(0).unwrap().note, 68);
+
+        studio.set_synth_scale(0, 8);
+        assert_eq!(studio.last_error(), 11);
+        studio.clear_synth_scale();
+        studio.set_synth_step(0, 55, false, false, true);
+        assert_eq!(studio.synth.sequencer.get_step(0).unwrap().note, 55);
+    }
+
+    #[test]
+    fn test_double_length_patterns() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.load_drum_pattern(0);
+        studio.double_synth_pattern();
+        studio.double_drum_pattern();
+        assert_eq!(studio.last_error(), 0);
+        assert_eq!(studio.get_synth_pattern_length(), 32);
+        assert_eq!(studio.get_drum_pattern_length(), 32);
+        studio.double_synth_pattern();
+        assert_eq!(studio.last_error(), 4);
+
+        // The second half can be edited and survives a state round trip
+        studio.set_synth_step(20, 60, true, false, true);
+        assert_eq!(studio.last_error(), 0);
+        studio.reverse_drum_pattern();
+        let blob = studio.export_state();
+        let mut restored = Studio::new();
+        restored.import_state(&blob);
+        assert_eq!(restored.last_error(), 0);
+        assert_eq!(restored.get_synth_pattern(), studio.get_synth_pattern());
+        assert_eq!(restored.get_drum_pattern(), studio.get_drum_pattern());
+
+        // Bars are still 16 steps long, so a two-bar pattern wraps every other bar
+        studio.start();
+        let mut buffer = vec![0.0f32; studio.samples_per_bar()];
+        studio.process(&mut buffer);
+        assert!((16..32).contains(&studio.synth.sequencer.current_step()));
+    }
+
+    #[test]
+    fn test_bulk_pattern_transfer() {
+        let mut studio = Studio::new();
+        studio.load_synth_preset(0);
+        studio.load_drum_pattern(0);
+        let synth = studio.get_synth_pattern();
+        let drums = studio.get_drum_pattern();
+        assert_eq!(synth.len(), 16 * 16);
+        assert_eq!(drums.len(), 160);
+
+        let mut other = Studio::new();
+        other.set_synth_pattern(&synth);
+        other.set_drum_pattern(&drums);
+        assert_eq!(other.get_synth_pattern(), synth);
+        assert_eq!(other.get_drum_pattern(), drums);
+    }
+
+    #[test]
+    fn test_render_length() {
+        let mut studio = Studio::new();
+        studio.set_tempo(120.0);
+        let bar = studio.samples_per_bar();
+        let out = studio.render(2);
+        assert_eq!(out.len(), bar * 2);
+        assert!(out.iter().any(|&s| s.abs() > 0.001));
+        assert!(!studio.is_playing());
+    }
+
+    #[test]
+    fn test_render_wav() {
+        let mut studio = Studio::new();
+        let bar = studio.samples_per_bar();
+        let wav = studio.render_wav(1, true);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(wav.len(), 44 + bar * 2);
+    }
+
+    #[test]
+    fn test_render_stereo_interleaves_channels() {
+        let render = |stereo: bool| {
+            let mut studio = Studio::new();
+            studio.load_synth_preset(0);
+            if stereo { studio.render_stereo(1) } else { studio.render(1) }
+        };
+        let mono = render(false);
+        let stereo = render(true);
+        assert_eq!(stereo.len(), mono.len() * 2);
+        // Everything is centred, so both channels match the mono render
+        assert!(stereo.chunks_exact(2).zip(&mono).all(|(frame, &m)| frame[0] == m && frame[1] == m));
+
+        let mut studio = Studio::new();
+        let bar = studio.samples_per_bar();
+        let wav = studio.render_wav_stereo(1, false);
+        assert_eq!(u16::from_le_bytes([wav[22], wav[23]]), 2);
+        assert_eq!(wav.len(), 44 + bar * 4);
+    }
+
+    #[test]
+    fn test_render_peak_normalized() {
+        let mut studio = Studio::new();
+        studio.set_export_normalize(1, -1.0);
+        let out = studio.render(1);
+        let peak = out.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 10.0_f32.powf(-1.0 / 20.0)).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_render_loop_folds_tail() {
+        let mut studio = Studio::new();
+        studio.set_synth_decay(2000.0);
+        studio.load_synth_preset(0);
+        let plain = studio.render(1);
+        let looped = studio.render_loop(1);
+        assert_eq!(plain.len(), looped.len());
+        // The folded tail changes the start of the loop
+        let diff: f32 = plain.iter().zip(looped.iter()).take(1000).map(|(a, b)| (a - b).abs()).sum();
+        assert!(diff > 0.0);
+    }
+
+    #[test]
+    fn test_synth_process_never_allocates() {
+        let mut synth = Synth::new();
+        synth.set_output_sample_rate(48000.0);
+        synth.set_midi_out_enabled(true);
+        synth.set_cutoff_mod_buffer(&[0.5; 512]);
+        synth.start();
+
+        let mut buffer = [0.0f32; 512];
+        let mut midi = [0u32; 64];
+        synth.process(&mut buffer);
+        let count = alloc_counter::allocations_in(|| {
+            for _ in 0..200 {
+                synth.process(&mut buffer);
+                synth.drain_midi_out_into(&mut midi);
+                synth.set_cutoff_mod_buffer(&[0.5; 512]);
+            }
+        });
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_studio_process_never_allocates() {
+        let mut studio = Studio::new();
+        studio.set_output_sample_rate(48000.0);
+        studio.set_midi_out_enabled(true);
+        studio.set_delay_feedback(0.6);
+        studio.set_drum_delay_send(1, 0.5);
+        studio.set_wow_flutter(0.5);
+        studio.set_vinyl_level(0.3);
+        studio.set_synth_variation_chance(0.5);
+        studio.start();
+
+        // Host-sized blocks larger than the engine's, with host events
+        // mixed in and the stutter engaged halfway through
+        let mut buffer = [0.0f32; 2048];
+        let mut right = [0.0f32; 1024];
+        let mut midi = [0u32; 64];
+        let mut multi = [0.0f32; MULTI_OUTPUTS * 256];
+        studio.process(&mut buffer);
+        let count = alloc_counter::allocations_in(|| {
+            for i in 0..100 {
+                studio.queue_host_events(&[10.0, 2.0, 0.0, 0.0, 20.0, 3.0, 0.0, 600.0]);
+                studio.set_stutter(i >= 50);
+                studio.process(&mut buffer);
+                studio.process_stereo(&mut buffer[..1024], &mut right);
+                studio.process_multi(&mut multi);
+                studio.drain_midi_out_into(&mut midi);
+            }
+        });
+        assert_eq!(count, 0);
     }
 }
@@ -0,0 +1,90 @@
+//! Parameter locks: synth knob settings a step overrides while it plays
+//!
+//! Elektron-style, a locked step sets its knobs when it starts and hands
+//! them back when the next step starts. Positions are stored like MIDI CC
+//! values, as a 0.0 - 1.0 knob position mapped through
+//! AutomationParam::scale_unit().
+
+use crate::automation::{AutomationParam, PARAM_COUNT};
+
+/// Knob positions a step locks, one byte per AutomationParam: 0 = not
+/// locked, 1 - 255 = position
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Locks([u8; PARAM_COUNT]);
+
+impl Locks {
+    pub const NONE: Locks = Locks([0; PARAM_COUNT]);
+
+    /// Lock `param` to a knob position (0.0 - 1.0)
+    pub fn set(&mut self, param: AutomationParam, position: f32) {
+        self.0[param.index()] = 1 + (position.clamp(0.0, 1.0) * 254.0).round() as u8;
+    }
+
+    pub fn clear(&mut self, param: AutomationParam) {
+        self.0[param.index()] = 0;
+    }
+
+    /// Knob position `param` is locked to, if it is
+    pub fn get(&self, param: AutomationParam) -> Option<f32> {
+        match self.0[param.index()] {
+            0 => None,
+            byte => Some((byte - 1) as f32 / 254.0),
+        }
+    }
+
+    /// Locked parameters and the values they are locked to, in the
+    /// parameter's own units
+    pub fn values(&self) -> impl Iterator<Item = (AutomationParam, f32)> + '_ {
+        AutomationParam::ALL
+            .into_iter()
+            .filter_map(|param| self.get(param).map(|position| (param, param.scale_unit(position))))
+    }
+
+    pub fn to_bytes(self) -> [u8; PARAM_COUNT] {
+        self.0
+    }
+
+    /// Unpack to_bytes(); missing trailing bytes read as unlocked
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut locks = Self::NONE;
+        for (lock, &byte) in locks.0.iter_mut().zip(bytes) {
+            *lock = byte;
+        }
+        locks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_clear() {
+        let mut locks = Locks::NONE;
+        locks.set(AutomationParam::Resonance, 0.5);
+        assert_eq!(locks.get(AutomationParam::Resonance), Some(0.5));
+        assert_eq!(locks.get(AutomationParam::Cutoff), None);
+        locks.clear(AutomationParam::Resonance);
+        assert_eq!(locks, Locks::NONE);
+    }
+
+    #[test]
+    fn test_values_are_scaled() {
+        let mut locks = Locks::NONE;
+        locks.set(AutomationParam::Cutoff, 1.0);
+        locks.set(AutomationParam::Distortion, 0.0);
+        let values: Vec<_> = locks.values().collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].0, AutomationParam::Cutoff);
+        assert!((values[0].1 - 20000.0).abs() < 1.0);
+        assert_eq!(values[1], (AutomationParam::Distortion, 0.0));
+    }
+
+    #[test]
+    fn test_byte_round_trip() {
+        let mut locks = Locks::NONE;
+        locks.set(AutomationParam::Decay, 0.25);
+        assert_eq!(Locks::from_bytes(&locks.to_bytes()), locks);
+        assert_eq!(Locks::from_bytes(&[]), Locks::NONE);
+    }
+}
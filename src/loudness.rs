@@ -0,0 +1,206 @@
+//! Loudness measurement and normalization for exported renders
+
+use std::f64::consts::PI;
+
+/// How an export should be normalized after rendering
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Normalize {
+    Off,
+    /// Scale so the highest sample peak sits at the given dBFS
+    Peak(f32),
+    /// Scale so integrated loudness (ITU-R BS.1770) hits the given LUFS,
+    /// never letting the peak exceed -1 dBFS
+    Lufs(f32),
+}
+
+/// Ceiling used to keep LUFS normalization from clipping
+const PEAK_CEILING_DB: f32 = -1.0;
+
+/// Absolute sample peak in dBFS (-inf for silence)
+pub fn peak_db(samples: &[f32]) -> f32 {
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    20.0 * peak.log10()
+}
+
+/// Gated integrated loudness in LUFS (-inf if everything is below the gate)
+/// of equal-length channels, each weighted equally as BS.1770 does for
+/// left and right
+pub fn integrated_lufs(channels: &[&[f32]], sample_rate: f32) -> f32 {
+    let weighted: Vec<Vec<f64>> = channels.iter().map(|c| k_weight(c, sample_rate as f64)).collect();
+    let len = weighted.iter().map(|w| w.len()).min().unwrap_or(0);
+
+    // 400ms blocks with 75% overlap
+    let block = (0.4 * sample_rate as f64) as usize;
+    let hop = (block / 4).max(1);
+    if block == 0 || len < block {
+        return f32::NEG_INFINITY;
+    }
+
+    let powers: Vec<f64> = (0..=(len - block) / hop)
+        .map(|i| {
+            let start = i * hop;
+            weighted
+                .iter()
+                .map(|w| w[start..start + block].iter().map(|x| x * x).sum::<f64>() / block as f64)
+                .sum()
+        })
+        .collect();
+
+    // Absolute gate at -70 LUFS, then relative gate 10 LU below
+    let absolute: Vec<f64> = powers.iter().copied().filter(|&p| power_to_lufs(p) > -70.0).collect();
+    if absolute.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let relative_gate = power_to_lufs(mean(&absolute)) - 10.0;
+    let gated: Vec<f64> = absolute.into_iter().filter(|&p| power_to_lufs(p) > relative_gate).collect();
+
+    power_to_lufs(mean(&gated)) as f32
+}
+
+/// Apply the normalization mode in place
+pub fn normalize(samples: &mut [f32], sample_rate: f32, mode: Normalize) {
+    if let Some(gain) = normalize_gain(&[samples], sample_rate, mode) {
+        for sample in samples.iter_mut() {
+            *sample *= gain;
+        }
+    }
+}
+
+/// Normalize a stereo pair with one gain, so the image is kept
+pub fn normalize_stereo(left: &mut [f32], right: &mut [f32], sample_rate: f32, mode: Normalize) {
+    if let Some(gain) = normalize_gain(&[left, right], sample_rate, mode) {
+        for sample in left.iter_mut().chain(right.iter_mut()) {
+            *sample *= gain;
+        }
+    }
+}
+
+/// Linear gain the mode calls for, or None to leave the audio alone
+fn normalize_gain(channels: &[&[f32]], sample_rate: f32, mode: Normalize) -> Option<f32> {
+    let peak = channels.iter().map(|c| peak_db(c)).fold(f32::NEG_INFINITY, f32::max);
+    let gain_db = match mode {
+        Normalize::Off => return None,
+        Normalize::Peak(target) => target - peak,
+        Normalize::Lufs(target) => {
+            let loudness = integrated_lufs(channels, sample_rate);
+            (target - loudness).min(PEAK_CEILING_DB - peak)
+        }
+    };
+    gain_db.is_finite().then(|| 10.0_f32.powf(gain_db / 20.0))
+}
+
+fn power_to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// BS.1770 K-weighting: high-shelf "head" filter followed by a high-pass
+fn k_weight(samples: &[f32], fs: f64) -> Vec<f64> {
+    // Stage 1: high shelf
+    let (f0, gain, q) = (1_681.974_450_955_533, 3.999_843_853_973_347, 0.707_175_236_955_419_6);
+    let k = (PI * f0 / fs).tan();
+    let vh = 10.0_f64.powf(gain / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    // Stage 2: high pass
+    let (f0, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+    let k = (PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+
+    let stage1 = shelf.run(samples.iter().map(|&s| s as f64));
+    highpass.run(stage1.into_iter())
+}
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    fn run(&self, input: impl Iterator<Item = f64>) -> Vec<f64> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        input
+            .map(|x| {
+                let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+                x2 = x1;
+                x1 = x;
+                y2 = y1;
+                y1 = y;
+                y
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f32, amp: f32, seconds: f32) -> Vec<f32> {
+        let len = (48000.0 * seconds) as usize;
+        (0..len)
+            .map(|i| amp * (2.0 * std::f32::consts::PI * freq * i as f32 / 48000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_peak_normalize() {
+        let mut samples = sine(440.0, 0.25, 0.5);
+        normalize(&mut samples, 48000.0, Normalize::Peak(-1.0));
+        assert!((peak_db(&samples) + 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_reference_tone_loudness() {
+        // A full-scale 1kHz sine measures about -3 LUFS in mono
+        let samples = sine(1000.0, 1.0, 2.0);
+        let lufs = integrated_lufs(&[&samples], 48000.0);
+        assert!((lufs + 3.01).abs() < 0.1, "measured {}", lufs);
+    }
+
+    #[test]
+    fn test_lufs_normalize_respects_ceiling() {
+        let mut samples = sine(1000.0, 0.1, 2.0);
+        normalize(&mut samples, 48000.0, Normalize::Lufs(-14.0));
+        assert!((integrated_lufs(&[&samples], 48000.0) + 14.0).abs() < 0.1);
+
+        // Asking for something absurdly loud stops at the peak ceiling
+        normalize(&mut samples, 48000.0, Normalize::Lufs(0.0));
+        assert!(peak_db(&samples) <= -0.99);
+    }
+
+    #[test]
+    fn test_stereo_sums_channel_power() {
+        // The same tone in both channels reads 3 LU louder than in one
+        let samples = sine(1000.0, 0.5, 2.0);
+        let mono = integrated_lufs(&[&samples], 48000.0);
+        let stereo = integrated_lufs(&[&samples, &samples], 48000.0);
+        assert!((stereo - mono - 3.01).abs() < 0.05, "mono {} stereo {}", mono, stereo);
+
+        let mut left = samples.clone();
+        let mut right: Vec<f32> = samples.iter().map(|s| s * 0.5).collect();
+        normalize_stereo(&mut left, &mut right, 48000.0, Normalize::Peak(-1.0));
+        assert!((peak_db(&left) + 1.0).abs() < 0.01);
+        assert!((peak_db(&right) + 7.02).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_silence_untouched() {
+        let mut samples = vec![0.0f32; 48000];
+        normalize(&mut samples, 48000.0, Normalize::Lufs(-14.0));
+        normalize(&mut samples, 48000.0, Normalize::Peak(-1.0));
+        assert!(samples.iter().all(|&s| s == 0.0));
+    }
+}
@@ -0,0 +1,408 @@
+//! MIDI message constants and note mappings
+
+use crate::automation::AutomationParam;
+use crate::drums::DrumTrack;
+
+// Channel voice message types (upper nibble of the status byte)
+pub const NOTE_OFF: u8 = 0x80;
+pub const NOTE_ON: u8 = 0x90;
+pub const CONTROL_CHANGE: u8 = 0xB0;
+pub const PITCH_BEND: u8 = 0xE0;
+
+// System messages
+pub const SONG_POSITION: u8 = 0xF2;
+pub const TIMING_CLOCK: u8 = 0xF8;
+pub const START: u8 = 0xFA;
+pub const CONTINUE: u8 = 0xFB;
+pub const STOP: u8 = 0xFC;
+
+/// MIDI clock ticks per quarter note, and per sixteenth step
+pub const CLOCKS_PER_BEAT: u32 = 24;
+pub const CLOCKS_PER_STEP: u32 = 6;
+
+/// General MIDI percussion channel (channel 10, zero-based)
+pub const DRUM_CHANNEL: u8 = 9;
+
+/// Velocities sent for normal and accented sequencer steps
+const VELOCITY_NORMAL: u8 = 100;
+const VELOCITY_ACCENT: u8 = 127;
+
+/// Incoming notes at or above this velocity play accented
+pub const ACCENT_VELOCITY: u8 = 100;
+
+/// Pitch bend range in semitones either side of centre
+pub const BEND_RANGE: f32 = 2.0;
+
+/// Outgoing events kept between drains before new ones are dropped
+const OUT_QUEUE_CAPACITY: usize = 256;
+
+/// Keys remembered while held; pressing more forgets the oldest
+const MAX_HELD_KEYS: usize = 16;
+
+/// Map a General MIDI percussion note to a drum voice
+pub fn gm_drum(note: u8) -> Option<DrumTrack> {
+    match note {
+        35 | 36 => Some(DrumTrack::Kick),          // Acoustic / Bass Drum 1
+        37 | 38 | 40 => Some(DrumTrack::Snare),    // Side Stick, Snare, Electric Snare
+        39 => Some(DrumTrack::Clap),               // Hand Clap
+        42 | 44 => Some(DrumTrack::ClosedHH),      // Closed / Pedal Hi-Hat
+        46 => Some(DrumTrack::OpenHH),             // Open Hi-Hat
+        _ => None,
+    }
+}
+
+/// Pitch bend message data as semitones (-BEND_RANGE to BEND_RANGE)
+pub fn bend_semitones(lsb: u8, msb: u8) -> f32 {
+    let value = ((msb as i32 & 0x7F) << 7 | (lsb as i32 & 0x7F)) - 8192;
+    value as f32 / 8192.0 * BEND_RANGE
+}
+
+/// A MIDI message at a sample offset within the block it was generated in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MidiEvent {
+    pub offset: u32,
+    pub status: u8,
+    pub data1: u8,
+    pub data2: u8,
+}
+
+/// Queue of outgoing note events generated by the sequencer, drained by the
+/// host once per block. Storage is reserved up front so queuing never allocates.
+pub struct MidiOut {
+    events: Vec<MidiEvent>,
+    enabled: bool,
+    channel: u8,
+    held_note: Option<u8>,
+}
+
+impl MidiOut {
+    pub fn new() -> Self {
+        Self {
+            events: Vec::with_capacity(OUT_QUEUE_CAPACITY),
+            enabled: false,
+            channel: 0,
+            held_note: None,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.events.clear();
+            self.held_note = None;
+        }
+    }
+
+    pub fn set_channel(&mut self, channel: u8) {
+        self.channel = channel & 0x0F;
+    }
+
+    /// Queue a sequencer note. Slides start the new note before releasing the
+    /// held one, so mono hardware synths play them legato.
+    pub fn note(&mut self, offset: u32, note: u8, accent: bool, slide: bool) {
+        let velocity = if accent { VELOCITY_ACCENT } else { VELOCITY_NORMAL };
+        let previous = self.held_note.take();
+
+        if slide && previous == Some(note) {
+            // Sliding into the same pitch just keeps the note held
+        } else if slide {
+            self.push(offset, NOTE_ON, note, velocity);
+            if let Some(prev) = previous {
+                self.push(offset, NOTE_OFF, prev, 0);
+            }
+        } else {
+            if let Some(prev) = previous {
+                self.push(offset, NOTE_OFF, prev, 0);
+            }
+            self.push(offset, NOTE_ON, note, velocity);
+        }
+        self.held_note = Some(note);
+    }
+
+    /// Release the held note, if any
+    pub fn release(&mut self, offset: u32) {
+        if let Some(prev) = self.held_note.take() {
+            self.push(offset, NOTE_OFF, prev, 0);
+        }
+    }
+
+    /// Take all queued events, flattened as [offset, status, data1, data2, ...]
+    pub fn drain(&mut self) -> Vec<u32> {
+        let packed = self.events
+            .iter()
+            .flat_map(|e| [e.offset, e.status as u32, e.data1 as u32, e.data2 as u32])
+            .collect();
+        self.events.clear();
+        packed
+    }
+
+    /// Move as many queued events as fit into `out`, in the drain() layout,
+    /// without allocating. Returns the number of values written.
+    pub fn drain_into(&mut self, out: &mut [u32]) -> usize {
+        let count = self.events.len().min(out.len() / 4);
+        for (slot, e) in out.chunks_exact_mut(4).zip(self.events.drain(..count)) {
+            slot.copy_from_slice(&[e.offset, e.status as u32, e.data1 as u32, e.data2 as u32]);
+        }
+        count * 4
+    }
+
+    fn push(&mut self, offset: u32, kind: u8, data1: u8, data2: u8) {
+        if self.enabled && self.events.len() < OUT_QUEUE_CAPACITY {
+            self.events.push(MidiEvent { offset, status: kind | self.channel, data1, data2 });
+        }
+    }
+}
+
+impl Default for MidiOut {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Which synth parameter each incoming CC number controls, with a learn
+/// mode that assigns the next CC received
+pub struct CcMap {
+    params: [Option<AutomationParam>; 128],
+    learning: Option<AutomationParam>,
+}
+
+impl CcMap {
+    /// Starts with the common assignments: CC 74 cutoff, CC 71 resonance,
+    /// CC 70 env mod, CC 75 decay, CC 76 accent and CC 77 distortion
+    pub fn new() -> Self {
+        let mut params = [None; 128];
+        params[74] = Some(AutomationParam::Cutoff);
+        params[71] = Some(AutomationParam::Resonance);
+        params[70] = Some(AutomationParam::EnvMod);
+        params[75] = Some(AutomationParam::Decay);
+        params[76] = Some(AutomationParam::Accent);
+        params[77] = Some(AutomationParam::Distortion);
+        Self { params, learning: None }
+    }
+
+    /// Assign the next CC received to `param`
+    pub fn start_learn(&mut self, param: AutomationParam) {
+        self.learning = Some(param);
+    }
+
+    pub fn cancel_learn(&mut self) {
+        self.learning = None;
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learning.is_some()
+    }
+
+    /// Handle an incoming CC. While learning this captures the mapping and
+    /// returns None; otherwise returns the mapped parameter and its value.
+    pub fn handle(&mut self, cc: u8, value: u8) -> Option<(AutomationParam, f32)> {
+        let cc = (cc & 0x7F) as usize;
+        if let Some(param) = self.learning.take() {
+            // One CC per parameter: learning moves the assignment
+            for slot in self.params.iter_mut().filter(|p| **p == Some(param)) {
+                *slot = None;
+            }
+            self.params[cc] = Some(param);
+            return None;
+        }
+        let param = self.params[cc]?;
+        Some((param, param.scale_unit((value & 0x7F) as f32 / 127.0)))
+    }
+
+    pub fn clear(&mut self) {
+        self.params = [None; 128];
+    }
+
+    /// Mapping as [cc, parameter] byte pairs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.params
+            .iter()
+            .enumerate()
+            .filter_map(|(cc, p)| p.map(|p| [cc as u8, p.index() as u8]))
+            .flatten()
+            .collect()
+    }
+
+    /// Replace the mapping from to_bytes() pairs. Returns false and keeps
+    /// the current mapping if any pair is invalid.
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> bool {
+        if !bytes.len().is_multiple_of(2) {
+            return false;
+        }
+        let mut params = [None; 128];
+        for pair in bytes.chunks_exact(2) {
+            let Some(param) = AutomationParam::from_index(pair[1]) else {
+                return false;
+            };
+            let Some(slot) = params.get_mut(pair[0] as usize) else {
+                return false;
+            };
+            *slot = Some(param);
+        }
+        self.params = params;
+        true
+    }
+}
+
+impl Default for CcMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keys held down, most recent last, so a mono voice can go back to the
+/// key still held when the one sounding is let go
+pub struct HeldKeys {
+    keys: [u8; MAX_HELD_KEYS],
+    count: usize,
+}
+
+impl HeldKeys {
+    pub fn new() -> Self {
+        Self {
+            keys: [0; MAX_HELD_KEYS],
+            count: 0,
+        }
+    }
+
+    /// A key went down; one already held moves to the top
+    pub fn press(&mut self, key: u8) {
+        self.release(key);
+        if self.count == MAX_HELD_KEYS {
+            self.keys.copy_within(1.., 0);
+            self.count -= 1;
+        }
+        self.keys[self.count] = key;
+        self.count += 1;
+    }
+
+    /// A key came up; keys that aren't held are ignored
+    pub fn release(&mut self, key: u8) {
+        if let Some(i) = self.keys[..self.count].iter().position(|&k| k == key) {
+            self.keys.copy_within(i + 1..self.count, i);
+            self.count -= 1;
+        }
+    }
+
+    /// The most recently pressed key still held
+    pub fn top(&self) -> Option<u8> {
+        self.keys[..self.count].last().copied()
+    }
+}
+
+impl Default for HeldKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gm_drum_map() {
+        assert_eq!(gm_drum(36), Some(DrumTrack::Kick));
+        assert_eq!(gm_drum(38), Some(DrumTrack::Snare));
+        assert_eq!(gm_drum(42), Some(DrumTrack::ClosedHH));
+        assert_eq!(gm_drum(46), Some(DrumTrack::OpenHH));
+        assert_eq!(gm_drum(39), Some(DrumTrack::Clap));
+        assert_eq!(gm_drum(60), None);
+    }
+
+    #[test]
+    fn test_bend_semitones() {
+        assert_eq!(bend_semitones(0, 64), 0.0);
+        assert_eq!(bend_semitones(0, 0), -BEND_RANGE);
+        assert!((bend_semitones(127, 127) - BEND_RANGE).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_cc_learn() {
+        let mut map = CcMap::new();
+        map.start_learn(AutomationParam::Cutoff);
+        assert_eq!(map.handle(20, 64), None);
+        assert!(!map.is_learning());
+
+        // The old CC 74 assignment moved to CC 20
+        assert_eq!(map.handle(74, 127), None);
+        let (param, value) = map.handle(20, 127).unwrap();
+        assert_eq!(param, AutomationParam::Cutoff);
+        assert!((value - 20000.0).abs() < 1.0);
+
+        let mut copy = CcMap::new();
+        copy.clear();
+        assert!(copy.load_bytes(&map.to_bytes()));
+        assert_eq!(copy.to_bytes(), map.to_bytes());
+        assert!(!copy.load_bytes(&[20, 99]));
+    }
+
+    #[test]
+    fn test_held_keys_return_to_last_held() {
+        let mut keys = HeldKeys::new();
+        keys.press(48);
+        keys.press(55);
+        keys.press(60);
+        keys.release(55);
+        assert_eq!(keys.top(), Some(60));
+        keys.release(60);
+        assert_eq!(keys.top(), Some(48));
+        keys.release(61);
+        keys.release(48);
+        assert_eq!(keys.top(), None);
+
+        // Past the limit the oldest key goes
+        for key in 0..=MAX_HELD_KEYS as u8 {
+            keys.press(key);
+        }
+        for key in (1..=MAX_HELD_KEYS as u8).rev() {
+            assert_eq!(keys.top(), Some(key));
+            keys.release(key);
+        }
+        assert_eq!(keys.top(), None);
+    }
+
+    #[test]
+    fn test_midi_out_disabled_by_default() {
+        let mut out = MidiOut::new();
+        out.note(0, 36, false, false);
+        assert!(out.drain().is_empty());
+    }
+
+    #[test]
+    fn test_midi_out_notes() {
+        let mut out = MidiOut::new();
+        out.set_enabled(true);
+        out.set_channel(2);
+        out.note(0, 36, true, false);
+        out.note(10, 48, false, false);
+        assert_eq!(out.drain(), vec![0, 0x92, 36, 127, 10, 0x82, 36, 0, 10, 0x92, 48, 100]);
+        assert!(out.drain().is_empty());
+    }
+
+    #[test]
+    fn test_midi_out_slide_overlaps() {
+        let mut out = MidiOut::new();
+        out.set_enabled(true);
+        out.note(0, 36, false, false);
+        out.note(5, 43, false, true);
+        out.release(9);
+        let events = out.drain();
+        // New note starts before the old one ends
+        assert_eq!(&events[4..], &[5, 0x90, 43, 100, 5, 0x80, 36, 0, 9, 0x80, 43, 0]);
+    }
+
+    #[test]
+    fn test_drain_into_keeps_overflow_queued() {
+        let mut out = MidiOut::new();
+        out.set_enabled(true);
+        out.note(0, 36, true, false);
+        out.note(10, 48, false, false);
+
+        // Room for two events out of three
+        let mut buffer = [0u32; 10];
+        assert_eq!(out.drain_into(&mut buffer), 8);
+        assert_eq!(&buffer[..8], &[0, 0x90, 36, 127, 10, 0x80, 36, 0]);
+        assert_eq!(out.drain(), vec![10, 0x90, 48, 100]);
+    }
+}
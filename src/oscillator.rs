@@ -1,43 +1,200 @@
+use crate::rng::Rng;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Waveform {
     Saw,
     Square,
+    Triangle,
+    /// Square with a variable duty cycle, set by the pulse width
+    Pulse,
+    /// White noise, ignoring the pitch
+    Noise,
 }
 
-/// Band-limited oscillator using PolyBLEP for anti-aliasing
+impl Waveform {
+    /// 0 = saw, 1 = square, 2 = triangle, 3 = pulse, 4 = noise
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Waveform::Saw),
+            1 => Some(Waveform::Square),
+            2 => Some(Waveform::Triangle),
+            3 => Some(Waveform::Pulse),
+            4 => Some(Waveform::Noise),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+}
+
+/// How the oscillator suppresses aliasing, from cheapest to cleanest
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AntiAlias {
+    /// Two-sample polynomial BLEP
+    PolyBlep,
+    /// Four-sample BLEP from an integrated cubic B-spline
+    Blep4,
+    /// Naive waveform rendered at 8x and filtered down
+    Oversampled,
+}
+
+impl AntiAlias {
+    /// 0 = PolyBLEP, 1 = four-point BLEP, 2 = oversampled
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(AntiAlias::PolyBlep),
+            1 => Some(AntiAlias::Blep4),
+            2 => Some(AntiAlias::Oversampled),
+            _ => None,
+        }
+    }
+}
+
+/// Band-limited oscillator, using PolyBLEP for anti-aliasing by default
+#[wasm_bindgen]
 pub struct Oscillator {
     sample_rate: f32,
     phase: f32,
     frequency: f32,
     waveform: Waveform,
+    anti_alias: AntiAlias,
+    decimator: Decimator,
+
+    // Pulse duty cycle and the modulation added to it
+    pulse_width: f32,
+    pwm: f32,
+
+    noise: Rng,
+
+    // Anti-click: offset added after a discontinuity, decaying to zero
+    last_output: f32,
+    declick_offset: f32,
+    declick_decay: f32,
+    declick_pending: bool,
 }
 
+/// Time for the anti-click offset to fade out
+const DECLICK_MS: f32 = 2.0;
+
+/// Narrowest and widest duty cycles, short of the pulse vanishing
+const MIN_PULSE_WIDTH: f32 = 0.05;
+const MAX_PULSE_WIDTH: f32 = 0.95;
+
+/// Sub-samples rendered per output sample in oversampled mode
+const OVERSAMPLE: usize = 8;
+
+#[wasm_bindgen]
 impl Oscillator {
+    #[wasm_bindgen(constructor)]
     pub fn new(sample_rate: f32) -> Self {
         Self {
             sample_rate,
             phase: 0.0,
             frequency: 440.0,
             waveform: Waveform::Saw,
+            anti_alias: AntiAlias::PolyBlep,
+            decimator: Decimator::new(),
+            pulse_width: 0.5,
+            pwm: 0.0,
+            noise: Rng::new(0x303),
+            last_output: 0.0,
+            declick_offset: 0.0,
+            declick_decay: (-1.0 / (DECLICK_MS / 1000.0 * sample_rate)).exp(),
+            declick_pending: false,
         }
     }
 
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.declick_decay = (-1.0 / (DECLICK_MS / 1000.0 * sample_rate)).exp();
+    }
+
     pub fn set_frequency(&mut self, freq: f32) {
         self.frequency = freq.clamp(20.0, 20000.0);
     }
 
     pub fn set_waveform(&mut self, waveform: Waveform) {
-        self.waveform = waveform;
+        if waveform != self.waveform {
+            self.waveform = waveform;
+            self.declick_pending = true;
+        }
+    }
+
+    pub fn waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    /// Duty cycle of the pulse wave (0.05 - 0.95, 0.5 is square)
+    pub fn set_pulse_width(&mut self, width: f32) {
+        self.pulse_width = width.clamp(MIN_PULSE_WIDTH, MAX_PULSE_WIDTH);
+    }
+
+    pub fn pulse_width(&self) -> f32 {
+        self.pulse_width
+    }
+
+    /// Offset added to the pulse width, e.g. from an LFO
+    pub fn set_pulse_width_mod(&mut self, amount: f32) {
+        self.pwm = amount;
+    }
+
+    pub fn set_anti_alias(&mut self, mode: AntiAlias) {
+        if mode != self.anti_alias {
+            self.anti_alias = mode;
+            self.decimator.reset();
+            self.declick_pending = true;
+        }
+    }
+
+    pub fn anti_alias(&self) -> AntiAlias {
+        self.anti_alias
+    }
+
+    /// Jump to `phase` (0.0 - 1.0) in the cycle without smoothing, for
+    /// setting up an oscillator before it plays
+    pub fn set_phase(&mut self, phase: f32) {
+        self.phase = phase.rem_euclid(1.0);
+    }
+
+    /// Restart the waveform from the beginning of its cycle
+    pub fn reset_phase(&mut self) {
+        self.phase = 0.0;
+        self.declick_pending = true;
     }
 
     pub fn process(&mut self) -> f32 {
         let phase_inc = self.frequency / self.sample_rate;
 
-        let output = match self.waveform {
-            Waveform::Saw => self.saw_polyblep(phase_inc),
-            Waveform::Square => self.square_polyblep(phase_inc),
+        let raw = match (self.anti_alias, self.waveform) {
+            // Noise has no edges to band-limit
+            (_, Waveform::Noise) => self.noise.next_f32() * 2.0 - 1.0,
+            (AntiAlias::PolyBlep, Waveform::Saw) => self.saw_polyblep(phase_inc),
+            (AntiAlias::PolyBlep, Waveform::Square) => self.square_polyblep(phase_inc),
+            (AntiAlias::PolyBlep, Waveform::Pulse) => self.pulse_polyblep(phase_inc),
+            (AntiAlias::Blep4, Waveform::Saw) => self.saw_blep4(phase_inc),
+            (AntiAlias::Blep4, Waveform::Square) => self.square_blep4(phase_inc),
+            (AntiAlias::Blep4, Waveform::Pulse) => self.pulse_blep4(phase_inc),
+            // The triangle has corners rather than steps, so both BLEP
+            // modes round them with a BLAMP
+            (AntiAlias::PolyBlep | AntiAlias::Blep4, Waveform::Triangle) => self.triangle_polyblamp(phase_inc),
+            (AntiAlias::Oversampled, _) => self.oversampled(phase_inc),
         };
 
+        // Bridge jumps caused by waveform switches or phase resets with an
+        // offset that fades out over a couple of milliseconds
+        if self.declick_pending {
+            self.declick_offset += self.last_output - (raw + self.declick_offset);
+            self.declick_pending = false;
+        }
+        let output = raw + self.declick_offset;
+        self.declick_offset *= self.declick_decay;
+        self.last_output = output;
+
         // Advance phase
         self.phase += phase_inc;
         if self.phase >= 1.0 {
@@ -47,6 +204,13 @@ impl Oscillator {
         output
     }
 
+    /// Fill `output` with consecutive samples
+    pub fn process_block(&mut self, output: &mut [f32]) {
+        for sample in output.iter_mut() {
+            *sample = self.process();
+        }
+    }
+
     /// Sawtooth wave with PolyBLEP anti-aliasing
     fn saw_polyblep(&self, phase_inc: f32) -> f32 {
         // Naive sawtooth: goes from -1 to 1 over one period
@@ -69,6 +233,65 @@ impl Oscillator {
         output
     }
 
+    /// Pulse wave with PolyBLEP at the rising edge and the falling edge
+    /// set by the width, centred so the duty cycle doesn't shift its DC
+    fn pulse_polyblep(&self, phase_inc: f32) -> f32 {
+        let width = self.width();
+        let naive = naive_pulse(self.phase, width);
+        naive + self.polyblep(self.phase, phase_inc) - self.polyblep((self.phase + 1.0 - width) % 1.0, phase_inc)
+    }
+
+    /// Triangle with PolyBLAMP rounding its two corners
+    fn triangle_polyblamp(&self, phase_inc: f32) -> f32 {
+        // The slope flips by 8 per cycle at each corner, and the BLAMP is
+        // scaled, like polyblep(), for a change of two
+        let corner = 4.0 * phase_inc;
+        naive_triangle(self.phase) + corner * (polyblamp(self.phase, phase_inc) - polyblamp((self.phase + 0.5) % 1.0, phase_inc))
+    }
+
+    /// Sawtooth with the four-point BLEP
+    fn saw_blep4(&self, phase_inc: f32) -> f32 {
+        2.0 * self.phase - 1.0 - blep4(self.phase, phase_inc)
+    }
+
+    /// Square with the four-point BLEP at both transitions
+    fn square_blep4(&self, phase_inc: f32) -> f32 {
+        let naive = if self.phase < 0.5 { 1.0 } else { -1.0 };
+        naive + blep4(self.phase, phase_inc) - blep4((self.phase + 0.5) % 1.0, phase_inc)
+    }
+
+    /// Pulse with the four-point BLEP at both edges
+    fn pulse_blep4(&self, phase_inc: f32) -> f32 {
+        let width = self.width();
+        naive_pulse(self.phase, width) + blep4(self.phase, phase_inc) - blep4((self.phase + 1.0 - width) % 1.0, phase_inc)
+    }
+
+    /// Pulse width with its modulation applied
+    fn width(&self) -> f32 {
+        (self.pulse_width + self.pwm).clamp(MIN_PULSE_WIDTH, MAX_PULSE_WIDTH)
+    }
+
+    /// Naive waveform at OVERSAMPLE times the rate through the decimation
+    /// filter, keeping the last sub-sample
+    fn oversampled(&mut self, phase_inc: f32) -> f32 {
+        let step = phase_inc / OVERSAMPLE as f32;
+        let width = self.width();
+        let mut out = 0.0;
+        for i in 0..OVERSAMPLE {
+            let phase = (self.phase + step * i as f32) % 1.0;
+            let naive = match self.waveform {
+                Waveform::Saw => 2.0 * phase - 1.0,
+                Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+                Waveform::Triangle => naive_triangle(phase),
+                Waveform::Pulse => naive_pulse(phase, width),
+                // Noise is rendered directly and never gets here
+                Waveform::Noise => 0.0,
+            };
+            out = self.decimator.process(naive);
+        }
+        out
+    }
+
     /// PolyBLEP (polynomial band-limited step) correction
     /// Smooths discontinuities to reduce aliasing
     fn polyblep(&self, t: f32, dt: f32) -> f32 {
@@ -86,6 +309,108 @@ impl Oscillator {
     }
 }
 
+/// Triangle from -1 at phase 0 up to 1 at phase 0.5 and back
+fn naive_triangle(phase: f32) -> f32 {
+    1.0 - 4.0 * (phase - 0.5).abs()
+}
+
+/// Pulse high for `width` of the cycle, offset to average zero
+fn naive_pulse(phase: f32, width: f32) -> f32 {
+    let naive = if phase < width { 1.0 } else { -1.0 };
+    naive - (2.0 * width - 1.0)
+}
+
+/// PolyBLAMP (band-limited ramp) correction for a corner at phase 0 where
+/// the slope rises by two per sample, the integral of polyblep()
+fn polyblamp(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = t / dt - 1.0;
+        -x * x * x / 3.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        x * x * x / 3.0
+    } else {
+        0.0
+    }
+}
+
+/// Four-point BLEP correction for a rising step of 2 at phase 0, the
+/// residual of an integrated cubic B-spline spanning two samples each side
+fn blep4(t: f32, dt: f32) -> f32 {
+    // Keep the two windows from overlapping at very high pitches
+    let dt = dt.min(0.25);
+    if t < 2.0 * dt {
+        -2.0 * spline_step(-t / dt)
+    } else if t > 1.0 - 2.0 * dt {
+        2.0 * spline_step((t - 1.0) / dt)
+    } else {
+        0.0
+    }
+}
+
+/// Integral of the cubic B-spline from -2 to `x`, for `x` in -2..=0
+fn spline_step(x: f32) -> f32 {
+    if x < -1.0 {
+        (2.0 + x).powi(4) / 24.0
+    } else {
+        0.5 + x * (2.0 / 3.0) - x.powi(3) / 3.0 - x.powi(4) / 8.0
+    }
+}
+
+/// Eighth-order Butterworth lowpass run at the oversampled rate, cutting
+/// just below the output Nyquist frequency
+struct Decimator {
+    sections: [Biquad; 4],
+}
+
+impl Decimator {
+    fn new() -> Self {
+        // Cutoff as a fraction of the oversampled rate
+        let cutoff = 0.45 / OVERSAMPLE as f32;
+        Self {
+            sections: [0.5098, 0.6013, 0.9000, 2.5629].map(|q| Biquad::lowpass(cutoff, q)),
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        self.sections.iter_mut().fold(input, |x, section| section.process(x))
+    }
+
+    fn reset(&mut self) {
+        for section in &mut self.sections {
+            section.z = [0.0; 2];
+        }
+    }
+}
+
+/// Transposed direct form II biquad
+struct Biquad {
+    b: [f32; 3],
+    a: [f32; 2],
+    z: [f32; 2],
+}
+
+impl Biquad {
+    fn lowpass(cutoff: f32, q: f32) -> Self {
+        let w = 2.0 * std::f32::consts::PI * cutoff;
+        let alpha = w.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        let b1 = (1.0 - w.cos()) / a0;
+        Self {
+            b: [b1 / 2.0, b1, b1 / 2.0],
+            a: [-2.0 * w.cos() / a0, (1.0 - alpha) / a0],
+            z: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,7 +429,7 @@ mod tests {
 
         for _ in 0..1000 {
             let sample = osc.process();
-            assert!(sample >= -1.5 && sample <= 1.5);
+            assert!((-1.5..=1.5).contains(&sample));
         }
     }
 
@@ -116,8 +441,151 @@ mod tests {
 
         for _ in 0..1000 {
             let sample = osc.process();
-            assert!(sample >= -1.5 && sample <= 1.5);
+            assert!((-1.5..=1.5).contains(&sample));
+        }
+    }
+
+    #[test]
+    fn test_waveform_switch_is_click_free() {
+        let mut osc = Oscillator::new(44100.0);
+        osc.set_frequency(110.0);
+
+        // Run to mid-cycle where saw and square differ the most
+        for _ in 0..100 {
+            osc.process();
         }
+        let before = osc.process();
+        osc.set_waveform(Waveform::Square);
+        let after = osc.process();
+        assert!((after - before).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_phase_reset_is_click_free() {
+        let mut osc = Oscillator::new(44100.0);
+        osc.set_frequency(110.0);
+        for _ in 0..150 {
+            osc.process();
+        }
+        let before = osc.process();
+        osc.reset_phase();
+        let after = osc.process();
+        assert!((after - before).abs() < 0.1);
+    }
+
+    /// Share of a saw's energy that lands between its harmonics, in dB
+    fn aliasing_db(mut render: impl FnMut() -> f32) -> f32 {
+        // 91 cycles in 2048 samples puts every harmonic on a bin and every
+        // alias between them
+        const N: usize = 2048;
+        const CYCLES: usize = 91;
+        for _ in 0..N {
+            render();
+        }
+        let samples: Vec<f32> = (0..N).map(|_| render()).collect();
+        let (mut harmonic, mut alias) = (0.0f64, 0.0f64);
+        for bin in 1..N / 2 {
+            let (mut re, mut im) = (0.0f64, 0.0f64);
+            for (i, &s) in samples.iter().enumerate() {
+                let angle = std::f64::consts::TAU * ((bin * i) % N) as f64 / N as f64;
+                re += s as f64 * angle.cos();
+                im -= s as f64 * angle.sin();
+            }
+            let power = re * re + im * im;
+            if bin % CYCLES == 0 { harmonic += power } else { alias += power }
+        }
+        (10.0 * (alias / harmonic).log10()) as f32
+    }
+
+    fn osc_aliasing(mode: AntiAlias) -> f32 {
+        let mut osc = Oscillator::new(44100.0);
+        osc.set_anti_alias(mode);
+        osc.set_frequency(91.0 * 44100.0 / 2048.0);
+        aliasing_db(|| osc.process())
+    }
+
+    #[test]
+    fn test_anti_alias_modes_reduce_aliasing() {
+        let mut phase = 0.0f32;
+        let naive = aliasing_db(|| {
+            phase = (phase + 91.0 / 2048.0) % 1.0;
+            2.0 * phase - 1.0
+        });
+        let polyblep = osc_aliasing(AntiAlias::PolyBlep);
+        let blep4 = osc_aliasing(AntiAlias::Blep4);
+        let oversampled = osc_aliasing(AntiAlias::Oversampled);
+        assert!(polyblep < naive - 10.0, "naive {} polyblep {}", naive, polyblep);
+        assert!(blep4 < polyblep - 3.0, "polyblep {} blep4 {}", polyblep, blep4);
+        assert!(oversampled < naive - 10.0, "naive {} oversampled {}", naive, oversampled);
+    }
+
+    #[test]
+    fn test_triangle_and_pulse_are_band_limited() {
+        let mut phase = 0.0f32;
+        let naive_triangle_db = aliasing_db(|| {
+            phase = (phase + 91.0 / 2048.0) % 1.0;
+            naive_triangle(phase)
+        });
+        let mut phase = 0.0f32;
+        let naive_pulse_db = aliasing_db(|| {
+            phase = (phase + 91.0 / 2048.0) % 1.0;
+            naive_pulse(phase, 0.3)
+        });
+        for mode in [AntiAlias::PolyBlep, AntiAlias::Blep4, AntiAlias::Oversampled] {
+            let render = |waveform: Waveform| {
+                let mut osc = Oscillator::new(44100.0);
+                osc.set_anti_alias(mode);
+                osc.set_waveform(waveform);
+                osc.set_pulse_width(0.3);
+                osc.set_frequency(91.0 * 44100.0 / 2048.0);
+                aliasing_db(|| osc.process())
+            };
+            let triangle = render(Waveform::Triangle);
+            let pulse = render(Waveform::Pulse);
+            assert!(triangle < naive_triangle_db - 6.0, "{:?} naive {} triangle {}", mode, naive_triangle_db, triangle);
+            assert!(pulse < naive_pulse_db - 10.0, "{:?} naive {} pulse {}", mode, naive_pulse_db, pulse);
+        }
+    }
+
+    #[test]
+    fn test_pulse_width_and_modulation() {
+        // Share of the cycle spent high
+        let duty = |width: f32, modulation: f32| {
+            let mut osc = Oscillator::new(44100.0);
+            osc.set_waveform(Waveform::Pulse);
+            osc.set_frequency(100.0);
+            osc.set_pulse_width(width);
+            osc.set_pulse_width_mod(modulation);
+            let samples: Vec<f32> = (0..4410).map(|_| osc.process()).collect();
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            assert!(mean.abs() < 0.02, "width {} mean {}", width, mean);
+            samples.iter().filter(|&&s| s > mean).count() as f32 / samples.len() as f32
+        };
+        assert!((duty(0.5, 0.0) - 0.5).abs() < 0.02);
+        assert!((duty(0.2, 0.0) - 0.2).abs() < 0.02);
+        assert!((duty(0.2, 0.3) - 0.5).abs() < 0.02);
+        // Modulation can't push past the narrowest pulse
+        assert!((duty(0.2, -1.0) - MIN_PULSE_WIDTH).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_noise() {
+        let mut osc = Oscillator::new(44100.0);
+        osc.set_waveform(Waveform::Noise);
+        let samples: Vec<f32> = (0..4410).map(|_| osc.process()).collect();
+        assert!(samples.iter().all(|s| (-1.5..=1.5).contains(s)));
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        assert!(mean.abs() < 0.05);
+        // Neighbouring samples are unrelated
+        let correlation: f32 = samples.windows(2).map(|w| w[0] * w[1]).sum::<f32>() / samples.len() as f32;
+        assert!(correlation.abs() < 0.05);
+    }
+
+    #[test]
+    fn test_waveform_from_index() {
+        assert_eq!(Waveform::from_index(2), Some(Waveform::Triangle));
+        assert_eq!(Waveform::from_index(4).map(Waveform::index), Some(4));
+        assert_eq!(Waveform::from_index(5), None);
     }
 
     #[test]
@@ -0,0 +1,41 @@
+//! Pan law shared by the synth bus and the drum voices
+
+/// Left and right gains for `pan` (-1.0 = hard left, 1.0 = hard right).
+/// Both channels stay at unity in the centre, so a centred mix folds back
+/// to exactly the mono output, and the far side fades out as a source
+/// moves across.
+pub fn pan_gains(pan: f32) -> (f32, f32) {
+    let pan = pan.clamp(-1.0, 1.0);
+    ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+}
+
+/// Place a mono sample in the stereo field
+pub fn pan(sample: f32, pan: f32) -> (f32, f32) {
+    let (left, right) = pan_gains(pan);
+    (sample * left, sample * right)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_centre_is_unity() {
+        assert_eq!(pan_gains(0.0), (1.0, 1.0));
+        assert_eq!(pan(0.5, 0.0), (0.5, 0.5));
+    }
+
+    #[test]
+    fn test_hard_pan_silences_other_side() {
+        assert_eq!(pan_gains(-1.0), (1.0, 0.0));
+        assert_eq!(pan_gains(1.0), (0.0, 1.0));
+        assert_eq!(pan_gains(4.0), (0.0, 1.0));
+    }
+
+    #[test]
+    fn test_partial_pan_fades_far_side() {
+        let (left, right) = pan_gains(0.5);
+        assert_eq!(left, 0.5);
+        assert_eq!(right, 1.0);
+    }
+}
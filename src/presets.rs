@@ -1,4 +1,6 @@
-use crate::sequencer::Step;
+use crate::sequencer::{Step, DEFAULT_GATE, FULL_LEVEL};
+use crate::locks::Locks;
+use crate::trig::Trig;
 
 /// A complete preset with pattern and synth settings
 pub struct Preset {
@@ -14,11 +16,11 @@ pub struct Preset {
 
 // Helper to create steps more easily
 const fn step(note: u8, accent: bool, slide: bool, active: bool) -> Step {
-    Step { note, accent, slide, active }
+    Step { note, accent, slide, active, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE }
 }
 
 const fn rest() -> Step {
-    Step { note: 36, accent: false, slide: false, active: false }
+    Step { note: 36, accent: false, slide: false, active: false, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE }
 }
 
 /// Classic 90s acid house patterns
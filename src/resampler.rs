@@ -0,0 +1,338 @@
+//! Streaming sample-rate converter
+//!
+//! Lets the engine keep rendering at its internal rate while the host runs
+//! at another one, instead of everything playing back detuned.
+
+use std::f64::consts::PI;
+
+use crate::BLOCK_SIZE;
+
+/// Filter taps per output sample
+const TAPS: usize = 16;
+const HALF: usize = TAPS / 2;
+
+/// Fractional positions in the kernel table; positions in between are
+/// linearly interpolated
+const PHASES: usize = 256;
+
+/// Passband edge as a fraction of the lower Nyquist frequency
+const PASSBAND: f64 = 0.95;
+
+/// Input samples kept between calls before the buffer has to grow
+const INPUT_CAPACITY: usize = 4096;
+
+/// Output samples produced before consumed input is dropped, so the input
+/// buffer stays within INPUT_CAPACITY however large the host's block is
+const OUTPUT_CHUNK: usize = 256;
+
+/// Windowed-sinc resampler that pulls input from a render callback, at
+/// most BLOCK_SIZE samples at a time and no more than the output asked for
+/// needs, and produces output at the target rate. Mono, stereo and planar
+/// calls share one read position; channels past the first are only filled
+/// by the calls that ask for them.
+pub struct Resampler {
+    /// Input samples advanced per output sample
+    step: f64,
+    /// Read position into `input`
+    pos: f64,
+    /// Input per channel, left (or mono) first
+    input: Vec<Vec<f32>>,
+    /// One planar block of every channel, as handed to the render callback
+    block: Vec<f32>,
+    kernel: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: f32, output_rate: f32, channels: usize) -> Self {
+        let step = input_rate as f64 / output_rate as f64;
+        let channel = || {
+            let mut input = Vec::with_capacity(INPUT_CAPACITY);
+            input.resize(TAPS, 0.0);
+            input
+        };
+        let channels = channels.max(1);
+        Self {
+            step,
+            pos: HALF as f64,
+            input: (0..channels).map(|_| channel()).collect(),
+            block: vec![0.0; channels * BLOCK_SIZE],
+            kernel: build_kernel(PASSBAND * step.recip().min(1.0)),
+        }
+    }
+
+    /// Input samples from the next one rendered to the one heard at output
+    /// sample `offset` of the next call. The filter reads HALF samples
+    /// ahead, so everything rendered is heard HALF input samples late;
+    /// offsets are pushed back by the same amount, which also keeps them
+    /// clear of input already rendered.
+    pub fn input_offset(&self, offset: u32) -> u32 {
+        let target = self.pos + HALF as f64 + offset as f64 * self.step;
+        (target.round() - self.input[0].len() as f64).max(0.0) as u32
+    }
+
+    /// Fill `output`, calling `render` for more input whenever needed. Never
+    /// allocates for output rates down to a sixth of the input rate.
+    pub fn process<F: FnMut(&mut [f32])>(&mut self, output: &mut [f32], mut render: F) {
+        let len = output.len();
+        self.process_chunks(len, 1, 1, &mut |_, i, sample| output[i] = sample, &mut |block: &mut [f32], frames| {
+            render(&mut block[..frames])
+        });
+    }
+
+    /// Stereo `process`; `render` fills a block of each channel
+    pub fn process_stereo<F: FnMut(&mut [f32], &mut [f32])>(&mut self, left: &mut [f32], right: &mut [f32], mut render: F) {
+        let len = left.len().min(right.len());
+        let mut write = |channel: usize, i: usize, sample: f32| {
+            if channel == 0 {
+                left[i] = sample;
+            } else {
+                right[i] = sample;
+            }
+        };
+        self.process_chunks(len, 2, 2, &mut write, &mut |block: &mut [f32], frames| {
+            let (left, right) = block.split_at_mut(frames);
+            render(left, &mut right[..frames])
+        });
+    }
+
+    /// Mono `process` with a gate and pitch CV block rendered alongside.
+    /// The CV isn't filtered like the audio but takes the input sample
+    /// nearest each output sample, so gate edges stay sharp instead of
+    /// ringing, and it lines up with the audio it was rendered with. Needs
+    /// at least three channels; `gate` and `pitch` may be shorter than
+    /// `output`.
+    pub fn process_with_cv<F>(&mut self, output: &mut [f32], gate: &mut [f32], pitch: &mut [f32], mut render: F)
+    where
+        F: FnMut(&mut [f32], &mut [f32], &mut [f32]),
+    {
+        let len = output.len();
+        let mut write = |channel: usize, i: usize, sample: f32| {
+            let target = match channel {
+                0 => output.get_mut(i),
+                1 => gate.get_mut(i),
+                _ => pitch.get_mut(i),
+            };
+            if let Some(target) = target {
+                *target = sample;
+            }
+        };
+        self.process_chunks(len, 3, 1, &mut write, &mut |block: &mut [f32], frames| {
+            let (audio, cv) = block.split_at_mut(frames);
+            let (gate, pitch) = cv.split_at_mut(frames);
+            render(audio, gate, &mut pitch[..frames])
+        });
+    }
+
+    /// `process` for every channel at once. `output` holds each channel's
+    /// samples one after another, and `render` fills a block laid out the
+    /// same way, up to BLOCK_SIZE samples per channel.
+    pub fn process_planar<F: FnMut(&mut [f32])>(&mut self, output: &mut [f32], mut render: F) {
+        let channels = self.input.len();
+        let len = output.len() / channels;
+        self.process_chunks(len, channels, channels, &mut |channel, i, sample| output[channel * len + i] = sample, &mut |block: &mut [f32], _| {
+            render(block)
+        });
+    }
+
+    /// Produce `len` samples of the first `channels` channels, handing each
+    /// to `write` with its channel and index. The first `filtered` are
+    /// filtered; the rest take the nearest input sample. `render` fills a
+    /// planar block of every channel with the frame count it is given.
+    fn process_chunks<W, F>(&mut self, len: usize, channels: usize, filtered: usize, write: &mut W, render: &mut F)
+    where
+        W: FnMut(usize, usize, f32),
+        F: FnMut(&mut [f32], usize),
+    {
+        for start in (0..len).step_by(OUTPUT_CHUNK) {
+            self.process_chunk(start, OUTPUT_CHUNK.min(len - start), channels, filtered, write, render);
+        }
+    }
+
+    fn process_chunk<W, F>(&mut self, start: usize, len: usize, channels: usize, filtered: usize, write: &mut W, render: &mut F)
+    where
+        W: FnMut(usize, usize, f32),
+        F: FnMut(&mut [f32], usize),
+    {
+        // Render just what this chunk reads, so events queued before the
+        // next call can still land in input that isn't rendered yet
+        let last = self.pos + (len - 1) as f64 * self.step;
+        self.render_until(last as usize + HALF + 1, render);
+
+        for i in start..start + len {
+            let index = self.pos as usize;
+            self.render_until(index + HALF + 1, render);
+
+            let phase = (self.pos - index as f64) * PHASES as f64;
+            let p = phase as usize;
+            let blend = (phase - p as f64) as f32;
+            let a = &self.kernel[p * TAPS..(p + 1) * TAPS];
+            let b = &self.kernel[(p + 1) * TAPS..(p + 2) * TAPS];
+            let filter = |input: &[f32]| -> f32 {
+                input[index + 1 - HALF..=index + HALF]
+                    .iter()
+                    .zip(a.iter().zip(b))
+                    .map(|(&x, (&wa, &wb))| x * (wa + (wb - wa) * blend))
+                    .sum()
+            };
+
+            let nearest = (self.pos + 0.5) as usize;
+            for (channel, input) in self.input.iter().take(channels).enumerate() {
+                let sample = if channel < filtered { filter(input) } else { input[nearest] };
+                write(channel, i, sample);
+            }
+
+            self.pos += self.step;
+        }
+
+        // Drop input that has scrolled out of the filter window
+        let consumed = (self.pos as usize).saturating_sub(HALF);
+        if consumed > 0 {
+            for input in self.input.iter_mut() {
+                input.drain(..consumed);
+            }
+            self.pos -= consumed as f64;
+        }
+    }
+
+    /// Render input until there are `len` samples of it
+    fn render_until<F: FnMut(&mut [f32], usize)>(&mut self, len: usize, render: &mut F) {
+        let channels = self.input.len();
+        while self.input[0].len() < len {
+            let frames = (len - self.input[0].len()).min(BLOCK_SIZE);
+            let block = &mut self.block[..channels * frames];
+            render(block, frames);
+            for (input, block) in self.input.iter_mut().zip(block.chunks(frames)) {
+                input.extend_from_slice(block);
+            }
+        }
+    }
+}
+
+/// Kernel table: PHASES + 1 rows of TAPS weights, each row normalized to
+/// unity DC gain. `cutoff` is relative to the input Nyquist frequency.
+fn build_kernel(cutoff: f64) -> Vec<f32> {
+    let mut kernel = Vec::with_capacity((PHASES + 1) * TAPS);
+    for p in 0..=PHASES {
+        let frac = p as f64 / PHASES as f64;
+        let row: Vec<f64> = (0..TAPS)
+            .map(|k| {
+                // Distance from the output position to input sample k
+                let x = k as f64 + 1.0 - HALF as f64 - frac;
+                sinc(cutoff * x) * blackman(x / HALF as f64)
+            })
+            .collect();
+        let sum: f64 = row.iter().sum();
+        kernel.extend(row.iter().map(|w| (w / sum) as f32));
+    }
+    kernel
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Blackman window over -1.0..=1.0
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render a sine at `freq` through the resampler and return the output
+    fn resample_sine(freq: f32, from: f32, to: f32, len: usize) -> Vec<f32> {
+        let mut resampler = Resampler::new(from, to, 1);
+        let mut phase = 0.0f32;
+        let mut out = vec![0.0; len];
+        for chunk in out.chunks_mut(100) {
+            resampler.process(chunk, |block| {
+                for s in block.iter_mut() {
+                    *s = (phase * std::f32::consts::TAU).sin();
+                    phase = (phase + freq / from) % 1.0;
+                }
+            });
+        }
+        out
+    }
+
+    fn rising_crossings(samples: &[f32]) -> usize {
+        samples.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count()
+    }
+
+    #[test]
+    fn test_pitch_preserved() {
+        // One second at 48k of a 440Hz tone rendered at 44.1k
+        let out = resample_sine(440.0, 44100.0, 48000.0, 48000);
+        let crossings = rising_crossings(&out[100..]);
+        assert!((438..=440).contains(&crossings), "crossings {}", crossings);
+    }
+
+    #[test]
+    fn test_level_preserved() {
+        let out = resample_sine(1000.0, 44100.0, 48000.0, 4800);
+        let peak = out[100..].iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 1.0).abs() < 0.02, "peak {}", peak);
+    }
+
+    #[test]
+    fn test_downsampling_removes_content_above_nyquist() {
+        // 20kHz can't be represented at 22.05k output and must be filtered out
+        let out = resample_sine(20000.0, 44100.0, 22050.0, 4410);
+        let peak = out[100..].iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(peak < 0.1, "peak {}", peak);
+    }
+
+    #[test]
+    fn test_stereo_channels_match_mono() {
+        let mono = resample_sine(1000.0, 44100.0, 48000.0, 1000);
+
+        let mut resampler = Resampler::new(44100.0, 48000.0, 2);
+        let mut phase = 0.0f32;
+        let mut left = vec![0.0; 1000];
+        let mut right = vec![0.0; 1000];
+        for (l, r) in left.chunks_mut(100).zip(right.chunks_mut(100)) {
+            resampler.process_stereo(l, r, |block_l, block_r| {
+                for (sl, sr) in block_l.iter_mut().zip(block_r.iter_mut()) {
+                    *sl = (phase * std::f32::consts::TAU).sin();
+                    *sr = -*sl;
+                    phase = (phase + 1000.0 / 44100.0) % 1.0;
+                }
+            });
+        }
+        assert_eq!(left, mono);
+        assert!(left.iter().zip(&right).all(|(l, r)| *r == -*l));
+    }
+
+    #[test]
+    fn test_planar_channels_match_mono() {
+        let mono = resample_sine(1000.0, 44100.0, 48000.0, 1000);
+
+        let mut resampler = Resampler::new(44100.0, 48000.0, 3);
+        let mut phase = 0.0f32;
+        let mut out = vec![0.0; 3000];
+        for chunk in out.chunks_mut(300) {
+            resampler.process_planar(chunk, |block| {
+                let frames = block.len() / 3;
+                for i in 0..frames {
+                    let s = (phase * std::f32::consts::TAU).sin();
+                    block[i] = s;
+                    block[frames + i] = 0.5 * s;
+                    block[2 * frames + i] = -s;
+                    phase = (phase + 1000.0 / 44100.0) % 1.0;
+                }
+            });
+        }
+        let channel = |c: usize| -> Vec<f32> { out.chunks(300).flat_map(|chunk| chunk[c * 100..(c + 1) * 100].to_vec()).collect() };
+        assert_eq!(channel(0), mono);
+        assert!(channel(1).iter().zip(&mono).all(|(h, m)| (h - 0.5 * m).abs() < 1e-6));
+        assert!(channel(2).iter().zip(&mono).all(|(n, m)| *n == -*m));
+    }
+}
@@ -0,0 +1,67 @@
+/// Small deterministic random source (xorshift32)
+/// The same seed always produces the same sequence, so generated patterns
+/// can be recreated from their seed
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // xorshift never leaves zero, so mix the seed into a non-zero state
+        let state = seed ^ 0x9E37_79B9;
+        Self { state: if state == 0 { 0x9E37_79B9 } else { state } }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    /// Uniform value in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// True with probability `p`
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.next_f32() < p
+    }
+
+    /// Random element of a non-empty slice
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.next_u32() as usize % items.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+        assert_ne!(Rng::new(1).next_u32(), Rng::new(2).next_u32());
+    }
+
+    #[test]
+    fn test_range() {
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            let v = rng.next_f32();
+            assert!((0.0..1.0).contains(&v));
+        }
+    }
+
+    #[test]
+    fn test_chance_roughly_matches() {
+        let mut rng = Rng::new(7);
+        let hits = (0..10000).filter(|_| rng.chance(0.25)).count();
+        assert!((2200..2800).contains(&hits), "hits {}", hits);
+    }
+}
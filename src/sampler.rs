@@ -0,0 +1,96 @@
+/// One-shot sample playback voice
+/// Plays a pre-rendered buffer from the start each time it is triggered
+pub struct Sampler {
+    buffer: Vec<f32>,
+    pos: usize,
+    playing: bool,
+}
+
+impl Sampler {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            pos: 0,
+            playing: false,
+        }
+    }
+
+    /// Replace the sample, stopping playback
+    pub fn load(&mut self, buffer: Vec<f32>) {
+        self.buffer = buffer;
+        self.stop();
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer = Vec::new();
+        self.stop();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Restart playback from the beginning
+    pub fn trigger(&mut self) {
+        self.pos = 0;
+        self.playing = !self.is_empty();
+    }
+
+    pub fn stop(&mut self) {
+        self.pos = 0;
+        self.playing = false;
+    }
+
+    pub fn process(&mut self) -> f32 {
+        if !self.playing {
+            return 0.0;
+        }
+        let out = self.buffer[self.pos];
+        self.pos += 1;
+        if self.pos >= self.buffer.len() {
+            self.stop();
+        }
+        out
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plays_once() {
+        let mut sampler = Sampler::new();
+        sampler.load(vec![0.1, 0.2, 0.3]);
+        assert_eq!(sampler.process(), 0.0);
+
+        sampler.trigger();
+        let out: Vec<f32> = (0..5).map(|_| sampler.process()).collect();
+        assert_eq!(out, vec![0.1, 0.2, 0.3, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_retrigger_restarts() {
+        let mut sampler = Sampler::new();
+        sampler.load(vec![0.1, 0.2, 0.3]);
+        sampler.trigger();
+        sampler.process();
+        sampler.process();
+        sampler.trigger();
+        assert_eq!(sampler.process(), 0.1);
+    }
+
+    #[test]
+    fn test_empty_never_plays() {
+        let mut sampler = Sampler::new();
+        sampler.trigger();
+        assert_eq!(sampler.process(), 0.0);
+        assert!(sampler.is_empty());
+    }
+}
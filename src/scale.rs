@@ -0,0 +1,120 @@
+//! Keys and scales for keeping notes musical
+//! Used by the key lock to transpose presets and snap played notes
+
+/// Scale degrees as semitones above the root
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scale {
+    Chromatic,
+    Major,
+    Minor,
+    Dorian,
+    Phrygian,
+    MinorPentatonic,
+    HarmonicMinor,
+    PhrygianDominant,
+}
+
+impl Scale {
+    /// 0 = chromatic, 1 = major, 2 = minor, 3 = dorian, 4 = phrygian,
+    /// 5 = minor pentatonic, 6 = harmonic minor, 7 = phrygian dominant
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Scale::Chromatic),
+            1 => Some(Scale::Major),
+            2 => Some(Scale::Minor),
+            3 => Some(Scale::Dorian),
+            4 => Some(Scale::Phrygian),
+            5 => Some(Scale::MinorPentatonic),
+            6 => Some(Scale::HarmonicMinor),
+            7 => Some(Scale::PhrygianDominant),
+            _ => None,
+        }
+    }
+
+    pub fn intervals(self) -> &'static [u8] {
+        match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::PhrygianDominant => &[0, 1, 4, 5, 7, 8, 10],
+        }
+    }
+}
+
+/// A root pitch class (0 = C) and scale
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Key {
+    root: u8,
+    scale: Scale,
+}
+
+impl Key {
+    pub fn new(root: u8, scale: Scale) -> Self {
+        Self { root: root % 12, scale }
+    }
+
+    pub fn root(&self) -> u8 {
+        self.root
+    }
+
+    pub fn contains(&self, note: u8) -> bool {
+        let degree = (note + 12 - self.root) % 12;
+        self.scale.intervals().contains(&degree)
+    }
+
+    /// Move `note` to the nearest note in the key, going down on ties
+    pub fn snap(&self, note: u8) -> u8 {
+        (0..=6u8)
+            .flat_map(|d| [note.checked_sub(d), note.checked_add(d)])
+            .flatten()
+            .find(|&n| n <= 127 && self.contains(n))
+            .unwrap_or(note)
+    }
+
+    /// Snap a fractional MIDI note, keeping its offset from the semitone
+    pub fn snap_pitch(&self, note: f32) -> f32 {
+        let semitone = note.round().clamp(0.0, 127.0);
+        self.snap(semitone as u8) as f32 + (note - semitone)
+    }
+
+    /// Semitones (-6 to 5) that move pitch class `from` onto the root
+    pub fn transpose_from(&self, from: u8) -> i32 {
+        let up = (self.root as i32 - (from % 12) as i32).rem_euclid(12);
+        if up > 5 { up - 12 } else { up }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_to_scale() {
+        let key = Key::new(9, Scale::Minor); // A minor
+        assert_eq!(key.snap(57), 57); // A stays
+        assert_eq!(key.snap(58), 57); // A# ties between A and B, goes down
+        assert_eq!(key.snap(61), 60); // C# goes down to C
+        assert!((key.snap_pitch(61.2) - 60.2).abs() < 1e-4);
+
+        let key = Key::new(9, Scale::HarmonicMinor);
+        assert_eq!(key.snap(67), 68); // G goes up to the raised G#
+    }
+
+    #[test]
+    fn test_chromatic_keeps_every_note() {
+        let key = Key::new(0, Scale::Chromatic);
+        assert!((0..=127).all(|n| key.snap(n) == n));
+    }
+
+    #[test]
+    fn test_transpose_takes_shortest_way() {
+        let key = Key::new(0, Scale::Major);
+        assert_eq!(key.transpose_from(2), -2); // D down to C
+        assert_eq!(key.transpose_from(7), 5); // G up to C
+        assert_eq!(key.transpose_from(6), -6);
+    }
+}
@@ -1,23 +1,240 @@
-const STEPS: usize = 16;
+use crate::automation::PARAM_COUNT;
+use crate::clock::{Clock, Nudger, MAX_NUDGE};
+use crate::locks::Locks;
+use crate::rng::Rng;
+use crate::scale::Key;
+use crate::slots::Variation;
+use crate::trig::Trig;
+
+/// Steps in a bar, and in a pattern until it is lengthened
+pub const STEPS: usize = 16;
+/// Longest pattern either sequencer can hold
+pub const MAX_STEPS: usize = 32;
+/// Rate used until set_sample_rate is called
 const SAMPLE_RATE: f32 = 44100.0;
 
+
 /// A single step in the sequencer
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Step {
-    pub note: u8,     // MIDI note number
-    pub accent: bool, // Accent this step
-    pub slide: bool,  // Slide to this note from previous
-    pub active: bool, // Step is on/off
+    pub note: u8,       // MIDI note number
+    pub accent: bool,   // Accent this step
+    pub slide: bool,    // Slide to this note from previous
+    pub active: bool,   // Step is on/off
+    pub cents: i8,      // Micro-tuning offset (-100 to 100 cents)
+    pub level: u8,      // Note level (0-127), independent of accent
+    pub gate: u8,       // Gate length in percent of the step (1-100)
+    pub trig: Trig,     // Chance and condition for playing on each pass
+    pub ratchet: u8,    // Times the note is struck within the step (1-4)
+    pub nudge: i8,      // Timing offset in percent of a step (-50 to 50)
+    pub slide_time: u8, // Slide time in SLIDE_TIME_UNIT_MS, 0 = the synth's own
+    pub locks: Locks,   // Knobs set while the step plays
+}
+
+// Step flag bits in the packed byte format
+pub const FLAG_ACCENT: u8 = 1;
+pub const FLAG_SLIDE: u8 = 2;
+pub const FLAG_ACTIVE: u8 = 4;
+
+/// Bytes per step in the packed format: note, flags, cents, level, gate,
+/// probability, condition, ratchet, nudge, slide time, then one lock byte
+/// per AutomationParam
+pub const STEP_BYTES: usize = 10 + PARAM_COUNT;
+
+/// Step level that plays at full volume
+pub const FULL_LEVEL: u8 = 127;
+
+/// Gate length of a plain step, in percent of the step
+pub const DEFAULT_GATE: u8 = 50;
+
+/// Gate length that holds the note until the next step starts
+pub const TIE_GATE: u8 = 100;
+
+/// Most hits a ratcheted step can be divided into, for synth and drum steps
+pub const MAX_RATCHET: u8 = 4;
+
+/// Milliseconds per unit of a step's slide time
+pub const SLIDE_TIME_UNIT_MS: f32 = 5.0;
+
+impl Step {
+    /// Pitch in fractional MIDI notes, including the cents offset
+    pub fn pitch(&self) -> f32 {
+        self.note as f32 + self.cents as f32 / 100.0
+    }
+
+    /// Level as a VCA gain (0.0 - 1.0)
+    pub fn gain(&self) -> f32 {
+        self.level.min(FULL_LEVEL) as f32 / FULL_LEVEL as f32
+    }
+
+    /// Accent, slide and active packed as FLAG_* bits
+    pub fn flags(&self) -> u8 {
+        (self.accent as u8 * FLAG_ACCENT) | (self.slide as u8 * FLAG_SLIDE) | (self.active as u8 * FLAG_ACTIVE)
+    }
+
+    pub fn from_flags(note: u8, flags: u8, cents: i8, level: u8, gate: u8) -> Self {
+        Self {
+            note,
+            accent: flags & FLAG_ACCENT != 0,
+            slide: flags & FLAG_SLIDE != 0,
+            active: flags & FLAG_ACTIVE != 0,
+            cents,
+            level,
+            gate,
+            trig: Trig::ALWAYS,
+            ratchet: 1,
+            nudge: 0,
+            slide_time: 0,
+            locks: Locks::NONE,
+        }
+    }
+
+    /// Number of hits in the step, 0 counting as 1
+    pub fn hits(&self) -> u8 {
+        self.ratchet.clamp(1, MAX_RATCHET)
+    }
+
+    /// Slide time set on this step in milliseconds, if it overrides the
+    /// synth's own
+    pub fn slide_ms(&self) -> Option<f32> {
+        (self.slide_time > 0).then_some(self.slide_time as f32 * SLIDE_TIME_UNIT_MS)
+    }
+
+    /// Fraction of the step the gate stays open for, unless the next step
+    /// slides. A TIE_GATE step never closes before the next step.
+    pub fn gate_length(&self) -> f32 {
+        if self.gate >= TIE_GATE {
+            f32::INFINITY
+        } else {
+            self.gate as f32 / 100.0
+        }
+    }
+}
+
+/// Order the steps of a pattern play in
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    /// Forward then back, turning on the end steps without repeating them
+    PingPong,
+    Random,
+}
+
+impl Direction {
+    /// Direction for a UI index: 0 = forward, 1 = reverse, 2 = ping-pong,
+    /// 3 = random
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Direction::Forward),
+            1 => Some(Direction::Reverse),
+            2 => Some(Direction::PingPong),
+            3 => Some(Direction::Random),
+            _ => None,
+        }
+    }
+
+    /// Step played `count` steps into a run of a `length`-step pattern
+    pub fn step_at(self, count: u32, length: usize, rng: &mut Rng) -> usize {
+        let steps = length.max(1) as u32;
+        let step = match self {
+            Direction::Forward => count % steps,
+            Direction::Reverse => steps - 1 - count % steps,
+            Direction::PingPong if steps > 1 => {
+                let turn = count % (2 * steps - 2);
+                if turn < steps { turn } else { 2 * steps - 2 - turn }
+            }
+            Direction::PingPong => 0,
+            Direction::Random => rng.next_u32() % steps,
+        };
+        step as usize
+    }
+}
+
+/// Per-step flags that can be edited apart from the notes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lane {
+    Accent,
+    Slide,
+}
+
+impl Lane {
+    fn flag(self, step: &mut Step) -> &mut bool {
+        match self {
+            Lane::Accent => &mut step.accent,
+            Lane::Slide => &mut step.slide,
+        }
+    }
+}
+
+/// What the voice should do, as reported by `Sequencer::tick()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SeqEvent {
+    /// Start a note. With `slide` set and a note still held, glide to it.
+    NoteOn(Step),
+    /// The held note carries on into this step (a slide to the same pitch)
+    Tie(Step),
+    /// Another hit of a ratcheted step: restart the note without sliding
+    Ratchet(Step),
+    /// End of the gate: release the held note
+    NoteOff,
+    /// An inactive step
+    Rest,
+}
+
+impl SeqEvent {
+    /// True for events that mark the start of a new step
+    pub fn starts_step(&self) -> bool {
+        !matches!(self, SeqEvent::NoteOff | SeqEvent::Ratchet(_))
+    }
 }
 
 /// 16-step sequencer
 pub struct Sequencer {
-    steps: [Step; STEPS],
-    current: usize,
-    sample_counter: u32,
-    samples_per_step: u32,
+    // Pattern steps; only the first `length` are played
+    steps: [Step; MAX_STEPS],
+    length: usize,
     playing: bool,
-    tempo: f32,
+
+    // Next step to play, the one that last played and how far into the
+    // pass it came, and how many have played since start, counted from the
+    // start position
+    current: usize,
+    last: usize,
+    last_in_pass: usize,
+    count: u32,
+    direction: Direction,
+
+    // Step timing when not following a shared clock, progress towards the
+    // next step (0.0 - 1.0) and where the nudged steps fall against it
+    clock: Clock,
+    progress: f32,
+    nudger: Nudger,
+
+    // Completed passes through the pattern since start, for trig conditions
+    passes: u32,
+
+    // Gate tracking: the held note and the progress through the step at
+    // which it is released
+    held_note: Option<u8>,
+    release_pending: bool,
+    release_at: f32,
+
+    // The step playing, its number of hits and the next hit due
+    played: Step,
+    hits: u8,
+    next_hit: u8,
+
+    // Scale that edited notes are snapped into
+    scale: Option<Key>,
+
+    // Alternate pattern played on a loop with probability `variation_chance`,
+    // always as long as the pattern
+    variation: Option<[Step; MAX_STEPS]>,
+    variation_chance: f32,
+    playing_variation: bool,
+    rng: Rng,
 }
 
 impl Sequencer {
@@ -28,47 +245,191 @@ impl Sequencer {
             accent: false,
             slide: false,
             active: false,
+            cents: 0,
+            level: FULL_LEVEL,
+            gate: DEFAULT_GATE,
+            trig: Trig::ALWAYS,
+            ratchet: 1,
+            nudge: 0,
+            slide_time: 0,
+            locks: Locks::NONE,
         };
 
         Self {
-            steps: [default_step; STEPS],
-            current: 0,
-            sample_counter: 0,
-            samples_per_step: 0,
+            steps: [default_step; MAX_STEPS],
+            length: STEPS,
             playing: false,
-            tempo: 120.0,
+            current: 0,
+            last: 0,
+            last_in_pass: 0,
+            count: 0,
+            direction: Direction::Forward,
+            passes: 0,
+            held_note: None,
+            release_pending: false,
+            release_at: 0.0,
+            played: default_step,
+            hits: 1,
+            next_hit: 1,
+            clock: Clock::new(SAMPLE_RATE),
+            progress: 0.0,
+            nudger: Nudger::new(),
+            scale: None,
+            variation: None,
+            variation_chance: 0.0,
+            playing_variation: false,
+            rng: Rng::new(0x5eed),
         }
     }
 
     pub fn set_tempo(&mut self, bpm: f32) {
-        self.tempo = bpm.clamp(60.0, 300.0);
-        // 16th notes at given BPM
-        // beats per second = bpm / 60
-        // 16th notes per second = (bpm / 60) * 4
-        // samples per 16th = sample_rate / (16ths per second)
-        let sixteenths_per_second = (self.tempo / 60.0) * 4.0;
-        self.samples_per_step = (SAMPLE_RATE / sixteenths_per_second) as u32;
+        self.clock.set_tempo(bpm);
+    }
+
+    /// Delay every other 16th by up to MAX_SWING of a step (0.0 = straight)
+    pub fn set_swing(&mut self, amount: f32) {
+        self.clock.set_swing(amount);
+    }
+
+    pub fn swing(&self) -> f32 {
+        self.clock.swing()
+    }
+
+    /// Rate `tick` is called at, which the step length is counted in
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.clock.set_sample_rate(sample_rate);
+    }
+
+    /// Restart the random source used for trig chances and variations, so
+    /// a run from here plays the same way every time
+    pub fn set_seed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
     }
 
+    /// Replace a step, snapping its note into the scale if one is set
     pub fn set_step(&mut self, index: usize, step: Step) {
-        if index < STEPS {
-            self.steps[index] = step;
+        if index < self.length {
+            self.steps[index] = Step { note: self.snap(step.note), ..step };
+        }
+    }
+
+    /// Quantize notes to `key`: the pattern is snapped into it now, and
+    /// notes edited from here on are snapped as they are set
+    pub fn set_scale(&mut self, key: Key) {
+        self.scale = Some(key);
+        self.map_notes(|note| key.snap(note));
+    }
+
+    pub fn clear_scale(&mut self) {
+        self.scale = None;
+    }
+
+    pub fn scale(&self) -> Option<Key> {
+        self.scale
+    }
+
+    /// `note` moved into the scale, or unchanged if none is set
+    pub fn snap(&self, note: u8) -> u8 {
+        self.scale.map_or(note, |key| key.snap(note))
+    }
+
+    /// Shift every note of the pattern and its variation by `semitones`,
+    /// folding notes that would leave the MIDI range back by octaves and
+    /// snapping into the scale if one is set
+    pub fn transpose(&mut self, semitones: i8) {
+        let scale = self.scale;
+        self.map_notes(|note| {
+            let mut shifted = note as i32 + semitones as i32;
+            while shifted > 127 {
+                shifted -= 12;
+            }
+            while shifted < 0 {
+                shifted += 12;
+            }
+            scale.map_or(shifted as u8, |key| key.snap(shifted as u8))
+        });
+    }
+
+    /// Rewrite the note of every step, in the pattern and its variation
+    fn map_notes(&mut self, f: impl Fn(u8) -> u8) {
+        let length = self.length;
+        let variation = self.variation.iter_mut().flat_map(|v| &mut v[..length]);
+        for step in self.steps[..length].iter_mut().chain(variation) {
+            step.note = f(step.note);
         }
     }
 
     pub fn get_step(&self, index: usize) -> Option<&Step> {
-        self.steps.get(index)
+        self.steps().get(index)
+    }
+
+    /// The pattern's steps, `length()` of them
+    pub fn steps(&self) -> &[Step] {
+        &self.steps[..self.length]
+    }
+
+    pub fn get_step_mut(&mut self, index: usize) -> Option<&mut Step> {
+        self.steps[..self.length].get_mut(index)
+    }
+
+    /// Steps in the pattern (1 - MAX_STEPS)
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Pitch class (0 = C) heard most often across active steps, ties going
+    /// to the lowest note. None if no step is active.
+    pub fn root_note(&self) -> Option<u8> {
+        let mut counts = [0u32; 12];
+        let mut lowest: Option<u8> = None;
+        for step in self.steps().iter().filter(|s| s.active) {
+            counts[(step.note % 12) as usize] += 1;
+            lowest = Some(lowest.map_or(step.note, |n| n.min(step.note)));
+        }
+        let lowest = lowest? % 12;
+        let best = counts.iter().copied().max().unwrap_or(0);
+        if counts[lowest as usize] == best {
+            return Some(lowest);
+        }
+        (0..12).find(|&pc| counts[pc as usize] == best)
+    }
+
+    /// Order the steps play in from the next step on
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
     }
 
     pub fn start(&mut self) {
+        self.start_at(0);
+    }
+
+    /// Start `step` steps into the pattern's order, as if that many steps
+    /// had played since the top, so the pass count trig conditions use and
+    /// the direction's phase carry on from there
+    pub fn start_at(&mut self, step: usize) {
         self.playing = true;
-        self.current = 0;
-        self.sample_counter = 0;
-        self.set_tempo(self.tempo); // Recalculate samples_per_step
+        self.count = step as u32;
+        self.current = self.direction.step_at(self.count, self.length, &mut self.rng);
+        self.last = self.current;
+        self.last_in_pass = self.pass_position();
+        self.clock.start_at(step % self.length);
+        self.progress = 0.0;
+        self.nudger.reset();
+        self.passes = self.count / self.length as u32;
+        self.held_note = None;
+        self.release_pending = false;
+        self.hits = 1;
+        self.next_hit = 1;
+        self.roll_variation();
     }
 
     pub fn stop(&mut self) {
         self.playing = false;
+        self.clock.stop();
     }
 
     pub fn is_playing(&self) -> bool {
@@ -79,27 +440,250 @@ impl Sequencer {
         self.current
     }
 
-    /// Tick the sequencer. Returns Some(Step) when advancing to a new step.
-    pub fn tick(&mut self) -> Option<Step> {
-        if !self.playing || self.samples_per_step == 0 {
+    /// How many steps into a pass the next step is; 0 when it starts a new
+    /// pass, whatever the direction
+    pub fn pass_position(&self) -> usize {
+        (self.count % self.length as u32) as usize
+    }
+
+    /// Pattern position in steps (0.0 - length) of the step that last played,
+    /// including progress towards the next one
+    pub fn position(&self) -> f32 {
+        self.last as f32 + self.progress
+    }
+
+    /// Time into the pass in steps (0.0 - length), like position() but
+    /// counted in playing order, so it runs forward whatever the direction
+    pub fn pass_time(&self) -> f32 {
+        self.last_in_pass as f32 + self.progress
+    }
+
+    /// Number of samples between steps at the current tempo, including the
+    /// fraction that is carried from step to step
+    pub fn samples_per_step(&self) -> f64 {
+        self.clock.samples_per_step()
+    }
+
+    pub fn steps_per_bar(&self) -> usize {
+        STEPS
+    }
+
+    /// Average step length in milliseconds
+    pub fn step_duration_ms(&self) -> f32 {
+        60000.0 / self.clock.tempo() / 4.0
+    }
+
+    /// Tick the sequencer. Returns an event when a step starts or a gate ends.
+    pub fn tick(&mut self) -> Option<SeqEvent> {
+        if !self.playing {
             return None;
         }
+        let step_started = self.clock.tick();
+        self.progress = self.clock.progress();
+        self.advance(step_started)
+    }
+
+    /// Tick in step with a shared clock, after the clock's own tick, in
+    /// place of the sequencer's own sample count
+    pub fn tick_with(&mut self, clock: &Clock) -> Option<SeqEvent> {
+        if !self.playing {
+            return None;
+        }
+        self.progress = clock.progress();
+        self.advance(clock.step_started())
+    }
 
-        self.sample_counter += 1;
+    /// Play the next step once its nudged time comes, strike the next hit
+    /// of a ratcheted step when it is due, or end the gate once it has been
+    /// open long enough
+    fn advance(&mut self, step_started: bool) -> Option<SeqEvent> {
+        let upcoming = self.loop_steps()[self.current];
+        let due = self.nudger.due(step_started, self.progress, upcoming.nudge);
+        let elapsed = self.nudger.elapsed(self.progress);
+        if due {
+            self.nudger.fire(self.progress);
+            let mut step = upcoming;
+            step.active = step.active && step.trig.fires(self.passes, &mut self.rng);
+            self.last = self.current;
+            self.last_in_pass = self.pass_position();
+            self.count = self.count.wrapping_add(1);
+            self.current = self.direction.step_at(self.count, self.length, &mut self.rng);
+            if self.pass_position() == 0 {
+                // Choose the next loop now so a slide into it is seen
+                self.passes += 1;
+                self.roll_variation();
+            }
+            let next = self.loop_steps()[self.current];
+
+            let event = if !step.active {
+                SeqEvent::Rest
+            } else if step.slide && self.held_note == Some(step.note) {
+                SeqEvent::Tie(step)
+            } else {
+                SeqEvent::NoteOn(step)
+            };
 
-        if self.sample_counter >= self.samples_per_step {
-            self.sample_counter = 0;
-            let step = self.steps[self.current];
-            self.current = (self.current + 1) % STEPS;
-            Some(step)
+            self.held_note = if step.active { Some(step.note) } else { None };
+            self.played = step;
+            self.hits = if step.active { step.hits() } else { 1 };
+            self.next_hit = 1;
+            self.release_pending = step.active && !self.holds_into(&next, 0);
+            self.release_at = self.hit_release(0);
+            Some(event)
+        } else if self.next_hit < self.hits && elapsed >= self.next_hit as f32 / self.hits as f32 {
+            let hit = self.next_hit;
+            self.next_hit += 1;
+            let next = self.loop_steps()[self.current];
+            self.held_note = Some(self.played.note);
+            self.release_pending = !self.holds_into(&next, hit);
+            self.release_at = self.hit_release(hit);
+            Some(SeqEvent::Ratchet(Step { slide: false, ..self.played }))
+        } else if self.release_pending && elapsed >= self.release_at {
+            self.release_pending = false;
+            self.held_note = None;
+            Some(SeqEvent::NoteOff)
         } else {
             None
         }
     }
 
-    /// Load a pattern from an array of steps
-    pub fn load_pattern(&mut self, pattern: &[Step; STEPS]) {
-        self.steps = *pattern;
+    /// Whether `hit` of the step playing holds its gate open into `next`,
+    /// which only the last hit does, and only when the next step slides
+    fn holds_into(&self, next: &Step, hit: u8) -> bool {
+        hit + 1 == self.hits && next.active && next.slide
+    }
+
+    /// Progress through the step at which `hit` of the step playing is
+    /// released. Each hit gets the gate length of its share of the step;
+    /// only the last can be tied into the next step.
+    fn hit_release(&self, hit: u8) -> f32 {
+        let gate = self.played.gate_length();
+        let gate = if hit + 1 == self.hits { gate } else { gate.min(1.0) };
+        (hit as f32 + gate) / self.hits as f32
+    }
+
+    /// Replace the pattern, taking its length from `pattern` (1 - MAX_STEPS
+    /// steps; longer patterns are cut short). The variation belongs to the
+    /// pattern it was written for and is dropped, along with its chance.
+    pub fn load_pattern(&mut self, pattern: &[Step]) {
+        let length = pattern.len().min(MAX_STEPS);
+        if length == 0 {
+            return;
+        }
+        self.steps[..length].copy_from_slice(&pattern[..length]);
+        self.length = length;
+        self.current %= length;
+        self.clear_variation();
+        self.variation_chance = 0.0;
+    }
+
+    /// The whole pattern packed as STEP_BYTES per step
+    pub fn pattern_bytes(&self) -> Vec<u8> {
+        self.steps()
+            .iter()
+            .flat_map(|s| {
+                let [probability, condition] = s.trig.to_bytes();
+                let head = [s.note, s.flags(), s.cents as u8, s.level, s.gate, probability, condition, s.ratchet, s.nudge as u8, s.slide_time];
+                head.into_iter().chain(s.locks.to_bytes())
+            })
+            .collect()
+    }
+
+    /// Replace the whole pattern from the packed format, taking its length
+    /// from `bytes`. Returns false and leaves the pattern untouched if
+    /// `bytes` is not a whole number of steps, 1 - MAX_STEPS of them.
+    pub fn load_pattern_bytes(&mut self, bytes: &[u8]) -> bool {
+        match parse_pattern(bytes) {
+            Some(steps) => {
+                self.load_pattern(&steps);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set the alternate pattern from the packed format. Returns false and
+    /// keeps the current variation unless `bytes` holds a pattern as long as
+    /// the one playing.
+    pub fn set_variation_bytes(&mut self, bytes: &[u8]) -> bool {
+        match parse_pattern(bytes) {
+            Some(steps) if steps.len() == self.length => {
+                let mut variation = self.steps;
+                variation[..self.length].copy_from_slice(&steps);
+                self.variation = Some(variation);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn clear_variation(&mut self) {
+        self.variation = None;
+        self.playing_variation = false;
+    }
+
+    /// Chance (0.0 - 1.0) that any loop plays the variation
+    pub fn set_variation_chance(&mut self, chance: f32) {
+        self.variation_chance = chance.clamp(0.0, 1.0);
+    }
+
+    /// The variation and its chance, for storing with the pattern
+    pub fn variation(&self) -> Option<Variation<Step>> {
+        self.variation.map(|steps| Variation {
+            steps: steps[..self.length].to_vec(),
+            chance: self.variation_chance,
+        })
+    }
+
+    /// Put back a variation stored with the pattern just loaded. One of
+    /// another length doesn't fit and is ignored.
+    pub fn load_variation(&mut self, variation: Option<&Variation<Step>>) {
+        let Some(variation) = variation.filter(|v| v.steps.len() == self.length) else {
+            return;
+        };
+        let mut steps = self.steps;
+        steps[..self.length].copy_from_slice(&variation.steps);
+        self.variation = Some(steps);
+        self.set_variation_chance(variation.chance);
+    }
+
+    /// True while the current loop is the variation
+    pub fn is_playing_variation(&self) -> bool {
+        self.playing_variation
+    }
+
+    /// Steps of the loop being played
+    fn loop_steps(&self) -> &[Step] {
+        match &self.variation {
+            Some(variation) if self.playing_variation => &variation[..self.length],
+            _ => self.steps(),
+        }
+    }
+
+    fn roll_variation(&mut self) {
+        self.playing_variation = self.variation.is_some() && self.rng.chance(self.variation_chance);
+    }
+
+    /// Replace notes and flags from separate per-step arrays, snapping the
+    /// notes into the scale if one is set and keeping each step's cents,
+    /// level, gate, trig, ratchet, nudge and slide time. Returns false and changes
+    /// nothing unless both arrays have one entry per step.
+    pub fn load_notes_and_flags(&mut self, notes: &[u8], flags: &[u8]) -> bool {
+        if notes.len() != self.length || flags.len() != self.length {
+            return false;
+        }
+        let scale = self.scale;
+        for (step, (&note, &bits)) in self.steps[..self.length].iter_mut().zip(notes.iter().zip(flags)) {
+            let note = scale.map_or(note, |key| key.snap(note));
+            *step = Step {
+                trig: step.trig,
+                ratchet: step.ratchet,
+                nudge: step.nudge,
+                slide_time: step.slide_time,
+                ..Step::from_flags(note, bits, step.cents, step.level, step.gate)
+            };
+        }
+        true
     }
 
     /// Clear the pattern
@@ -108,6 +692,87 @@ impl Sequencer {
             step.active = false;
         }
     }
+
+    /// Rotate a lane by `n` steps (negative rotates left), leaving notes
+    /// where they are
+    pub fn rotate_lane(&mut self, lane: Lane, n: i32) {
+        let steps = &mut self.steps[..self.length];
+        let mut flags = [false; MAX_STEPS];
+        for (flag, step) in flags.iter_mut().zip(steps.iter_mut()) {
+            *flag = *lane.flag(step);
+        }
+        let flags = &mut flags[..steps.len()];
+        flags.rotate_right(n.rem_euclid(steps.len() as i32) as usize);
+        for (step, &flag) in steps.iter_mut().zip(flags.iter()) {
+            *lane.flag(step) = flag;
+        }
+    }
+
+    /// Set each step's flag in a lane with probability `density`
+    pub fn randomize_lane(&mut self, lane: Lane, density: f32, rng: &mut Rng) {
+        for step in self.steps[..self.length].iter_mut() {
+            *lane.flag(step) = rng.chance(density.clamp(0.0, 1.0));
+        }
+    }
+
+    pub fn clear_lane(&mut self, lane: Lane) {
+        for step in self.steps[..self.length].iter_mut() {
+            *lane.flag(step) = false;
+        }
+    }
+
+    // Whole-pattern operations
+
+    /// Move every step `n` steps later (negative moves them earlier),
+    /// wrapping round the end of the pattern
+    pub fn rotate(&mut self, n: i32) {
+        let length = self.length;
+        self.steps[..length].rotate_right(n.rem_euclid(length as i32) as usize);
+    }
+
+    /// Play the pattern backwards. Slides move with the pair of notes they
+    /// join, so the same notes still glide into each other.
+    pub fn reverse(&mut self) {
+        let (original, length) = (self.steps, self.length);
+        for (i, step) in self.steps[..length].iter_mut().enumerate() {
+            // Step i now leads into what came before it
+            let glide = original[(2 * length - 2 - i) % length];
+            *step = Step { slide: glide.slide, slide_time: glide.slide_time, ..original[length - 1 - i] };
+        }
+    }
+
+    /// Replace the second half of the pattern with the first half played
+    /// backwards, slides following their notes as in reverse()
+    pub fn mirror(&mut self) {
+        let (original, length) = (self.steps, self.length);
+        for i in 0..length / 2 {
+            let (slide, slide_time) = if i > 0 { (original[i - 1].slide, original[i - 1].slide_time) } else { (false, 0) };
+            self.steps[length - 1 - i] = Step { slide, slide_time, ..original[i] };
+        }
+    }
+
+    /// Accent every unaccented step and clear the accents that were set
+    pub fn invert_accents(&mut self) {
+        for step in self.steps[..self.length].iter_mut() {
+            step.accent = !step.accent;
+        }
+    }
+
+    /// Play the pattern, and its variation, twice over in a pattern of
+    /// twice the length. Returns false if that would be longer than
+    /// MAX_STEPS.
+    pub fn double(&mut self) -> bool {
+        let length = self.length;
+        if length * 2 > MAX_STEPS {
+            return false;
+        }
+        self.steps.copy_within(..length, length);
+        if let Some(variation) = &mut self.variation {
+            variation.copy_within(..length, length);
+        }
+        self.length = length * 2;
+        true
+    }
 }
 
 impl Default for Sequencer {
@@ -116,9 +781,32 @@ impl Default for Sequencer {
     }
 }
 
+/// Unpack a pattern of STEP_BYTES per step, 1 - MAX_STEPS steps long
+pub fn parse_pattern(bytes: &[u8]) -> Option<Vec<Step>> {
+    let length = bytes.len() / STEP_BYTES;
+    if !bytes.len().is_multiple_of(STEP_BYTES) || !(1..=MAX_STEPS).contains(&length) {
+        return None;
+    }
+    let mut steps = Vec::with_capacity(length);
+    for b in bytes.chunks_exact(STEP_BYTES) {
+        let cents = (b[2] as i8).clamp(-100, 100);
+        steps.push(Step {
+            trig: Trig::from_bytes(b[5], b[6]),
+            ratchet: b[7].clamp(1, MAX_RATCHET),
+            nudge: (b[8] as i8).clamp(-MAX_NUDGE, MAX_NUDGE),
+            slide_time: b[9],
+            locks: Locks::from_bytes(&b[10..]),
+            ..Step::from_flags(b[0], b[1], cents, b[3].min(FULL_LEVEL), b[4].clamp(1, TIE_GATE))
+        });
+    }
+    Some(steps)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scale::Scale;
+    use crate::trig::Condition;
 
     #[test]
     fn test_sequencer_creation() {
@@ -140,13 +828,13 @@ mod tests {
     fn test_sequencer_advances() {
         let mut seq = Sequencer::new();
         seq.set_tempo(120.0);
-        seq.set_step(0, Step { note: 48, accent: true, slide: false, active: true });
+        seq.set_step(0, Step { note: 48, accent: true, slide: false, active: true, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE });
         seq.start();
 
         // Tick until we get a step
         let mut step_received = false;
         for _ in 0..50000 {
-            if let Some(step) = seq.tick() {
+            if let Some(SeqEvent::NoteOn(step)) = seq.tick() {
                 step_received = true;
                 assert_eq!(step.note, 48);
                 assert!(step.accent);
@@ -180,15 +868,365 @@ mod tests {
         assert!(wrap_count >= 2, "Sequencer should wrap around");
     }
 
+    /// Collect events for the first `steps` steps
+    fn events(seq: &mut Sequencer, steps: usize) -> Vec<SeqEvent> {
+        let len = (seq.samples_per_step() * steps as f64).ceil() as usize;
+        (0..len).filter_map(|_| seq.tick()).collect()
+    }
+
+    #[test]
+    fn test_gate_ends_mid_step() {
+        let mut seq = Sequencer::new();
+        let note = Step { note: 48, accent: false, slide: false, active: true, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE };
+        seq.set_step(0, note);
+        seq.start();
+        assert_eq!(events(&mut seq, 2), vec![SeqEvent::NoteOn(note), SeqEvent::NoteOff, SeqEvent::Rest]);
+    }
+
+    #[test]
+    fn test_step_gate_length() {
+        let mut seq = Sequencer::new();
+        seq.set_sample_rate(48000.0);
+        let short = Step { note: 48, accent: false, slide: false, active: true, cents: 0, level: FULL_LEVEL, gate: 10, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE };
+        seq.set_step(0, short);
+        seq.set_step(1, Step { gate: TIE_GATE, ..short });
+        seq.start();
+
+        let mut offs = Vec::new();
+        for i in 1..=6000 * 3 {
+            if seq.tick() == Some(SeqEvent::NoteOff) {
+                offs.push(i);
+            }
+        }
+        // 10% into step 0; the tied step 1 is held until step 2, a rest
+        assert_eq!(offs, vec![6000 + 600]);
+    }
+
+    #[test]
+    fn test_ratchet_splits_step() {
+        let mut seq = Sequencer::new();
+        seq.set_sample_rate(48000.0);
+        let note = Step { note: 48, accent: false, slide: false, active: true, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 2, nudge: 0, slide_time: 0, locks: Locks::NONE };
+        seq.set_step(0, note);
+        seq.start();
+
+        let mut events = Vec::new();
+        for i in 1..=6000 * 2 {
+            if let Some(event) = seq.tick() {
+                events.push((i, event));
+            }
+        }
+        // Each half of the step gets a hit gated for half its length
+        assert_eq!(
+            events,
+            vec![
+                (6000, SeqEvent::NoteOn(note)),
+                (7500, SeqEvent::NoteOff),
+                (9000, SeqEvent::Ratchet(note)),
+                (10500, SeqEvent::NoteOff),
+                (12000, SeqEvent::Rest),
+            ]
+        );
+        assert!(!SeqEvent::Ratchet(note).starts_step());
+    }
+
+    #[test]
+    fn test_nudge_moves_note_and_gate() {
+        let mut seq = Sequencer::new();
+        seq.set_sample_rate(48000.0);
+        let note = Step { note: 48, accent: false, slide: false, active: true, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: -20, slide_time: 0, locks: Locks::NONE };
+        seq.set_step(1, note);
+        seq.start();
+
+        let mut events = Vec::new();
+        for i in 1..=6000 * 3 {
+            if let Some(event) = seq.tick() {
+                events.push((i, event));
+            }
+        }
+        // Step 1 plays 1200 samples early and keeps its full gate length
+        let rest = SeqEvent::Rest;
+        assert_eq!(events, vec![(6000, rest), (10800, SeqEvent::NoteOn(note)), (13800, SeqEvent::NoteOff), (18000, rest)]);
+    }
+
+    #[test]
+    fn test_slide_holds_gate_and_ties() {
+        let mut seq = Sequencer::new();
+        let first = Step { note: 48, accent: false, slide: false, active: true, cents: 0, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE };
+        let glide = Step { note: 51, slide: true, ..first };
+        let tie = Step { note: 51, slide: true, ..first };
+        seq.set_step(0, first);
+        seq.set_step(1, glide);
+        seq.set_step(2, tie);
+        seq.start();
+        assert_eq!(
+            events(&mut seq, 4),
+            vec![
+                SeqEvent::NoteOn(first),
+                SeqEvent::NoteOn(glide),
+                SeqEvent::Tie(tie),
+                SeqEvent::NoteOff,
+                SeqEvent::Rest,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_swing_delays_offbeats() {
+        let mut seq = Sequencer::new();
+        seq.set_sample_rate(48000.0);
+        seq.set_swing(0.5);
+        seq.start();
+        // 6000 samples per step, off-beats 2250 late
+        let step = 6000;
+        let delay = 2250;
+
+        let mut starts = Vec::new();
+        for i in 1..=step * 5 {
+            if seq.tick().is_some_and(|e| e.starts_step()) {
+                starts.push(i);
+            }
+        }
+        // Downbeats stay on the grid, off-beats land late
+        assert_eq!(starts, vec![step, step * 2 + delay, step * 3, step * 4 + delay, step * 5]);
+    }
+
+    #[test]
+    fn test_directions() {
+        let mut rng = Rng::new(1);
+        let order = |direction: Direction, rng: &mut Rng| (0..32).map(|n| direction.step_at(n, STEPS, rng)).collect::<Vec<_>>();
+        assert_eq!(order(Direction::Forward, &mut rng)[14..18], [14, 15, 0, 1]);
+        assert_eq!(order(Direction::Reverse, &mut rng)[..3], [15, 14, 13]);
+        assert_eq!(order(Direction::PingPong, &mut rng)[14..18], [14, 15, 14, 13]);
+        assert_eq!(order(Direction::PingPong, &mut rng)[29..32], [1, 0, 1]);
+        assert!(order(Direction::Random, &mut rng).iter().all(|&s| s < STEPS));
+        assert_eq!(Direction::from_index(4), None);
+
+        // Passes still end every 16 steps going backwards
+        let mut seq = Sequencer::new();
+        for (i, step) in seq.steps.iter_mut().enumerate() {
+            step.note = 36 + i as u8;
+            step.active = true;
+        }
+        seq.set_direction(Direction::Reverse);
+        seq.start();
+        let notes: Vec<u8> = events(&mut seq, 17)
+            .into_iter()
+            .filter_map(|e| match e {
+                SeqEvent::NoteOn(step) => Some(step.note),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(notes[..3], [51, 50, 49]);
+        assert_eq!(notes[15..], [36, 51]);
+        assert_eq!(seq.pass_position(), 1);
+    }
+
+    #[test]
+    fn test_step_pitch_includes_cents() {
+        let step = Step { note: 48, accent: false, slide: false, active: true, cents: -50, level: FULL_LEVEL, gate: DEFAULT_GATE, trig: Trig::ALWAYS, ratchet: 1, nudge: 0, slide_time: 0, locks: Locks::NONE };
+        assert_eq!(step.pitch(), 47.5);
+    }
+
+    #[test]
+    fn test_step_duration_matches_samples() {
+        let mut seq = Sequencer::new();
+        seq.set_tempo(133.0);
+        // The fraction of a sample is carried, so the average step is exact
+        let ms = seq.step_duration_ms();
+        assert!((ms - 60000.0 / 133.0 / 4.0).abs() < 1e-4);
+        assert!((ms as f64 / 1000.0 * SAMPLE_RATE as f64 - seq.samples_per_step()).abs() < 0.01);
+        assert_eq!(seq.steps_per_bar(), 16);
+    }
+
+    #[test]
+    fn test_pattern_bytes_round_trip() {
+        let mut seq = Sequencer::new();
+        seq.set_step(3, Step { note: 50, accent: true, slide: true, active: true, cents: -20, level: 60, gate: 80, trig: Trig { probability: 40, condition: Condition::NotFirst }, ratchet: 3, nudge: -30, slide_time: 0, locks: Locks::NONE });
+        let bytes = seq.pattern_bytes();
+        assert_eq!(&bytes[3 * STEP_BYTES..3 * STEP_BYTES + 9], &[50, 7, (-20i8) as u8, 60, 80, 40, 2, 3, (-30i8) as u8]);
+
+        let mut other = Sequencer::new();
+        assert!(other.load_pattern_bytes(&bytes));
+        assert_eq!(other.get_step(3), seq.get_step(3));
+        assert!(!other.load_pattern_bytes(&bytes[..10]));
+    }
+
+    #[test]
+    fn test_root_note() {
+        let mut seq = Sequencer::new();
+        for i in 0..16 {
+            seq.get_step_mut(i).unwrap().active = false;
+        }
+        assert_eq!(seq.root_note(), None);
+
+        // Two Gs outvote a single low A
+        for (i, note) in [(0, 45), (1, 43), (2, 55)] {
+            let step = seq.get_step_mut(i).unwrap();
+            step.note = note;
+            step.active = true;
+        }
+        assert_eq!(seq.root_note(), Some(7));
+    }
+
+    #[test]
+    fn test_rotate_lane_keeps_notes() {
+        let mut seq = Sequencer::new();
+        seq.get_step_mut(0).unwrap().accent = true;
+        seq.get_step_mut(0).unwrap().note = 40;
+        seq.rotate_lane(Lane::Accent, 3);
+        assert!(!seq.get_step(0).unwrap().accent);
+        assert!(seq.get_step(3).unwrap().accent);
+        assert_eq!(seq.get_step(0).unwrap().note, 40);
+
+        seq.rotate_lane(Lane::Accent, -4);
+        assert!(seq.get_step(15).unwrap().accent);
+
+        let mut rng = Rng::new(1);
+        seq.randomize_lane(Lane::Slide, 1.0, &mut rng);
+        assert!((0..16).all(|i| seq.get_step(i).unwrap().slide));
+        seq.clear_lane(Lane::Slide);
+        assert!((0..16).all(|i| !seq.get_step(i).unwrap().slide));
+    }
+
+    #[test]
+    fn test_pattern_operations() {
+        let mut seq = Sequencer::new();
+        let notes = |seq: &Sequencer| seq.steps().iter().map(|s| s.note).collect::<Vec<_>>();
+        for i in 0..16 {
+            seq.get_step_mut(i).unwrap().note = 40 + i as u8;
+        }
+        // Step 2 slides into step 3
+        seq.get_step_mut(2).unwrap().slide = true;
+        seq.get_step_mut(2).unwrap().slide_time = 40;
+        seq.reverse();
+        assert_eq!(notes(&seq)[..3], [55, 54, 53]);
+        // Reversed, step 12 (old 3) slides into step 13 (old 2), taking
+        // its slide time along
+        assert!(seq.get_step(12).unwrap().slide);
+        assert_eq!(seq.get_step(12).unwrap().slide_time, 40);
+        assert_eq!(seq.steps().iter().filter(|s| s.slide).count(), 1);
+        seq.reverse();
+        assert!(seq.get_step(2).unwrap().slide);
+
+        seq.rotate(-2);
+        assert_eq!(notes(&seq)[..2], [42, 43]);
+        assert_eq!(seq.get_step(15).unwrap().note, 41);
+        seq.rotate(2);
+
+        seq.mirror();
+        assert_eq!(notes(&seq)[8..], [47, 46, 45, 44, 43, 42, 41, 40]);
+        assert!(seq.get_step(12).unwrap().slide);
+
+        seq.get_step_mut(0).unwrap().accent = true;
+        seq.invert_accents();
+        assert_eq!(seq.steps().iter().filter(|s| s.accent).count(), 15);
+
+        assert!(seq.double());
+        assert_eq!(seq.length(), 32);
+        assert_eq!(seq.steps()[..16], seq.steps()[16..]);
+        assert_eq!(seq.pattern_bytes().len(), 32 * STEP_BYTES);
+        assert!(!seq.double());
+    }
+
+    #[test]
+    fn test_pattern_length_follows_loaded_pattern() {
+        let mut seq = Sequencer::new();
+        let bytes = seq.pattern_bytes();
+        assert!(seq.load_pattern_bytes(&bytes[..8 * STEP_BYTES]));
+        assert_eq!(seq.length(), 8);
+        assert!(seq.get_step(8).is_none());
+        assert!(!seq.load_pattern_bytes(&[]));
+        assert!(!seq.load_pattern_bytes(&bytes.repeat(3)));
+
+        // An 8-step pattern loops every 8 steps, and a variation must match it
+        assert!(!seq.set_variation_bytes(&bytes));
+        assert!(seq.set_variation_bytes(&bytes[..8 * STEP_BYTES]));
+        seq.start();
+        for _ in 0..9 {
+            while !matches!(seq.tick(), Some(SeqEvent::NoteOn(_) | SeqEvent::Rest)) {}
+        }
+        assert_eq!(seq.current_step(), 1);
+    }
+
+    #[test]
+    fn test_transpose_and_scale() {
+        let mut seq = Sequencer::new();
+        for (i, note) in [(0, 36), (1, 40), (2, 125)] {
+            seq.get_step_mut(i).unwrap().note = note;
+        }
+        seq.transpose(5);
+        let notes = |seq: &Sequencer| (0..3).map(|i| seq.get_step(i).unwrap().note).collect::<Vec<_>>();
+        // 130 is out of range, so it folds down an octave
+        assert_eq!(notes(&seq), vec![41, 45, 118]);
+
+        // In C minor the A (45) snaps down to A flat as the scale is set
+        seq.set_scale(Key::new(0, Scale::Minor));
+        assert_eq!(notes(&seq), vec![41, 44, 118]);
+        seq.set_step(3, Step { note: 52, ..*seq.get_step(3).unwrap() });
+        assert_eq!(seq.get_step(3).unwrap().note, 51);
+        seq.transpose(-1);
+        assert_eq!(notes(&seq), vec![39, 43, 116]);
+
+        seq.clear_scale();
+        seq.transpose(1);
+        assert_eq!(notes(&seq), vec![40, 44, 117]);
+    }
+
+    #[test]
+    fn test_load_notes_and_flags() {
+        let mut seq = Sequencer::new();
+        seq.get_step_mut(1).unwrap().cents = 30;
+        let notes: Vec<u8> = (36..52).collect();
+        let flags = [FLAG_ACTIVE | FLAG_ACCENT; 16];
+        assert!(seq.load_notes_and_flags(&notes, &flags));
+
+        let step = seq.get_step(1).unwrap();
+        assert_eq!(step.note, 37);
+        assert!(step.active && step.accent && !step.slide);
+        assert_eq!(step.cents, 30);
+
+        // Mismatched lengths leave the pattern alone
+        assert!(!seq.load_notes_and_flags(&notes[..8], &flags));
+        assert_eq!(seq.get_step(1).unwrap().note, 37);
+    }
+
+    #[test]
+    fn test_variation_plays_by_chance() {
+        let mut seq = Sequencer::new();
+        let mut variation = Sequencer::new();
+        variation.set_step(0, Step::from_flags(48, FLAG_ACTIVE, 0, FULL_LEVEL, DEFAULT_GATE));
+        assert!(seq.set_variation_bytes(&variation.pattern_bytes()));
+        assert!(!seq.set_variation_bytes(&[0; 3]));
+
+        // Count loops whose first step plays the variation's note
+        let mut count_variations = |chance: f32| {
+            seq.set_variation_chance(chance);
+            seq.start();
+            let mut count = 0;
+            for _ in 0..(100 * STEPS) {
+                while !seq.tick().is_some_and(|e| e.starts_step()) {}
+                if seq.current_step() == 1 && seq.is_playing_variation() {
+                    count += 1;
+                }
+            }
+            count
+        };
+        assert_eq!(count_variations(0.0), 0);
+        assert_eq!(count_variations(1.0), 100);
+        let half = count_variations(0.5);
+        assert!((30..70).contains(&half), "played {} of 100", half);
+    }
+
     #[test]
     fn test_tempo_change() {
         let mut seq = Sequencer::new();
 
         seq.set_tempo(60.0);
-        let slow_samples = seq.samples_per_step;
+        let slow_samples = seq.samples_per_step();
 
         seq.set_tempo(120.0);
-        let fast_samples = seq.samples_per_step;
+        let fast_samples = seq.samples_per_step();
 
         // Faster tempo = fewer samples per step
         assert!(fast_samples < slow_samples);
@@ -0,0 +1,182 @@
+/// Shape of the pitch glide between slid notes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SlideCurve {
+    /// One-pole lag on the pitch: sets off at full speed and eases into
+    /// the new note
+    #[default]
+    Exponential,
+    /// Constant rate in semitones, arriving exactly at the slide time
+    Linear,
+    /// The TB-303's slide RC followed by the smoothing on the pitch CV, so
+    /// the glide leaves the old note gently as well as settling gently
+    Rc,
+}
+
+impl SlideCurve {
+    /// Curve for a UI index: 0 = exponential, 1 = linear, 2 = 303 RC
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(SlideCurve::Exponential),
+            1 => Some(SlideCurve::Linear),
+            2 => Some(SlideCurve::Rc),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Distance from the target, in semitones, at which a glide snaps onto it
+const SETTLED: f32 = 0.01;
+
+/// Pitch glide from one note to the next
+#[derive(Clone, Copy, Debug)]
+pub struct Glide {
+    curve: SlideCurve,
+    pitch: f32,
+    target: f32,
+    // Per-sample lag coefficient, or semitones per sample for the linear
+    // curve
+    rate: f32,
+    // First RC stage of the 303 curve
+    lag: f32,
+    active: bool,
+}
+
+impl Glide {
+    pub fn new(note: f32) -> Self {
+        Self {
+            curve: SlideCurve::Exponential,
+            pitch: note,
+            target: note,
+            rate: 0.0,
+            lag: note,
+            active: false,
+        }
+    }
+
+    /// Jump straight to `note`, ending any glide
+    pub fn set(&mut self, note: f32) {
+        self.pitch = note;
+        self.target = note;
+        self.lag = note;
+        self.active = false;
+    }
+
+    /// Glide from the current pitch to `note` with `curve`, taking about
+    /// `samples` samples
+    pub fn start(&mut self, note: f32, curve: SlideCurve, samples: f32) {
+        let samples = samples.max(1.0);
+        self.curve = curve;
+        self.target = note;
+        self.lag = self.pitch;
+        self.rate = match curve {
+            SlideCurve::Exponential => 1.0 / samples,
+            SlideCurve::Linear => (note - self.pitch).abs() / samples,
+            // Two stages with half the time each take about as long overall
+            SlideCurve::Rc => (2.0 / samples).min(1.0),
+        };
+        self.active = true;
+    }
+
+    /// Finish the glide where it was heading
+    pub fn settle(&mut self) {
+        self.set(self.target);
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Advance one sample and return the pitch in MIDI notes
+    pub fn process(&mut self) -> f32 {
+        if !self.active {
+            return self.pitch;
+        }
+        let distance = self.target - self.pitch;
+        match self.curve {
+            SlideCurve::Exponential => self.pitch += distance * self.rate,
+            SlideCurve::Linear => self.pitch += distance.clamp(-self.rate, self.rate),
+            SlideCurve::Rc => {
+                self.lag += (self.target - self.lag) * self.rate;
+                self.pitch += (self.lag - self.pitch) * self.rate;
+            }
+        }
+        // Only the 303 curve has a first stage still to settle
+        let stage = if self.curve == SlideCurve::Rc { self.lag } else { self.target };
+        if (self.target - self.pitch).abs() <= SETTLED && (self.target - stage).abs() <= SETTLED {
+            self.settle();
+        }
+        self.pitch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples until the glide from 36 to 48 is done, with the pitch a
+    /// quarter of the way through
+    fn run(curve: SlideCurve, samples: f32) -> (usize, f32) {
+        let mut glide = Glide::new(36.0);
+        glide.start(48.0, curve, samples);
+        let mut quarter = 0.0;
+        let mut count = 0;
+        while glide.is_active() {
+            let pitch = glide.process();
+            count += 1;
+            if count == samples as usize / 4 {
+                quarter = pitch;
+            }
+        }
+        assert_eq!(glide.pitch(), 48.0);
+        (count, quarter)
+    }
+
+    #[test]
+    fn test_linear_arrives_on_time() {
+        let (count, quarter) = run(SlideCurve::Linear, 1000.0);
+        assert!((999..=1001).contains(&count), "count {}", count);
+        assert!((quarter - 39.0).abs() < 0.05, "quarter {}", quarter);
+    }
+
+    #[test]
+    fn test_curves_differ_in_shape() {
+        let (exponential_len, exponential) = run(SlideCurve::Exponential, 1000.0);
+        let (linear_len, linear) = run(SlideCurve::Linear, 1000.0);
+        let (_, rc) = run(SlideCurve::Rc, 1000.0);
+        // The 303 RC leaves the old note gently, and the one-pole takes
+        // its time settling into the new one
+        assert!(rc < exponential && rc < linear, "{} {} {}", rc, exponential, linear);
+        assert!(exponential_len > 2 * linear_len);
+    }
+
+    #[test]
+    fn test_longer_slides_take_longer() {
+        for curve in [SlideCurve::Exponential, SlideCurve::Linear, SlideCurve::Rc] {
+            let (short, _) = run(curve, 500.0);
+            let (long, _) = run(curve, 5000.0);
+            assert!(long > short * 5, "{:?} {} {}", curve, short, long);
+        }
+    }
+
+    #[test]
+    fn test_set_ends_glide() {
+        let mut glide = Glide::new(36.0);
+        glide.start(48.0, SlideCurve::Rc, 1000.0);
+        glide.process();
+        glide.set(40.0);
+        assert!(!glide.is_active());
+        assert_eq!(glide.process(), 40.0);
+    }
+}
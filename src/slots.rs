@@ -0,0 +1,130 @@
+//! User pattern memory
+//! Each synth slot holds a synth pattern and, optionally, the tempo and knob
+//! settings it was written with, like the factory presets do. Drum slots
+//! hold just the pattern. Either keeps the pattern's variation with it.
+
+use crate::drums::sequencer::DrumStep;
+use crate::error::ApiError;
+use crate::sequencer::Step;
+
+pub const SLOT_COUNT: usize = 8;
+
+/// Tempo and synth knobs stored alongside a pattern
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sound {
+    pub tempo: f32,
+    /// Synth knobs in the order Synth::params() writes them
+    pub params: Vec<f32>,
+}
+
+/// Alternate pattern kept with a stored one, and the chance (0.0 - 1.0)
+/// that a loop plays it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variation<T> {
+    pub steps: Vec<T>,
+    pub chance: f32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatternSlot {
+    pub steps: Vec<Step>,
+    pub variation: Option<Variation<Step>>,
+    pub sound: Option<Sound>,
+}
+
+/// A pattern with its variation, as drum slots and songs store them
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoredPattern<T> {
+    pub steps: Vec<T>,
+    pub variation: Option<Variation<T>>,
+}
+
+pub type DrumSlot = StoredPattern<DrumStep>;
+
+pub struct PatternSlots<T = PatternSlot> {
+    slots: [Option<T>; SLOT_COUNT],
+}
+
+impl<T: Clone> PatternSlots<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Default::default(),
+        }
+    }
+
+    pub fn store(&mut self, index: usize, slot: T) -> Result<(), ApiError> {
+        let target = self.slots.get_mut(index).ok_or(ApiError::SlotIndex)?;
+        *target = Some(slot);
+        Ok(())
+    }
+
+    pub fn get(&self, index: usize) -> Result<&T, ApiError> {
+        let slot = self.slots.get(index).ok_or(ApiError::SlotIndex)?;
+        slot.as_ref().ok_or(ApiError::EmptySlot)
+    }
+
+    pub fn is_filled(&self, index: usize) -> bool {
+        self.get(index).is_ok()
+    }
+
+    /// Copy slot `from` over slot `to`, which must both be in range
+    pub fn copy(&mut self, from: usize, to: usize) -> Result<(), ApiError> {
+        self.slots.get(to).ok_or(ApiError::SlotIndex)?;
+        let slot = self.get(from)?.clone();
+        self.store(to, slot)
+    }
+}
+
+impl<T: Clone> Default for PatternSlots<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets::PRESETS;
+
+    fn slot(sound: Option<Sound>) -> PatternSlot {
+        PatternSlot {
+            steps: PRESETS[0].steps.to_vec(),
+            variation: None,
+            sound,
+        }
+    }
+
+    #[test]
+    fn test_store_and_get() {
+        let mut slots = PatternSlots::new();
+        let sound = Sound { tempo: 128.0, params: vec![500.0, 0.7] };
+        slots.store(3, slot(Some(sound.clone()))).unwrap();
+        assert_eq!(slots.get(3).unwrap().sound, Some(sound));
+        assert!(slots.is_filled(3));
+    }
+
+    #[test]
+    fn test_empty_slot() {
+        let slots: PatternSlots = PatternSlots::new();
+        assert_eq!(slots.get(0), Err(ApiError::EmptySlot));
+        assert!(!slots.is_filled(0));
+    }
+
+    #[test]
+    fn test_slot_index_checked() {
+        let mut slots = PatternSlots::new();
+        assert_eq!(slots.store(SLOT_COUNT, slot(None)), Err(ApiError::SlotIndex));
+        assert_eq!(slots.get(SLOT_COUNT), Err(ApiError::SlotIndex));
+    }
+
+    #[test]
+    fn test_copy() {
+        let mut slots = PatternSlots::new();
+        slots.store(1, slot(None)).unwrap();
+        slots.copy(1, 6).unwrap();
+        assert_eq!(slots.get(6), slots.get(1));
+        assert_eq!(slots.copy(2, 3), Err(ApiError::EmptySlot));
+        assert_eq!(slots.copy(1, SLOT_COUNT), Err(ApiError::SlotIndex));
+        assert!(!slots.is_filled(3));
+    }
+}
@@ -0,0 +1,245 @@
+//! Song mode: a bank of synth and drum patterns and a chain that plays
+//! them in order, each for a number of bars
+
+use crate::drums::sequencer::DrumStep;
+use crate::error::ApiError;
+use crate::sequencer::Step;
+use crate::slots::StoredPattern;
+
+/// Patterns of each kind the song can hold
+pub const SONG_PATTERNS: usize = 16;
+
+/// Longest chain accepted
+pub const MAX_CHAIN: usize = 64;
+
+/// One link in the chain: which patterns to play and for how many bars
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChainEntry {
+    pub synth: usize,
+    pub drums: usize,
+    pub repeats: u32,
+}
+
+/// What happens at the end of a bar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BarEnd {
+    /// The current entry carries on
+    Same,
+    /// Move on to this entry
+    Next(ChainEntry),
+    /// The chain has run out and doesn't loop
+    Finished,
+}
+
+pub struct Song {
+    synth_patterns: [Option<StoredPattern<Step>>; SONG_PATTERNS],
+    drum_patterns: [Option<StoredPattern<DrumStep>>; SONG_PATTERNS],
+    chain: Vec<ChainEntry>,
+    looping: bool,
+
+    // Playback position: entry in the chain and bars left on it, or None
+    // until the first bar begins
+    entry: Option<usize>,
+    bars_left: u32,
+    finished: bool,
+}
+
+impl Song {
+    pub fn new() -> Self {
+        Self {
+            synth_patterns: Default::default(),
+            drum_patterns: Default::default(),
+            chain: Vec::with_capacity(MAX_CHAIN),
+            looping: true,
+            entry: None,
+            bars_left: 0,
+            finished: false,
+        }
+    }
+
+    pub fn set_synth_pattern(&mut self, index: usize, pattern: StoredPattern<Step>) -> Result<(), ApiError> {
+        let target = self.synth_patterns.get_mut(index).ok_or(ApiError::SlotIndex)?;
+        *target = Some(pattern);
+        Ok(())
+    }
+
+    pub fn set_drum_pattern(&mut self, index: usize, pattern: StoredPattern<DrumStep>) -> Result<(), ApiError> {
+        let target = self.drum_patterns.get_mut(index).ok_or(ApiError::SlotIndex)?;
+        *target = Some(pattern);
+        Ok(())
+    }
+
+    pub fn synth_pattern(&self, index: usize) -> Option<&StoredPattern<Step>> {
+        self.synth_patterns.get(index)?.as_ref()
+    }
+
+    pub fn drum_pattern(&self, index: usize) -> Option<&StoredPattern<DrumStep>> {
+        self.drum_patterns.get(index)?.as_ref()
+    }
+
+    /// Replace the chain. Every entry must name patterns in range and play
+    /// for at least one bar; the chain is left as it was otherwise.
+    pub fn set_chain(&mut self, chain: &[ChainEntry]) -> Result<(), ApiError> {
+        if chain.len() > MAX_CHAIN || chain.iter().any(|e| e.repeats == 0) {
+            return Err(ApiError::ChainFormat);
+        }
+        if chain.iter().any(|e| e.synth >= SONG_PATTERNS || e.drums >= SONG_PATTERNS) {
+            return Err(ApiError::SlotIndex);
+        }
+        self.chain.clear();
+        self.chain.extend_from_slice(chain);
+        self.rewind();
+        Ok(())
+    }
+
+    pub fn chain(&self) -> &[ChainEntry] {
+        &self.chain
+    }
+
+    /// Go back to the top of the chain once the end is reached, rather
+    /// than finishing
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// Start again from the first entry at the next bar
+    pub fn rewind(&mut self) {
+        self.entry = None;
+        self.bars_left = 0;
+        self.finished = false;
+    }
+
+    /// Index of the entry playing, if a bar has begun
+    pub fn entry(&self) -> Option<usize> {
+        self.entry
+    }
+
+    /// Whether the last bar of a non-looping chain has been reached
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Called as the last step of each bar starts to find what the next one plays
+    pub fn end_bar(&mut self) -> BarEnd {
+        if self.chain.is_empty() {
+            return BarEnd::Same;
+        }
+        let next = match self.entry {
+            None => 0,
+            Some(_) if self.bars_left > 1 => {
+                self.bars_left -= 1;
+                return BarEnd::Same;
+            }
+            Some(entry) if entry + 1 < self.chain.len() => entry + 1,
+            Some(_) if self.looping => 0,
+            Some(_) => {
+                self.finished = true;
+                return BarEnd::Finished;
+            }
+        };
+        self.entry = Some(next);
+        self.bars_left = self.chain[next].repeats;
+        BarEnd::Next(self.chain[next])
+    }
+
+    /// Go to bar `bar` counted from the top of the chain, where playing
+    /// through from the start would have reached, and return what it plays
+    pub fn seek(&mut self, bar: u32) -> BarEnd {
+        self.rewind();
+        let length: u32 = self.chain.iter().map(|e| e.repeats).sum();
+        if length == 0 {
+            return BarEnd::Same;
+        }
+        if bar >= length && !self.looping {
+            self.entry = Some(self.chain.len() - 1);
+            self.finished = true;
+            return BarEnd::Finished;
+        }
+        let mut bar = bar % length;
+        let mut next = 0;
+        while bar >= self.chain[next].repeats {
+            bar -= self.chain[next].repeats;
+            next += 1;
+        }
+        self.entry = Some(next);
+        self.bars_left = self.chain[next].repeats - bar;
+        BarEnd::Next(self.chain[next])
+    }
+}
+
+impl Default for Song {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(synth: usize, repeats: u32) -> ChainEntry {
+        ChainEntry { synth, drums: synth, repeats }
+    }
+
+    #[test]
+    fn test_chain_plays_each_entry_for_its_bars() {
+        let mut song = Song::new();
+        song.set_chain(&[entry(0, 2), entry(1, 1)]).unwrap();
+        let bars: Vec<BarEnd> = (0..4).map(|_| song.end_bar()).collect();
+        assert_eq!(
+            bars,
+            vec![BarEnd::Next(entry(0, 2)), BarEnd::Same, BarEnd::Next(entry(1, 1)), BarEnd::Next(entry(0, 2))]
+        );
+    }
+
+    #[test]
+    fn test_chain_can_finish() {
+        let mut song = Song::new();
+        song.set_looping(false);
+        song.set_chain(&[entry(3, 1)]).unwrap();
+        assert_eq!(song.end_bar(), BarEnd::Next(entry(3, 1)));
+        assert_eq!(song.end_bar(), BarEnd::Finished);
+        assert!(song.is_finished());
+        assert_eq!(song.entry(), Some(0));
+    }
+
+    #[test]
+    fn test_seek_matches_playing_through() {
+        let chain = [entry(0, 2), entry(1, 1), entry(2, 3)];
+        let song = |looping: bool| {
+            let mut song = Song::new();
+            song.set_looping(looping);
+            song.set_chain(&chain).unwrap();
+            song
+        };
+        for looping in [true, false] {
+            for bar in 0..8 {
+                let mut played = song(looping);
+                for _ in 0..=bar {
+                    played.end_bar();
+                }
+                let mut sought = song(looping);
+                let expected = match played.entry() {
+                    _ if played.is_finished() => BarEnd::Finished,
+                    Some(entry) => BarEnd::Next(chain[entry]),
+                    None => BarEnd::Same,
+                };
+                assert_eq!(sought.seek(bar), expected, "bar {}", bar);
+                assert_eq!(sought.entry(), played.entry());
+                // Both carry on the same way
+                assert_eq!(sought.end_bar(), played.end_bar(), "bar {}", bar);
+            }
+        }
+        assert_eq!(Song::new().seek(3), BarEnd::Same);
+    }
+
+    #[test]
+    fn test_invalid_chain_is_rejected() {
+        let mut song = Song::new();
+        song.set_chain(&[entry(0, 4)]).unwrap();
+        assert_eq!(song.set_chain(&[entry(0, 0)]), Err(ApiError::ChainFormat));
+        assert_eq!(song.set_chain(&[entry(SONG_PATTERNS, 1)]), Err(ApiError::SlotIndex));
+        assert_eq!(song.chain(), &[entry(0, 4)]);
+        assert_eq!(Song::new().end_bar(), BarEnd::Same);
+    }
+}
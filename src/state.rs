@@ -0,0 +1,371 @@
+//! Saved session format
+//!
+//! A blob is a magic tag and format version followed by tagged,
+//! length-prefixed sections. Readers skip sections they don't know and keep
+//! the current value for sections that are missing, so new fields are added
+//! as new sections. Changing the layout of an existing section bumps
+//! FORMAT_VERSION and adds a migration step to `Session::upgrade`.
+
+use crate::automation::PARAM_COUNT;
+use crate::error::ApiError;
+use crate::locks::Locks;
+use crate::sequencer::{DEFAULT_GATE, FULL_LEVEL};
+use crate::trig::Trig;
+
+const MAGIC: &[u8; 4] = b"A303";
+
+/// Current format version
+/// 0 = bare synth pattern from get_pattern(), before sessions existed
+/// 1 = sectioned session blob
+/// 2 = synth pattern steps gain a level byte
+/// 3 = synth pattern steps gain a gate length byte
+/// 4 = synth and drum pattern steps gain probability and condition bytes
+/// 5 = synth and drum pattern steps gain a ratchet byte
+/// 6 = synth and drum pattern steps gain a nudge byte
+/// 7 = synth pattern steps gain parameter lock bytes
+/// 8 = synth pattern steps gain a slide time byte before the locks
+/// 9 = drum pattern steps gain a velocity byte per track
+pub const FORMAT_VERSION: u8 = 9;
+
+/// Synth pattern bytes per step before version 2: note, flags, cents
+const V1_STEP_BYTES: usize = 3;
+
+/// Synth pattern bytes per step before version 3: note, flags, cents, level
+const V2_STEP_BYTES: usize = 4;
+
+/// Synth pattern bytes per step before version 4: ..., level, gate
+const V3_STEP_BYTES: usize = 5;
+
+/// Drum pattern bytes per step before version 4: the track bits
+const V3_DRUM_STEP_BYTES: usize = 1;
+
+/// Synth pattern bytes per step before version 5: ..., probability, condition
+const V4_STEP_BYTES: usize = 7;
+
+/// Drum pattern bytes per step before version 5: track bits, probability,
+/// condition
+const V4_DRUM_STEP_BYTES: usize = 3;
+
+/// Synth pattern bytes per step before version 6: ..., ratchet
+const V5_STEP_BYTES: usize = 8;
+
+/// Drum pattern bytes per step before version 6: ..., ratchet
+const V5_DRUM_STEP_BYTES: usize = 4;
+
+/// Synth pattern bytes per step before version 7: ..., nudge
+const V6_STEP_BYTES: usize = 9;
+
+/// Synth pattern bytes per step before version 8: ..., nudge, locks
+const V7_STEP_BYTES: usize = V6_STEP_BYTES + PARAM_COUNT;
+
+/// Drum pattern bytes per step before version 9: ..., nudge
+const V8_DRUM_STEP_BYTES: usize = 5;
+
+/// Drum tracks with a velocity byte from version 9
+const DRUM_TRACKS: usize = 5;
+
+/// Length of a version 0 blob: one pattern, no header
+const LEGACY_PATTERN_LEN: usize = 16 * V1_STEP_BYTES;
+
+const SECTION_TEMPO: u8 = 1;
+const SECTION_SYNTH: u8 = 2;
+const SECTION_MIXER: u8 = 3;
+const SECTION_SYNTH_PATTERN: u8 = 4;
+const SECTION_DRUM_PATTERN: u8 = 5;
+const SECTION_CC_MAP: u8 = 6;
+
+/// Everything a saved session holds; `None` or empty means the blob didn't
+/// include it and the current value should be kept
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Session {
+    pub tempo: Option<f32>,
+    /// Synth knobs in the order the engine writes them; blobs from older
+    /// versions may hold fewer
+    pub synth: Vec<f32>,
+    /// Synth, drum and master volume
+    pub mixer: Vec<f32>,
+    pub synth_pattern: Option<Vec<u8>>,
+    pub drum_pattern: Option<Vec<u8>>,
+    /// MIDI CC assignments as [cc, parameter] pairs
+    pub cc_map: Option<Vec<u8>>,
+}
+
+impl Session {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+
+        if let Some(tempo) = self.tempo {
+            write_section(&mut out, SECTION_TEMPO, &floats_to_bytes(&[tempo]));
+        }
+        write_section(&mut out, SECTION_SYNTH, &floats_to_bytes(&self.synth));
+        write_section(&mut out, SECTION_MIXER, &floats_to_bytes(&self.mixer));
+        if let Some(pattern) = &self.synth_pattern {
+            write_section(&mut out, SECTION_SYNTH_PATTERN, pattern);
+        }
+        if let Some(pattern) = &self.drum_pattern {
+            write_section(&mut out, SECTION_DRUM_PATTERN, pattern);
+        }
+        if let Some(map) = &self.cc_map {
+            write_section(&mut out, SECTION_CC_MAP, map);
+        }
+        out
+    }
+
+    /// Parse a blob of any known version, upgrading older ones
+    pub fn decode(bytes: &[u8]) -> Result<Self, ApiError> {
+        let Some(body) = bytes.strip_prefix(MAGIC) else {
+            return Self::from_legacy_pattern(bytes);
+        };
+        let (&version, mut rest) = body.split_first().ok_or(ApiError::StateFormat)?;
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(ApiError::StateFormat);
+        }
+
+        let mut session = Session::default();
+        while !rest.is_empty() {
+            let (tag, data, next) = read_section(rest)?;
+            match tag {
+                SECTION_TEMPO => session.tempo = bytes_to_floats(data).first().copied(),
+                SECTION_SYNTH => session.synth = bytes_to_floats(data),
+                SECTION_MIXER => session.mixer = bytes_to_floats(data),
+                SECTION_SYNTH_PATTERN => session.synth_pattern = Some(data.to_vec()),
+                SECTION_DRUM_PATTERN => session.drum_pattern = Some(data.to_vec()),
+                SECTION_CC_MAP => session.cc_map = Some(data.to_vec()),
+                // Written by a newer version
+                _ => {}
+            }
+            rest = next;
+        }
+        Ok(session.upgrade(version))
+    }
+
+    /// Run the migrations for every version after `version`
+    fn upgrade(mut self, version: u8) -> Self {
+        if version < 2 {
+            self.synth_pattern = self.synth_pattern.map(|p| add_step_levels(&p));
+        }
+        if version < 3 {
+            self.synth_pattern = self.synth_pattern.map(|p| add_step_gates(&p));
+        }
+        if version < 4 {
+            self.synth_pattern = self.synth_pattern.map(|p| add_step_trigs(&p, V3_STEP_BYTES));
+            self.drum_pattern = self.drum_pattern.map(|p| add_step_trigs(&p, V3_DRUM_STEP_BYTES));
+        }
+        if version < 5 {
+            self.synth_pattern = self.synth_pattern.map(|p| add_step_ratchets(&p, V4_STEP_BYTES));
+            self.drum_pattern = self.drum_pattern.map(|p| add_step_ratchets(&p, V4_DRUM_STEP_BYTES));
+        }
+        if version < 6 {
+            self.synth_pattern = self.synth_pattern.map(|p| add_step_nudges(&p, V5_STEP_BYTES));
+            self.drum_pattern = self.drum_pattern.map(|p| add_step_nudges(&p, V5_DRUM_STEP_BYTES));
+        }
+        if version < 7 {
+            self.synth_pattern = self.synth_pattern.map(|p| add_step_locks(&p));
+        }
+        if version < 8 {
+            self.synth_pattern = self.synth_pattern.map(|p| add_step_slide_times(&p));
+        }
+        if version < 9 {
+            self.drum_pattern = self.drum_pattern.map(|p| add_drum_velocities(&p));
+        }
+        self
+    }
+
+    /// Version 0: share codes that were just the synth pattern, laid out
+    /// as in a version 1 session
+    fn from_legacy_pattern(bytes: &[u8]) -> Result<Self, ApiError> {
+        if bytes.len() != LEGACY_PATTERN_LEN {
+            return Err(ApiError::StateFormat);
+        }
+        let session = Session {
+            synth_pattern: Some(bytes.to_vec()),
+            ..Session::default()
+        };
+        Ok(session.upgrade(1))
+    }
+}
+
+/// Version 2 migration: steps saved without a level play at full level
+fn add_step_levels(pattern: &[u8]) -> Vec<u8> {
+    append_step_bytes(pattern, V1_STEP_BYTES, &[FULL_LEVEL])
+}
+
+/// Version 3 migration: steps saved without a gate length keep the fixed
+/// gate they always had
+fn add_step_gates(pattern: &[u8]) -> Vec<u8> {
+    append_step_bytes(pattern, V2_STEP_BYTES, &[DEFAULT_GATE])
+}
+
+/// Version 4 migration: steps saved without a trig always play
+fn add_step_trigs(pattern: &[u8], step_bytes: usize) -> Vec<u8> {
+    append_step_bytes(pattern, step_bytes, &Trig::ALWAYS.to_bytes())
+}
+
+/// Version 5 migration: steps saved without a ratchet play once
+fn add_step_ratchets(pattern: &[u8], step_bytes: usize) -> Vec<u8> {
+    append_step_bytes(pattern, step_bytes, &[1])
+}
+
+/// Version 6 migration: steps saved without a nudge play on the grid
+fn add_step_nudges(pattern: &[u8], step_bytes: usize) -> Vec<u8> {
+    append_step_bytes(pattern, step_bytes, &[0])
+}
+
+/// Version 7 migration: steps saved without locks lock nothing
+fn add_step_locks(pattern: &[u8]) -> Vec<u8> {
+    append_step_bytes(pattern, V6_STEP_BYTES, &Locks::NONE.to_bytes())
+}
+
+/// Version 8 migration: steps saved without a slide time use the synth's
+fn add_step_slide_times(pattern: &[u8]) -> Vec<u8> {
+    pattern
+        .chunks(V7_STEP_BYTES)
+        .flat_map(|step| {
+            let (head, locks) = step.split_at(V6_STEP_BYTES.min(step.len()));
+            head.iter().chain(&[0]).chain(locks).copied()
+        })
+        .collect()
+}
+
+/// Version 9 migration: drum hits saved without a velocity play at full
+/// level
+fn add_drum_velocities(pattern: &[u8]) -> Vec<u8> {
+    append_step_bytes(pattern, V8_DRUM_STEP_BYTES, &[FULL_LEVEL; DRUM_TRACKS])
+}
+
+/// Add `extra` to the end of each `step_bytes`-long step
+fn append_step_bytes(pattern: &[u8], step_bytes: usize, extra: &[u8]) -> Vec<u8> {
+    pattern
+        .chunks(step_bytes)
+        .flat_map(|step| step.iter().chain(extra).copied())
+        .collect()
+}
+
+fn write_section(out: &mut Vec<u8>, tag: u8, data: &[u8]) {
+    out.push(tag);
+    out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+/// Split off one section, returning its tag, data and the remaining bytes
+fn read_section(bytes: &[u8]) -> Result<(u8, &[u8], &[u8]), ApiError> {
+    if bytes.len() < 3 {
+        return Err(ApiError::StateFormat);
+    }
+    let len = u16::from_le_bytes([bytes[1], bytes[2]]) as usize;
+    let rest = &bytes[3..];
+    if rest.len() < len {
+        return Err(ApiError::StateFormat);
+    }
+    Ok((bytes[0], &rest[..len], &rest[len..]))
+}
+
+fn floats_to_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn bytes_to_floats(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::STEP_BYTES;
+
+    #[test]
+    fn test_round_trip() {
+        let session = Session {
+            tempo: Some(132.0),
+            synth: vec![800.0, 0.7, 0.5],
+            mixer: vec![0.7, 0.8, 0.9],
+            synth_pattern: Some(vec![1; 64]),
+            drum_pattern: Some(vec![5; 16]),
+            cc_map: Some(vec![74, 0]),
+        };
+        let blob = session.encode();
+        assert_eq!(blob[4], FORMAT_VERSION);
+        assert_eq!(Session::decode(&blob), Ok(session));
+    }
+
+    #[test]
+    fn test_unknown_sections_skipped_and_newer_versions_rejected() {
+        let session = Session {
+            tempo: Some(128.0),
+            ..Session::default()
+        };
+        let mut blob = session.encode();
+        write_section(&mut blob, 200, &[1, 2, 3]);
+        assert_eq!(Session::decode(&blob).unwrap().tempo, Some(128.0));
+
+        blob[4] = FORMAT_VERSION + 1;
+        assert_eq!(Session::decode(&blob), Err(ApiError::StateFormat));
+
+        // Truncated section
+        let blob = session.encode();
+        assert!(Session::decode(&blob[..blob.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_legacy_pattern_migrates() {
+        let pattern = vec![36; LEGACY_PATTERN_LEN];
+        let session = Session::decode(&pattern).unwrap();
+        let upgraded = session.synth_pattern.unwrap();
+        assert_eq!(upgraded.len(), 16 * STEP_BYTES);
+        assert_eq!(&upgraded[..10], &[36, 36, 36, FULL_LEVEL, DEFAULT_GATE, 100, 0, 1, 0, 0]);
+        assert_eq!(&upgraded[10..STEP_BYTES], &Locks::NONE.to_bytes());
+        assert_eq!(session.tempo, None);
+
+        assert!(Session::decode(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_version_1_patterns_gain_levels() {
+        let mut blob = MAGIC.to_vec();
+        blob.push(1);
+        write_section(&mut blob, SECTION_SYNTH_PATTERN, &[36, 4, 0].repeat(16));
+        let pattern = Session::decode(&blob).unwrap().synth_pattern.unwrap();
+        assert_eq!(pattern, [36, 4, 0, FULL_LEVEL, DEFAULT_GATE, 100, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0].repeat(16));
+
+        let mut blob = MAGIC.to_vec();
+        blob.push(2);
+        write_section(&mut blob, SECTION_SYNTH_PATTERN, &[36, 4, 0, 90].repeat(16));
+        let pattern = Session::decode(&blob).unwrap().synth_pattern.unwrap();
+        assert_eq!(pattern, [36, 4, 0, 90, DEFAULT_GATE, 100, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0].repeat(16));
+    }
+
+    #[test]
+    fn test_version_3_patterns_gain_step_bytes() {
+        let mut blob = MAGIC.to_vec();
+        blob.push(3);
+        write_section(&mut blob, SECTION_SYNTH_PATTERN, &[36, 4, 0, 90, 75].repeat(16));
+        write_section(&mut blob, SECTION_DRUM_PATTERN, &[5; 16]);
+        let session = Session::decode(&blob).unwrap();
+        assert_eq!(session.synth_pattern.unwrap(), [36, 4, 0, 90, 75, 100, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0].repeat(16));
+        assert_eq!(session.drum_pattern.unwrap(), [5, 100, 0, 1, 0, 127, 127, 127, 127, 127].repeat(16));
+    }
+
+    #[test]
+    fn test_version_7_patterns_gain_slide_times() {
+        let step = [36, 4, 0, 90, 75, 100, 0, 2, 10, 0, 128, 0, 0, 0, 255];
+        let mut blob = MAGIC.to_vec();
+        blob.push(7);
+        write_section(&mut blob, SECTION_SYNTH_PATTERN, &step.repeat(16));
+        let pattern = Session::decode(&blob).unwrap().synth_pattern.unwrap();
+        assert_eq!(pattern, [36, 4, 0, 90, 75, 100, 0, 2, 10, 0, 0, 128, 0, 0, 0, 255].repeat(16));
+    }
+
+    #[test]
+    fn test_version_8_drum_patterns_gain_velocities() {
+        let mut blob = MAGIC.to_vec();
+        blob.push(8);
+        write_section(&mut blob, SECTION_DRUM_PATTERN, &[37, 80, 0, 2, 246].repeat(16));
+        let pattern = Session::decode(&blob).unwrap().drum_pattern.unwrap();
+        assert_eq!(pattern, [37, 80, 0, 2, 246, 127, 127, 127, 127, 127].repeat(16));
+    }
+}
@@ -0,0 +1,160 @@
+//! Unison voice
+//! Several detuned saws summed for the big rave-lead sound, spread across
+//! the stereo field. The spread is carried as a side signal next to the
+//! mono sum, so folding to mono gives exactly the centred voice.
+
+use crate::oscillator::{Oscillator, Waveform};
+
+/// Most saws stacked at once
+pub const MAX_VOICES: usize = 5;
+
+/// Detune of the outermost saws at full detune, in cents each way
+const MAX_DETUNE_CENTS: f32 = 50.0;
+
+pub struct SuperSaw {
+    oscillators: [Oscillator; MAX_VOICES],
+    voices: usize,
+    detune: f32,
+    spread: f32,
+
+    // Per-voice pitch ratio and stereo position (-1.0 - 1.0)
+    ratios: [f32; MAX_VOICES],
+    pans: [f32; MAX_VOICES],
+}
+
+impl SuperSaw {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut supersaw = Self {
+            oscillators: std::array::from_fn(|i| {
+                let mut osc = Oscillator::new(sample_rate);
+                osc.set_waveform(Waveform::Saw);
+                // Start the saws apart so they don't phase as one
+                osc.set_phase(i as f32 * 0.618 % 1.0);
+                osc
+            }),
+            voices: 1,
+            detune: 0.3,
+            spread: 0.5,
+            ratios: [1.0; MAX_VOICES],
+            pans: [0.0; MAX_VOICES],
+        };
+        supersaw.update_voices();
+        supersaw
+    }
+
+    /// Number of saws (1 - 5); 1 turns unison off
+    pub fn set_voices(&mut self, voices: usize) {
+        self.voices = voices.clamp(1, MAX_VOICES);
+        self.update_voices();
+    }
+
+    pub fn voices(&self) -> usize {
+        self.voices
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.voices > 1
+    }
+
+    /// How far the saws spread in pitch (0.0 - 1.0, up to 50 cents each way)
+    pub fn set_detune(&mut self, amount: f32) {
+        self.detune = amount.clamp(0.0, 1.0);
+        self.update_voices();
+    }
+
+    pub fn detune(&self) -> f32 {
+        self.detune
+    }
+
+    /// How far the saws spread across the stereo field (0.0 - 1.0)
+    pub fn set_spread(&mut self, amount: f32) {
+        self.spread = amount.clamp(0.0, 1.0);
+        self.update_voices();
+    }
+
+    pub fn spread(&self) -> f32 {
+        self.spread
+    }
+
+    /// Spread the voices evenly from lowest on the left to highest on the
+    /// right, with an odd count keeping one in tune in the centre
+    fn update_voices(&mut self) {
+        for i in 0..self.voices {
+            let position = if self.voices == 1 { 0.0 } else { 2.0 * i as f32 / (self.voices - 1) as f32 - 1.0 };
+            self.ratios[i] = (position * self.detune * MAX_DETUNE_CENTS / 1200.0).exp2();
+            self.pans[i] = position * self.spread;
+        }
+    }
+
+    pub fn set_frequency(&mut self, freq: f32) {
+        for (osc, ratio) in self.oscillators.iter_mut().zip(self.ratios).take(self.voices) {
+            osc.set_frequency(freq * ratio);
+        }
+    }
+
+    /// Next sample as the mono sum and the side signal; left is
+    /// mid - side and right is mid + side
+    pub fn process(&mut self) -> (f32, f32) {
+        let mut mid = 0.0;
+        let mut side = 0.0;
+        for (osc, pan) in self.oscillators.iter_mut().zip(self.pans).take(self.voices) {
+            let sample = osc.process();
+            mid += sample;
+            side += sample * pan;
+        }
+        // Uncorrelated saws add in power, so scale by the square root
+        let gain = 1.0 / (self.voices as f32).sqrt();
+        (mid * gain, side * gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(supersaw: &mut SuperSaw, len: usize) -> (Vec<f32>, Vec<f32>) {
+        supersaw.set_frequency(110.0);
+        (0..len).map(|_| supersaw.process()).unzip()
+    }
+
+    #[test]
+    fn test_single_voice_is_a_plain_saw() {
+        let mut supersaw = SuperSaw::new(44100.0);
+        assert!(!supersaw.is_active());
+        let mut osc = Oscillator::new(44100.0);
+        osc.set_frequency(110.0);
+        let (mid, side) = render(&mut supersaw, 100);
+        assert!(side.iter().all(|&s| s == 0.0));
+        // The lone saw starts at phase 0 like any oscillator
+        for (a, _) in mid.iter().zip(0..) {
+            assert_eq!(*a, osc.process());
+        }
+    }
+
+    #[test]
+    fn test_detune_beats() {
+        // Detuned saws drift in and out of phase, so the level swells
+        let swing = |detune: f32| {
+            let mut supersaw = SuperSaw::new(44100.0);
+            supersaw.set_voices(3);
+            supersaw.set_detune(detune);
+            let (mid, _) = render(&mut supersaw, 44100);
+            let peaks: Vec<f32> = mid.chunks(2205).map(|c| c.iter().fold(0.0f32, |m, s| m.max(s.abs()))).collect();
+            peaks.iter().cloned().fold(0.0f32, f32::max) - peaks.iter().cloned().fold(f32::MAX, f32::min)
+        };
+        assert!(swing(1.0) > swing(0.0) + 0.1);
+    }
+
+    #[test]
+    fn test_spread_makes_side_signal() {
+        let side_energy = |spread: f32| {
+            let mut supersaw = SuperSaw::new(44100.0);
+            supersaw.set_voices(5);
+            supersaw.set_spread(spread);
+            let (_, side) = render(&mut supersaw, 4410);
+            side.iter().map(|s| s * s).sum::<f32>()
+        };
+        assert_eq!(side_energy(0.0), 0.0);
+        assert!(side_energy(1.0) > side_energy(0.5));
+    }
+}
@@ -0,0 +1,138 @@
+//! Trig conditions: whether a step plays on a given pass of the pattern
+//!
+//! Each step carries a chance and an Elektron-style condition, checked every
+//! time the step comes round so a pattern can change from loop to loop.
+
+use crate::rng::Rng;
+
+/// Probability that always plays
+pub const ALWAYS_PLAYS: u8 = 100;
+
+/// Longest cycle a ratio condition can count
+pub const MAX_CYCLE: u8 = 8;
+
+/// Which passes of the pattern a step plays on
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Condition {
+    #[default]
+    Always,
+    /// Only the first pass after the sequencer starts
+    First,
+    /// Every pass but the first
+    NotFirst,
+    /// Pass `pass` of every `cycle` (1:2 plays on odd passes, 2:4 on the
+    /// second of every four)
+    Ratio { pass: u8, cycle: u8 },
+}
+
+impl Condition {
+    /// Packed as one byte: 0 = always, 1 = first, 2 = not first, or
+    /// cycle * 16 + pass for a ratio
+    pub fn to_byte(self) -> u8 {
+        match self {
+            Condition::Always => 0,
+            Condition::First => 1,
+            Condition::NotFirst => 2,
+            Condition::Ratio { pass, cycle } => cycle << 4 | pass,
+        }
+    }
+
+    /// Unpack to_byte(); None for bytes that aren't a valid condition
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Condition::Always),
+            1 => Some(Condition::First),
+            2 => Some(Condition::NotFirst),
+            _ => {
+                let (pass, cycle) = (byte & 0x0F, byte >> 4);
+                let valid = (2..=MAX_CYCLE).contains(&cycle) && (1..=cycle).contains(&pass);
+                valid.then_some(Condition::Ratio { pass, cycle })
+            }
+        }
+    }
+
+    /// Whether the step plays on `pass`, counted from 0 at the start
+    pub fn holds(self, pass: u32) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::First => pass == 0,
+            Condition::NotFirst => pass > 0,
+            Condition::Ratio { pass: p, cycle } => pass % cycle as u32 == p as u32 - 1,
+        }
+    }
+}
+
+/// A step's chance (0-100%) and condition
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trig {
+    pub probability: u8,
+    pub condition: Condition,
+}
+
+impl Trig {
+    pub const ALWAYS: Trig = Trig { probability: ALWAYS_PLAYS, condition: Condition::Always };
+
+    /// Whether the step plays on `pass`. The chance is only rolled when the
+    /// condition holds and is below 100%, so plain steps never use up random
+    /// numbers.
+    pub fn fires(self, pass: u32, rng: &mut Rng) -> bool {
+        self.condition.holds(pass)
+            && (self.probability >= ALWAYS_PLAYS || rng.chance(self.probability as f32 / 100.0))
+    }
+
+    /// Packed as [probability, condition]
+    pub fn to_bytes(self) -> [u8; 2] {
+        [self.probability, self.condition.to_byte()]
+    }
+
+    /// Unpack to_bytes(); invalid conditions read as Always
+    pub fn from_bytes(probability: u8, condition: u8) -> Self {
+        Self {
+            probability: probability.min(ALWAYS_PLAYS),
+            condition: Condition::from_byte(condition).unwrap_or_default(),
+        }
+    }
+}
+
+impl Default for Trig {
+    fn default() -> Self {
+        Self::ALWAYS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditions() {
+        let half = Condition::Ratio { pass: 1, cycle: 2 };
+        let passes = |c: Condition| (0..8).filter(|&p| c.holds(p)).collect::<Vec<_>>();
+        assert_eq!(passes(half), vec![0, 2, 4, 6]);
+        assert_eq!(passes(Condition::Ratio { pass: 4, cycle: 4 }), vec![3, 7]);
+        assert_eq!(passes(Condition::First), vec![0]);
+        assert_eq!(passes(Condition::NotFirst), vec![1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_byte_round_trip() {
+        for condition in [Condition::Always, Condition::First, Condition::NotFirst, Condition::Ratio { pass: 3, cycle: 8 }] {
+            assert_eq!(Condition::from_byte(condition.to_byte()), Some(condition));
+        }
+        // 3:2 and 1:1 aren't conditions
+        assert_eq!(Condition::from_byte(0x23), None);
+        assert_eq!(Condition::from_byte(0x11), None);
+        assert_eq!(Trig::from_bytes(250, 0x23), Trig::ALWAYS);
+    }
+
+    #[test]
+    fn test_probability() {
+        let mut rng = Rng::new(1);
+        let trig = Trig { probability: 25, condition: Condition::Always };
+        let fired = (0..1000).filter(|_| trig.fires(0, &mut rng)).count();
+        assert!((200..300).contains(&fired), "fired {} of 1000", fired);
+        assert!((0..100).all(|_| Trig::ALWAYS.fires(0, &mut rng)));
+        let never = Trig { probability: 0, ..Trig::ALWAYS };
+        assert!((0..100).all(|_| !never.fires(0, &mut rng)));
+    }
+}
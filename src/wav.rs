@@ -0,0 +1,141 @@
+//! WAV (RIFF) encoder for exporting rendered audio
+
+/// Sample formats supported by the encoder
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WavFormat {
+    /// 16-bit signed integer PCM
+    Pcm16,
+    /// 32-bit IEEE float
+    Float32,
+}
+
+/// Encode mono samples as a WAV file.
+/// With `dither` set, 16-bit output gets TPDF dither before rounding so quiet
+/// tails fade into noise instead of truncating into distortion.
+pub fn encode_wav(samples: &[f32], sample_rate: u32, format: WavFormat, dither: bool) -> Vec<u8> {
+    encode_wav_interleaved(samples, 1, sample_rate, format, dither)
+}
+
+/// Encode frames of `channels` interleaved samples as a WAV file
+pub fn encode_wav_interleaved(samples: &[f32], channels: u16, sample_rate: u32, format: WavFormat, dither: bool) -> Vec<u8> {
+    let (format_tag, bits): (u16, u16) = match format {
+        WavFormat::Pcm16 => (1, 16),
+        WavFormat::Float32 => (3, 32),
+    };
+    let block_align = channels * bits / 8;
+    let byte_rate = sample_rate * block_align as u32;
+    let data_len = (samples.len() * bits as usize / 8) as u32;
+
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+
+    // RIFF header
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+
+    // fmt chunk
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&format_tag.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&byte_rate.to_le_bytes());
+    out.extend_from_slice(&block_align.to_le_bytes());
+    out.extend_from_slice(&bits.to_le_bytes());
+
+    // data chunk
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+
+    match format {
+        WavFormat::Pcm16 => {
+            let mut rng = Dither::new();
+            for &sample in samples {
+                let value = quantize_16(sample, if dither { rng.tpdf() } else { 0.0 });
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        WavFormat::Float32 => {
+            for &sample in samples {
+                out.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+    }
+
+    out
+}
+
+/// Scale to 16-bit, add `dither` (in LSBs) and round to nearest
+fn quantize_16(sample: f32, dither: f32) -> i16 {
+    let scaled = sample.clamp(-1.0, 1.0) * 32767.0 + dither;
+    scaled.round().clamp(-32768.0, 32767.0) as i16
+}
+
+/// Triangular-PDF dither source (sum of two uniform values, ±1 LSB)
+struct Dither {
+    state: u32,
+}
+
+impl Dither {
+    fn new() -> Self {
+        Self { state: 0x1234_5678 }
+    }
+
+    /// Uniform value in [-0.5, 0.5)
+    fn uniform(&mut self) -> f32 {
+        // xorshift32
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state as f32 / u32::MAX as f32) - 0.5
+    }
+
+    fn tpdf(&mut self) -> f32 {
+        self.uniform() + self.uniform()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let wav = encode_wav(&[0.0; 100], 44100, WavFormat::Pcm16, false);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(wav.len(), 44 + 200);
+        assert_eq!(u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]), 44100);
+    }
+
+    #[test]
+    fn test_float_format() {
+        let wav = encode_wav(&[0.5; 10], 48000, WavFormat::Float32, false);
+        assert_eq!(u16::from_le_bytes([wav[20], wav[21]]), 3);
+        assert_eq!(wav.len(), 44 + 40);
+        assert_eq!(f32::from_le_bytes([wav[44], wav[45], wav[46], wav[47]]), 0.5);
+    }
+
+    #[test]
+    fn test_rounding() {
+        // Half an LSB rounds up rather than truncating to zero
+        assert_eq!(quantize_16(0.6 / 32767.0, 0.0), 1);
+        assert_eq!(quantize_16(-0.6 / 32767.0, 0.0), -1);
+        assert_eq!(quantize_16(2.0, 0.0), 32767);
+    }
+
+    #[test]
+    fn test_dither_keeps_quiet_signal_alive() {
+        // A signal below half an LSB vanishes without dither but survives
+        // (on average) with it
+        let quiet = vec![0.3 / 32767.0; 10000];
+        let plain = encode_wav(&quiet, 44100, WavFormat::Pcm16, false);
+        let dithered = encode_wav(&quiet, 44100, WavFormat::Pcm16, true);
+
+        let sum = |wav: &[u8]| -> i32 {
+            wav[44..].chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]]) as i32).sum()
+        };
+        assert_eq!(sum(&plain), 0);
+        assert!(sum(&dithered) > 1000);
+    }
+}